@@ -16,12 +16,32 @@ struct Args {
     #[arg(required = true)]
     files: Vec<PathBuf>,
     /// Write the output to `OUTPUT.csv` [default:
-    /// `R<run_number>_trg_scalers.csv`]
+    /// `R<run_number>_trg_scalers.csv` or `R<run_number>_trg_live_time.csv`]
     #[arg(short, long)]
     output: Option<PathBuf>,
     /// Print detailed information about errors (if any)
     #[arg(short, long)]
     verbose: bool,
+    /// Only export events with a TRG time (in seconds since the start of the
+    /// run) greater than or equal to `START_TIME`
+    #[arg(long)]
+    start_time: Option<f64>,
+    /// Only export events with a TRG time (in seconds since the start of the
+    /// run) less than or equal to `END_TIME`
+    #[arg(long)]
+    end_time: Option<f64>,
+    /// Only export events with a serial number greater than or equal to
+    /// `FIRST_SERIAL`
+    #[arg(long)]
+    first_serial: Option<u32>,
+    /// Only export events with a serial number less than or equal to
+    /// `LAST_SERIAL`
+    #[arg(long)]
+    last_serial: Option<u32>,
+    /// Instead of the scalers of each event, export the live-time fraction
+    /// (output over input counts) in bins of `LIVE_TIME` seconds
+    #[arg(long)]
+    live_time: Option<f64>,
 }
 
 #[derive(Debug, Default, serde::Serialize)]
@@ -35,8 +55,74 @@ struct Row {
     output: Option<u32>,
 }
 
+#[derive(Debug, serde::Serialize)]
+struct LiveTimeRow {
+    start_time: f64,
+    end_time: f64,
+    input: u32,
+    output: u32,
+    live_fraction: Option<f64>,
+}
+
+// Group events in bins of `bin_width` seconds. Each row covers the time between
+// the last event of the previous (non-empty) bin and the last event of the
+// current bin, so no counts are lost if there are empty bins in between. The
+// first event is only used as the starting point of the first bin.
+fn live_time_rows(rows: impl IntoIterator<Item = Row>, bin_width: f64) -> Vec<LiveTimeRow> {
+    let points: Vec<_> = rows
+        .into_iter()
+        .filter_map(|row| Some((row.trg_time?, row.input?, row.output?)))
+        .collect();
+    let bin = |t: f64| (t / bin_width).floor();
+
+    let mut edges: Vec<_> = points.first().copied().into_iter().collect();
+    for (i, &point) in points.iter().enumerate().skip(1) {
+        if points
+            .get(i + 1)
+            .is_none_or(|next| bin(next.0) != bin(point.0))
+        {
+            edges.push(point);
+        }
+    }
+
+    edges
+        .windows(2)
+        .map(|w| {
+            let [(start_time, start_input, start_output), (end_time, end_input, end_output)] =
+                [w[0], w[1]];
+            // Counters are 32 bits and can wrap around in long runs.
+            let input = end_input.wrapping_sub(start_input);
+            let output = end_output.wrapping_sub(start_output);
+
+            LiveTimeRow {
+                start_time,
+                end_time,
+                input,
+                output,
+                // There is no live-time fraction without any input trigger.
+                live_fraction: (input != 0).then(|| f64::from(output) / f64::from(input)),
+            }
+        })
+        .collect()
+}
+
 fn main() -> Result<()> {
     let args = Args::parse();
+    if let Some(bin_width) = args.live_time {
+        ensure!(
+            bin_width > 0.0 && bin_width.is_finite(),
+            "live-time bin width must be a positive number of seconds"
+        );
+    }
+    if let (Some(start), Some(end)) = (args.start_time, args.end_time) {
+        ensure!(start <= end, "start time must not be after the end time");
+    }
+    if let (Some(first), Some(last)) = (args.first_serial, args.last_serial) {
+        ensure!(
+            first <= last,
+            "first serial number must not be after the last serial number"
+        );
+    }
     let (run_number, files) =
         alpha_g_analysis::sort_run_files(args.files).context("failed to sort input files")?;
 
@@ -130,10 +216,35 @@ fn main() -> Result<()> {
             }
         },
     );
+    // The cumulative TRG time has to be calculated from all the events, so the
+    // cuts can only be applied after the scan.
+    let rows = rows.filter(|row| {
+        let in_time_window = match (args.start_time, args.end_time) {
+            (None, None) => true,
+            // Events without a TRG time can't be placed in the time window.
+            (start, end) => row.trg_time.is_some_and(|t| {
+                start.is_none_or(|start| t >= start) && end.is_none_or(|end| t <= end)
+            }),
+        };
+        let in_serial_range = args
+            .first_serial
+            .is_none_or(|first| row.serial_number >= first)
+            && args
+                .last_serial
+                .is_none_or(|last| row.serial_number <= last);
+
+        in_time_window && in_serial_range
+    });
 
     let output = args
         .output
-        .unwrap_or_else(|| PathBuf::from(format!("R{run_number}_trg_scalers")))
+        .unwrap_or_else(|| {
+            if args.live_time.is_some() {
+                PathBuf::from(format!("R{run_number}_trg_live_time"))
+            } else {
+                PathBuf::from(format!("R{run_number}_trg_scalers"))
+            }
+        })
         .with_extension("csv");
     let mut wtr = std::fs::File::create(&output)
         .with_context(|| format!("failed to create `{}`", output.display()))?;
@@ -149,9 +260,16 @@ fn main() -> Result<()> {
     )
     .context("failed to write csv header")?;
     let mut wtr = csv::Writer::from_writer(wtr);
-    for row in rows {
-        wtr.serialize(row)
-            .context("failed to write row to csv data")?;
+    if let Some(bin_width) = args.live_time {
+        for row in live_time_rows(rows, bin_width) {
+            wtr.serialize(row)
+                .context("failed to write row to csv data")?;
+        }
+    } else {
+        for row in rows {
+            wtr.serialize(row)
+                .context("failed to write row to csv data")?;
+        }
     }
     wtr.flush().context("failed to flush csv data")?;
 
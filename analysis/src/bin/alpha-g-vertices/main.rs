@@ -1,13 +1,15 @@
 use alpha_g_detector::midas::EventId;
-use alpha_g_physics::{MainEvent, TRG_CLOCK_FREQ};
+use alpha_g_physics::reconstruction::ReconstructionConfig;
+use alpha_g_physics::{Diagnostics, DriftTablesByRun, RunContext, TRG_CLOCK_FREQ};
 use anyhow::{ensure, Context, Result};
 use clap::Parser;
 use indicatif::{
     MultiProgress, ParallelProgressIterator, ProgressBar, ProgressDrawTarget, ProgressStyle,
 };
 use rayon::prelude::*;
+use std::collections::BTreeMap;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use uom::si::length::meter;
 use uom::si::time::second;
 
@@ -24,6 +26,14 @@ struct Args {
     /// Print detailed information about errors (if any)
     #[arg(short, long)]
     verbose: bool,
+    /// Print a summary of the reconstruction diagnostics (time spent on each
+    /// stage, number of spacepoints/clusters/tracks, and track fit failures)
+    #[arg(short, long)]
+    diagnostics: bool,
+    /// TOML file with the reconstruction configuration (e.g. to select the
+    /// track fit algorithm) [default: built-in configuration]
+    #[arg(short, long)]
+    config: Option<PathBuf>,
 }
 
 #[derive(Debug, Default, serde::Serialize)]
@@ -43,8 +53,17 @@ fn main() -> Result<()> {
         .context("failed to initialize global thread pool")?;
 
     let args = Args::parse();
+    let config = match &args.config {
+        Some(path) => ReconstructionConfig::open(path)
+            .with_context(|| format!("failed to load `{}`", path.display()))?,
+        None => ReconstructionConfig::default(),
+    };
     let (run_number, files) =
         alpha_g_analysis::sort_run_files(args.files).context("failed to sort input files")?;
+    // Resolve the calibration of the run only once for all events.
+    let mut context = RunContext::new(run_number, &DriftTablesByRun::new())
+        .with_context(|| format!("failed to load calibration of run `{run_number}`"))?;
+    context.config = config;
     // Progress bars were flickering with the default draw target rate.
     let multi_progress = MultiProgress::with_draw_target(ProgressDrawTarget::stderr_with_hz(1));
     // ETA is 0 until the first file is processed. So just don't show it until
@@ -91,8 +110,16 @@ fn main() -> Result<()> {
                     let banks = event
                         .into_iter()
                         .map(|bank| (bank.name(), bank.data_slice()));
-                    match MainEvent::try_from_banks(run_number, banks) {
-                        Ok(event) => (serial_number, Some(event.timestamp()), event.vertex()),
+                    match context
+                        .main_event(banks)
+                        .map(|event| context.reconstruct_with_diagnostics(&event))
+                    {
+                        Ok((event, diagnostics)) => (
+                            serial_number,
+                            Some(event.timestamp),
+                            event.vertexing.primary.map(|info| info.position),
+                            Some(diagnostics),
+                        ),
                         Err(error) => {
                             if args.verbose {
                                 // Use `pb` rather than `tp_bar`. Otherwise the
@@ -102,7 +129,7 @@ fn main() -> Result<()> {
                                 // for slow-updating progress bars.
                                 pb.println(format!("Error in event `{serial_number}`: {error}"));
                             }
-                            (serial_number, None, None)
+                            (serial_number, None, None, None)
                         }
                     }
                 }),
@@ -116,9 +143,18 @@ fn main() -> Result<()> {
     }
     tp_bar.finish_and_clear();
 
+    if args.diagnostics {
+        let num_failed = rows.iter().filter(|(_, _, _, d)| d.is_none()).count();
+        print_diagnostics(
+            rows.iter().filter_map(|(_, _, _, d)| d.as_ref()),
+            num_failed,
+            args.config.as_deref(),
+        );
+    }
+
     let rows = rows.into_iter().scan(
         (None, 0),
-        |(previous, cumulative), (serial_number, timestamp, vertex)| {
+        |(previous, cumulative), (serial_number, timestamp, vertex, _)| {
             // If we don't have a timestamp, it is OK to use the previous one
             // because this counter overflows every 68 seconds.
             // This will only be problematic if we go over a full minute
@@ -171,3 +207,78 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+// Print the sum of the diagnostics of all events. Events that could not be
+// reconstructed (e.g. bad data banks) have no diagnostics and are only counted.
+// The configuration file (if any) is printed because the diagnostics depend on
+// it.
+fn print_diagnostics<'a>(
+    diagnostics: impl Iterator<Item = &'a Diagnostics>,
+    num_failed: usize,
+    config: Option<&Path>,
+) {
+    let mut num_events = 0;
+    let mut total = Diagnostics::default();
+    let mut failures = BTreeMap::new();
+    for d in diagnostics {
+        num_events += 1;
+        total.durations.avalanches += d.durations.avalanches;
+        total.durations.spacepoints += d.durations.spacepoints;
+        total.durations.spiral_tagging += d.durations.spiral_tagging;
+        total.durations.clustering += d.durations.clustering;
+        total.durations.cosmic_tagging += d.durations.cosmic_tagging;
+        total.durations.track_fitting += d.durations.track_fitting;
+        total.durations.vertexing += d.durations.vertexing;
+        total.num_avalanches += d.num_avalanches;
+        total.num_spacepoints += d.num_spacepoints;
+        total.num_spirals += d.num_spirals;
+        total.num_unclustered_spacepoints += d.num_unclustered_spacepoints;
+        total.num_clusters += d.num_clusters;
+        total.num_cosmics += d.num_cosmics;
+        total.num_tracks += d.num_tracks;
+        total.num_fit_iter += d.num_fit_iter;
+        total.num_vertices += d.num_vertices;
+        for error in &d.track_fit_failures {
+            *failures.entry(error.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    eprintln!("Reconstruction diagnostics ({num_events} events):");
+    match config {
+        Some(path) => eprintln!("  config: `{}`", path.display()),
+        None => eprintln!("  config: built-in"),
+    }
+    eprintln!("  failed events: {num_failed}");
+    let durations = total.durations;
+    for (stage, duration) in [
+        ("avalanches", durations.avalanches),
+        ("spacepoints", durations.spacepoints),
+        ("spiral tagging", durations.spiral_tagging),
+        ("clustering", durations.clustering),
+        ("cosmic tagging", durations.cosmic_tagging),
+        ("track fitting", durations.track_fitting),
+        ("vertexing", durations.vertexing),
+    ] {
+        eprintln!("  {stage}: {duration:.2?}");
+    }
+    eprintln!("  avalanches: {}", total.num_avalanches);
+    eprintln!(
+        "  spacepoints: {} ({} unclustered)",
+        total.num_spacepoints, total.num_unclustered_spacepoints
+    );
+    eprintln!("  spirals: {}", total.num_spirals);
+    eprintln!(
+        "  clusters: {} ({} rejected by the track fit)",
+        total.num_clusters,
+        failures.values().sum::<usize>()
+    );
+    eprintln!("  cosmics: {}", total.num_cosmics);
+    eprintln!(
+        "  tracks: {} ({} fit iterations)",
+        total.num_tracks, total.num_fit_iter
+    );
+    eprintln!("  vertices: {}", total.num_vertices);
+    for (reason, count) in failures {
+        eprintln!("  track fit failure `{reason}`: {count}");
+    }
+}
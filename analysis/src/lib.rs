@@ -1,6 +1,13 @@
-use midasio::file::{initial_timestamp_unchecked, run_number_unchecked, TryFileViewFromBytesError};
+use alpha_g_detector::midas::EventId;
+use alpha_g_physics::{ReconstructedEvent, RunContext, TryMainEventFromDataBanksError};
+use midasio::file::{
+    initial_timestamp_unchecked, run_number_unchecked, FileView, TryFileViewFromBytesError,
+};
+use std::collections::BTreeMap;
 use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::io::Read;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
@@ -58,6 +65,12 @@ pub enum AlphaIOError {
     /// Duplicate files by their initial timestamp.
     #[error("duplicate initial timestamp in `{}` and `{}`", .path1.display(), .path2.display())]
     DuplicateInitialTimestamp { path1: PathBuf, path2: PathBuf },
+    /// Subrun index can not be determined from the file name.
+    #[error("unknown subrun index of `{}`", .path.display())]
+    UnknownSubrunIndex { path: PathBuf },
+    /// Duplicate files by their subrun index.
+    #[error("duplicate subrun index in `{}` and `{}`", .path1.display(), .path2.display())]
+    DuplicateSubrunIndex { path1: PathBuf, path2: PathBuf },
 }
 
 /// Read the entire contents of a file (auto-detecting compression).
@@ -142,3 +155,284 @@ pub fn sort_run_files<P: AsRef<Path>>(
         files.into_iter().map(|(_, _, path)| path).collect(),
     ))
 }
+
+/// Return the subrun index of an ALPHA-g file based on its name.
+///
+/// ALPHA-g files are named `run<run_number>sub<subrun_index>.mid` (plus an
+/// optional compression extension). Returns [`None`] if the file name does not
+/// follow this convention.
+pub fn subrun_index<P: AsRef<Path>>(path: P) -> Option<u32> {
+    let name = path.as_ref().file_name()?.to_str()?;
+    let (_, rest) = name.rsplit_once("sub")?;
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(rest, |end| &rest[..end]);
+
+    digits.parse().ok()
+}
+
+/// Return an iterator over the reconstructed main events in a MIDAS file.
+///
+/// All non-main events (e.g. Chronobox or Barrel Veto events) are skipped.
+/// Each item is the serial number of the event together with the result of
+/// [`RunContext::reconstruct_banks`]. The `context` (which should be created
+/// for the run number of the file) resolves the calibration and drift tables
+/// only once for all the events. Events are reconstructed lazily, one at a
+/// time, as the iterator is advanced.
+pub fn reconstruct_main_events<'a>(
+    file_view: FileView<'a>,
+    context: &'a RunContext,
+) -> impl Iterator<
+    Item = (
+        u32,
+        Result<ReconstructedEvent, TryMainEventFromDataBanksError>,
+    ),
+> + 'a {
+    file_view
+        .into_iter()
+        .filter(|event| matches!(EventId::try_from(event.id()), Ok(EventId::Main)))
+        .map(move |event| {
+            let serial_number = event.serial_number();
+            let banks = event
+                .into_iter()
+                .map(|bank| (bank.name(), bank.data_slice()));
+
+            (serial_number, context.reconstruct_banks(banks))
+        })
+}
+
+/// Discontinuity found by a [`FileSequenceValidator`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SequenceGap {
+    /// Range of subrun indices without a corresponding file.
+    MissingSubruns(Range<u32>),
+    /// Range of serial numbers missing for a given event ID.
+    MissingSerialNumbers { event_id: u16, range: Range<u32> },
+    /// Serial number of a given event ID that is smaller than the one before.
+    OutOfOrderSerialNumber {
+        event_id: u16,
+        previous: u32,
+        found: u32,
+    },
+    /// Serial number of a given event ID that is repeated.
+    DuplicateSerialNumber { event_id: u16, serial_number: u32 },
+}
+
+impl fmt::Display for SequenceGap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingSubruns(range) => {
+                write!(f, "missing subruns `{}..{}`", range.start, range.end)
+            }
+            Self::MissingSerialNumbers { event_id, range } => write!(
+                f,
+                "missing serial numbers `{}..{}` of event ID `{event_id}`",
+                range.start, range.end
+            ),
+            Self::OutOfOrderSerialNumber {
+                event_id,
+                previous,
+                found,
+            } => write!(
+                f,
+                "out of order serial number `{found}` after `{previous}` of event ID `{event_id}`"
+            ),
+            Self::DuplicateSerialNumber {
+                event_id,
+                serial_number,
+            } => write!(
+                f,
+                "duplicate serial number `{serial_number}` of event ID `{event_id}`"
+            ),
+        }
+    }
+}
+
+// Discontinuity (if any) between two consecutive serial numbers of the same
+// event ID.
+fn serial_number_gap(event_id: u16, previous: u32, current: u32) -> Option<SequenceGap> {
+    match previous.checked_add(1) {
+        Some(next) if current == next => None,
+        Some(next) if current > next => Some(SequenceGap::MissingSerialNumbers {
+            event_id,
+            range: next..current,
+        }),
+        _ if current == previous => Some(SequenceGap::DuplicateSerialNumber {
+            event_id,
+            serial_number: current,
+        }),
+        _ => Some(SequenceGap::OutOfOrderSerialNumber {
+            event_id,
+            previous,
+            found: current,
+        }),
+    }
+}
+
+// Summary of a single file needed to check continuity with the rest of the
+// files in a run.
+#[derive(Clone, Debug)]
+struct FileSummary {
+    path: PathBuf,
+    subrun_index: u32,
+    // Event ID -> (first, largest) serial number.
+    serial_numbers: BTreeMap<u16, (u32, u32)>,
+}
+
+/// Validator of the completeness of all the files of an individual run.
+///
+/// Checks that:
+/// - All files correspond to the same run number.
+/// - There are no missing subrun indices (starting from `0`).
+/// - The serial numbers of each event ID are contiguous (without duplicates,
+///   and in increasing order) within and across files.
+///
+/// Files can be added in any order; they are ordered by their subrun index
+/// when the gaps are reported.
+#[derive(Clone, Debug, Default)]
+pub struct FileSequenceValidator {
+    run_number: Option<u32>,
+    files: Vec<FileSummary>,
+    // Serial number gaps found within individual files.
+    internal_gaps: Vec<SequenceGap>,
+}
+
+impl FileSequenceValidator {
+    /// Create an empty validator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add a file given its run number, subrun index, and the
+    /// `(event_id, serial_number)` of all its events (in file order).
+    ///
+    /// Returns an error if the run number does not match previously added
+    /// files, or if a file with the same subrun index was already added.
+    pub fn push<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        run_number: u32,
+        subrun_index: u32,
+        events: impl IntoIterator<Item = (u16, u32)>,
+    ) -> Result<(), AlphaIOError> {
+        let path = path.as_ref().to_owned();
+        match self.run_number {
+            Some(expected) if expected != run_number => {
+                return Err(AlphaIOError::BadRunNumber {
+                    path,
+                    expected,
+                    found: run_number,
+                });
+            }
+            Some(_) => {}
+            None => self.run_number = Some(run_number),
+        }
+        if let Some(other) = self
+            .files
+            .iter()
+            .find(|file| file.subrun_index == subrun_index)
+        {
+            return Err(AlphaIOError::DuplicateSubrunIndex {
+                path1: other.path.clone(),
+                path2: path,
+            });
+        }
+
+        let mut serial_numbers: BTreeMap<u16, (u32, u32)> = BTreeMap::new();
+        for (event_id, serial_number) in events {
+            serial_numbers
+                .entry(event_id)
+                .and_modify(|(_, last)| {
+                    if let Some(gap) = serial_number_gap(event_id, *last, serial_number) {
+                        self.internal_gaps.push(gap);
+                    }
+                    // Out of order serial numbers should not be reported again
+                    // as missing.
+                    *last = serial_number.max(*last);
+                })
+                .or_insert((serial_number, serial_number));
+        }
+
+        self.files.push(FileSummary {
+            path,
+            subrun_index,
+            serial_numbers,
+        });
+        Ok(())
+    }
+    /// Read and add a MIDAS file (auto-detecting compression). The subrun
+    /// index is obtained from the file name (see [`subrun_index`]).
+    pub fn push_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), AlphaIOError> {
+        let subrun_index = subrun_index(&path).ok_or_else(|| AlphaIOError::UnknownSubrunIndex {
+            path: path.as_ref().to_owned(),
+        })?;
+        let contents = read(&path)?;
+        let file_view = midasio::FileView::try_from(&contents[..])?;
+
+        self.push(
+            path,
+            file_view.run_number(),
+            subrun_index,
+            file_view
+                .into_iter()
+                .map(|event| (event.id(), event.serial_number())),
+        )
+    }
+    /// Return the run number of the added files. Returns [`None`] if no files
+    /// have been added yet.
+    pub fn run_number(&self) -> Option<u32> {
+        self.run_number
+    }
+    /// Return all the discontinuities found in the added files.
+    pub fn gaps(&self) -> Vec<SequenceGap> {
+        let mut files: Vec<_> = self.files.iter().collect();
+        files.sort_unstable_by_key(|file| file.subrun_index);
+
+        let mut subrun_gaps = Vec::new();
+        let mut serial_gaps = self.internal_gaps.clone();
+        let mut expected_subrun = 0;
+        let mut last_serial_numbers: BTreeMap<u16, u32> = BTreeMap::new();
+        for file in files {
+            if file.subrun_index > expected_subrun {
+                subrun_gaps.push(SequenceGap::MissingSubruns(
+                    expected_subrun..file.subrun_index,
+                ));
+            }
+            expected_subrun = file.subrun_index.saturating_add(1);
+
+            for (&event_id, &(first, last)) in &file.serial_numbers {
+                match last_serial_numbers.get_mut(&event_id) {
+                    Some(previous) => {
+                        if let Some(gap) = serial_number_gap(event_id, *previous, first) {
+                            serial_gaps.push(gap);
+                        }
+                        *previous = last.max(*previous);
+                    }
+                    None => {
+                        last_serial_numbers.insert(event_id, last);
+                    }
+                }
+            }
+        }
+        serial_gaps.sort_by_key(|gap| match gap {
+            SequenceGap::MissingSerialNumbers { event_id, range } => (*event_id, range.start),
+            SequenceGap::OutOfOrderSerialNumber {
+                event_id, found, ..
+            } => (*event_id, *found),
+            SequenceGap::DuplicateSerialNumber {
+                event_id,
+                serial_number,
+            } => (*event_id, *serial_number),
+            SequenceGap::MissingSubruns(_) => unreachable!(),
+        });
+
+        subrun_gaps.extend(serial_gaps);
+        subrun_gaps
+    }
+    /// Return `true` if no discontinuities were found in the added files.
+    pub fn is_complete(&self) -> bool {
+        self.gaps().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests;
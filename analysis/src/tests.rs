@@ -0,0 +1,231 @@
+use super::*;
+use alpha_g_physics::DriftTablesByRun;
+
+// Valid TRG packet (same as in the detector crate tests).
+const TRG_PACKET: [u8; 80] = [
+    255, 0, 0, 0, 0, 0, 0, 128, 254, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0, 5, 0, 0, 0, 6, 0,
+    0, 0, 7, 0, 0, 0, 8, 0, 0, 128, 2, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 9, 0, 10, 0, 11, 0, 0, 0,
+    0, 0, 0, 0, 12, 0, 0, 0, 13, 0, 0, 0, 14, 0, 0, 0, 0, 0, 0, 224,
+];
+
+// ID, serial number, and (16-bit) data banks (name and data) of an event.
+type Event<'a> = (u16, u32, &'a [(&'a str, &'a [u8])]);
+
+// Little endian MIDAS file with the given events.
+fn midas_file(run_number: u32, events: &[Event]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend(0x8000u16.to_le_bytes());
+    bytes.extend(0x494Du16.to_le_bytes());
+    bytes.extend(run_number.to_le_bytes());
+    bytes.extend(0u32.to_le_bytes());
+    bytes.extend(0u32.to_le_bytes());
+    for &(id, serial_number, banks) in events {
+        let mut data = Vec::new();
+        for &(name, slice) in banks {
+            data.extend(name.as_bytes());
+            data.extend(1u16.to_le_bytes());
+            data.extend(u16::try_from(slice.len()).unwrap().to_le_bytes());
+            data.extend(slice);
+            data.resize(data.len().next_multiple_of(8), 0);
+        }
+        bytes.extend(id.to_le_bytes());
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend(serial_number.to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend((u32::try_from(data.len()).unwrap() + 8).to_le_bytes());
+        bytes.extend(u32::try_from(data.len()).unwrap().to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(data);
+    }
+    bytes.extend(0x8001u16.to_le_bytes());
+    bytes.extend(0x494Du16.to_le_bytes());
+    bytes.extend(run_number.to_le_bytes());
+    bytes.extend(0u32.to_le_bytes());
+    bytes.extend(0u32.to_le_bytes());
+
+    bytes
+}
+
+#[test]
+fn reconstruct_main_events_midas_file() {
+    // Simulation run; it doesn't require any calibration file.
+    let run_number = u32::MAX;
+    let bytes = midas_file(
+        run_number,
+        &[
+            (1, 0, &[("ATAT", &TRG_PACKET)]),
+            // Chronobox event.
+            (4, 0, &[]),
+            // Main event without a TRG bank.
+            (1, 1, &[]),
+        ],
+    );
+    let file_view = FileView::try_from(&bytes[..]).unwrap();
+    let context = RunContext::new(run_number, &DriftTablesByRun::new()).unwrap();
+
+    let events: Vec<_> = reconstruct_main_events(file_view, &context).collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].0, 0);
+    let event = events[0].1.as_ref().unwrap();
+    assert!(event.spacepoints.is_empty());
+    assert_eq!(events[1].0, 1);
+    assert!(matches!(
+        events[1].1,
+        Err(TryMainEventFromDataBanksError::MissingTrgBank)
+    ));
+}
+
+#[test]
+fn file_sequence_validator_complete() {
+    let mut validator = FileSequenceValidator::new();
+    validator
+        .push("run0.mid", 1, 1, [(1, 3), (2, 1), (1, 4)])
+        .unwrap();
+    validator
+        .push("run1.mid", 1, 0, [(1, 0), (1, 1), (1, 2), (2, 0)])
+        .unwrap();
+
+    assert_eq!(validator.run_number(), Some(1));
+    assert!(validator.is_complete());
+}
+
+#[test]
+fn file_sequence_validator_bad_run_number() {
+    let mut validator = FileSequenceValidator::new();
+    validator.push("a.mid", 1, 0, []).unwrap();
+
+    assert!(matches!(
+        validator.push("b.mid", 2, 1, []),
+        Err(AlphaIOError::BadRunNumber {
+            expected: 1,
+            found: 2,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn file_sequence_validator_duplicate_subrun_index() {
+    let mut validator = FileSequenceValidator::new();
+    validator.push("a.mid", 1, 0, []).unwrap();
+
+    assert!(matches!(
+        validator.push("b.mid", 1, 0, []),
+        Err(AlphaIOError::DuplicateSubrunIndex { .. })
+    ));
+}
+
+#[test]
+fn file_sequence_validator_missing_subruns() {
+    let mut validator = FileSequenceValidator::new();
+    validator.push("a.mid", 1, 1, []).unwrap();
+    validator.push("b.mid", 1, 4, []).unwrap();
+
+    assert_eq!(
+        validator.gaps(),
+        [
+            SequenceGap::MissingSubruns(0..1),
+            SequenceGap::MissingSubruns(2..4),
+        ]
+    );
+}
+
+#[test]
+fn file_sequence_validator_missing_serial_numbers() {
+    let mut validator = FileSequenceValidator::new();
+    validator
+        .push("a.mid", 1, 0, [(1, 0), (1, 2), (2, 0)])
+        .unwrap();
+    validator.push("b.mid", 1, 1, [(1, 5), (2, 1)]).unwrap();
+
+    assert_eq!(
+        validator.gaps(),
+        [
+            SequenceGap::MissingSerialNumbers {
+                event_id: 1,
+                range: 1..2,
+            },
+            SequenceGap::MissingSerialNumbers {
+                event_id: 1,
+                range: 3..5,
+            },
+        ]
+    );
+}
+
+#[test]
+fn file_sequence_validator_duplicate_serial_numbers() {
+    let mut validator = FileSequenceValidator::new();
+    validator
+        .push("a.mid", 1, 0, [(1, 0), (1, 1), (1, 1)])
+        .unwrap();
+    validator.push("b.mid", 1, 1, [(1, 1), (1, 2)]).unwrap();
+
+    assert_eq!(
+        validator.gaps(),
+        [
+            SequenceGap::DuplicateSerialNumber {
+                event_id: 1,
+                serial_number: 1,
+            },
+            SequenceGap::DuplicateSerialNumber {
+                event_id: 1,
+                serial_number: 1,
+            },
+        ]
+    );
+}
+
+#[test]
+fn file_sequence_validator_out_of_order_serial_numbers() {
+    let mut validator = FileSequenceValidator::new();
+    validator
+        .push("a.mid", 1, 0, [(1, 0), (1, 2), (1, 1), (1, 3)])
+        .unwrap();
+    validator.push("b.mid", 1, 1, [(1, 2)]).unwrap();
+
+    assert_eq!(
+        validator.gaps(),
+        [
+            SequenceGap::MissingSerialNumbers {
+                event_id: 1,
+                range: 1..2,
+            },
+            SequenceGap::OutOfOrderSerialNumber {
+                event_id: 1,
+                previous: 2,
+                found: 1,
+            },
+            SequenceGap::OutOfOrderSerialNumber {
+                event_id: 1,
+                previous: 3,
+                found: 2,
+            },
+        ]
+    );
+}
+
+#[test]
+fn file_sequence_validator_serial_number_overflow() {
+    let mut validator = FileSequenceValidator::new();
+    validator
+        .push("a.mid", 1, 0, [(1, u32::MAX - 1), (1, u32::MAX)])
+        .unwrap();
+    validator.push("b.mid", 1, 1, [(1, u32::MAX)]).unwrap();
+    validator.push("c.mid", 1, 2, [(1, 0)]).unwrap();
+
+    assert_eq!(
+        validator.gaps(),
+        [
+            SequenceGap::OutOfOrderSerialNumber {
+                event_id: 1,
+                previous: u32::MAX,
+                found: 0,
+            },
+            SequenceGap::DuplicateSerialNumber {
+                event_id: 1,
+                serial_number: u32::MAX,
+            },
+        ]
+    );
+}
@@ -1,4 +1,6 @@
+use lazy_static::lazy_static;
 use std::fmt;
+use std::sync::RwLock;
 use thiserror::Error;
 
 // Only imported for documentation. If you notice that this is no longer the
@@ -64,7 +66,7 @@ impl TryFrom<u8> for Adc32ChannelId {
 }
 
 /// ADC channel ID in an Alpha16 board.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ChannelId {
     /// Barrel Veto SiPM channel.
     A16(Adc16ChannelId),
@@ -135,6 +137,157 @@ const ALPHA16BOARDS: [(&str, [u8; 6]); 8] = [
     ("18", [216, 128, 57, 104, 142, 82]),
 ];
 
+lazy_static! {
+    // Boards registered at runtime (see `register_board`). These are only
+    // looked up after the known `ALPHA16BOARDS`, and are guaranteed to not
+    // duplicate any of them.
+    static ref EXTRA_ALPHA16BOARDS: RwLock<Vec<(&'static str, [u8; 6])>> =
+        RwLock::new(Vec::new());
+}
+
+// Return the first known or registered board that satisfies the predicate.
+fn find_board<P>(predicate: P) -> Option<BoardId>
+where
+    P: Fn(&(&'static str, [u8; 6])) -> bool,
+{
+    let extra = EXTRA_ALPHA16BOARDS.read().unwrap();
+
+    ALPHA16BOARDS
+        .iter()
+        .chain(extra.iter())
+        .find(|pair| predicate(pair))
+        .map(|&(name, mac_address)| BoardId { name, mac_address })
+}
+
+/// The error type returned when registering a new [`BoardId`] fails.
+#[derive(Error, Debug)]
+pub enum RegisterBoardError {
+    /// The board name is not 2 uppercase ASCII alphanumeric characters (as
+    /// required by the data bank names).
+    #[error("bad board name `{name}`")]
+    BadName { name: String },
+    /// A board with the same name is already known.
+    #[error("duplicate board name `{name}`")]
+    DuplicateName { name: String },
+    /// A board with the same mac address is already known.
+    #[error("duplicate mac address `{mac_address:?}`")]
+    DuplicateMacAddress { mac_address: [u8; 6] },
+    /// The list of boards could not be parsed.
+    #[error("bad board list format: {message}")]
+    BadFormat { message: String },
+}
+
+/// Register a new Alpha16 board at runtime.
+///
+/// All conversions to [`BoardId`] (e.g. from a data bank name) will recognize
+/// the new board afterwards. This is meant to handle boards that were installed
+/// after the release of this crate; please open an issue/PR to add the new
+/// board to the list of known boards.
+///
+/// # Examples
+///
+/// ```
+/// # use alpha_g_detector::alpha16::RegisterBoardError;
+/// # fn main() -> Result<(), RegisterBoardError> {
+/// use alpha_g_detector::alpha16::{register_board, BoardId};
+///
+/// let board_id = register_board("Z0", [1, 2, 3, 4, 5, 6])?;
+/// assert_eq!(BoardId::try_from("Z0").unwrap(), board_id);
+///
+/// assert!(register_board("09", [1, 2, 3, 4, 5, 7]).is_err());
+/// # Ok(())
+/// # }
+/// ```
+pub fn register_board(name: &str, mac_address: [u8; 6]) -> Result<BoardId, RegisterBoardError> {
+    register_boards([(name.to_string(), mac_address)]).map(|mut boards| boards.pop().unwrap())
+}
+
+/// Register new Alpha16 boards at runtime from the contents of a RON file.
+///
+/// The file has the same format as the list of known boards i.e. a list of
+/// `("name", [mac address])` pairs. Either all the boards are registered, or
+/// none of them are. See [`register_board`] for more details.
+///
+/// # Examples
+///
+/// ```
+/// # use alpha_g_detector::alpha16::RegisterBoardError;
+/// # fn main() -> Result<(), RegisterBoardError> {
+/// use alpha_g_detector::alpha16::{register_boards_from_ron, BoardId};
+///
+/// let contents = r#"[("Z1", [10, 2, 3, 4, 5, 6])]"#;
+/// let boards = register_boards_from_ron(contents)?;
+/// assert_eq!(boards, [BoardId::try_from("Z1").unwrap()]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn register_boards_from_ron(contents: &str) -> Result<Vec<BoardId>, RegisterBoardError> {
+    // Serde expects fixed-size arrays to be tuples in RON. Use a `Vec` to
+    // allow the mac address to be written as a list (same as in this file).
+    let pairs: Vec<(String, Vec<u8>)> =
+        ron::from_str(contents).map_err(|e| RegisterBoardError::BadFormat {
+            message: e.to_string(),
+        })?;
+    let pairs = pairs
+        .into_iter()
+        .map(|(name, mac_address)| {
+            let mac_address =
+                mac_address
+                    .try_into()
+                    .map_err(|mac: Vec<u8>| RegisterBoardError::BadFormat {
+                        message: format!("mac address `{mac:?}` is not 6 bytes long"),
+                    })?;
+            Ok((name, mac_address))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    register_boards(pairs)
+}
+
+fn register_boards<I>(pairs: I) -> Result<Vec<BoardId>, RegisterBoardError>
+where
+    I: IntoIterator<Item = (String, [u8; 6])>,
+{
+    // Hold the lock during validation to avoid races between two threads
+    // registering the same board.
+    let mut extra = EXTRA_ALPHA16BOARDS.write().unwrap();
+
+    let mut new: Vec<(String, [u8; 6])> = Vec::new();
+    for (name, mac_address) in pairs {
+        if name.len() != 2
+            || !name.chars().all(|c| c.is_ascii_alphanumeric())
+            || name.chars().any(|c| c.is_ascii_lowercase())
+        {
+            return Err(RegisterBoardError::BadName { name });
+        }
+        let known = ALPHA16BOARDS
+            .iter()
+            .chain(extra.iter())
+            .map(|&(name, mac_address)| (name, mac_address))
+            .chain(new.iter().map(|(name, mac)| (name.as_str(), *mac)));
+        for pair in known {
+            if name == pair.0 {
+                return Err(RegisterBoardError::DuplicateName { name });
+            }
+            if mac_address == pair.1 {
+                return Err(RegisterBoardError::DuplicateMacAddress { mac_address });
+            }
+        }
+        new.push((name, mac_address));
+    }
+
+    Ok(new
+        .into_iter()
+        .map(|(name, mac_address)| {
+            // Registered boards live until the end of the program anyway.
+            let name = Box::leak(name.into_boxed_str());
+            extra.push((name, mac_address));
+
+            BoardId { name, mac_address }
+        })
+        .collect())
+}
+
 /// Identity of a physical Alpha16 board.
 ///
 /// It is important to notice that a [`BoardId`] is different to a
@@ -151,15 +304,7 @@ impl TryFrom<&str> for BoardId {
     type Error = ParseBoardIdError;
 
     fn try_from(name: &str) -> Result<Self, Self::Error> {
-        for pair in ALPHA16BOARDS {
-            if name == pair.0 {
-                return Ok(BoardId {
-                    name: pair.0,
-                    mac_address: pair.1,
-                });
-            }
-        }
-        Err(ParseBoardIdError {
+        find_board(|pair| name == pair.0).ok_or(ParseBoardIdError {
             input: name.to_string(),
         })
     }
@@ -168,15 +313,7 @@ impl TryFrom<[u8; 6]> for BoardId {
     type Error = TryBoardIdFromMacAddressError;
 
     fn try_from(mac: [u8; 6]) -> Result<Self, Self::Error> {
-        for pair in ALPHA16BOARDS {
-            if mac == pair.1 {
-                return Ok(BoardId {
-                    name: pair.0,
-                    mac_address: pair.1,
-                });
-            }
-        }
-        Err(TryBoardIdFromMacAddressError { input: mac })
+        find_board(|pair| mac == pair.1).ok_or(TryBoardIdFromMacAddressError { input: mac })
     }
 }
 impl BoardId {
@@ -215,6 +352,88 @@ impl BoardId {
     pub fn mac_address(&self) -> [u8; 6] {
         self.mac_address
     }
+    /// Return an iterator over all the valid BV channels (i.e. ADC16) of an
+    /// Alpha16 board.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use alpha_g_detector::alpha16::TryBoardIdFromMacAddressError;
+    /// # fn main() -> Result<(), TryBoardIdFromMacAddressError> {
+    /// use alpha_g_detector::alpha16::BoardId;
+    ///
+    /// let board_id = BoardId::try_from([216, 128, 57, 104, 142, 82])?;
+    /// assert_eq!(board_id.adc16_channels().count(), 16);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn adc16_channels(&self) -> impl Iterator<Item = ChannelId> {
+        (0..=15).map(|n| ChannelId::A16(Adc16ChannelId(n)))
+    }
+    /// Return an iterator over all the valid anode wire channels (i.e. ADC32)
+    /// of an Alpha16 board.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use alpha_g_detector::alpha16::TryBoardIdFromMacAddressError;
+    /// # fn main() -> Result<(), TryBoardIdFromMacAddressError> {
+    /// use alpha_g_detector::alpha16::BoardId;
+    ///
+    /// let board_id = BoardId::try_from([216, 128, 57, 104, 142, 82])?;
+    /// assert_eq!(board_id.adc32_channels().count(), 32);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn adc32_channels(&self) -> impl Iterator<Item = ChannelId> {
+        (0..=31).map(|n| ChannelId::A32(Adc32ChannelId(n)))
+    }
+}
+
+/// Trigger mode of an Alpha16 board.
+///
+/// The trigger mode is encoded in the packet type of an ADC packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerMode {
+    /// Data acquisition triggered by the TRG board. This is the normal data
+    /// taking mode (packet type `1`).
+    External,
+    /// Data acquisition triggered by the channel itself crossing a threshold
+    /// e.g. during threshold scans (packet type `2`).
+    SelfTrigger,
+}
+
+/// Encoding of the [`ChannelId`] in byte `5` of an ADC packet.
+///
+/// Externally triggered packets always use [`ChannelLayout::Split`]. There is
+/// no documentation of the channel numbering of self-triggered packets; they
+/// are parsed with the same layout by default. Use e.g.
+/// [`AdcPacket::try_from_slice_with_layout`] if a threshold scan was recorded
+/// with a different numbering.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChannelLayout {
+    /// Barrel Veto channels are in the range `[0, 15]`, and radial Time
+    /// Projection Chamber channels are in the range `[128, 159]`.
+    #[default]
+    Split,
+    /// Barrel Veto channels are in the range `[0, 15]`, and radial Time
+    /// Projection Chamber channels are in the range `[16, 47]` (as used in some
+    /// places by agana).
+    Contiguous,
+}
+
+impl ChannelLayout {
+    fn channel_id(self, num: u8) -> Result<ChannelId, TryChannelIdFromUnsignedError> {
+        let first_a32 = match self {
+            Self::Split => 128,
+            Self::Contiguous => 16,
+        };
+        if num < first_a32 {
+            Ok(ChannelId::A16(num.try_into()?))
+        } else {
+            Ok(ChannelId::A32((num - first_a32).try_into()?))
+        }
+    }
 }
 
 /// The error type returned when conversion from
@@ -284,7 +503,7 @@ pub enum TryAdcPacketFromSliceError {
 ///
 /// |Byte(s)|Description|
 /// |:-:|:-:|
-/// |0|Fixed to 1|
+/// |0|Packet type|
 /// |1|Fixed to 3|
 /// |2-3|Accepted trigger|
 /// |4|Module ID|
@@ -304,8 +523,14 @@ pub enum TryAdcPacketFromSliceError {
 ///
 /// Bytes `[12..size - 4]` are only included in the packet if the `keep_bit` is
 /// set after data suppression.
+///
+/// The packet type is `1` for externally triggered packets and `2` for
+/// self-triggered packets (see [`TriggerMode`]). Self-triggered packets are
+/// assumed to have the layout above; only the encoding of the channel ID is
+/// configurable (see [`ChannelLayout`]).
 #[derive(Clone, Debug)]
 pub struct AdcV3Packet {
+    trigger_mode: TriggerMode,
     accepted_trigger: u16,
     module_id: ModuleId,
     channel_id: ChannelId,
@@ -374,7 +599,30 @@ impl AdcV3Packet {
     /// # }
     /// ```
     pub fn packet_type(&self) -> u8 {
-        1
+        match self.trigger_mode {
+            TriggerMode::External => 1,
+            TriggerMode::SelfTrigger => 2,
+        }
+    }
+    /// Return the [`TriggerMode`] of the Alpha16 board when the packet was
+    /// generated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use alpha_g_detector::alpha16::TryAdcPacketFromSliceError;
+    /// # fn main() -> Result<(), TryAdcPacketFromSliceError> {
+    /// use alpha_g_detector::alpha16::{AdcV3Packet, TriggerMode};
+    ///
+    /// let buffer = [2, 3, 0, 4, 5, 6, 2, 187, 0, 0, 0, 7, 224, 0, 0, 0];
+    /// let packet = AdcV3Packet::try_from(&buffer[..])?;
+    ///
+    /// assert_eq!(packet.trigger_mode(), TriggerMode::SelfTrigger);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn trigger_mode(&self) -> TriggerMode {
+        self.trigger_mode
     }
     /// Return the packet version. For [`AdcV3Packet`] it is fixed to `3`.
     ///
@@ -686,34 +934,68 @@ const BASELINE_SAMPLES: usize = 64;
 // And the minimum index is one after the baseline.
 const MIN_KEEP_LAST: usize = (BASELINE_SAMPLES + 2) / 2 + 1;
 
+// Baseline calculated by the firmware from the first `BASELINE_SAMPLES`.
+// The waveform must have at least `BASELINE_SAMPLES`.
+fn data_suppression_baseline(waveform: &[i16]) -> i16 {
+    // Add over i32 to avoid overflow
+    let num = waveform[..BASELINE_SAMPLES]
+        .iter()
+        .map(|n| i32::from(*n))
+        .sum::<i32>();
+    // Integer division in the firmware rounds towards negative infinity.
+    num.div_euclid(64).try_into().unwrap()
+}
+
 impl TryFrom<&[u8]> for AdcV3Packet {
     type Error = TryAdcPacketFromSliceError;
 
-    // All fields are big endian
     fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
+        Self::try_from_slice_with_layout(slice, ChannelLayout::default())
+    }
+}
+
+impl AdcV3Packet {
+    /// Same as [`AdcV3Packet::try_from`], but with an arbitrary
+    /// [`ChannelLayout`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use alpha_g_detector::alpha16::TryAdcPacketFromSliceError;
+    /// # fn main() -> Result<(), TryAdcPacketFromSliceError> {
+    /// use alpha_g_detector::alpha16::{Adc32ChannelId, AdcV3Packet, ChannelId, ChannelLayout};
+    ///
+    /// let buffer = [2, 3, 0, 4, 5, 20, 2, 187, 0, 0, 0, 7, 224, 0, 0, 0];
+    /// let packet = AdcV3Packet::try_from_slice_with_layout(&buffer[..], ChannelLayout::Contiguous)?;
+    ///
+    /// assert_eq!(packet.channel_id(), ChannelId::A32(Adc32ChannelId::try_from(4)?));
+    /// # Ok(())
+    /// # }
+    /// ```
+    // All fields are big endian
+    pub fn try_from_slice_with_layout(
+        slice: &[u8],
+        layout: ChannelLayout,
+    ) -> Result<Self, TryAdcPacketFromSliceError> {
         if slice.len() < 16 {
-            return Err(Self::Error::IncompleteSlice {
+            return Err(TryAdcPacketFromSliceError::IncompleteSlice {
                 found: slice.len(),
                 min_expected: 16,
             });
         }
 
-        if slice[0] != 1 {
-            return Err(Self::Error::UnknownType { found: slice[0] });
-        }
+        let trigger_mode = match slice[0] {
+            1 => TriggerMode::External,
+            2 => TriggerMode::SelfTrigger,
+            found => return Err(TryAdcPacketFromSliceError::UnknownType { found }),
+        };
         if slice[1] != 3 {
-            return Err(Self::Error::UnknownVersion { found: slice[1] });
+            return Err(TryAdcPacketFromSliceError::UnknownVersion { found: slice[1] });
         }
         let accepted_trigger = slice[2..4].try_into().unwrap();
         let accepted_trigger = u16::from_be_bytes(accepted_trigger);
         let module_id = ModuleId::try_from(slice[4])?;
-        // A value of [0-15] is BV, and a value of [128-159] is rTPC
-        let channel_id = slice[5];
-        let channel_id = if channel_id < 128 {
-            ChannelId::A16(channel_id.try_into()?)
-        } else {
-            ChannelId::A32((channel_id - 128).try_into()?)
-        };
+        let channel_id = layout.channel_id(slice[5])?;
         let requested_samples = slice[6..8].try_into().unwrap();
         let requested_samples = u16::from_be_bytes(requested_samples).into();
         let lsw_event_timestamp = slice[8..12].try_into().unwrap();
@@ -728,21 +1010,22 @@ impl TryFrom<&[u8]> for AdcV3Packet {
 
         if slice.len() == 16 {
             if !suppression_enabled {
-                return Err(Self::Error::IncompleteSlice {
+                return Err(TryAdcPacketFromSliceError::IncompleteSlice {
                     found: 16,
                     min_expected: 36,
                 });
             }
             if keep_bit {
-                return Err(Self::Error::KeepBitMismatch { found: keep_bit });
+                return Err(TryAdcPacketFromSliceError::KeepBitMismatch { found: keep_bit });
             }
             if keep_last != 0 {
-                return Err(Self::Error::BadKeepLast {
+                return Err(TryAdcPacketFromSliceError::BadKeepLast {
                     found: keep_last,
                     limit: 0,
                 });
             }
             return Ok(AdcV3Packet {
+                trigger_mode,
                 accepted_trigger,
                 module_id,
                 channel_id,
@@ -760,14 +1043,14 @@ impl TryFrom<&[u8]> for AdcV3Packet {
         }
 
         if slice.len() < 36 {
-            return Err(Self::Error::IncompleteSlice {
+            return Err(TryAdcPacketFromSliceError::IncompleteSlice {
                 found: slice.len(),
                 min_expected: 36,
             });
         }
 
         if slice[12..14] != [0, 0] {
-            return Err(Self::Error::ZeroMismatch {
+            return Err(TryAdcPacketFromSliceError::ZeroMismatch {
                 found: slice[12..14].try_into().unwrap(),
             });
         }
@@ -783,7 +1066,7 @@ impl TryFrom<&[u8]> for AdcV3Packet {
         let build_timestamp = u32::from_be_bytes(build_timestamp);
         let waveform_bytes = slice.len() - 36;
         if waveform_bytes % 2 != 0 {
-            return Err(Self::Error::IncompleteSlice {
+            return Err(TryAdcPacketFromSliceError::IncompleteSlice {
                 // waveform bytes + header + footer
                 found: waveform_bytes + 36,
                 min_expected: waveform_bytes + 37,
@@ -795,52 +1078,40 @@ impl TryFrom<&[u8]> for AdcV3Packet {
             .collect();
 
         if waveform.len() < BASELINE_SAMPLES {
-            return Err(Self::Error::BadNumberOfSamples {
+            return Err(TryAdcPacketFromSliceError::BadNumberOfSamples {
                 found: waveform.len(),
                 min: BASELINE_SAMPLES,
                 max: requested_samples - 2,
             });
         }
-        let data_baseline = {
-            // Add over i32 to avoid overflow
-            let num = waveform[..BASELINE_SAMPLES]
-                .iter()
-                .map(|n| i32::from(*n))
-                .sum::<i32>();
-            let d = num / 64;
-            if num % 64 < 0 {
-                d - 1
-            } else {
-                d
-            }
-        };
-        if data_baseline != suppression_baseline.into() {
-            return Err(Self::Error::BaselineMismatch {
+        let data_baseline = data_suppression_baseline(&waveform);
+        if data_baseline != suppression_baseline {
+            return Err(TryAdcPacketFromSliceError::BaselineMismatch {
                 found: suppression_baseline,
-                expected: data_baseline.try_into().unwrap(),
+                expected: data_baseline,
             });
         }
 
         if suppression_enabled {
             if !keep_bit {
-                return Err(Self::Error::KeepBitMismatch { found: keep_bit });
+                return Err(TryAdcPacketFromSliceError::KeepBitMismatch { found: keep_bit });
             }
             if keep_last < MIN_KEEP_LAST {
-                return Err(Self::Error::BadKeepLast {
+                return Err(TryAdcPacketFromSliceError::BadKeepLast {
                     found: keep_last,
                     limit: MIN_KEEP_LAST,
                 });
             }
             let last_index = (keep_last - 1) * 2 - 2;
             if waveform.len() <= last_index {
-                return Err(Self::Error::BadNumberOfSamples {
+                return Err(TryAdcPacketFromSliceError::BadNumberOfSamples {
                     found: waveform.len(),
                     min: last_index + 1,
                     max: requested_samples - 2,
                 });
             }
             if waveform.len() > requested_samples - 2 {
-                return Err(Self::Error::BadNumberOfSamples {
+                return Err(TryAdcPacketFromSliceError::BadNumberOfSamples {
                     found: waveform.len(),
                     min: last_index + 1,
                     max: requested_samples - 2,
@@ -849,27 +1120,27 @@ impl TryFrom<&[u8]> for AdcV3Packet {
         } else {
             if keep_bit {
                 if keep_last < MIN_KEEP_LAST {
-                    return Err(Self::Error::BadKeepLast {
+                    return Err(TryAdcPacketFromSliceError::BadKeepLast {
                         found: keep_last,
                         limit: MIN_KEEP_LAST,
                     });
                 }
                 let last_index = (keep_last - 1) * 2 - 2;
                 if waveform.len() <= last_index {
-                    return Err(Self::Error::BadNumberOfSamples {
+                    return Err(TryAdcPacketFromSliceError::BadNumberOfSamples {
                         found: waveform.len(),
                         min: last_index + 1,
                         max: requested_samples - 2,
                     });
                 }
             } else if keep_last != 0 {
-                return Err(Self::Error::BadKeepLast {
+                return Err(TryAdcPacketFromSliceError::BadKeepLast {
                     found: keep_last,
                     limit: 0,
                 });
             }
             if waveform.len() != requested_samples - 2 {
-                return Err(Self::Error::BadNumberOfSamples {
+                return Err(TryAdcPacketFromSliceError::BadNumberOfSamples {
                     found: waveform.len(),
                     min: requested_samples - 2,
                     max: requested_samples - 2,
@@ -878,6 +1149,7 @@ impl TryFrom<&[u8]> for AdcV3Packet {
         }
 
         Ok(AdcV3Packet {
+            trigger_mode,
             accepted_trigger,
             module_id,
             channel_id,
@@ -935,6 +1207,28 @@ impl AdcPacket {
             Self::V3(packet) => packet.packet_type(),
         }
     }
+    /// Return the [`TriggerMode`] of the Alpha16 board when the packet was
+    /// generated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use alpha_g_detector::alpha16::TryAdcPacketFromSliceError;
+    /// # fn main() -> Result<(), TryAdcPacketFromSliceError> {
+    /// use alpha_g_detector::alpha16::{AdcPacket, TriggerMode};
+    ///
+    /// let buffer = [1, 3, 0, 4, 5, 6, 2, 187, 0, 0, 0, 7, 224, 0, 0, 0];
+    /// let packet = AdcPacket::try_from(&buffer[..])?;
+    ///
+    /// assert_eq!(packet.trigger_mode(), TriggerMode::External);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn trigger_mode(&self) -> TriggerMode {
+        match self {
+            Self::V3(packet) => packet.trigger_mode(),
+        }
+    }
     /// Return the packet version.
     ///
     /// # Examples
@@ -1297,9 +1591,138 @@ impl TryFrom<&[u8]> for AdcPacket {
     type Error = TryAdcPacketFromSliceError;
 
     fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
-        Ok(AdcPacket::V3(AdcV3Packet::try_from(slice)?))
+        Self::try_from_slice_with_layout(slice, ChannelLayout::default())
+    }
+}
+
+impl AdcPacket {
+    /// Same as [`AdcPacket::try_from`], but with an arbitrary
+    /// [`ChannelLayout`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use alpha_g_detector::alpha16::TryAdcPacketFromSliceError;
+    /// # fn main() -> Result<(), TryAdcPacketFromSliceError> {
+    /// use alpha_g_detector::alpha16::{Adc16ChannelId, AdcPacket, ChannelId, ChannelLayout};
+    ///
+    /// let buffer = [2, 3, 0, 4, 5, 6, 2, 187, 0, 0, 0, 7, 224, 0, 0, 0];
+    /// let packet = AdcPacket::try_from_slice_with_layout(&buffer[..], ChannelLayout::Contiguous)?;
+    ///
+    /// assert_eq!(packet.channel_id(), ChannelId::A16(Adc16ChannelId::try_from(6)?));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_from_slice_with_layout(
+        slice: &[u8],
+        layout: ChannelLayout,
+    ) -> Result<Self, TryAdcPacketFromSliceError> {
+        Ok(AdcPacket::V3(AdcV3Packet::try_from_slice_with_layout(
+            slice, layout,
+        )?))
+    }
+}
+
+/// The error type returned when emulating the Alpha16 data suppression fails.
+#[derive(Error, Debug)]
+#[error("short waveform (expected at least 64 samples, found `{found}`)")]
+pub struct EmulateSuppressionError {
+    found: usize,
+}
+
+/// Output of the Alpha16 data suppression as implemented in the firmware. See
+/// [`emulate_suppression`] for more details.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuppressionOutput {
+    baseline: i16,
+    keep_last: usize,
+    waveform: Vec<i16>,
+}
+
+impl SuppressionOutput {
+    /// Return the data suppression baseline. This is equivalent to
+    /// [`AdcPacket::suppression_baseline`].
+    pub fn baseline(&self) -> i16 {
+        self.baseline
+    }
+    /// Return the `keep_last` counter. This is equivalent to
+    /// [`AdcPacket::keep_last`].
+    pub fn keep_last(&self) -> usize {
+        self.keep_last
+    }
+    /// Return [`true`] if at least one sample is over the data suppression
+    /// threshold. This is equivalent to [`AdcPacket::keep_bit`].
+    pub fn keep_bit(&self) -> bool {
+        self.keep_last != 0
+    }
+    /// Return the waveform samples that are kept after data suppression. This
+    /// is equivalent to [`AdcPacket::waveform`].
+    pub fn waveform(&self) -> &[i16] {
+        &self.waveform
     }
 }
 
+/// Emulate the data suppression of an Alpha16 board.
+///
+/// Given a complete (unsuppressed) waveform, return what the firmware would
+/// keep with a given data suppression `threshold`. Anode wire and SiPM signals
+/// are negative pulses, hence a sample is over threshold if it is more than
+/// `threshold` below the baseline. Only samples after the baseline samples are
+/// checked against the threshold.
+///
+/// The firmware keeps `keep_more` samples after the last sample over threshold
+/// (truncated to the length of the input waveform).
+///
+/// # Examples
+///
+/// ```
+/// # use alpha_g_detector::alpha16::EmulateSuppressionError;
+/// # fn main() -> Result<(), EmulateSuppressionError> {
+/// use alpha_g_detector::alpha16::emulate_suppression;
+///
+/// let mut waveform = vec![0; 100];
+/// let output = emulate_suppression(&waveform, 10, 5)?;
+/// assert!(!output.keep_bit());
+/// assert!(output.waveform().is_empty());
+///
+/// waveform[80] = -11;
+/// let output = emulate_suppression(&waveform, 10, 5)?;
+/// assert!(output.keep_bit());
+/// assert_eq!(output.waveform(), &waveform[..86]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn emulate_suppression(
+    waveform: &[i16],
+    threshold: u16,
+    keep_more: usize,
+) -> Result<SuppressionOutput, EmulateSuppressionError> {
+    if waveform.len() < BASELINE_SAMPLES {
+        return Err(EmulateSuppressionError {
+            found: waveform.len(),
+        });
+    }
+    let baseline = data_suppression_baseline(waveform);
+
+    let last_over_threshold = waveform[BASELINE_SAMPLES..]
+        .iter()
+        // Convert to i32 to avoid overflow
+        .rposition(|&v| i32::from(baseline) - i32::from(v) > i32::from(threshold))
+        .map(|i| i + BASELINE_SAMPLES);
+
+    Ok(match last_over_threshold {
+        Some(index) => SuppressionOutput {
+            baseline,
+            keep_last: (index + 2) / 2 + 1,
+            waveform: waveform[..(index + 1 + keep_more).min(waveform.len())].to_vec(),
+        },
+        None => SuppressionOutput {
+            baseline,
+            keep_last: 0,
+            waveform: Vec::new(),
+        },
+    })
+}
+
 #[cfg(test)]
 mod tests;
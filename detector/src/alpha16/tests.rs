@@ -156,6 +156,31 @@ fn board_id() {
     assert!(board_id.is_err());
 }
 
+#[test]
+fn board_id_channels() {
+    for pair in ALPHA16BOARDS {
+        let board_id = BoardId::try_from(pair.1).unwrap();
+
+        let channels: Vec<ChannelId> = board_id.adc16_channels().collect();
+        assert_eq!(channels.len(), 16);
+        for (i, channel) in (0..=15).zip(channels) {
+            assert_eq!(
+                channel,
+                ChannelId::A16(Adc16ChannelId::try_from(i).unwrap())
+            );
+        }
+
+        let channels: Vec<ChannelId> = board_id.adc32_channels().collect();
+        assert_eq!(channels.len(), 32);
+        for (i, channel) in (0..=31).zip(channels) {
+            assert_eq!(
+                channel,
+                ChannelId::A32(Adc32ChannelId::try_from(i).unwrap())
+            );
+        }
+    }
+}
+
 const SHORT_ADC_V3_PACKET: [u8; 16] = [1, 3, 0, 1, 2, 3, 2, 187, 0, 0, 0, 4, 224, 0, 0, 0];
 const LONG_ADC_V3_PACKET: [u8; 166] = [
     1, 3, 0, 1, 2, 3, 2, 187, 0, 0, 0, 4, 0, 0, 216, 128, 57, 104, 142, 82, 0, 0, 0, 0, 0, 0, 0, 5,
@@ -310,7 +335,7 @@ fn adc_v3_packet_incomplete_slice() {
 fn adc_v3_packet_unknown_type() {
     let mut bad_packet = SHORT_ADC_V3_PACKET;
     for i in 0..=255 {
-        if i == 1 {
+        if i == 1 || i == 2 {
             continue;
         }
         bad_packet[0] = i;
@@ -324,7 +349,7 @@ fn adc_v3_packet_unknown_type() {
 
     let mut bad_packet = LONG_ADC_V3_PACKET;
     for i in 0..=255 {
-        if i == 1 {
+        if i == 1 || i == 2 {
             continue;
         }
         bad_packet[0] = i;
@@ -645,6 +670,34 @@ fn adc_v3_packet_type() {
     );
 }
 
+#[test]
+fn adc_v3_packet_trigger_mode() {
+    assert_eq!(
+        AdcV3Packet::try_from(&SHORT_ADC_V3_PACKET[..])
+            .unwrap()
+            .trigger_mode(),
+        TriggerMode::External
+    );
+    assert_eq!(
+        AdcV3Packet::try_from(&LONG_ADC_V3_PACKET[..])
+            .unwrap()
+            .trigger_mode(),
+        TriggerMode::External
+    );
+
+    let mut self_trigger = SHORT_ADC_V3_PACKET;
+    self_trigger[0] = 2;
+    let packet = AdcV3Packet::try_from(&self_trigger[..]).unwrap();
+    assert_eq!(packet.trigger_mode(), TriggerMode::SelfTrigger);
+    assert_eq!(packet.packet_type(), 2);
+
+    let mut self_trigger = LONG_ADC_V3_PACKET;
+    self_trigger[0] = 2;
+    let packet = AdcV3Packet::try_from(&self_trigger[..]).unwrap();
+    assert_eq!(packet.trigger_mode(), TriggerMode::SelfTrigger);
+    assert_eq!(packet.packet_type(), 2);
+}
+
 #[test]
 fn adc_v3_packet_version() {
     assert_eq!(
@@ -712,6 +765,46 @@ fn adc_v3_packet_channel_id() {
     }
 }
 
+#[test]
+fn adc_v3_packet_channel_layout() {
+    let mut packet = SHORT_ADC_V3_PACKET;
+    for (channel, split, contiguous) in [
+        (
+            3,
+            Some(ChannelId::A16(Adc16ChannelId(3))),
+            Some(ChannelId::A16(Adc16ChannelId(3))),
+        ),
+        (16, None, Some(ChannelId::A32(Adc32ChannelId(0)))),
+        (47, None, Some(ChannelId::A32(Adc32ChannelId(31)))),
+        (48, None, None),
+        (128, Some(ChannelId::A32(Adc32ChannelId(0))), None),
+        (159, Some(ChannelId::A32(Adc32ChannelId(31))), None),
+        (160, None, None),
+    ] {
+        packet[5] = channel;
+        for (layout, expected) in [
+            (ChannelLayout::Split, split),
+            (ChannelLayout::Contiguous, contiguous),
+        ] {
+            let result = AdcV3Packet::try_from_slice_with_layout(&packet[..], layout);
+            match expected {
+                Some(expected) => assert_eq!(result.unwrap().channel_id(), expected),
+                None => assert!(matches!(
+                    result,
+                    Err(TryAdcPacketFromSliceError::UnknownChannelId(_))
+                )),
+            }
+        }
+    }
+    // The default layout is the same for all trigger modes.
+    packet[0] = 2;
+    packet[5] = 130;
+    assert_eq!(
+        AdcPacket::try_from(&packet[..]).unwrap().channel_id(),
+        ChannelId::A32(Adc32ChannelId(2))
+    );
+}
+
 #[test]
 fn adc_v3_packet_requested_samples() {
     assert_eq!(
@@ -940,6 +1033,22 @@ fn adc_packet_type() {
     );
 }
 
+#[test]
+fn adc_packet_trigger_mode() {
+    assert_eq!(
+        AdcPacket::try_from(&SHORT_ADC_V3_PACKET[..])
+            .unwrap()
+            .trigger_mode(),
+        TriggerMode::External
+    );
+
+    let mut self_trigger = LONG_ADC_V3_PACKET;
+    self_trigger[0] = 2;
+    let packet = AdcPacket::try_from(&self_trigger[..]).unwrap();
+    assert_eq!(packet.trigger_mode(), TriggerMode::SelfTrigger);
+    assert_eq!(packet.packet_type(), 2);
+}
+
 #[test]
 fn adc_packet_version() {
     assert_eq!(
@@ -1171,3 +1280,171 @@ fn adc_packet_is_suppression_enabled() {
         .is_suppression_enabled()
         .unwrap());
 }
+
+#[test]
+fn register_board_ok() {
+    let board_id = register_board("Y0", [1, 0, 0, 0, 0, 1]).unwrap();
+    assert_eq!(board_id.name(), "Y0");
+    assert_eq!(board_id.mac_address(), [1, 0, 0, 0, 0, 1]);
+
+    assert_eq!(BoardId::try_from("Y0").unwrap(), board_id);
+    assert_eq!(BoardId::try_from([1, 0, 0, 0, 0, 1]).unwrap(), board_id);
+    assert_eq!(
+        crate::midas::Adc32BankName::try_from("CY0F")
+            .unwrap()
+            .board_id(),
+        board_id
+    );
+}
+
+#[test]
+fn register_board_bad_name() {
+    for name in ["Y", "Y11", "y1", "Y_"] {
+        match register_board(name, [2, 0, 0, 0, 0, 1]) {
+            Err(RegisterBoardError::BadName { name: found }) => assert_eq!(found, name),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn register_board_duplicates() {
+    for pair in ALPHA16BOARDS {
+        match register_board(pair.0, [3, 0, 0, 0, 0, 1]) {
+            Err(RegisterBoardError::DuplicateName { name }) => assert_eq!(name, pair.0),
+            _ => unreachable!(),
+        }
+        match register_board("Y2", pair.1) {
+            Err(RegisterBoardError::DuplicateMacAddress { mac_address }) => {
+                assert_eq!(mac_address, pair.1)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    register_board("Y3", [4, 0, 0, 0, 0, 1]).unwrap();
+    assert!(register_board("Y3", [5, 0, 0, 0, 0, 1]).is_err());
+}
+
+#[test]
+fn register_boards_from_ron_ok() {
+    let contents = r#"[
+        ("Y4", [6, 0, 0, 0, 0, 1]),
+        ("Y5", [7, 0, 0, 0, 0, 1]),
+    ]"#;
+    let boards = register_boards_from_ron(contents).unwrap();
+    assert_eq!(boards.len(), 2);
+    assert_eq!(boards[0], BoardId::try_from("Y4").unwrap());
+    assert_eq!(boards[1], BoardId::try_from("Y5").unwrap());
+}
+
+#[test]
+fn register_boards_from_ron_all_or_nothing() {
+    let contents = r#"[
+        ("Y6", [8, 0, 0, 0, 0, 1]),
+        ("Y7", [8, 0, 0, 0, 0, 1]),
+    ]"#;
+    match register_boards_from_ron(contents) {
+        Err(RegisterBoardError::DuplicateMacAddress { mac_address }) => {
+            assert_eq!(mac_address, [8, 0, 0, 0, 0, 1])
+        }
+        _ => unreachable!(),
+    }
+    assert!(BoardId::try_from("Y6").is_err());
+    assert!(BoardId::try_from("Y7").is_err());
+}
+
+#[test]
+fn register_boards_from_ron_bad_format() {
+    assert!(matches!(
+        register_boards_from_ron("[(\"Y8\", [9, 0, 0, 0, 0])]"),
+        Err(RegisterBoardError::BadFormat { .. })
+    ));
+    assert!(BoardId::try_from("Y8").is_err());
+}
+
+#[test]
+fn emulate_suppression_short_waveform() {
+    let waveform = [0; 63];
+    match emulate_suppression(&waveform, 0, 0) {
+        Err(EmulateSuppressionError { found }) => assert_eq!(found, 63),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn emulate_suppression_baseline() {
+    let mut waveform = vec![0; 100];
+    assert_eq!(emulate_suppression(&waveform, 0, 0).unwrap().baseline(), 0);
+
+    waveform[0] = 64;
+    assert_eq!(emulate_suppression(&waveform, 0, 0).unwrap().baseline(), 1);
+
+    waveform[0] = -1;
+    assert_eq!(emulate_suppression(&waveform, 0, 0).unwrap().baseline(), -1);
+    // Samples after the baseline samples don't contribute.
+    waveform[0] = 0;
+    waveform[64] = 6400;
+    assert_eq!(emulate_suppression(&waveform, 0, 0).unwrap().baseline(), 0);
+}
+
+#[test]
+fn emulate_suppression_below_threshold() {
+    let mut waveform = vec![0; 100];
+    waveform[99] = -10;
+    // Positive pulses are never over threshold.
+    waveform[70] = i16::MAX;
+    // Baseline samples are not checked against the threshold.
+    waveform[63] = -11 * 64;
+    waveform[0] = 11 * 64;
+
+    let output = emulate_suppression(&waveform, 10, 0).unwrap();
+    assert!(!output.keep_bit());
+    assert_eq!(output.keep_last(), 0);
+    assert!(output.waveform().is_empty());
+}
+
+#[test]
+fn emulate_suppression_over_threshold() {
+    let mut waveform = vec![0; 100];
+    waveform[70] = -11;
+    waveform[80] = -11;
+
+    let output = emulate_suppression(&waveform, 10, 0).unwrap();
+    assert!(output.keep_bit());
+    assert_eq!(output.keep_last(), 42);
+    assert_eq!(output.waveform(), &waveform[..81]);
+
+    let output = emulate_suppression(&waveform, 10, 10).unwrap();
+    assert_eq!(output.waveform(), &waveform[..91]);
+
+    let output = emulate_suppression(&waveform, 10, 1000).unwrap();
+    assert_eq!(output.waveform(), &waveform[..]);
+}
+
+#[test]
+fn emulate_suppression_valid_packet() {
+    let requested_samples: u16 = 699;
+    let mut waveform = vec![100; usize::from(requested_samples) - 2];
+    for (i, index) in [64, 65, 200, 201, 696].into_iter().enumerate() {
+        waveform[index] = -1000;
+        for keep_more in [0, 1, 2, 50] {
+            let output = emulate_suppression(&waveform, 500, keep_more).unwrap();
+
+            let mut packet = vec![1, 3, 0, 1, 2, 3];
+            packet.extend(requested_samples.to_be_bytes());
+            packet.extend([0, 0, 0, 4, 0, 0, 216, 128, 57, 104, 142, 82]);
+            packet.extend([0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 6]);
+            packet.extend(output.waveform().iter().flat_map(|v| v.to_be_bytes()));
+            let footer = u16::try_from(output.keep_last()).unwrap() | (1 << 12) | (1 << 13);
+            packet.extend(footer.to_be_bytes());
+            packet.extend(output.baseline().to_be_bytes());
+
+            let packet = AdcV3Packet::try_from(&packet[..]).unwrap();
+            assert_eq!(packet.waveform(), output.waveform());
+            assert_eq!(packet.keep_last(), output.keep_last());
+            assert_eq!(packet.suppression_baseline(), output.baseline());
+        }
+        waveform[index] = 100 + i as i16;
+    }
+}
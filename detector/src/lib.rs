@@ -32,5 +32,11 @@ pub mod trigger;
 /// clock channel.
 pub mod chronobox;
 
+/// Data quality.
+///
+/// Per-channel occupancy, baseline RMS, saturation, and dead-channel flags
+/// accumulated over a stream of events. Shared by all the monitoring tools.
+pub mod quality;
+
 #[cfg(test)]
 mod tests;
@@ -1,4 +1,7 @@
+use lazy_static::lazy_static;
 use std::fmt;
+use std::ops::Range;
+use std::sync::RwLock;
 use thiserror::Error;
 
 // Only imported for documentation. If you notice that this is no longer the
@@ -124,6 +127,186 @@ const PADWING_BOARDS: [(&str, [u8; 6], u32); 71] = [
     ("91", [236, 40, 190, 114, 84, 2], 1925064940),
 ];
 
+lazy_static! {
+    // Boards registered at runtime (see `register_board`). These are only
+    // looked up after the known `PADWING_BOARDS`, and are guaranteed to not
+    // duplicate any of them.
+    static ref EXTRA_PADWING_BOARDS: RwLock<Vec<(&'static str, [u8; 6], u32)>> =
+        RwLock::new(Vec::new());
+}
+
+// Return the first known or registered board that satisfies the predicate.
+fn find_board<P>(predicate: P) -> Option<BoardId>
+where
+    P: Fn(&(&'static str, [u8; 6], u32)) -> bool,
+{
+    let extra = EXTRA_PADWING_BOARDS.read().unwrap();
+
+    PADWING_BOARDS
+        .iter()
+        .chain(extra.iter())
+        .find(|triplet| predicate(triplet))
+        .map(|&(name, mac_address, device_id)| BoardId {
+            name,
+            mac_address,
+            device_id,
+        })
+}
+
+/// The error type returned when registering a new [`BoardId`] fails.
+#[derive(Error, Debug)]
+pub enum RegisterBoardError {
+    /// The board name is not 2 ASCII digits (as required by the data bank
+    /// names).
+    #[error("bad board name `{name}`")]
+    BadName { name: String },
+    /// The device ID is not the first 4 bytes of the mac address as a little
+    /// endian integer.
+    #[error("device id `{device_id}` doesn't match mac address `{mac_address:?}`")]
+    DeviceIdMismatch {
+        mac_address: [u8; 6],
+        device_id: u32,
+    },
+    /// A board with the same name is already known.
+    #[error("duplicate board name `{name}`")]
+    DuplicateName { name: String },
+    /// A board with the same mac address is already known.
+    #[error("duplicate mac address `{mac_address:?}`")]
+    DuplicateMacAddress { mac_address: [u8; 6] },
+    /// A board with the same device ID is already known.
+    #[error("duplicate device id `{device_id}`")]
+    DuplicateDeviceId { device_id: u32 },
+    /// The list of boards could not be parsed.
+    #[error("bad board list format: {message}")]
+    BadFormat { message: String },
+}
+
+/// Register a new PadWing board at runtime.
+///
+/// All conversions to [`BoardId`] (e.g. from a data bank name) will recognize
+/// the new board afterwards. This is meant to handle boards that were installed
+/// after the release of this crate; please open an issue/PR to add the new
+/// board to the list of known boards.
+///
+/// # Examples
+///
+/// ```
+/// # use alpha_g_detector::padwing::RegisterBoardError;
+/// # fn main() -> Result<(), RegisterBoardError> {
+/// use alpha_g_detector::padwing::{register_board, BoardId};
+///
+/// let board_id = register_board("99", [1, 2, 3, 4, 5, 6], 67305985)?;
+/// assert_eq!(BoardId::try_from("99").unwrap(), board_id);
+///
+/// assert!(register_board("00", [1, 2, 3, 4, 5, 7], 67305985).is_err());
+/// # Ok(())
+/// # }
+/// ```
+pub fn register_board(
+    name: &str,
+    mac_address: [u8; 6],
+    device_id: u32,
+) -> Result<BoardId, RegisterBoardError> {
+    register_boards([(name.to_string(), mac_address, device_id)])
+        .map(|mut boards| boards.pop().unwrap())
+}
+
+/// Register new PadWing boards at runtime from the contents of a RON file.
+///
+/// The file has the same format as the list of known boards i.e. a list of
+/// `("name", [mac address], device_id)` triplets. Either all the boards are
+/// registered, or none of them are. See [`register_board`] for more details.
+///
+/// # Examples
+///
+/// ```
+/// # use alpha_g_detector::padwing::RegisterBoardError;
+/// # fn main() -> Result<(), RegisterBoardError> {
+/// use alpha_g_detector::padwing::{register_boards_from_ron, BoardId};
+///
+/// let contents = r#"[("98", [10, 2, 3, 4, 5, 6], 67305994)]"#;
+/// let boards = register_boards_from_ron(contents)?;
+/// assert_eq!(boards, [BoardId::try_from("98").unwrap()]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn register_boards_from_ron(contents: &str) -> Result<Vec<BoardId>, RegisterBoardError> {
+    // Serde expects fixed-size arrays to be tuples in RON. Use a `Vec` to
+    // allow the mac address to be written as a list (same as in this file).
+    let triplets: Vec<(String, Vec<u8>, u32)> =
+        ron::from_str(contents).map_err(|e| RegisterBoardError::BadFormat {
+            message: e.to_string(),
+        })?;
+    let triplets = triplets
+        .into_iter()
+        .map(|(name, mac_address, device_id)| {
+            let mac_address =
+                mac_address
+                    .try_into()
+                    .map_err(|mac: Vec<u8>| RegisterBoardError::BadFormat {
+                        message: format!("mac address `{mac:?}` is not 6 bytes long"),
+                    })?;
+            Ok((name, mac_address, device_id))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    register_boards(triplets)
+}
+
+fn register_boards<I>(triplets: I) -> Result<Vec<BoardId>, RegisterBoardError>
+where
+    I: IntoIterator<Item = (String, [u8; 6], u32)>,
+{
+    // Hold the lock during validation to avoid races between two threads
+    // registering the same board.
+    let mut extra = EXTRA_PADWING_BOARDS.write().unwrap();
+
+    let mut new: Vec<(String, [u8; 6], u32)> = Vec::new();
+    for (name, mac_address, device_id) in triplets {
+        if name.len() != 2 || !name.chars().all(|c| c.is_ascii_digit()) {
+            return Err(RegisterBoardError::BadName { name });
+        }
+        if u32::from_le_bytes(mac_address[..4].try_into().unwrap()) != device_id {
+            return Err(RegisterBoardError::DeviceIdMismatch {
+                mac_address,
+                device_id,
+            });
+        }
+        let known = PADWING_BOARDS
+            .iter()
+            .chain(extra.iter())
+            .map(|&(name, mac_address, device_id)| (name, mac_address, device_id))
+            .chain(new.iter().map(|(name, mac, id)| (name.as_str(), *mac, *id)));
+        for triplet in known {
+            if name == triplet.0 {
+                return Err(RegisterBoardError::DuplicateName { name });
+            }
+            if mac_address == triplet.1 {
+                return Err(RegisterBoardError::DuplicateMacAddress { mac_address });
+            }
+            if device_id == triplet.2 {
+                return Err(RegisterBoardError::DuplicateDeviceId { device_id });
+            }
+        }
+        new.push((name, mac_address, device_id));
+    }
+
+    Ok(new
+        .into_iter()
+        .map(|(name, mac_address, device_id)| {
+            // Registered boards live until the end of the program anyway.
+            let name = Box::leak(name.into_boxed_str());
+            extra.push((name, mac_address, device_id));
+
+            BoardId {
+                name,
+                mac_address,
+                device_id,
+            }
+        })
+        .collect())
+}
+
 /// Identity of a physical PadWing board.
 ///
 /// It is important to notice that a [`BoardId`] is different to a
@@ -141,16 +324,7 @@ impl TryFrom<&str> for BoardId {
     type Error = ParseBoardIdError;
 
     fn try_from(name: &str) -> Result<Self, Self::Error> {
-        for triplet in PADWING_BOARDS {
-            if name == triplet.0 {
-                return Ok(BoardId {
-                    name: triplet.0,
-                    mac_address: triplet.1,
-                    device_id: triplet.2,
-                });
-            }
-        }
-        Err(ParseBoardIdError {
+        find_board(|triplet| name == triplet.0).ok_or(ParseBoardIdError {
             input: name.to_string(),
         })
     }
@@ -159,32 +333,15 @@ impl TryFrom<[u8; 6]> for BoardId {
     type Error = TryBoardIdFromMacAddressError;
 
     fn try_from(mac: [u8; 6]) -> Result<Self, Self::Error> {
-        for triplet in PADWING_BOARDS {
-            if mac == triplet.1 {
-                return Ok(BoardId {
-                    name: triplet.0,
-                    mac_address: triplet.1,
-                    device_id: triplet.2,
-                });
-            }
-        }
-        Err(TryBoardIdFromMacAddressError { input: mac })
+        find_board(|triplet| mac == triplet.1).ok_or(TryBoardIdFromMacAddressError { input: mac })
     }
 }
 impl TryFrom<u32> for BoardId {
     type Error = TryBoardIdFromUnsignedError;
 
     fn try_from(device_id: u32) -> Result<Self, Self::Error> {
-        for triplet in PADWING_BOARDS {
-            if device_id == triplet.2 {
-                return Ok(BoardId {
-                    name: triplet.0,
-                    mac_address: triplet.1,
-                    device_id: triplet.2,
-                });
-            }
-        }
-        Err(TryBoardIdFromUnsignedError { input: device_id })
+        find_board(|triplet| device_id == triplet.2)
+            .ok_or(TryBoardIdFromUnsignedError { input: device_id })
     }
 }
 impl BoardId {
@@ -278,6 +435,26 @@ impl TryFrom<u8> for AfterId {
         }
     }
 }
+impl AfterId {
+    /// Return an iterator over all the valid channels of an AFTER chip. This
+    /// includes the reset, FPN, and pad channels, in readout order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use alpha_g_detector::padwing::{AfterId, ChannelId};
+    ///
+    /// let pads = AfterId::A
+    ///     .channels()
+    ///     .filter(|c| matches!(c, ChannelId::Pad(_)))
+    ///     .count();
+    /// assert_eq!(pads, 72);
+    /// ```
+    pub fn channels(&self) -> impl Iterator<Item = ChannelId> {
+        // Same conversion from readout index for all AFTER chips.
+        (1..=79).map(|i| ChannelId::try_from(i).unwrap())
+    }
+}
 impl TryFrom<char> for AfterId {
     type Error = ParseAfterIdError;
 
@@ -1332,6 +1509,36 @@ impl PwbV2Packet {
             None
         }
     }
+    /// Return the [`WaveformStats`] of the waveform received by a channel in a
+    /// PadWing board. The peak amplitude and charge are calculated over the
+    /// given `window` of samples. Return [`None`] if the given channel was not
+    /// sent.
+    ///
+    /// See [`waveform_stats`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use alpha_g_detector::padwing::{ChannelId, PwbV2Packet};
+    ///
+    /// let payload = [2, 65, 0, 0, 236, 40, 255, 135, 84, 2, 1, 0, 2, 0, 0, 0, 0, 0, 0, 0, 100, 0, 255, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 200, 0, 6, 7, 204, 204, 204, 204];
+    /// let packet = PwbV2Packet::try_from(&payload[..])?;
+    ///
+    /// assert!(packet.waveform_stats_at(ChannelId::try_from(10)?, 0..511)?.is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn waveform_stats_at(
+        &self,
+        channel: ChannelId,
+        window: Range<usize>,
+    ) -> Result<Option<WaveformStats>, CalculateWaveformStatsError> {
+        self.waveform_at(channel)
+            .map(|waveform| waveform_stats(waveform, window))
+            .transpose()
+    }
 }
 
 impl TryFrom<&[u8]> for PwbV2Packet {
@@ -1966,6 +2173,36 @@ impl PwbPacket {
             Self::V2(packet) => packet.waveform_at(channel),
         }
     }
+    /// Return the [`WaveformStats`] of the waveform received by a channel in a
+    /// PadWing board. The peak amplitude and charge are calculated over the
+    /// given `window` of samples. Return [`None`] if the given channel was not
+    /// sent.
+    ///
+    /// See [`waveform_stats`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// use alpha_g_detector::padwing::{ChannelId, PwbPacket};
+    ///
+    /// let payload = [2, 65, 0, 0, 236, 40, 255, 135, 84, 2, 1, 0, 2, 0, 0, 0, 0, 0, 0, 0, 100, 0, 255, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 200, 0, 6, 7, 204, 204, 204, 204];
+    /// let packet = PwbPacket::try_from(&payload[..])?;
+    ///
+    /// assert!(packet.waveform_stats_at(ChannelId::try_from(10)?, 0..511)?.is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn waveform_stats_at(
+        &self,
+        channel: ChannelId,
+        window: Range<usize>,
+    ) -> Result<Option<WaveformStats>, CalculateWaveformStatsError> {
+        match self {
+            Self::V2(packet) => packet.waveform_stats_at(channel, window),
+        }
+    }
     /// Return [`true`] if this PWB packet is a [`PwbV2Packet`], and [`false`]
     /// otherwise.
     ///
@@ -2038,5 +2275,157 @@ pub fn suppression_baseline(
     Ok(Some((num / 64).try_into().unwrap()))
 }
 
+/// Emulate the data suppression of a Padwing board.
+///
+/// Given a complete waveform, return what the firmware would keep with a given
+/// data suppression `threshold`. A channel is either sent completely or not at
+/// all, hence this returns either the complete waveform or [`None`]. The
+/// complete waveform is always kept if there is no data suppression for the
+/// given run number.
+///
+/// Cathode pad signals are negative pulses, hence a sample is over threshold if
+/// it is more than `threshold` below the [`suppression_baseline`].
+///
+/// # Examples
+///
+/// ```
+/// # use alpha_g_detector::padwing::CalculateSuppressionBaselineError;
+/// # fn main() -> Result<(), CalculateSuppressionBaselineError> {
+/// use alpha_g_detector::padwing::emulate_suppression;
+///
+/// let mut waveform = vec![0; 100];
+/// assert!(emulate_suppression(0, &waveform, 10)?.is_none());
+///
+/// waveform[80] = -11;
+/// assert_eq!(emulate_suppression(0, &waveform, 10)?, Some(&waveform[..]));
+/// # Ok(())
+/// # }
+/// ```
+pub fn emulate_suppression(
+    run_number: u32,
+    waveform: &[i16],
+    threshold: u16,
+) -> Result<Option<&[i16]>, CalculateSuppressionBaselineError> {
+    let Some(baseline) = suppression_baseline(run_number, waveform)? else {
+        return Ok(Some(waveform));
+    };
+    // Same as the baseline calculation, the first 4 samples are ignored.
+    let is_over_threshold = waveform[4..]
+        .iter()
+        // Convert to i32 to avoid overflow
+        .any(|&v| i32::from(baseline) - i32::from(v) > i32::from(threshold));
+
+    Ok(is_over_threshold.then_some(waveform))
+}
+
+/// The error type returned when calculating the [`WaveformStats`] of a PWB
+/// waveform fails.
+#[derive(Error, Debug)]
+pub enum CalculateWaveformStatsError {
+    /// The waveform is too short to calculate the baseline.
+    #[error("short waveform (expected at least 68 samples, found `{found}`)")]
+    ShortWaveform { found: usize },
+    /// The integration window is empty or out of bounds.
+    #[error("bad integration window `{window:?}` (waveform has `{len}` samples)")]
+    BadWindow { window: Range<usize>, len: usize },
+}
+
+/// Summary statistics of a PWB channel waveform.
+///
+/// Cathode pad signals are negative pulses with respect to the baseline. Both
+/// the peak amplitude and the integrated charge are calculated with this
+/// polarity i.e. a signal pulse gives positive values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WaveformStats {
+    baseline: f64,
+    peak_amplitude: f64,
+    charge: f64,
+    is_saturated: bool,
+}
+
+impl WaveformStats {
+    /// Return the baseline of the waveform. This is the average over the same
+    /// samples used by the Padwing board firmware to calculate the data
+    /// suppression baseline.
+    pub fn baseline(&self) -> f64 {
+        self.baseline
+    }
+    /// Return the maximum amplitude (below the baseline) within the
+    /// integration window.
+    pub fn peak_amplitude(&self) -> f64 {
+        self.peak_amplitude
+    }
+    /// Return the signed integral of the waveform relative to the baseline
+    /// within the integration window i.e. the sum of `baseline - sample` over
+    /// all samples in the window. Samples above the baseline contribute
+    /// negatively.
+    pub fn charge(&self) -> f64 {
+        self.charge
+    }
+    /// Return [`true`] if any sample in the waveform (not only within the
+    /// integration window) is equal to [`PWB_MIN`] or [`PWB_MAX`].
+    pub fn is_saturated(&self) -> bool {
+        self.is_saturated
+    }
+}
+
+/// Calculate the [`WaveformStats`] of a PWB waveform. The peak amplitude and
+/// charge are calculated over the given `window` of samples.
+///
+/// # Examples
+///
+/// ```
+/// # use alpha_g_detector::padwing::CalculateWaveformStatsError;
+/// # fn main() -> Result<(), CalculateWaveformStatsError> {
+/// use alpha_g_detector::padwing::waveform_stats;
+///
+/// let mut waveform = vec![100; 200];
+/// waveform[150] = 40;
+/// waveform[151] = 70;
+///
+/// let stats = waveform_stats(&waveform, 100..200)?;
+/// assert_eq!(stats.baseline(), 100.0);
+/// assert_eq!(stats.peak_amplitude(), 60.0);
+/// assert_eq!(stats.charge(), 90.0);
+/// assert!(!stats.is_saturated());
+/// # Ok(())
+/// # }
+/// ```
+pub fn waveform_stats(
+    waveform: &[i16],
+    window: Range<usize>,
+) -> Result<WaveformStats, CalculateWaveformStatsError> {
+    if waveform.len() < 68 {
+        return Err(CalculateWaveformStatsError::ShortWaveform {
+            found: waveform.len(),
+        });
+    }
+    if window.is_empty() || window.end > waveform.len() {
+        return Err(CalculateWaveformStatsError::BadWindow {
+            window,
+            len: waveform.len(),
+        });
+    }
+    // Same samples as `suppression_baseline`, but without truncating the
+    // average to an integer.
+    let baseline = waveform[4..][..64]
+        .iter()
+        .map(|&n| f64::from(n))
+        .sum::<f64>()
+        / 64.0;
+
+    let pulse = waveform[window].iter().map(|&n| baseline - f64::from(n));
+    let peak_amplitude = pulse.clone().reduce(f64::max).unwrap();
+    let charge = pulse.sum();
+    let is_saturated = waveform.iter().any(|&n| n == PWB_MIN || n == PWB_MAX);
+
+    Ok(WaveformStats {
+        baseline,
+        peak_amplitude,
+        charge,
+        is_saturated,
+    })
+}
+
 #[cfg(test)]
 mod tests;
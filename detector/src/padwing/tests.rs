@@ -79,6 +79,32 @@ fn try_from_char_after() {
     }
 }
 
+#[test]
+fn after_id_channels() {
+    for after in [AfterId::A, AfterId::B, AfterId::C, AfterId::D] {
+        let channels: Vec<ChannelId> = after.channels().collect();
+        assert_eq!(channels.len(), 79);
+        for (i, channel) in (1..=79).zip(channels) {
+            assert_eq!(channel, ChannelId::try_from(i).unwrap());
+        }
+        let resets = after
+            .channels()
+            .filter(|c| matches!(c, ChannelId::Reset(_)))
+            .count();
+        assert_eq!(resets, 3);
+        let fpns = after
+            .channels()
+            .filter(|c| matches!(c, ChannelId::Fpn(_)))
+            .count();
+        assert_eq!(fpns, 4);
+        let pads = after
+            .channels()
+            .filter(|c| matches!(c, ChannelId::Pad(_)))
+            .count();
+        assert_eq!(pads, 72);
+    }
+}
+
 const CHUNK: [u8; 28] = [
     236, 40, 255, 135, 2, 0, 0, 0, 3, 0, 0, 1, 5, 0, 1, 0, 143, 203, 131, 81, 255, 0, 0, 0, 122,
     92, 155, 159,
@@ -1728,3 +1754,251 @@ fn suppression_baseline_ok() {
         _ => unreachable!(),
     }
 }
+
+#[test]
+fn waveform_stats_short_waveform() {
+    let waveform = [0; 67];
+    match waveform_stats(&waveform, 0..67) {
+        Err(CalculateWaveformStatsError::ShortWaveform { found }) => {
+            assert_eq!(found, 67);
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn waveform_stats_bad_window() {
+    let waveform = [0; 100];
+    for window in [0..101, 50..50, 100..101] {
+        match waveform_stats(&waveform, window.clone()) {
+            Err(CalculateWaveformStatsError::BadWindow { window: found, len }) => {
+                assert_eq!(found, window);
+                assert_eq!(len, 100);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn waveform_stats_ok() {
+    let mut waveform = vec![10; 100];
+    let stats = waveform_stats(&waveform, 0..100).unwrap();
+    assert_eq!(stats.baseline(), 10.0);
+    assert_eq!(stats.peak_amplitude(), 0.0);
+    assert_eq!(stats.charge(), 0.0);
+    assert!(!stats.is_saturated());
+    // Samples outside [4, 68) do not contribute to the baseline.
+    waveform[0] = 0;
+    waveform[68] = 0;
+    let stats = waveform_stats(&waveform, 0..100).unwrap();
+    assert_eq!(stats.baseline(), 10.0);
+    assert_eq!(stats.peak_amplitude(), 10.0);
+    assert_eq!(stats.charge(), 20.0);
+    // Only the window contributes to the amplitude and charge.
+    let stats = waveform_stats(&waveform, 1..68).unwrap();
+    assert_eq!(stats.peak_amplitude(), 0.0);
+    assert_eq!(stats.charge(), 0.0);
+
+    waveform[4] = 42;
+    let stats = waveform_stats(&waveform, 69..100).unwrap();
+    assert_eq!(stats.baseline(), 10.5);
+    assert_eq!(stats.peak_amplitude(), 0.5);
+    assert_eq!(stats.charge(), 15.5);
+}
+
+#[test]
+fn waveform_stats_charge_above_baseline() {
+    let mut waveform = vec![10; 100];
+    waveform[80] = 4;
+    waveform[81] = 12;
+    let stats = waveform_stats(&waveform, 70..100).unwrap();
+    assert_eq!(stats.peak_amplitude(), 6.0);
+    assert_eq!(stats.charge(), 4.0);
+}
+
+#[test]
+fn waveform_stats_saturation() {
+    let mut waveform = vec![0; 100];
+    waveform[99] = PWB_MIN;
+    assert!(waveform_stats(&waveform, 0..10).unwrap().is_saturated());
+
+    waveform[99] = PWB_MAX;
+    assert!(waveform_stats(&waveform, 0..10).unwrap().is_saturated());
+
+    waveform[99] = PWB_MAX - 1;
+    assert!(!waveform_stats(&waveform, 0..10).unwrap().is_saturated());
+}
+
+#[test]
+fn pwb_v2_packet_waveform_stats_at() {
+    let packet = PwbV2Packet::try_from(&ODD_PWB_V2_PACKET[..]).unwrap();
+
+    assert!(packet
+        .waveform_stats_at(ChannelId::try_from(1).unwrap(), 0..5)
+        .unwrap()
+        .is_none());
+    match packet.waveform_stats_at(ChannelId::try_from(57).unwrap(), 0..5) {
+        Err(CalculateWaveformStatsError::ShortWaveform { found }) => {
+            assert_eq!(found, 5);
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn pwb_packet_waveform_stats_at() {
+    let packet = PwbPacket::try_from(&ODD_PWB_V2_PACKET[..]).unwrap();
+
+    assert!(packet
+        .waveform_stats_at(ChannelId::try_from(1).unwrap(), 0..5)
+        .unwrap()
+        .is_none());
+    match packet.waveform_stats_at(ChannelId::try_from(57).unwrap(), 0..5) {
+        Err(CalculateWaveformStatsError::ShortWaveform { found }) => {
+            assert_eq!(found, 5);
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn register_board_ok() {
+    let board_id = register_board("99", [1, 0, 0, 0, 0, 1], 1).unwrap();
+    assert_eq!(board_id.name(), "99");
+    assert_eq!(board_id.mac_address(), [1, 0, 0, 0, 0, 1]);
+    assert_eq!(board_id.device_id(), 1);
+
+    assert_eq!(BoardId::try_from("99").unwrap(), board_id);
+    assert_eq!(BoardId::try_from([1, 0, 0, 0, 0, 1]).unwrap(), board_id);
+    assert_eq!(BoardId::try_from(1).unwrap(), board_id);
+    assert_eq!(
+        PadwingBankName::try_from("PC99").unwrap().board_id(),
+        board_id
+    );
+}
+
+#[test]
+fn register_board_bad_name() {
+    for name in ["9", "911", "Y1", "9_"] {
+        match register_board(name, [2, 0, 0, 0, 0, 1], 2) {
+            Err(RegisterBoardError::BadName { name: found }) => assert_eq!(found, name),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn register_board_device_id_mismatch() {
+    match register_board("98", [3, 0, 0, 0, 0, 1], 4) {
+        Err(RegisterBoardError::DeviceIdMismatch {
+            mac_address,
+            device_id,
+        }) => {
+            assert_eq!(mac_address, [3, 0, 0, 0, 0, 1]);
+            assert_eq!(device_id, 4);
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn register_board_duplicates() {
+    for triplet in PADWING_BOARDS {
+        match register_board(triplet.0, [4, 0, 0, 0, 0, 1], 4) {
+            Err(RegisterBoardError::DuplicateName { name }) => assert_eq!(name, triplet.0),
+            _ => unreachable!(),
+        }
+        match register_board("97", triplet.1, triplet.2) {
+            Err(RegisterBoardError::DuplicateMacAddress { mac_address }) => {
+                assert_eq!(mac_address, triplet.1)
+            }
+            _ => unreachable!(),
+        }
+        let mut mac_address = triplet.1;
+        mac_address[5] += 1;
+        match register_board("97", mac_address, triplet.2) {
+            Err(RegisterBoardError::DuplicateDeviceId { device_id }) => {
+                assert_eq!(device_id, triplet.2)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    register_board("96", [5, 0, 0, 0, 0, 1], 5).unwrap();
+    assert!(register_board("96", [6, 0, 0, 0, 0, 1], 6).is_err());
+}
+
+#[test]
+fn register_boards_from_ron_ok() {
+    let contents = r#"[
+        ("95", [7, 0, 0, 0, 0, 1], 7),
+        ("94", [8, 0, 0, 0, 0, 1], 8),
+    ]"#;
+    let boards = register_boards_from_ron(contents).unwrap();
+    assert_eq!(boards.len(), 2);
+    assert_eq!(boards[0], BoardId::try_from("95").unwrap());
+    assert_eq!(boards[1], BoardId::try_from("94").unwrap());
+}
+
+#[test]
+fn register_boards_from_ron_all_or_nothing() {
+    let contents = r#"[
+        ("93", [9, 0, 0, 0, 0, 1], 9),
+        ("93", [10, 0, 0, 0, 0, 1], 10),
+    ]"#;
+    match register_boards_from_ron(contents) {
+        Err(RegisterBoardError::DuplicateName { name }) => assert_eq!(name, "93"),
+        _ => unreachable!(),
+    }
+    assert!(BoardId::try_from("93").is_err());
+}
+
+#[test]
+fn register_boards_from_ron_bad_format() {
+    assert!(matches!(
+        register_boards_from_ron("[(\"92\", [11, 0, 0, 0, 0, 1])]"),
+        Err(RegisterBoardError::BadFormat { .. })
+    ));
+    assert!(BoardId::try_from("92").is_err());
+}
+
+#[test]
+fn emulate_suppression_short_slice() {
+    let slice = [0; 67];
+    match emulate_suppression(0, &slice, 0) {
+        Err(CalculateSuppressionBaselineError { found }) => {
+            assert_eq!(found, 67);
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn emulate_suppression_below_threshold() {
+    let mut waveform = vec![0; 100];
+    waveform[99] = -10;
+    // Positive pulses are never over threshold.
+    waveform[70] = PWB_MAX;
+    // The first 4 samples are ignored.
+    waveform[0] = PWB_MIN;
+
+    assert!(emulate_suppression(0, &waveform, 10).unwrap().is_none());
+}
+
+#[test]
+fn emulate_suppression_over_threshold() {
+    let mut waveform = vec![0; 100];
+    waveform[99] = -11;
+    assert_eq!(
+        emulate_suppression(0, &waveform, 10).unwrap().unwrap(),
+        &waveform[..]
+    );
+
+    waveform[99] = 0;
+    waveform[4] = -11 * 64;
+    assert_eq!(
+        emulate_suppression(0, &waveform, 10).unwrap().unwrap(),
+        &waveform[..]
+    );
+}
@@ -0,0 +1,244 @@
+use crate::alpha16::{ADC_MAX, ADC_MIN};
+use crate::padwing::{PWB_MAX, PWB_MIN};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+use thiserror::Error;
+
+/// Digitizer-dependent properties of a waveform needed to calculate data
+/// quality metrics.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WaveformFormat {
+    /// Samples used to calculate the baseline (and its RMS).
+    pub baseline_samples: Range<usize>,
+    /// Minimum value at which the waveform saturates.
+    pub min: i16,
+    /// Maximum value at which the waveform saturates.
+    pub max: i16,
+}
+
+/// [`WaveformFormat`] of the Alpha16 waveforms. The baseline samples are the
+/// same used by the Alpha16 data suppression.
+pub const ALPHA16_FORMAT: WaveformFormat = WaveformFormat {
+    baseline_samples: 0..64,
+    min: ADC_MIN,
+    max: ADC_MAX,
+};
+
+/// [`WaveformFormat`] of the PWB waveforms. The baseline samples are the same
+/// used by the PWB data suppression.
+pub const PWB_FORMAT: WaveformFormat = WaveformFormat {
+    baseline_samples: 4..68,
+    min: PWB_MIN,
+    max: PWB_MAX,
+};
+
+/// The error type returned when adding a waveform to a [`QualityMonitor`]
+/// fails.
+#[derive(Debug, Error)]
+#[error("short waveform (expected at least `{min_expected}` samples, found `{found}`)")]
+pub struct AddWaveformError {
+    found: usize,
+    min_expected: usize,
+}
+
+/// Data quality metrics of a single channel accumulated over multiple events.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChannelQuality {
+    hits: u64,
+    saturated: u64,
+    baseline_samples: u64,
+    // Sum of the squared deviations of each baseline sample from the mean
+    // baseline of its own waveform.
+    baseline_sum_sq: f64,
+}
+
+impl ChannelQuality {
+    /// Return the number of events with data in this channel.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+    /// Return the number of events in which the waveform of this channel
+    /// saturated.
+    pub fn saturated(&self) -> u64 {
+        self.saturated
+    }
+    /// Return the fraction of events (with data in this channel) in which the
+    /// waveform saturated. Returns [`None`] if the channel has no hits.
+    pub fn saturation_fraction(&self) -> Option<f64> {
+        (self.hits > 0).then(|| self.saturated as f64 / self.hits as f64)
+    }
+    /// Return the RMS of the baseline samples with respect to the mean baseline
+    /// of each individual waveform (i.e. the electronic noise). Returns
+    /// [`None`] if the channel has no hits.
+    pub fn baseline_rms(&self) -> Option<f64> {
+        (self.baseline_samples > 0)
+            .then(|| (self.baseline_sum_sq / self.baseline_samples as f64).sqrt())
+    }
+}
+
+/// Accumulator of per-channel data quality metrics over a stream of events.
+///
+/// Channels are identified by an arbitrary key e.g.
+/// [`TpcWirePosition`](crate::alpha16::aw_map::TpcWirePosition) or
+/// [`TpcPadPosition`](crate::padwing::map::TpcPadPosition). Use a separate
+/// monitor for each type of channel.
+///
+/// # Examples
+///
+/// ```
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// use alpha_g_detector::quality::{QualityMonitor, PWB_FORMAT};
+///
+/// let mut monitor = QualityMonitor::new();
+///
+/// let waveform = vec![100; 511];
+/// monitor.add_event([(0, &waveform[..])], &PWB_FORMAT)?;
+/// monitor.add_event([], &PWB_FORMAT)?;
+///
+/// assert_eq!(monitor.events(), 2);
+/// assert_eq!(monitor.occupancy(&0), 0.5);
+/// assert_eq!(monitor.channel(&0).unwrap().baseline_rms(), Some(0.0));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "K: Eq + Hash + Serialize",
+    deserialize = "K: Eq + Hash + Deserialize<'de>"
+))]
+pub struct QualityMonitor<K> {
+    events: u64,
+    channels: HashMap<K, ChannelQuality>,
+}
+
+impl<K> Default for QualityMonitor<K> {
+    fn default() -> Self {
+        Self {
+            events: 0,
+            channels: HashMap::new(),
+        }
+    }
+}
+
+// Cannot be derived because comparing the inner HashMap requires `K: Eq + Hash`.
+impl<K: Eq + Hash> PartialEq for QualityMonitor<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.events == other.events && self.channels == other.channels
+    }
+}
+
+impl<K: Eq + Hash> QualityMonitor<K> {
+    /// Create an empty monitor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add all the waveforms of a single event. Channels without data in the
+    /// event (e.g. due to data suppression) should simply not be included.
+    ///
+    /// Returns an error if a waveform is shorter than the baseline samples of
+    /// the given [`WaveformFormat`]. The monitor is left unchanged in that
+    /// case.
+    pub fn add_event<'a, I>(
+        &mut self,
+        waveforms: I,
+        format: &WaveformFormat,
+    ) -> Result<(), AddWaveformError>
+    where
+        I: IntoIterator<Item = (K, &'a [i16])>,
+    {
+        let mut updates = Vec::new();
+        for (channel, waveform) in waveforms {
+            let Some(baseline) = waveform.get(format.baseline_samples.clone()) else {
+                return Err(AddWaveformError {
+                    found: waveform.len(),
+                    min_expected: format.baseline_samples.end,
+                });
+            };
+            let mean = baseline.iter().map(|&v| f64::from(v)).sum::<f64>() / baseline.len() as f64;
+            let sum_sq = baseline
+                .iter()
+                .map(|&v| (f64::from(v) - mean).powi(2))
+                .sum::<f64>();
+            let is_saturated = waveform.iter().any(|&v| v <= format.min || v >= format.max);
+
+            updates.push((channel, baseline.len(), sum_sq, is_saturated));
+        }
+
+        self.events += 1;
+        for (channel, samples, sum_sq, is_saturated) in updates {
+            let quality = self.channels.entry(channel).or_default();
+            quality.hits += 1;
+            quality.saturated += u64::from(is_saturated);
+            quality.baseline_samples += samples as u64;
+            quality.baseline_sum_sq += sum_sq;
+        }
+
+        Ok(())
+    }
+    /// Combine the metrics accumulated by a different monitor (e.g. over a
+    /// different set of events) into this monitor.
+    pub fn merge(&mut self, other: Self) {
+        self.events += other.events;
+        for (channel, other) in other.channels {
+            let quality = self.channels.entry(channel).or_default();
+            quality.hits += other.hits;
+            quality.saturated += other.saturated;
+            quality.baseline_samples += other.baseline_samples;
+            quality.baseline_sum_sq += other.baseline_sum_sq;
+        }
+    }
+    /// Return the total number of events.
+    pub fn events(&self) -> u64 {
+        self.events
+    }
+    /// Return the metrics of a given channel. Returns [`None`] if the channel
+    /// has not had any hits.
+    pub fn channel(&self, channel: &K) -> Option<&ChannelQuality> {
+        self.channels.get(channel)
+    }
+    /// Return an iterator over all channels with at least one hit and their
+    /// metrics (in arbitrary order).
+    pub fn channels(&self) -> impl Iterator<Item = (&K, &ChannelQuality)> {
+        self.channels.iter()
+    }
+    /// Return the fraction of events with data in a given channel.
+    pub fn occupancy(&self, channel: &K) -> f64 {
+        if self.events == 0 {
+            return 0.0;
+        }
+        let hits = self.channels.get(channel).map_or(0, |quality| quality.hits);
+
+        hits as f64 / self.events as f64
+    }
+    /// Return the channels (out of all the given `channels`) that look dead.
+    /// A channel is flagged as dead if its occupancy is less than
+    /// `min_occupancy`, or if its baseline RMS is less than `min_baseline_rms`
+    /// (e.g. a disconnected preamplifier gives a flat waveform).
+    ///
+    /// Nothing is flagged if the monitor has no events.
+    pub fn dead_channels<I>(&self, channels: I, min_occupancy: f64, min_baseline_rms: f64) -> Vec<K>
+    where
+        I: IntoIterator<Item = K>,
+    {
+        if self.events == 0 {
+            return Vec::new();
+        }
+
+        channels
+            .into_iter()
+            .filter(|channel| {
+                self.occupancy(channel) < min_occupancy
+                    || self
+                        .channel(channel)
+                        .and_then(ChannelQuality::baseline_rms)
+                        .is_some_and(|rms| rms < min_baseline_rms)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,116 @@
+use super::*;
+use crate::alpha16::aw_map::{TpcWirePosition, TPC_ANODE_WIRES};
+
+fn wire(index: usize) -> TpcWirePosition {
+    TpcWirePosition::try_from(index).unwrap()
+}
+
+#[test]
+fn channel_quality_empty() {
+    let quality = ChannelQuality::default();
+    assert_eq!(quality.hits(), 0);
+    assert_eq!(quality.saturated(), 0);
+    assert_eq!(quality.saturation_fraction(), None);
+    assert_eq!(quality.baseline_rms(), None);
+}
+
+#[test]
+fn quality_monitor_add_event() {
+    let mut monitor = QualityMonitor::new();
+    let mut noisy = vec![0; 200];
+    for (i, v) in noisy.iter_mut().enumerate().take(64) {
+        *v = if i % 2 == 0 { 10 } else { -10 };
+    }
+    let mut saturated = vec![0; 200];
+    saturated[150] = ADC_MAX;
+
+    monitor
+        .add_event(
+            [(wire(0), &noisy[..]), (wire(1), &saturated[..])],
+            &ALPHA16_FORMAT,
+        )
+        .unwrap();
+    monitor
+        .add_event([(wire(0), &noisy[..])], &ALPHA16_FORMAT)
+        .unwrap();
+
+    assert_eq!(monitor.events(), 2);
+    assert_eq!(monitor.occupancy(&wire(0)), 1.0);
+    assert_eq!(monitor.occupancy(&wire(1)), 0.5);
+    assert_eq!(monitor.occupancy(&wire(2)), 0.0);
+
+    let quality = monitor.channel(&wire(0)).unwrap();
+    assert_eq!(quality.hits(), 2);
+    assert_eq!(quality.saturation_fraction(), Some(0.0));
+    assert_eq!(quality.baseline_rms(), Some(10.0));
+
+    let quality = monitor.channel(&wire(1)).unwrap();
+    assert_eq!(quality.saturation_fraction(), Some(1.0));
+    assert_eq!(quality.baseline_rms(), Some(0.0));
+
+    assert!(monitor.channel(&wire(2)).is_none());
+    assert_eq!(monitor.channels().count(), 2);
+}
+
+#[test]
+fn quality_monitor_short_waveform() {
+    let mut monitor = QualityMonitor::new();
+    let good = [0; 100];
+    let short = [0; 67];
+
+    assert!(monitor
+        .add_event([(0, &good[..]), (1, &short[..])], &PWB_FORMAT)
+        .is_err());
+    assert_eq!(monitor, QualityMonitor::new());
+}
+
+#[test]
+fn quality_monitor_merge() {
+    let waveform = [0; 100];
+    let mut first = QualityMonitor::new();
+    first.add_event([(0, &waveform[..])], &PWB_FORMAT).unwrap();
+    let mut second = QualityMonitor::new();
+    second.add_event([(1, &waveform[..])], &PWB_FORMAT).unwrap();
+    second.add_event([(0, &waveform[..])], &PWB_FORMAT).unwrap();
+
+    first.merge(second);
+    assert_eq!(first.events(), 3);
+    assert_eq!(first.channel(&0).unwrap().hits(), 2);
+    assert_eq!(first.channel(&1).unwrap().hits(), 1);
+}
+
+#[test]
+fn quality_monitor_dead_channels() {
+    let mut monitor = QualityMonitor::new();
+    assert!(monitor
+        .dead_channels((0..TPC_ANODE_WIRES).map(wire), 0.5, 1.0)
+        .is_empty());
+
+    let mut noisy = [0; 100];
+    noisy[1] = 10;
+    let flat = [0; 100];
+    for _ in 0..10 {
+        monitor
+            .add_event(
+                [(wire(0), &noisy[..]), (wire(1), &flat[..])],
+                &ALPHA16_FORMAT,
+            )
+            .unwrap();
+    }
+
+    let dead = monitor.dead_channels((0..3).map(wire), 0.5, 1.0);
+    assert_eq!(dead, vec![wire(1), wire(2)]);
+}
+
+#[test]
+fn quality_monitor_serde_round_trip() {
+    let waveform = [0; 100];
+    let mut monitor = QualityMonitor::new();
+    monitor
+        .add_event([(wire(5), &waveform[..])], &ALPHA16_FORMAT)
+        .unwrap();
+
+    let serialized = ron::to_string(&monitor).unwrap();
+    let deserialized: QualityMonitor<TpcWirePosition> = ron::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, monitor);
+}
@@ -0,0 +1,256 @@
+use crate::calibration::bv::gain::try_bv_gain;
+use crate::signal_processing::ConstantFractionDiscriminator;
+use crate::MapBvGainError;
+use alpha_g_detector::alpha16::ADC16_RATE;
+use std::f64::consts::TAU;
+use thiserror::Error;
+use uom::si::angle::radian;
+use uom::si::f64::*;
+use uom::si::length::meter;
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+
+/// Number of scintillator bars in the Barrel Veto.
+pub const BV_BARS: usize = 64;
+
+/// Length of a Barrel Veto scintillator bar. The center of the bars is at
+/// `z = 0`.
+///
+/// This is the nominal length of the EJ-200 bars in the Barrel Veto design,
+/// i.e. 150 mm longer than the rTPC
+/// ([`DETECTOR_LENGTH`](alpha_g_detector::padwing::map::DETECTOR_LENGTH)) at
+/// each end to fully cover its acceptance.
+pub const BAR_LENGTH: Length = Length {
+    dimension: uom::lib::marker::PhantomData,
+    units: uom::lib::marker::PhantomData,
+    value: 2.604,
+};
+
+/// Radial position of the center of the Barrel Veto scintillator bars.
+///
+/// This is the nominal inner radius of the Barrel Veto (215 mm) plus half the
+/// thickness of a bar (15 mm).
+pub const BAR_RADIUS: Length = Length {
+    dimension: uom::lib::marker::PhantomData,
+    units: uom::lib::marker::PhantomData,
+    value: 0.2225,
+};
+
+// Effective propagation speed of the scintillation light along a bar (i.e.
+// including the reflections on the bar surface). The EJ-200 datasheet gives a
+// refractive index of 1.58 (i.e. 1.9e8 m/s); the light reflected on the bar
+// surface travels a longer path than the bar length, which reduces the speed
+// along the bar axis.
+//
+// If the reconstructed `z` of the hits is ever calibrated against tracks,
+// update this value with the measured slope.
+const EFFECTIVE_LIGHT_SPEED: Velocity = Velocity {
+    dimension: uom::lib::marker::PhantomData,
+    units: uom::lib::marker::PhantomData,
+    value: 1.2e8,
+};
+// Attenuation length of the scintillation light along a bar. The EJ-200
+// datasheet gives a bulk attenuation length of 3.8 m; the losses on every
+// reflection on the bar surface make the effective attenuation length shorter.
+// This is only used for uncalibrated hits; the gain calibration measures the
+// attenuation length of each bar (see `calibration::bv::gain`).
+const ATTENUATION_LENGTH: Length = Length {
+    dimension: uom::lib::marker::PhantomData,
+    units: uom::lib::marker::PhantomData,
+    value: 1.7,
+};
+
+/// The error type returned when conversion from [`usize`] to [`BarPosition`]
+/// fails.
+#[derive(Debug, Error)]
+#[error("unknown conversion from index `{index}` to BarPosition")]
+pub struct TryBarPositionFromIndexError {
+    index: usize,
+}
+
+/// Position of a scintillator bar in the Barrel Veto.
+///
+/// Bars are indexed counter-clockwise (as seen from the positive `z` axis) in
+/// the range `0..BV_BARS`. The center of the 0th bar is at `phi = 0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BarPosition(usize);
+
+impl TryFrom<usize> for BarPosition {
+    type Error = TryBarPositionFromIndexError;
+
+    fn try_from(index: usize) -> Result<Self, Self::Error> {
+        if index < BV_BARS {
+            Ok(Self(index))
+        } else {
+            Err(TryBarPositionFromIndexError { index })
+        }
+    }
+}
+
+impl From<BarPosition> for usize {
+    fn from(position: BarPosition) -> Self {
+        position.0
+    }
+}
+
+impl BarPosition {
+    /// Return the azimuthal angle of the center of the bar.
+    pub fn phi(&self) -> Angle {
+        Angle::new::<radian>(TAU * self.0 as f64 / BV_BARS as f64)
+    }
+}
+
+/// Pulse observed by a single SiPM.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SipmPulse {
+    /// Time with respect to the beginning of the waveform.
+    pub t: Time,
+    /// Amplitude of the pulse in arbitrary units.
+    pub amplitude: f64,
+}
+
+/// Return all the pulses in a baseline-subtracted SiPM `signal`.
+///
+/// The time of each pulse is given by the constant-fraction discriminator, and
+/// its amplitude is the largest (absolute) sample between the time of the
+/// pulse and the time of the next one (or the end of the signal).
+pub fn sipm_pulses(signal: &[f64], cfd: &ConstantFractionDiscriminator) -> Vec<SipmPulse> {
+    let times = cfd.pulse_times(signal);
+
+    times
+        .iter()
+        .enumerate()
+        .map(|(i, &t)| {
+            let start = t.floor() as usize;
+            let end = times
+                .get(i + 1)
+                .map_or(signal.len(), |&next| next.floor() as usize);
+            let amplitude = signal[start..end]
+                .iter()
+                .map(|v| v.abs())
+                .fold(0.0, f64::max);
+
+            SipmPulse {
+                t: Time::new::<second>(t / ADC16_RATE),
+                amplitude,
+            }
+        })
+        .collect()
+}
+
+/// Particle hit on a Barrel Veto scintillator bar.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BvHit {
+    /// Bar that was hit.
+    pub bar: BarPosition,
+    /// Axial position of the hit. The center of the detector is at `z = 0`.
+    pub z: Length,
+    /// Time of the hit, with the same reference as the SiPM pulses.
+    pub t: Time,
+    /// Deposited energy in arbitrary units. This is corrected for the light
+    /// attenuation along the bar. Only energies from [`calibrated_bv_hits`]
+    /// are comparable across different bars.
+    pub energy: f64,
+}
+
+impl BvHit {
+    /// Return the azimuthal angle of the hit.
+    pub fn phi(&self) -> Angle {
+        self.bar.phi()
+    }
+}
+
+/// Combine the SiPM pulses at both ends of a scintillator bar into hits. The
+/// `top` SiPM is the one at positive `z`.
+///
+/// Given that light takes a finite time to reach each end of the bar, the
+/// difference between the arrival times gives the axial position of the hit.
+/// Similarly, the attenuation cancels out in the geometric mean of both
+/// amplitudes. A pulse is only combined with a pulse at the other end if their
+/// time difference is physical (i.e. corresponds to a position inside the
+/// bar). Pairs are made greedily from the smallest time difference. Pulses
+/// without a partner are ignored.
+pub fn bv_hits(bar: BarPosition, top: &[SipmPulse], bottom: &[SipmPulse]) -> Vec<BvHit> {
+    hits_with_gain(bar, top, bottom, [1.0, 1.0], ATTENUATION_LENGTH)
+}
+
+/// Same as [`bv_hits`], but the SiPM amplitudes are corrected by the gain
+/// calibration of the bar for the given run number. The attenuation length of
+/// each bar is also taken from the calibration.
+///
+/// This makes the energy of hits comparable across all bars (e.g. to select
+/// minimum ionizing particles). Returns an error if there is no calibration for
+/// the bar in the given run number.
+pub fn calibrated_bv_hits(
+    run_number: u32,
+    bar: BarPosition,
+    top: &[SipmPulse],
+    bottom: &[SipmPulse],
+) -> Result<Vec<BvHit>, MapBvGainError> {
+    let gain = try_bv_gain(run_number, bar.0)?;
+
+    Ok(hits_with_gain(
+        bar,
+        top,
+        bottom,
+        [gain.top, gain.bottom],
+        Length::new::<meter>(gain.attenuation_length),
+    ))
+}
+
+// Implementation of `bv_hits` given the [top, bottom] SiPM gains and the
+// attenuation length of the bar.
+fn hits_with_gain(
+    bar: BarPosition,
+    top: &[SipmPulse],
+    bottom: &[SipmPulse],
+    [top_gain, bottom_gain]: [f64; 2],
+    attenuation_length: Length,
+) -> Vec<BvHit> {
+    let max_time_difference = BAR_LENGTH / EFFECTIVE_LIGHT_SPEED;
+
+    let mut pairs = Vec::new();
+    for (i, t) in top.iter().enumerate() {
+        for (j, b) in bottom.iter().enumerate() {
+            let diff = (b.t - t.t).abs();
+            if diff <= max_time_difference {
+                pairs.push((diff, i, j));
+            }
+        }
+    }
+    // Stable sort to keep the output deterministic.
+    pairs.sort_by(|a, b| a.0.get::<second>().total_cmp(&b.0.get::<second>()));
+
+    let mut used_top = vec![false; top.len()];
+    let mut used_bottom = vec![false; bottom.len()];
+    let mut hits = Vec::new();
+    for (_, i, j) in pairs {
+        if used_top[i] || used_bottom[j] {
+            continue;
+        }
+        used_top[i] = true;
+        used_bottom[j] = true;
+
+        let (t, b) = (top[i], bottom[j]);
+        // Light reaches the top end first if the hit is at positive `z`.
+        let z = 0.5 * EFFECTIVE_LIGHT_SPEED * (b.t - t.t);
+        let time = 0.5 * (t.t + b.t) - 0.5 * BAR_LENGTH / EFFECTIVE_LIGHT_SPEED;
+        // The product of both amplitudes is proportional to
+        // exp(-BAR_LENGTH / ATTENUATION_LENGTH), independent of `z`.
+        let attenuation = (0.5 * BAR_LENGTH / attenuation_length).get::<ratio>().exp();
+        let energy = (t.amplitude / top_gain * b.amplitude / bottom_gain).sqrt() * attenuation;
+
+        hits.push(BvHit {
+            bar,
+            z,
+            t: time,
+            energy,
+        });
+    }
+    hits.sort_by(|a, b| a.t.get::<second>().total_cmp(&b.t.get::<second>()));
+
+    hits
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,160 @@
+use super::*;
+use crate::signal_processing::Polarity;
+use uom::si::length::{centimeter, meter};
+use uom::si::time::nanosecond;
+
+#[test]
+fn bar_position_try_from_index() {
+    for index in 0..BV_BARS {
+        let bar = BarPosition::try_from(index).unwrap();
+        assert_eq!(usize::from(bar), index);
+    }
+    assert!(BarPosition::try_from(BV_BARS).is_err());
+}
+
+#[test]
+fn bar_position_phi() {
+    let bar = BarPosition::try_from(0).unwrap();
+    assert_eq!(bar.phi(), Angle::new::<radian>(0.0));
+
+    let bar = BarPosition::try_from(BV_BARS / 4).unwrap();
+    let diff = bar.phi() - Angle::HALF_TURN / 2.0;
+    assert!(diff.abs() < Angle::new::<radian>(1e-12));
+}
+
+#[test]
+fn trivial_sipm_pulses() {
+    let cfd = ConstantFractionDiscriminator {
+        fraction: 0.5,
+        threshold: 10.0,
+        polarity: Polarity::Negative,
+    };
+    let mut signal = vec![0.0; 100];
+    signal[20..25].copy_from_slice(&[-50.0, -100.0, -60.0, -30.0, -5.0]);
+    signal[60..63].copy_from_slice(&[-100.0, -200.0, -50.0]);
+
+    let pulses = sipm_pulses(&signal, &cfd);
+    assert_eq!(pulses.len(), 2);
+
+    let diff = pulses[0].t - Time::new::<nanosecond>(200.0);
+    assert!(diff.abs() < Time::new::<nanosecond>(1e-6));
+    assert_eq!(pulses[0].amplitude, 100.0);
+    let diff = pulses[1].t - Time::new::<nanosecond>(600.0);
+    assert!(diff.abs() < Time::new::<nanosecond>(1e-6));
+    assert_eq!(pulses[1].amplitude, 200.0);
+}
+
+fn pulses_at(z: Length, t: Time, energy: f64) -> (SipmPulse, SipmPulse) {
+    let to_top = 0.5 * BAR_LENGTH - z;
+    let to_bottom = 0.5 * BAR_LENGTH + z;
+    let top = SipmPulse {
+        t: t + to_top / EFFECTIVE_LIGHT_SPEED,
+        amplitude: energy * (-(to_top / ATTENUATION_LENGTH).get::<ratio>()).exp(),
+    };
+    let bottom = SipmPulse {
+        t: t + to_bottom / EFFECTIVE_LIGHT_SPEED,
+        amplitude: energy * (-(to_bottom / ATTENUATION_LENGTH).get::<ratio>()).exp(),
+    };
+
+    (top, bottom)
+}
+
+#[test]
+fn trivial_bv_hits() {
+    let bar = BarPosition::try_from(7).unwrap();
+    for z in [-1.0, -0.3, 0.0, 0.5, 1.2] {
+        let z = Length::new::<meter>(z);
+        let t = Time::new::<nanosecond>(100.0);
+        let (top, bottom) = pulses_at(z, t, 300.0);
+
+        let hits = bv_hits(bar, &[top], &[bottom]);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].bar, bar);
+        assert_eq!(hits[0].phi(), bar.phi());
+
+        let diff = hits[0].z - z;
+        assert!(diff.abs() < Length::new::<meter>(1e-9));
+        let diff = hits[0].t - t;
+        assert!(diff.abs() < Time::new::<nanosecond>(1e-6));
+        assert!((hits[0].energy - 300.0).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn bv_hits_unphysical_time_difference() {
+    let bar = BarPosition::try_from(0).unwrap();
+    let (top, mut bottom) = pulses_at(
+        Length::new::<centimeter>(0.0),
+        Time::new::<nanosecond>(100.0),
+        300.0,
+    );
+    bottom.t += 1.1 * BAR_LENGTH / EFFECTIVE_LIGHT_SPEED;
+
+    assert!(bv_hits(bar, &[top], &[bottom]).is_empty());
+    assert!(bv_hits(bar, &[top], &[]).is_empty());
+}
+
+#[test]
+fn bv_hits_multiple_pulses() {
+    let bar = BarPosition::try_from(0).unwrap();
+    let (top_1, bottom_1) = pulses_at(
+        Length::new::<centimeter>(30.0),
+        Time::new::<nanosecond>(500.0),
+        100.0,
+    );
+    let (top_2, bottom_2) = pulses_at(
+        Length::new::<centimeter>(-50.0),
+        Time::new::<nanosecond>(100.0),
+        200.0,
+    );
+
+    let hits = bv_hits(bar, &[top_1, top_2], &[bottom_2, bottom_1]);
+    assert_eq!(hits.len(), 2);
+    // Sorted by time.
+    let diff = hits[0].z - Length::new::<centimeter>(-50.0);
+    assert!(diff.abs() < Length::new::<meter>(1e-9));
+    let diff = hits[1].z - Length::new::<centimeter>(30.0);
+    assert!(diff.abs() < Length::new::<meter>(1e-9));
+}
+
+#[test]
+fn calibrated_bv_hits_simulation() {
+    let bar = BarPosition::try_from(12).unwrap();
+    let (top, bottom) = pulses_at(
+        Length::new::<meter>(0.4),
+        Time::new::<nanosecond>(50.0),
+        120.0,
+    );
+
+    let hits = calibrated_bv_hits(u32::MAX, bar, &[top], &[bottom]).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert!((hits[0].energy - 120.0).abs() < 1e-6);
+}
+
+#[test]
+fn calibrated_bv_hits_missing_map() {
+    let bar = BarPosition::try_from(0).unwrap();
+    let (top, bottom) = pulses_at(
+        Length::new::<meter>(0.0),
+        Time::new::<nanosecond>(50.0),
+        120.0,
+    );
+
+    assert!(calibrated_bv_hits(0, bar, &[top], &[bottom]).is_err());
+}
+
+#[test]
+fn hits_with_gain_correction() {
+    let bar = BarPosition::try_from(3).unwrap();
+    let (mut top, mut bottom) = pulses_at(
+        Length::new::<meter>(-0.7),
+        Time::new::<nanosecond>(10.0),
+        80.0,
+    );
+    top.amplitude *= 2.0;
+    bottom.amplitude *= 0.5;
+
+    let hits = hits_with_gain(bar, &[top], &[bottom], [2.0, 0.5], ATTENUATION_LENGTH);
+    assert_eq!(hits.len(), 1);
+    assert!((hits[0].energy - 80.0).abs() < 1e-6);
+}
@@ -0,0 +1,79 @@
+use std::ops::RangeInclusive;
+use thiserror::Error;
+
+/// The error type returned when adding a value to [`ByRun`] (or declaring
+/// simulated runs) for a range of runs that overlaps with a previously added
+/// range.
+#[derive(Debug, Error)]
+#[error("run range `{new:?}` overlaps with `{existing:?}`")]
+pub struct OverlappingRunsError {
+    pub(crate) new: RangeInclusive<u32>,
+    pub(crate) existing: RangeInclusive<u32>,
+}
+
+/// Collection of values for different ranges of run numbers.
+///
+/// The running conditions (e.g. gas mixture, drift field, or magnetic field)
+/// have changed between data taking campaigns. Use this to select the correct
+/// value given a run number. The default value is used for any run number not
+/// explicitly added.
+#[derive(Clone, Debug)]
+pub struct ByRun<T> {
+    values: Vec<(RangeInclusive<u32>, T)>,
+    default: T,
+}
+
+impl<T: Default> Default for ByRun<T> {
+    fn default() -> Self {
+        Self::with_default(T::default())
+    }
+}
+
+impl<T: Default> ByRun<T> {
+    /// Create an empty collection. All run numbers map to `T::default()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T> ByRun<T> {
+    /// Create an empty collection. All run numbers map to the given default
+    /// value.
+    pub fn with_default(default: T) -> Self {
+        Self {
+            values: Vec::new(),
+            default,
+        }
+    }
+    /// Use the given value for a range of run numbers. Returns an error if the
+    /// range overlaps with a previously added range.
+    pub fn insert(
+        &mut self,
+        runs: RangeInclusive<u32>,
+        value: T,
+    ) -> Result<(), OverlappingRunsError> {
+        if let Some((existing, _)) = self
+            .values
+            .iter()
+            .find(|(existing, _)| existing.start() <= runs.end() && runs.start() <= existing.end())
+        {
+            return Err(OverlappingRunsError {
+                new: runs,
+                existing: existing.clone(),
+            });
+        }
+        self.values.push((runs, value));
+
+        Ok(())
+    }
+    /// Return the value for a given run number.
+    pub fn get(&self, run_number: u32) -> &T {
+        self.values
+            .iter()
+            .find(|(runs, _)| runs.contains(&run_number))
+            .map_or(&self.default, |(_, value)| value)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,31 @@
+use super::*;
+
+#[test]
+fn by_run_overlapping_ranges() {
+    let mut by_run = ByRun::new();
+    by_run.insert(100..=200, 1).unwrap();
+    assert!(by_run.insert(200..=300, 2).is_err());
+    assert!(by_run.insert(50..=100, 2).is_err());
+    assert!(by_run.insert(120..=130, 2).is_err());
+    assert!(by_run.insert(0..=u32::MAX, 2).is_err());
+    by_run.insert(201..=300, 2).unwrap();
+
+    assert_eq!(*by_run.get(100), 1);
+    assert_eq!(*by_run.get(200), 1);
+    assert_eq!(*by_run.get(201), 2);
+    assert_eq!(*by_run.get(300), 2);
+}
+
+#[test]
+fn by_run_default_value() {
+    let by_run: ByRun<u32> = ByRun::new();
+    for run_number in [0, 100, u32::MAX] {
+        assert_eq!(*by_run.get(run_number), 0);
+    }
+
+    let mut by_run = ByRun::with_default(7);
+    by_run.insert(10..=20, 1).unwrap();
+    for run_number in [0, 9, 21, u32::MAX] {
+        assert_eq!(*by_run.get(run_number), 7);
+    }
+}
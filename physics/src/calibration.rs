@@ -1,3 +1,13 @@
+use crate::calibration::format::{Calibration, CalibrationHeader, ParseCalibrationFileError};
+use crate::OverlappingRunsError;
+use lazy_static::lazy_static;
+use std::any::Any;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+
 // This is a macro definition for a convenient way to include multiple files
 // as byte arrays. It takes a directory and multiple file names.
 // The contents of all files are embedded at compile time.
@@ -13,3 +23,358 @@ macro_rules! includes {
 pub(crate) mod wires;
 // Pad calibration
 pub(crate) mod pads;
+// Per-channel timing (T0) calibration of both wires and pads
+pub(crate) mod time;
+// Per-channel software amplitude thresholds of both wires and pads
+pub(crate) mod threshold;
+// Azimuthal correction of the SpacePoints
+pub(crate) mod phi_correction;
+// Header (provenance) shared by all calibration files
+pub(crate) mod format;
+// Barrel Veto calibration
+pub(crate) mod bv;
+// All rTPC calibrations of a single run resolved at once
+pub(crate) mod set;
+
+// Directory with calibration files that take precedence over the embedded
+// ones. This makes it possible to iterate on a new calibration without having
+// to recompile (and redistribute) everything every time.
+//
+// The layout of the directory is the same as the embedded `data/calibration`
+// directory e.g. `<dir>/wires/gain/`. Each file is named after the first run
+// number for which it is valid, and it is valid until the next file:
+//     <dir>/wires/gain/11084.json
+//     <dir>/wires/gain/11356.json
+//     <dir>/wires/gain/simulation.json
+// The format of each file is the same as the embedded files of the
+// corresponding calibration (see the `format` module). A file is ignored if
+// the run range in its header does not contain the run number. If no file
+// applies to a given run number, the embedded calibration is used instead.
+//
+// The `simulation` file applies to all simulated runs (see `SIMULATED_RUNS`),
+// except those that were declared with their own calibration directory.
+const CALIBRATION_DIR_ENV: &str = "ALPHA_G_CALIBRATION_DIR";
+
+lazy_static! {
+    static ref CALIBRATION_DIR: RwLock<Option<PathBuf>> =
+        RwLock::new(std::env::var_os(CALIBRATION_DIR_ENV).map(Into::into));
+    // Files are only read from disk once. The key is the calibration kind (e.g.
+    // "wires/gain") and the run number.
+    static ref EXTERNAL_CALIBRATIONS: RwLock<
+        HashMap<(&'static str, u32), Option<ExternalCalibration>>,
+    > = Default::default();
+    // Ranges of run numbers that correspond to simulated (instead of real)
+    // data. Run number `u32::MAX` is always a simulated run with the ideal
+    // calibration unless it is explicitly declared otherwise.
+    static ref SIMULATED_RUNS: RwLock<
+        Vec<(RangeInclusive<u32>, SimulationCalibration)>,
+    > = Default::default();
+}
+
+type ExternalCalibration = Arc<dyn Any + Send + Sync>;
+
+pub(crate) fn set_calibration_dir(dir: Option<PathBuf>) {
+    *CALIBRATION_DIR.write().unwrap() = dir;
+    EXTERNAL_CALIBRATIONS.write().unwrap().clear();
+}
+
+/// Calibration of a range of simulated runs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum SimulationCalibration {
+    /// Same calibration as run number [`u32::MAX`] i.e. the embedded
+    /// simulation calibration (unless overridden by a `simulation` file in the
+    /// calibration directory).
+    #[default]
+    Ideal,
+    /// Calibration files in a directory with the same layout as the
+    /// calibration directory, where each file is named `simulation` e.g.
+    /// `<dir>/wires/gain/simulation.json`. Calibrations that are missing from
+    /// this directory are ideal.
+    Directory(PathBuf),
+}
+
+pub(crate) fn declare_simulated_runs(
+    runs: RangeInclusive<u32>,
+    calibration: SimulationCalibration,
+) -> Result<(), OverlappingRunsError> {
+    let mut simulated_runs = SIMULATED_RUNS.write().unwrap();
+    if let Some((existing, _)) = simulated_runs
+        .iter()
+        .find(|(existing, _)| existing.start() <= runs.end() && runs.start() <= existing.end())
+    {
+        return Err(OverlappingRunsError {
+            new: runs,
+            existing: existing.clone(),
+        });
+    }
+    simulated_runs.push((runs, calibration));
+    drop(simulated_runs);
+
+    EXTERNAL_CALIBRATIONS.write().unwrap().clear();
+
+    Ok(())
+}
+
+pub(crate) fn clear_simulated_runs() {
+    SIMULATED_RUNS.write().unwrap().clear();
+    EXTERNAL_CALIBRATIONS.write().unwrap().clear();
+}
+
+// Return the calibration of a simulated run. `None` if the run number
+// corresponds to real data.
+pub(crate) fn simulation_calibration(run_number: u32) -> Option<SimulationCalibration> {
+    SIMULATED_RUNS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|(runs, _)| runs.contains(&run_number))
+        .map(|(_, calibration)| calibration.clone())
+        .or((run_number == u32::MAX).then_some(SimulationCalibration::Ideal))
+}
+
+pub(crate) fn is_simulated_run(run_number: u32) -> bool {
+    run_number == u32::MAX
+        || SIMULATED_RUNS
+            .read()
+            .unwrap()
+            .iter()
+            .any(|(runs, _)| runs.contains(&run_number))
+}
+
+/// The error type returned when loading a calibration file from the calibration
+/// directory fails.
+#[derive(Debug, Error)]
+pub enum LoadExternalCalibrationError {
+    /// Error reading the calibration directory or file.
+    #[error("failed to read `{path:?}`")]
+    IoError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The contents of a calibration file are not in the expected format.
+    #[error("bad calibration file `{path:?}`")]
+    BadFormat {
+        path: PathBuf,
+        source: ParseCalibrationFileError,
+    },
+}
+
+// Find the file in `<dir>/<kind>/` that applies to a given run number.
+fn external_file(
+    dir: &Path,
+    kind: &str,
+    extension: &str,
+    run_number: u32,
+) -> Result<Option<PathBuf>, LoadExternalCalibrationError> {
+    let kind_dir = dir.join(kind);
+    if !kind_dir.is_dir() {
+        return Ok(None);
+    }
+    if is_simulated_run(run_number) {
+        let path = kind_dir.join(format!("simulation.{extension}"));
+        return Ok(path.is_file().then_some(path));
+    }
+
+    let entries =
+        std::fs::read_dir(&kind_dir).map_err(|source| LoadExternalCalibrationError::IoError {
+            path: kind_dir.clone(),
+            source,
+        })?;
+    let mut best: Option<(u32, PathBuf)> = None;
+    for entry in entries {
+        let path = entry
+            .map_err(|source| LoadExternalCalibrationError::IoError {
+                path: kind_dir.clone(),
+                source,
+            })?
+            .path();
+        if path.extension().and_then(|e| e.to_str()) != Some(extension) {
+            continue;
+        }
+        let Some(first_run) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if first_run <= run_number && best.as_ref().is_none_or(|(b, _)| first_run > *b) {
+            best = Some((first_run, path));
+        }
+    }
+
+    Ok(best.map(|(_, path)| path))
+}
+
+// Return the calibration from the calibration directory that applies to a
+// given run number (if any).
+pub(crate) fn try_external_calibration<T>(
+    // Relative path of the calibration within the calibration directory e.g.
+    // "wires/gain". This is also the kind in the header of the file.
+    kind: &'static str,
+    extension: &str,
+    run_number: u32,
+    parse: fn(&[u8]) -> Result<T, String>,
+) -> Result<Option<Arc<Calibration<T>>>, LoadExternalCalibrationError>
+where
+    T: Send + Sync + 'static,
+{
+    let downcast = |calibration: Option<ExternalCalibration>| {
+        // A given `kind` is always parsed into the same type.
+        calibration.map(|calibration| calibration.downcast().unwrap())
+    };
+    if let Some(calibration) = EXTERNAL_CALIBRATIONS
+        .read()
+        .unwrap()
+        .get(&(kind, run_number))
+    {
+        return Ok(downcast(calibration.clone()));
+    }
+
+    let dir = match simulation_calibration(run_number) {
+        Some(SimulationCalibration::Directory(dir)) => Some(dir),
+        _ => CALIBRATION_DIR.read().unwrap().clone(),
+    };
+    let calibration = match &dir {
+        Some(dir) => match external_file(dir, kind, extension, run_number)? {
+            Some(path) => {
+                let bytes = std::fs::read(&path).map_err(|source| {
+                    LoadExternalCalibrationError::IoError {
+                        path: path.clone(),
+                        source,
+                    }
+                })?;
+                let calibration = Calibration::try_from_bytes(kind, &bytes, parse)
+                    .map_err(|source| LoadExternalCalibrationError::BadFormat { path, source })?;
+                calibration
+                    .header
+                    .contains(run_number)
+                    .then(|| Arc::new(calibration) as ExternalCalibration)
+            }
+            None => None,
+        },
+        None => None,
+    };
+    EXTERNAL_CALIBRATIONS
+        .write()
+        .unwrap()
+        .insert((kind, run_number), calibration.clone());
+
+    Ok(downcast(calibration))
+}
+
+/// Policy to obtain the gain of a channel that is missing from the calibration
+/// of a given run number (e.g. a channel that was dead during the calibration
+/// run).
+///
+/// Calibrations are grouped in eras: the range of runs for which a single
+/// calibration file is valid. Fallbacks only look at the eras of real data,
+/// i.e. simulation runs never fall back to data calibrations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GainFallback {
+    /// Return an error.
+    #[default]
+    Error,
+    /// Use the gain of the closest previous era that has the channel.
+    Previous,
+    /// Use the gain of the closest next era that has the channel.
+    Next,
+    /// Linearly interpolate (as a function of run number) between the gains of
+    /// the closest previous and next eras that have the channel. Each era is
+    /// located at its first run. If the channel is only available in one
+    /// direction, that gain is used instead.
+    Interpolate,
+}
+
+// Gain calibration of a set of channels (e.g. wires or pads).
+type GainCalibration<K> = Arc<Calibration<HashMap<K, f64>>>;
+
+// Return the gain of `key` for a given run number following a fallback policy.
+// The error of `try_calibration` is returned if there is no calibration for the
+// run number, and `missing` if no gain is found for the channel.
+pub(crate) fn gain_with_fallback<K, E>(
+    run_number: u32,
+    key: &K,
+    fallback: GainFallback,
+    try_calibration: fn(u32) -> Result<GainCalibration<K>, E>,
+    missing: E,
+) -> Result<f64, E>
+where
+    K: Eq + std::hash::Hash,
+{
+    let calibration = try_calibration(run_number)?;
+    if let Some(&gain) = calibration.data.get(key) {
+        return Ok(gain);
+    }
+    if calibration.header.first_run == u32::MAX {
+        return Err(missing);
+    }
+    // Walk through the eras in a given direction until the channel is found.
+    // Each step has to move strictly away from the current era to avoid
+    // looping forever with inconsistent run ranges.
+    let find = |backwards: bool| {
+        let mut header = calibration.header.clone();
+        loop {
+            let run = if backwards {
+                header.first_run.checked_sub(1)?
+            } else {
+                header.last_run?.checked_add(1)?
+            };
+            let neighbor = try_calibration(run).ok()?;
+            let first_run = neighbor.header.first_run;
+            let moved = if backwards {
+                first_run < header.first_run
+            } else {
+                first_run > header.first_run
+            };
+            if first_run == u32::MAX || !moved {
+                return None;
+            }
+            if let Some(&gain) = neighbor.data.get(key) {
+                return Some((first_run, gain));
+            }
+            header = neighbor.header.clone();
+        }
+    };
+    let previous = || find(true);
+    let next = || find(false);
+
+    let gain = match fallback {
+        GainFallback::Error => None,
+        GainFallback::Previous => previous().map(|(_, gain)| gain),
+        GainFallback::Next => next().map(|(_, gain)| gain),
+        GainFallback::Interpolate => match (previous(), next()) {
+            (Some((run_0, gain_0)), Some((run_1, gain_1))) => {
+                let fraction = f64::from(run_number - run_0) / f64::from(run_1 - run_0);
+                Some(gain_0 + fraction * (gain_1 - gain_0))
+            }
+            (Some((_, gain)), None) | (None, Some((_, gain))) => Some(gain),
+            (None, None) => None,
+        },
+    };
+
+    gain.ok_or(missing)
+}
+
+// Return the header of all the calibrations that are used for a given run
+// number. Calibrations that are not available are skipped.
+pub(crate) fn calibration_headers(run_number: u32) -> Vec<CalibrationHeader> {
+    [
+        wires::baseline::try_wire_baseline_header(run_number).ok(),
+        wires::gain::try_wire_gain_header(run_number).ok(),
+        pads::baseline::try_pad_baseline_header(run_number).ok(),
+        pads::gain::try_pad_gain_header(run_number).ok(),
+        time::try_wire_time_offset_header(run_number).ok(),
+        time::try_pad_time_offset_header(run_number).ok(),
+        threshold::try_wire_threshold_header(run_number).ok(),
+        threshold::try_pad_threshold_header(run_number).ok(),
+        phi_correction::try_phi_correction_header(run_number).ok(),
+        bv::gain::try_bv_gain_header(run_number).ok(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,2 @@
+// Gain and attenuation calibration
+pub(crate) mod gain;
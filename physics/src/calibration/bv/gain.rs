@@ -0,0 +1,112 @@
+use crate::calibration::format::{Calibration, CalibrationHeader};
+use crate::calibration::{
+    is_simulated_run, try_external_calibration, LoadExternalCalibrationError,
+};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+// Each bar is calibrated independently. The gain of each SiPM is relative to a
+// reference, such that the calibrated energy of a hit is comparable across all
+// bars. The attenuation length (in meters) of the scintillation light also
+// changes slightly from bar to bar.
+//
+// Bars are identified by their index in the range `0..BV_BARS`.
+
+includes! {
+    DATA_PATH = "../../../data/calibration/bv/gain/";
+    // All the following files are embedded at compile time.
+    // Add new files to the list below.
+    BYTES_SIMULATION = "simulation_complete.json",
+}
+
+lazy_static! {
+    // Whenever a new file is added, generate the appropriate new HashMap.
+    // Do not delete any of the existing maps.
+    //
+    // Adding a new map is as simple as:
+    // complete_from_bytes(BYTES_NUMBER)
+    static ref MAP_SIMULATION: Arc<Calibration<HashMap<usize, BvGain>>> =
+        complete_from_bytes(BYTES_SIMULATION);
+}
+
+fn try_calibration(
+    run_number: u32,
+) -> Result<Arc<Calibration<HashMap<usize, BvGain>>>, MapBvGainError> {
+    if let Some(calibration) =
+        try_external_calibration(KIND, "json", run_number, try_data_from_bytes)?
+    {
+        return Ok(calibration);
+    }
+    // This map should be updated whenever a new file is added.
+    // The run range in the header of each file has to agree with this match
+    // (checked by unit tests).
+    let calibration = match run_number {
+        // Simulated runs (see `declare_simulated_runs`).
+        run if is_simulated_run(run) => &*MAP_SIMULATION,
+        _ => return Err(MapBvGainError::MissingMap { run_number }),
+    };
+
+    Ok(Arc::clone(calibration))
+}
+
+// Nothing below this line needs to be changed when adding a new file.
+
+const KIND: &str = "bv/gain";
+
+/// Calibration of a single Barrel Veto bar.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BvGain {
+    /// Relative gain of the SiPM at the top (positive `z`) end of the bar.
+    pub(crate) top: f64,
+    /// Relative gain of the SiPM at the bottom end of the bar.
+    pub(crate) bottom: f64,
+    /// Attenuation length (in meters) of the scintillation light.
+    pub(crate) attenuation_length: f64,
+}
+
+/// Try to get the gain calibration for a given bar. Return an error if there is
+/// no map available for the given run number or if there is no calibration for
+/// a given bar in the map.
+pub(crate) fn try_bv_gain(run_number: u32, bar: usize) -> Result<BvGain, MapBvGainError> {
+    try_calibration(run_number)?
+        .data
+        .get(&bar)
+        .copied()
+        .ok_or(MapBvGainError::MissingBar { run_number, bar })
+}
+
+/// Try to get the header of the Barrel Veto gain calibration used for a given
+/// run number.
+pub(crate) fn try_bv_gain_header(run_number: u32) -> Result<CalibrationHeader, MapBvGainError> {
+    Ok(try_calibration(run_number)?.header.clone())
+}
+
+/// The error type returned when the Barrel Veto gain calibration map is not
+/// available.
+#[derive(Debug, Error)]
+pub enum MapBvGainError {
+    #[error("no barrel veto gain calibration available for run number `{run_number}`")]
+    MissingMap { run_number: u32 },
+    #[error(
+        "no barrel veto gain calibration available for bar `{bar}` in run number `{run_number}`"
+    )]
+    MissingBar { run_number: u32, bar: usize },
+    #[error("failed to load external barrel veto gain calibration")]
+    External(#[from] LoadExternalCalibrationError),
+}
+
+fn complete_from_bytes(bytes: &[u8]) -> Arc<Calibration<HashMap<usize, BvGain>>> {
+    // Correctness of the file is checked by unit tests.
+    Arc::new(Calibration::try_from_bytes(KIND, bytes, try_data_from_bytes).unwrap())
+}
+
+fn try_data_from_bytes(bytes: &[u8]) -> Result<HashMap<usize, BvGain>, String> {
+    serde_json::from_slice(bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests;
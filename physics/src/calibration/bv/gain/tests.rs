@@ -0,0 +1,28 @@
+use super::*;
+use crate::bv::BV_BARS;
+use crate::calibration::format::assert_run_range_matches_header;
+
+#[test]
+fn try_bv_gain_map_error() {
+    for run_number in [0, 9277, 11186] {
+        for bar in 0..BV_BARS {
+            assert!(try_bv_gain(run_number, bar).is_err());
+        }
+    }
+}
+
+#[test]
+fn try_bv_gain_correctness_sim() {
+    for bar in 0..BV_BARS {
+        let gain = try_bv_gain(u32::MAX, bar).unwrap();
+        assert_eq!(gain.top, 1.0);
+        assert_eq!(gain.bottom, 1.0);
+        assert_eq!(gain.attenuation_length, 1.7);
+    }
+    assert!(try_bv_gain(u32::MAX, BV_BARS).is_err());
+}
+
+#[test]
+fn run_ranges_match_headers() {
+    assert_run_range_matches_header(&MAP_SIMULATION, try_calibration);
+}
@@ -0,0 +1,197 @@
+use crate::calibration::is_simulated_run;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+// All calibration files (both embedded and external) have the same layout: a
+// single line JSON header followed by the calibration data. The format of the
+// data depends on the calibration kind, e.g. wire maps are JSON and pad maps
+// are RON (pad positions are not valid JSON keys).
+//
+// Any change to the header that is not backwards compatible requires bumping
+// the schema version.
+const MAGIC: &str = "ALPHA-g calibration";
+const SCHEMA_VERSION: u32 = 1;
+
+/// Provenance of a calibration file.
+///
+/// Every calibration file starts with a single line header that identifies
+/// the calibration, the runs for which it is valid, and how it was produced.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CalibrationHeader {
+    magic: String,
+    schema_version: u32,
+    /// Calibration kind, e.g. `"wires/gain"`.
+    pub kind: String,
+    /// First run number for which the calibration is valid. Simulation
+    /// calibrations have a first (and last) run number of [`u32::MAX`].
+    pub first_run: u32,
+    /// Last run number (inclusive) for which the calibration is valid. `None`
+    /// means that it is valid for all subsequent runs.
+    pub last_run: Option<u32>,
+    /// Name of the binary (or procedure) that produced the calibration.
+    pub producer: String,
+    /// Git commit hash of the producer. `None` if unknown (e.g. calibrations
+    /// that predate this format).
+    pub git_hash: Option<String>,
+    /// Creation time in seconds since the Unix epoch. `None` if unknown.
+    pub created: Option<u64>,
+}
+
+/// The error type returned when parsing a calibration file fails.
+#[derive(Debug, Error)]
+pub enum ParseCalibrationFileError {
+    /// The file does not have a header line.
+    #[error("missing header")]
+    MissingHeader,
+    /// The header is not valid JSON or is missing fields.
+    #[error("bad header")]
+    BadHeader(#[from] serde_json::Error),
+    /// The header does not start with the expected magic string.
+    #[error("bad magic `{0}`")]
+    BadMagic(String),
+    /// The header has a schema version that is not supported.
+    #[error("unsupported schema version `{0}` (expected `{SCHEMA_VERSION}`)")]
+    UnsupportedSchemaVersion(u32),
+    /// The file contains a different calibration kind.
+    #[error("expected `{expected}` calibration, found `{found}`")]
+    WrongKind { expected: String, found: String },
+    /// The calibration data is not in the format of its kind.
+    #[error("bad calibration data: {0}")]
+    BadData(String),
+}
+
+impl CalibrationHeader {
+    /// Create a new header. The creation time is set to the current time.
+    pub fn new(
+        kind: impl Into<String>,
+        first_run: u32,
+        last_run: Option<u32>,
+        producer: impl Into<String>,
+        git_hash: Option<String>,
+    ) -> Self {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs());
+
+        Self {
+            magic: MAGIC.to_string(),
+            schema_version: SCHEMA_VERSION,
+            kind: kind.into(),
+            first_run,
+            last_run,
+            producer: producer.into(),
+            git_hash,
+            created,
+        }
+    }
+    /// Return `true` if the calibration is valid for the given run number.
+    /// Simulation calibrations (with a first run number of [`u32::MAX`]) are
+    /// only valid for simulated runs (see
+    /// [`declare_simulated_runs`](crate::declare_simulated_runs)), and data
+    /// calibrations are never valid for simulated runs.
+    pub fn contains(&self, run_number: u32) -> bool {
+        if is_simulated_run(run_number) {
+            return self.first_run == u32::MAX;
+        }
+
+        run_number >= self.first_run && self.last_run.is_none_or(|last| run_number <= last)
+    }
+    /// Return the contents of a calibration file with this header and the
+    /// given (already serialized) calibration data.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        // Serializing a struct with only strings and integers cannot fail.
+        let mut bytes = serde_json::to_vec(self).unwrap();
+        bytes.push(b'\n');
+        bytes.extend_from_slice(data);
+
+        bytes
+    }
+    /// Split the contents of a calibration file into its header and the
+    /// calibration data.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), ParseCalibrationFileError> {
+        // Check the magic and version before anything else. Future versions
+        // are free to change everything else in the header.
+        #[derive(Deserialize)]
+        struct Version {
+            magic: String,
+            schema_version: u32,
+        }
+
+        let newline = bytes
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or(ParseCalibrationFileError::MissingHeader)?;
+        let (header, data) = (&bytes[..newline], &bytes[newline + 1..]);
+
+        let version: Version = serde_json::from_slice(header)?;
+        if version.magic != MAGIC {
+            return Err(ParseCalibrationFileError::BadMagic(version.magic));
+        }
+        if version.schema_version != SCHEMA_VERSION {
+            return Err(ParseCalibrationFileError::UnsupportedSchemaVersion(
+                version.schema_version,
+            ));
+        }
+
+        Ok((serde_json::from_slice(header)?, data))
+    }
+}
+
+// Calibration data together with its provenance.
+#[derive(Debug)]
+pub(crate) struct Calibration<T> {
+    pub(crate) header: CalibrationHeader,
+    pub(crate) data: T,
+}
+
+impl<T> Calibration<T> {
+    pub(crate) fn try_from_bytes(
+        kind: &str,
+        bytes: &[u8],
+        parse: fn(&[u8]) -> Result<T, String>,
+    ) -> Result<Self, ParseCalibrationFileError> {
+        let (header, data) = CalibrationHeader::decode(bytes)?;
+        if header.kind != kind {
+            return Err(ParseCalibrationFileError::WrongKind {
+                expected: kind.to_string(),
+                found: header.kind,
+            });
+        }
+        let data = parse(data).map_err(ParseCalibrationFileError::BadData)?;
+
+        Ok(Self { header, data })
+    }
+}
+
+// Embedded calibration files are selected by a `match` on the run number. Check
+// that `try_calibration` selects `calibration` for the first and last run in
+// its header (or the latest run if the header is open-ended), and not for the
+// run right before it.
+#[cfg(test)]
+pub(crate) fn assert_run_range_matches_header<T, E>(
+    calibration: &std::sync::Arc<Calibration<T>>,
+    try_calibration: impl Fn(u32) -> Result<std::sync::Arc<Calibration<T>>, E>,
+) {
+    use std::sync::Arc;
+
+    let header = &calibration.header;
+    let last_run = header.last_run.unwrap_or(u32::MAX - 1);
+    for run_number in [header.first_run, last_run] {
+        assert!(header.contains(run_number));
+        assert!(
+            try_calibration(run_number).is_ok_and(|c| Arc::ptr_eq(&c, calibration)),
+            "run `{run_number}` doesn't select `{}` calibration `{}..`",
+            header.kind,
+            header.first_run
+        );
+    }
+    if let Some(previous) = header.first_run.checked_sub(1) {
+        assert!(!try_calibration(previous).is_ok_and(|c| Arc::ptr_eq(&c, calibration)));
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,75 @@
+use super::*;
+
+fn parse(bytes: &[u8]) -> Result<Vec<u32>, String> {
+    serde_json::from_slice(bytes).map_err(|e| e.to_string())
+}
+
+#[test]
+fn calibration_header_round_trip() {
+    let header = CalibrationHeader::new(
+        "wires/gain",
+        100,
+        Some(200),
+        "alpha-g-calibration",
+        Some(String::from("abc123")),
+    );
+    let bytes = header.encode(b"[1,2,3]");
+
+    let (decoded, data) = CalibrationHeader::decode(&bytes).unwrap();
+    assert_eq!(decoded, header);
+    assert_eq!(data, b"[1,2,3]");
+
+    let calibration = Calibration::try_from_bytes("wires/gain", &bytes, parse).unwrap();
+    assert_eq!(calibration.header, header);
+    assert_eq!(calibration.data, vec![1, 2, 3]);
+}
+
+#[test]
+fn calibration_header_contains() {
+    let header = CalibrationHeader::new("kind", 100, Some(200), "test", None);
+    assert!(!header.contains(99));
+    assert!(header.contains(100));
+    assert!(header.contains(200));
+    assert!(!header.contains(201));
+    assert!(!header.contains(u32::MAX));
+
+    let header = CalibrationHeader::new("kind", 100, None, "test", None);
+    assert!(header.contains(u32::MAX - 1));
+    assert!(!header.contains(u32::MAX));
+
+    let header = CalibrationHeader::new("kind", u32::MAX, Some(u32::MAX), "test", None);
+    assert!(!header.contains(100));
+    assert!(header.contains(u32::MAX));
+}
+
+#[test]
+fn calibration_file_errors() {
+    assert!(matches!(
+        CalibrationHeader::decode(b"{}"),
+        Err(ParseCalibrationFileError::MissingHeader)
+    ));
+    assert!(matches!(
+        CalibrationHeader::decode(b"not json\n{}"),
+        Err(ParseCalibrationFileError::BadHeader(_))
+    ));
+    assert!(matches!(
+        CalibrationHeader::decode(b"{\"magic\":\"other\",\"schema_version\":1}\n{}"),
+        Err(ParseCalibrationFileError::BadMagic(_))
+    ));
+
+    let header = CalibrationHeader::new("wires/gain", 100, None, "test", None);
+    let mut future = header.clone();
+    future.schema_version = SCHEMA_VERSION + 1;
+    assert!(matches!(
+        CalibrationHeader::decode(&future.encode(b"[]")),
+        Err(ParseCalibrationFileError::UnsupportedSchemaVersion(_))
+    ));
+    assert!(matches!(
+        Calibration::try_from_bytes("pads/gain", &header.encode(b"[]"), parse),
+        Err(ParseCalibrationFileError::WrongKind { .. })
+    ));
+    assert!(matches!(
+        Calibration::try_from_bytes("wires/gain", &header.encode(b"{}"), parse),
+        Err(ParseCalibrationFileError::BadData(_))
+    ));
+}
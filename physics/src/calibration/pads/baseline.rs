@@ -1,6 +1,11 @@
+use crate::calibration::format::{Calibration, CalibrationHeader};
+use crate::calibration::{
+    is_simulated_run, try_external_calibration, LoadExternalCalibrationError,
+};
 use alpha_g_detector::padwing::map::TpcPadPosition;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::sync::Arc;
 use thiserror::Error;
 
 includes! {
@@ -20,22 +25,28 @@ lazy_static! {
     // complete_from_bytes(BYTES_NUMBER)
     // or
     // update_previous_from_bytes(&PREVIOUS_HASHMAP, BYTES_NUMBER)
-    static ref MAP_SIMULATION: HashMap<TpcPadPosition, i16> = complete_from_bytes(BYTES_SIMULATION);
-    static ref MAP_9277: HashMap<TpcPadPosition, i16> = complete_from_bytes(BYTES_9277);
-    static ref MAP_11192: HashMap<TpcPadPosition, i16> = complete_from_bytes(BYTES_11192);
+    static ref MAP_SIMULATION: Arc<Calibration<HashMap<TpcPadPosition, i16>>> =
+        complete_from_bytes(BYTES_SIMULATION);
+    static ref MAP_9277: Arc<Calibration<HashMap<TpcPadPosition, i16>>> =
+        complete_from_bytes(BYTES_9277);
+    static ref MAP_11192: Arc<Calibration<HashMap<TpcPadPosition, i16>>> =
+        complete_from_bytes(BYTES_11192);
 }
 
-/// Try to get the baseline for a given pad. Return an error if there is no map
-/// available for the given run number or if there is no baseline for the given
-/// pad in the map.
-pub(crate) fn try_pad_baseline(
+fn try_calibration(
     run_number: u32,
-    pad: TpcPadPosition,
-) -> Result<i16, MapPadBaselineError> {
+) -> Result<Arc<Calibration<HashMap<TpcPadPosition, i16>>>, MapPadBaselineError> {
+    if let Some(calibration) =
+        try_external_calibration(KIND, "ron", run_number, try_data_from_bytes)?
+    {
+        return Ok(calibration);
+    }
     // This map should be updated whenever a new file is added.
-    let map = match run_number {
-        // u32::MAX corresponds to a simulation run.
-        u32::MAX => &*MAP_SIMULATION,
+    // The run range in the header of each file has to agree with this match
+    // (checked by unit tests).
+    let calibration = match run_number {
+        // Simulated runs (see `declare_simulated_runs`).
+        run if is_simulated_run(run) => &*MAP_SIMULATION,
         // The calibration was done on run 11192. But the detector was in this
         // configuration since it was turned on in run 11084.
         11084.. => &*MAP_11192,
@@ -43,12 +54,34 @@ pub(crate) fn try_pad_baseline(
         _ => return Err(MapPadBaselineError::MissingMap { run_number }),
     };
 
-    map.get(&pad)
+    Ok(Arc::clone(calibration))
+}
+
+// Nothing below this line needs to be changed when adding a new file.
+
+const KIND: &str = "pads/baseline";
+
+/// Try to get the baseline for a given pad. Return an error if there is no map
+/// available for the given run number or if there is no baseline for the given
+/// pad in the map.
+pub(crate) fn try_pad_baseline(
+    run_number: u32,
+    pad: TpcPadPosition,
+) -> Result<i16, MapPadBaselineError> {
+    try_calibration(run_number)?
+        .data
+        .get(&pad)
         .copied()
         .ok_or(MapPadBaselineError::MissingPad { run_number, pad })
 }
 
-// Nothing below this line needs to be changed when adding a new file.
+/// Try to get the header of the pad baseline calibration used for a given run
+/// number.
+pub(crate) fn try_pad_baseline_header(
+    run_number: u32,
+) -> Result<CalibrationHeader, MapPadBaselineError> {
+    Ok(try_calibration(run_number)?.header.clone())
+}
 
 /// The error type returned when the baseline calibration map is not available.
 #[derive(Debug, Error)]
@@ -60,15 +93,23 @@ pub enum MapPadBaselineError {
         run_number: u32,
         pad: TpcPadPosition,
     },
+    #[error("failed to load external pad baseline calibration")]
+    External(#[from] LoadExternalCalibrationError),
 }
 
-fn complete_from_bytes(bytes: &[u8]) -> HashMap<TpcPadPosition, i16> {
+fn complete_from_bytes(bytes: &[u8]) -> Arc<Calibration<HashMap<TpcPadPosition, i16>>> {
     // Correctness of the format is checked by unit tests.
-    let map: HashMap<TpcPadPosition, (f64, f64, usize)> = ron::de::from_bytes(bytes).unwrap();
+    Arc::new(Calibration::try_from_bytes(KIND, bytes, try_data_from_bytes).unwrap())
+}
+
+fn try_data_from_bytes(bytes: &[u8]) -> Result<HashMap<TpcPadPosition, i16>, String> {
+    let map: HashMap<TpcPadPosition, (f64, f64, usize)> =
+        ron::de::from_bytes(bytes).map_err(|e| e.to_string())?;
 
-    map.into_iter()
+    Ok(map
+        .into_iter()
         .map(|(pad, (baseline, _, _))| (pad, baseline.round() as i16))
-        .collect()
+        .collect())
 }
 
 // Implement `update_previous_from_bytes` whenever I need it for the first time.
@@ -1,4 +1,5 @@
 use super::*;
+use crate::calibration::format::assert_run_range_matches_header;
 use alpha_g_detector::padwing::{
     map::{TpcPadColumn, TpcPadRow, TPC_PAD_COLUMNS, TPC_PAD_ROWS},
     PWB_MAX, PWB_MIN,
@@ -125,3 +126,10 @@ fn try_pad_baseline_correctness_sim() {
         }
     }
 }
+
+#[test]
+fn run_ranges_match_headers() {
+    assert_run_range_matches_header(&MAP_SIMULATION, try_calibration);
+    assert_run_range_matches_header(&MAP_9277, try_calibration);
+    assert_run_range_matches_header(&MAP_11192, try_calibration);
+}
@@ -1,6 +1,12 @@
+use crate::calibration::format::{Calibration, CalibrationHeader};
+use crate::calibration::{
+    gain_with_fallback, is_simulated_run, try_external_calibration, GainFallback,
+    LoadExternalCalibrationError,
+};
 use alpha_g_detector::padwing::map::TpcPadPosition;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::sync::Arc;
 use thiserror::Error;
 
 includes! {
@@ -18,18 +24,28 @@ lazy_static! {
     //
     // Adding a new map is as simple as:
     // complete_from_bytes(BYTES_NUMBER)
-    static ref MAP_SIMULATION: HashMap<TpcPadPosition, f64> = complete_from_bytes(BYTES_SIMULATION);
-    static ref MAP_9277: HashMap<TpcPadPosition, f64> = complete_from_bytes(BYTES_9277);
-    static ref MAP_11186: HashMap<TpcPadPosition, f64> = complete_from_bytes(BYTES_11186);
+    static ref MAP_SIMULATION: Arc<Calibration<HashMap<TpcPadPosition, f64>>> =
+        complete_from_bytes(BYTES_SIMULATION);
+    static ref MAP_9277: Arc<Calibration<HashMap<TpcPadPosition, f64>>> =
+        complete_from_bytes(BYTES_9277);
+    static ref MAP_11186: Arc<Calibration<HashMap<TpcPadPosition, f64>>> =
+        complete_from_bytes(BYTES_11186);
 }
-/// Try to get the gain for a given pad. Return an error if there is no map
-/// available for the given run number or if there is no gain for a given pad in
-/// the map.
-pub(crate) fn try_pad_gain(run_number: u32, pad: TpcPadPosition) -> Result<f64, MapPadGainError> {
+
+fn try_calibration(
+    run_number: u32,
+) -> Result<Arc<Calibration<HashMap<TpcPadPosition, f64>>>, MapPadGainError> {
+    if let Some(calibration) =
+        try_external_calibration(KIND, "ron", run_number, try_data_from_bytes)?
+    {
+        return Ok(calibration);
+    }
     // This map should be updated whenever a new file is added.
-    let map = match run_number {
-        // u32::MAX corresponds to a simulation run.
-        u32::MAX => &*MAP_SIMULATION,
+    // The run range in the header of each file has to agree with this match
+    // (checked by unit tests).
+    let calibration = match run_number {
+        // Simulated runs (see `declare_simulated_runs`).
+        run if is_simulated_run(run) => &*MAP_SIMULATION,
         // The calibration was done on run 11186. But the detector was in this
         // configuration since run 11084 when it was turned on.
         11084.. => &*MAP_11186,
@@ -37,12 +53,45 @@ pub(crate) fn try_pad_gain(run_number: u32, pad: TpcPadPosition) -> Result<f64,
         _ => return Err(MapPadGainError::MissingMap { run_number }),
     };
 
-    map.get(&pad)
+    Ok(Arc::clone(calibration))
+}
+
+// Nothing below this line needs to be changed when adding a new file.
+
+const KIND: &str = "pads/gain";
+
+/// Try to get the gain for a given pad. Return an error if there is no map
+/// available for the given run number or if there is no gain for a given pad in
+/// the map.
+pub(crate) fn try_pad_gain(run_number: u32, pad: TpcPadPosition) -> Result<f64, MapPadGainError> {
+    try_calibration(run_number)?
+        .data
+        .get(&pad)
         .copied()
         .ok_or(MapPadGainError::MissingPad { run_number, pad })
 }
 
-// Nothing below this line needs to be changed when adding a new file.
+/// Same as [`try_pad_gain`], but a pad that is missing from the map is handled
+/// according to the given fallback policy.
+pub(crate) fn try_pad_gain_with_fallback(
+    run_number: u32,
+    pad: TpcPadPosition,
+    fallback: GainFallback,
+) -> Result<f64, MapPadGainError> {
+    gain_with_fallback(
+        run_number,
+        &pad,
+        fallback,
+        try_calibration,
+        MapPadGainError::MissingPad { run_number, pad },
+    )
+}
+
+/// Try to get the header of the pad gain calibration used for a given run
+/// number.
+pub(crate) fn try_pad_gain_header(run_number: u32) -> Result<CalibrationHeader, MapPadGainError> {
+    Ok(try_calibration(run_number)?.header.clone())
+}
 
 /// The error type returned when the gain calibration map is not available.
 #[derive(Debug, Error)]
@@ -54,11 +103,17 @@ pub enum MapPadGainError {
         run_number: u32,
         pad: TpcPadPosition,
     },
+    #[error("failed to load external pad gain calibration")]
+    External(#[from] LoadExternalCalibrationError),
 }
 
-fn complete_from_bytes(bytes: &[u8]) -> HashMap<TpcPadPosition, f64> {
+fn complete_from_bytes(bytes: &[u8]) -> Arc<Calibration<HashMap<TpcPadPosition, f64>>> {
     // Correctness of the file is checked by unit tests.
-    ron::de::from_bytes(bytes).unwrap()
+    Arc::new(Calibration::try_from_bytes(KIND, bytes, try_data_from_bytes).unwrap())
+}
+
+fn try_data_from_bytes(bytes: &[u8]) -> Result<HashMap<TpcPadPosition, f64>, String> {
+    ron::de::from_bytes(bytes).map_err(|e| e.to_string())
 }
 
 #[cfg(test)]
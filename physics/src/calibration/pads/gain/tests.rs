@@ -1,4 +1,5 @@
 use super::*;
+use crate::calibration::format::assert_run_range_matches_header;
 use alpha_g_detector::padwing::map::{TpcPadColumn, TpcPadRow, TPC_PAD_COLUMNS, TPC_PAD_ROWS};
 
 #[test]
@@ -97,3 +98,10 @@ fn try_pad_gain_correctness_sim() {
         }
     }
 }
+
+#[test]
+fn run_ranges_match_headers() {
+    assert_run_range_matches_header(&MAP_SIMULATION, try_calibration);
+    assert_run_range_matches_header(&MAP_9277, try_calibration);
+    assert_run_range_matches_header(&MAP_11186, try_calibration);
+}
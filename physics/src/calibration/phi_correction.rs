@@ -0,0 +1,91 @@
+use crate::calibration::format::{Calibration, CalibrationHeader};
+use crate::calibration::{try_external_calibration, LoadExternalCalibrationError};
+use crate::SpacePoint;
+use serde::Deserialize;
+use std::f64::consts::TAU;
+use std::sync::Arc;
+use thiserror::Error;
+use uom::si::angle::radian;
+use uom::si::f64::Angle;
+
+// Azimuthal correction of the SpacePoints e.g. to remove the r-phi bias due to
+// field-cage distortions near the mechanical supports. The correction is given
+// at the center of `N` equally spaced bins over a full turn (the first bin
+// starts at `phi = 0`), and it is linearly interpolated (periodically) between
+// bin centers. The corrected azimuthal angle of a SpacePoint is its original
+// angle plus the correction.
+//
+// Same as the thresholds, there are no embedded correction maps. Runs without
+// a correction file in the external calibration directory are not corrected.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PhiCorrection {
+    // Correction in radians at the center of each bin.
+    delta_phi: Vec<f64>,
+}
+
+impl PhiCorrection {
+    // Return the correction at a given azimuthal angle. Zero if the map is
+    // empty.
+    pub(crate) fn at(&self, phi: Angle) -> Angle {
+        let num_bins = self.delta_phi.len();
+        if num_bins == 0 {
+            return Angle::new::<radian>(0.0);
+        }
+        // Position in units of bins with respect to the center of the first
+        // bin.
+        let x = (phi.get::<radian>().rem_euclid(TAU) / TAU * num_bins as f64 - 0.5)
+            .rem_euclid(num_bins as f64);
+        let i = (x.floor() as usize).min(num_bins - 1);
+        let fraction = x - i as f64;
+        let (low, high) = (self.delta_phi[i], self.delta_phi[(i + 1) % num_bins]);
+
+        Angle::new::<radian>(low + fraction * (high - low))
+    }
+    // Return the SpacePoint with a corrected azimuthal angle.
+    pub(crate) fn apply(&self, sp: SpacePoint) -> SpacePoint {
+        SpacePoint {
+            phi: sp.phi + self.at(sp.phi),
+            ..sp
+        }
+    }
+}
+
+fn try_calibration(
+    run_number: u32,
+) -> Result<Arc<Calibration<PhiCorrection>>, MapPhiCorrectionError> {
+    try_external_calibration(KIND, "json", run_number, try_data_from_bytes)?
+        .ok_or(MapPhiCorrectionError::MissingMap { run_number })
+}
+
+const KIND: &str = "spacepoints/phi_correction";
+
+/// Try to get the azimuthal correction map for a given run number. Return an
+/// error if there is no map available for the given run number.
+pub(crate) fn try_phi_correction(run_number: u32) -> Result<PhiCorrection, MapPhiCorrectionError> {
+    Ok(try_calibration(run_number)?.data.clone())
+}
+
+/// Try to get the header of the azimuthal correction map used for a given run
+/// number.
+pub(crate) fn try_phi_correction_header(
+    run_number: u32,
+) -> Result<CalibrationHeader, MapPhiCorrectionError> {
+    Ok(try_calibration(run_number)?.header.clone())
+}
+
+/// The error type returned when the azimuthal correction map is not available.
+#[derive(Debug, Error)]
+pub enum MapPhiCorrectionError {
+    #[error("no azimuthal correction map available for run number `{run_number}`")]
+    MissingMap { run_number: u32 },
+    #[error("failed to load external azimuthal correction calibration")]
+    External(#[from] LoadExternalCalibrationError),
+}
+
+fn try_data_from_bytes(bytes: &[u8]) -> Result<PhiCorrection, String> {
+    serde_json::from_slice(bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,42 @@
+use super::*;
+use std::f64::consts::PI;
+
+#[test]
+fn try_phi_correction_map_error() {
+    for run_number in [0, 9277, 11186, 11506] {
+        assert!(matches!(
+            try_phi_correction(run_number),
+            Err(MapPhiCorrectionError::MissingMap { .. })
+        ));
+    }
+}
+
+#[test]
+fn phi_correction_from_bytes() {
+    let correction = try_data_from_bytes(br#"{"delta_phi":[0.0,0.01,0.0,-0.01]}"#).unwrap();
+    // Bin centers are at `pi / 4`, `3 pi / 4`, etc.
+    for (phi, expected) in [
+        (PI / 4.0, 0.0),
+        (3.0 * PI / 4.0, 0.01),
+        (PI / 2.0, 0.005),
+        (7.0 * PI / 4.0, -0.01),
+        // Wraps around between the last and first bins.
+        (0.0, -0.005),
+        (-PI / 4.0, -0.01),
+        (TAU, -0.005),
+    ] {
+        let result = correction.at(Angle::new::<radian>(phi)).get::<radian>();
+        assert!((result - expected).abs() < 1e-12, "{phi}: {result}");
+    }
+
+    assert!(try_data_from_bytes(br#"{"bins":[0.0]}"#).is_err());
+}
+
+#[test]
+fn empty_phi_correction() {
+    let correction = PhiCorrection::default();
+    assert_eq!(
+        correction.at(Angle::new::<radian>(1.0)),
+        Angle::new::<radian>(0.0)
+    );
+}
@@ -0,0 +1,386 @@
+use crate::calibration::pads::baseline::{
+    try_pad_baseline, try_pad_baseline_header, MapPadBaselineError,
+};
+use crate::calibration::pads::delay::{try_pad_delay, MapPadDelayError};
+use crate::calibration::pads::gain::{
+    try_pad_gain, try_pad_gain_header, try_pad_gain_with_fallback, MapPadGainError,
+};
+use crate::calibration::phi_correction::{
+    try_phi_correction, MapPhiCorrectionError, PhiCorrection,
+};
+use crate::calibration::threshold::{
+    try_pad_threshold, try_pad_threshold_header, try_wire_threshold, try_wire_threshold_header,
+    MapPadThresholdError, MapWireThresholdError,
+};
+use crate::calibration::time::{
+    try_pad_time_offset, try_pad_time_offset_header, try_wire_time_offset,
+    try_wire_time_offset_header, MapPadTimeOffsetError, MapWireTimeOffsetError,
+};
+use crate::calibration::wires::baseline::{
+    try_wire_baseline, try_wire_baseline_header, MapWireBaselineError,
+};
+use crate::calibration::wires::delay::{try_wire_delay, MapWireDelayError};
+use crate::calibration::wires::gain::{
+    try_wire_gain, try_wire_gain_header, try_wire_gain_with_fallback, MapWireGainError,
+};
+use crate::calibration::GainFallback;
+use crate::matching::Thresholds;
+use alpha_g_detector::alpha16::aw_map::{TpcWirePosition, TPC_ANODE_WIRES};
+use alpha_g_detector::padwing::map::{
+    TpcPadColumn, TpcPadPosition, TpcPadRow, TPC_PAD_COLUMNS, TPC_PAD_ROWS,
+};
+use thiserror::Error;
+use uom::si::f64::Angle;
+
+/// The error type returned when resolving a [`CalibrationSet`] fails.
+///
+/// A calibration that is simply not available for the run number is not an
+/// error; it is only reported when a channel is looked up.
+#[derive(Debug, Error)]
+pub enum LoadCalibrationSetError {
+    /// Wire baseline calibration failed.
+    #[error("wire baseline calibration failed")]
+    WireBaselineError(#[from] MapWireBaselineError),
+    /// Wire gain calibration failed.
+    #[error("wire gain calibration failed")]
+    WireGainError(#[from] MapWireGainError),
+    /// Wire timing calibration failed.
+    #[error("wire timing calibration failed")]
+    WireTimeOffsetError(#[from] MapWireTimeOffsetError),
+    /// Pad baseline calibration failed.
+    #[error("pad baseline calibration failed")]
+    PadBaselineError(#[from] MapPadBaselineError),
+    /// Pad gain calibration failed.
+    #[error("pad gain calibration failed")]
+    PadGainError(#[from] MapPadGainError),
+    /// Pad timing calibration failed.
+    #[error("pad timing calibration failed")]
+    PadTimeOffsetError(#[from] MapPadTimeOffsetError),
+    /// Wire threshold calibration failed.
+    #[error("wire threshold calibration failed")]
+    WireThresholdError(#[from] MapWireThresholdError),
+    /// Pad threshold calibration failed.
+    #[error("pad threshold calibration failed")]
+    PadThresholdError(#[from] MapPadThresholdError),
+    /// Azimuthal correction map failed.
+    #[error("azimuthal correction map failed")]
+    PhiCorrectionError(#[from] MapPhiCorrectionError),
+}
+
+/// Calibration of all the rTPC channels for a single run number.
+///
+/// Every individual calibration lookup has to find the appropriate
+/// calibration map for the run number (embedded or external). A
+/// `CalibrationSet` resolves all of them once, and then each lookup is just an
+/// index into an array.
+#[derive(Clone, Debug)]
+pub struct CalibrationSet {
+    run_number: u32,
+    // Each calibration is `None` if there is no map for the run number.
+    // Otherwise, there is one element per channel (`None` if the channel is
+    // missing from the map).
+    wire_baselines: Option<Vec<Option<i16>>>,
+    wire_gains: Option<Vec<Option<f64>>>,
+    wire_time_offsets: Option<Vec<Option<f64>>>,
+    wire_delay: Option<usize>,
+    pad_baselines: Option<Vec<Option<i16>>>,
+    pad_gains: Option<Vec<Option<f64>>>,
+    pad_time_offsets: Option<Vec<Option<f64>>>,
+    pad_delay: Option<usize>,
+    // Thresholds have a default for all channels.
+    wire_thresholds: Option<Vec<f64>>,
+    pad_thresholds: Option<Vec<f64>>,
+    phi_correction: Option<PhiCorrection>,
+}
+
+fn wires() -> impl Iterator<Item = TpcWirePosition> {
+    (0..TPC_ANODE_WIRES).map(|i| TpcWirePosition::try_from(i).unwrap())
+}
+
+// Same order as `pad_index`.
+fn pads() -> impl Iterator<Item = TpcPadPosition> {
+    (0..TPC_PAD_COLUMNS).flat_map(|column| {
+        (0..TPC_PAD_ROWS).map(move |row| TpcPadPosition {
+            column: TpcPadColumn::try_from(column).unwrap(),
+            row: TpcPadRow::try_from(row).unwrap(),
+        })
+    })
+}
+
+fn pad_index(pad: TpcPadPosition) -> usize {
+    usize::from(pad.column) * TPC_PAD_ROWS + usize::from(pad.row)
+}
+
+impl CalibrationSet {
+    /// Resolve all the rTPC calibrations for a given run number.
+    ///
+    /// Returns an error only if a calibration map exists but cannot be loaded
+    /// (e.g. a bad external calibration file). Calibrations that are not
+    /// available for the run number (or channels missing from a calibration)
+    /// return the corresponding error when they are looked up.
+    pub fn for_run(run_number: u32) -> Result<Self, LoadCalibrationSetError> {
+        let wire_baselines = match try_wire_baseline_header(run_number) {
+            Ok(_) => Some(
+                wires()
+                    .map(|wire| try_wire_baseline(run_number, wire).ok())
+                    .collect(),
+            ),
+            Err(MapWireBaselineError::MissingMap { .. }) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let wire_gains = match try_wire_gain_header(run_number) {
+            Ok(_) => Some(
+                wires()
+                    .map(|wire| try_wire_gain(run_number, wire).ok())
+                    .collect(),
+            ),
+            Err(MapWireGainError::MissingMap { .. }) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let wire_time_offsets = match try_wire_time_offset_header(run_number) {
+            Ok(_) => Some(
+                wires()
+                    .map(|wire| try_wire_time_offset(run_number, wire).ok())
+                    .collect(),
+            ),
+            Err(MapWireTimeOffsetError::MissingMap { .. }) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let pad_baselines = match try_pad_baseline_header(run_number) {
+            Ok(_) => Some(
+                pads()
+                    .map(|pad| try_pad_baseline(run_number, pad).ok())
+                    .collect(),
+            ),
+            Err(MapPadBaselineError::MissingMap { .. }) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let pad_gains = match try_pad_gain_header(run_number) {
+            Ok(_) => Some(
+                pads()
+                    .map(|pad| try_pad_gain(run_number, pad).ok())
+                    .collect(),
+            ),
+            Err(MapPadGainError::MissingMap { .. }) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let pad_time_offsets = match try_pad_time_offset_header(run_number) {
+            Ok(_) => Some(
+                pads()
+                    .map(|pad| try_pad_time_offset(run_number, pad).ok())
+                    .collect(),
+            ),
+            Err(MapPadTimeOffsetError::MissingMap { .. }) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let wire_thresholds = match try_wire_threshold_header(run_number) {
+            Ok(_) => Some(
+                wires()
+                    .map(|wire| try_wire_threshold(run_number, wire))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Err(MapWireThresholdError::MissingMap { .. }) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let pad_thresholds = match try_pad_threshold_header(run_number) {
+            Ok(_) => Some(
+                pads()
+                    .map(|pad| try_pad_threshold(run_number, pad))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Err(MapPadThresholdError::MissingMap { .. }) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let phi_correction = match try_phi_correction(run_number) {
+            Ok(correction) => Some(correction),
+            Err(MapPhiCorrectionError::MissingMap { .. }) => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            run_number,
+            wire_baselines,
+            wire_gains,
+            wire_time_offsets,
+            wire_delay: try_wire_delay(run_number).ok(),
+            pad_baselines,
+            pad_gains,
+            pad_time_offsets,
+            pad_delay: try_pad_delay(run_number).ok(),
+            wire_thresholds,
+            pad_thresholds,
+            phi_correction,
+        })
+    }
+    /// Return the run number of the calibration.
+    pub fn run_number(&self) -> u32 {
+        self.run_number
+    }
+    /// Return the baseline of a given wire.
+    pub fn wire_baseline(&self, wire: TpcWirePosition) -> Result<i16, MapWireBaselineError> {
+        let run_number = self.run_number;
+        let wire_baselines = self
+            .wire_baselines
+            .as_ref()
+            .ok_or(MapWireBaselineError::MissingMap { run_number })?;
+
+        wire_baselines[usize::from(wire)]
+            .ok_or(MapWireBaselineError::MissingWire { run_number, wire })
+    }
+    /// Return the gain of a given wire.
+    pub fn wire_gain(&self, wire: TpcWirePosition) -> Result<f64, MapWireGainError> {
+        let run_number = self.run_number;
+        let wire_gains = self
+            .wire_gains
+            .as_ref()
+            .ok_or(MapWireGainError::MissingMap { run_number })?;
+
+        wire_gains[usize::from(wire)].ok_or(MapWireGainError::MissingWire { run_number, wire })
+    }
+    /// Same as [`CalibrationSet::wire_gain`], but a wire that is missing from
+    /// the calibration is handled according to the given fallback policy.
+    pub fn wire_gain_with_fallback(
+        &self,
+        wire: TpcWirePosition,
+        fallback: GainFallback,
+    ) -> Result<f64, MapWireGainError> {
+        match self.wire_gain(wire) {
+            // Missing channels are rare. Not worth caching the fallback.
+            Err(MapWireGainError::MissingWire { .. }) => {
+                try_wire_gain_with_fallback(self.run_number, wire, fallback)
+            }
+            gain => gain,
+        }
+    }
+    /// Return the timing offset (in nanoseconds) of a given wire.
+    pub fn wire_time_offset(&self, wire: TpcWirePosition) -> Result<f64, MapWireTimeOffsetError> {
+        let run_number = self.run_number;
+        let wire_time_offsets = self
+            .wire_time_offsets
+            .as_ref()
+            .ok_or(MapWireTimeOffsetError::MissingMap { run_number })?;
+
+        wire_time_offsets[usize::from(wire)]
+            .ok_or(MapWireTimeOffsetError::MissingWire { run_number, wire })
+    }
+    /// Return the delay (in number of samples) of the anode wire waveforms.
+    pub fn wire_delay(&self) -> Result<usize, MapWireDelayError> {
+        self.wire_delay.ok_or(MapWireDelayError::MissingMap {
+            run_number: self.run_number,
+        })
+    }
+    /// Return the baseline of a given pad.
+    pub fn pad_baseline(&self, pad: TpcPadPosition) -> Result<i16, MapPadBaselineError> {
+        let run_number = self.run_number;
+        let pad_baselines = self
+            .pad_baselines
+            .as_ref()
+            .ok_or(MapPadBaselineError::MissingMap { run_number })?;
+
+        pad_baselines[pad_index(pad)].ok_or(MapPadBaselineError::MissingPad { run_number, pad })
+    }
+    /// Return the gain of a given pad.
+    pub fn pad_gain(&self, pad: TpcPadPosition) -> Result<f64, MapPadGainError> {
+        let run_number = self.run_number;
+        let pad_gains = self
+            .pad_gains
+            .as_ref()
+            .ok_or(MapPadGainError::MissingMap { run_number })?;
+
+        pad_gains[pad_index(pad)].ok_or(MapPadGainError::MissingPad { run_number, pad })
+    }
+    /// Same as [`CalibrationSet::pad_gain`], but a pad that is missing from
+    /// the calibration is handled according to the given fallback policy.
+    pub fn pad_gain_with_fallback(
+        &self,
+        pad: TpcPadPosition,
+        fallback: GainFallback,
+    ) -> Result<f64, MapPadGainError> {
+        match self.pad_gain(pad) {
+            Err(MapPadGainError::MissingPad { .. }) => {
+                try_pad_gain_with_fallback(self.run_number, pad, fallback)
+            }
+            gain => gain,
+        }
+    }
+    /// Return the timing offset (in nanoseconds) of a given pad.
+    pub fn pad_time_offset(&self, pad: TpcPadPosition) -> Result<f64, MapPadTimeOffsetError> {
+        let run_number = self.run_number;
+        let pad_time_offsets = self
+            .pad_time_offsets
+            .as_ref()
+            .ok_or(MapPadTimeOffsetError::MissingMap { run_number })?;
+
+        pad_time_offsets[pad_index(pad)]
+            .ok_or(MapPadTimeOffsetError::MissingPad { run_number, pad })
+    }
+    /// Return the delay (in number of samples) of the pad waveforms.
+    pub fn pad_delay(&self) -> Result<usize, MapPadDelayError> {
+        self.pad_delay.ok_or(MapPadDelayError::MissingMap {
+            run_number: self.run_number,
+        })
+    }
+    /// Return the software amplitude threshold of a given wire.
+    pub fn wire_threshold(&self, wire: TpcWirePosition) -> Result<f64, MapWireThresholdError> {
+        let wire_thresholds =
+            self.wire_thresholds
+                .as_ref()
+                .ok_or(MapWireThresholdError::MissingMap {
+                    run_number: self.run_number,
+                })?;
+
+        Ok(wire_thresholds[usize::from(wire)])
+    }
+    /// Return the software amplitude threshold of a given pad.
+    pub fn pad_threshold(&self, pad: TpcPadPosition) -> Result<f64, MapPadThresholdError> {
+        let pad_thresholds =
+            self.pad_thresholds
+                .as_ref()
+                .ok_or(MapPadThresholdError::MissingMap {
+                    run_number: self.run_number,
+                })?;
+
+        Ok(pad_thresholds[pad_index(pad)])
+    }
+    /// Return the azimuthal correction of a [`SpacePoint`](crate::SpacePoint)
+    /// at a given azimuthal angle. The corrected angle is the original angle
+    /// plus the correction.
+    pub fn phi_correction(&self, phi: Angle) -> Result<Angle, MapPhiCorrectionError> {
+        let phi_correction =
+            self.phi_correction
+                .as_ref()
+                .ok_or(MapPhiCorrectionError::MissingMap {
+                    run_number: self.run_number,
+                })?;
+
+        Ok(phi_correction.at(phi))
+    }
+    // Azimuthal correction of the SpacePoints. Without a correction map, the
+    // SpacePoints are not corrected.
+    pub(crate) fn phi_correction_map(&self) -> PhiCorrection {
+        self.phi_correction.clone().unwrap_or_default()
+    }
+    // Software amplitude thresholds of all channels. Without a threshold
+    // calibration, every positive amplitude is kept.
+    pub(crate) fn thresholds(&self) -> Thresholds {
+        let mut thresholds = Thresholds::default();
+        if let Some(wire_thresholds) = &self.wire_thresholds {
+            thresholds.wires.copy_from_slice(wire_thresholds);
+        }
+        if let Some(pad_thresholds) = &self.pad_thresholds {
+            // Same order as `pad_index`.
+            for (column, chunk) in thresholds
+                .pads
+                .iter_mut()
+                .zip(pad_thresholds.chunks_exact(TPC_PAD_ROWS))
+            {
+                column.copy_from_slice(chunk);
+            }
+        }
+
+        thresholds
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,82 @@
+use super::*;
+
+#[test]
+fn calibration_set_simulation() {
+    let set = CalibrationSet::for_run(u32::MAX).unwrap();
+    assert_eq!(set.run_number(), u32::MAX);
+
+    for wire in wires() {
+        assert_eq!(
+            set.wire_baseline(wire).ok(),
+            try_wire_baseline(u32::MAX, wire).ok()
+        );
+        assert_eq!(set.wire_gain(wire).ok(), try_wire_gain(u32::MAX, wire).ok());
+        assert_eq!(
+            set.wire_time_offset(wire).ok(),
+            try_wire_time_offset(u32::MAX, wire).ok()
+        );
+    }
+    for pad in pads() {
+        assert_eq!(
+            set.pad_baseline(pad).ok(),
+            try_pad_baseline(u32::MAX, pad).ok()
+        );
+        assert_eq!(set.pad_gain(pad).ok(), try_pad_gain(u32::MAX, pad).ok());
+        assert_eq!(
+            set.pad_time_offset(pad).ok(),
+            try_pad_time_offset(u32::MAX, pad).ok()
+        );
+    }
+    assert_eq!(set.wire_delay().ok(), try_wire_delay(u32::MAX).ok());
+    assert_eq!(set.pad_delay().ok(), try_pad_delay(u32::MAX).ok());
+}
+
+#[test]
+fn calibration_set_missing_maps() {
+    let set = CalibrationSet::for_run(0).unwrap();
+    let wire = TpcWirePosition::try_from(0).unwrap();
+    let pad = pads().next().unwrap();
+
+    assert!(matches!(
+        set.wire_baseline(wire),
+        Err(MapWireBaselineError::MissingMap { run_number: 0 })
+    ));
+    assert!(matches!(
+        set.wire_gain(wire),
+        Err(MapWireGainError::MissingMap { run_number: 0 })
+    ));
+    assert!(matches!(
+        set.wire_delay(),
+        Err(MapWireDelayError::MissingMap { run_number: 0 })
+    ));
+    assert!(matches!(
+        set.pad_baseline(pad),
+        Err(MapPadBaselineError::MissingMap { run_number: 0 })
+    ));
+    assert!(matches!(
+        set.pad_gain(pad),
+        Err(MapPadGainError::MissingMap { run_number: 0 })
+    ));
+    assert!(matches!(
+        set.pad_delay(),
+        Err(MapPadDelayError::MissingMap { run_number: 0 })
+    ));
+}
+
+#[test]
+fn calibration_set_missing_pad() {
+    // Run 9277 has a pad gain map, but some pads are missing from it.
+    let set = CalibrationSet::for_run(9277).unwrap();
+    let missing = pads()
+        .filter(|&pad| matches!(set.pad_gain(pad), Err(MapPadGainError::MissingPad { .. })))
+        .count();
+
+    assert_eq!(missing, 721);
+}
+
+#[test]
+fn pad_index_order() {
+    for (i, pad) in pads().enumerate() {
+        assert_eq!(pad_index(pad), i);
+    }
+}
@@ -0,0 +1,196 @@
+use super::*;
+
+fn temp_calibration_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("alpha_g_calibration_{name}_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("kind")).unwrap();
+
+    dir
+}
+
+#[test]
+fn external_file_missing_kind() {
+    let dir = temp_calibration_dir("missing_kind");
+
+    assert!(external_file(&dir, "other", "json", 100).unwrap().is_none());
+    assert!(external_file(&dir, "other", "json", u32::MAX)
+        .unwrap()
+        .is_none());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn external_file_run_ranges() {
+    let dir = temp_calibration_dir("run_ranges");
+    for name in ["100.json", "200.json", "150.ron", "300_old.json"] {
+        std::fs::write(dir.join("kind").join(name), "{}").unwrap();
+    }
+
+    assert!(external_file(&dir, "kind", "json", 99).unwrap().is_none());
+    for (run_number, first_run) in [(100, 100), (150, 100), (199, 100), (200, 200), (400, 200)] {
+        assert_eq!(
+            external_file(&dir, "kind", "json", run_number).unwrap(),
+            Some(dir.join("kind").join(format!("{first_run}.json")))
+        );
+    }
+    assert_eq!(
+        external_file(&dir, "kind", "ron", 160).unwrap(),
+        Some(dir.join("kind").join("150.ron"))
+    );
+    assert!(external_file(&dir, "kind", "json", u32::MAX)
+        .unwrap()
+        .is_none());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn external_file_simulation() {
+    let dir = temp_calibration_dir("simulation");
+    std::fs::write(dir.join("kind").join("simulation.json"), "{}").unwrap();
+
+    assert_eq!(
+        external_file(&dir, "kind", "json", u32::MAX).unwrap(),
+        Some(dir.join("kind").join("simulation.json"))
+    );
+    assert!(external_file(&dir, "kind", "json", 100).unwrap().is_none());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn try_external_calibration_override() {
+    fn parse(bytes: &[u8]) -> Result<HashMap<u32, f64>, String> {
+        serde_json::from_slice(bytes).map_err(|e| e.to_string())
+    }
+    let header = |first_run, last_run| {
+        CalibrationHeader::new("test/external", first_run, last_run, "test", None)
+    };
+
+    // Use a kind that does not exist in the embedded calibration. Otherwise
+    // this could interfere with other tests running in parallel.
+    let dir = temp_calibration_dir("override");
+    let kind_dir = dir.join("test/external");
+    std::fs::create_dir_all(&kind_dir).unwrap();
+    std::fs::write(
+        kind_dir.join("100.json"),
+        header(100, Some(199)).encode(br#"{"1": 2.5}"#),
+    )
+    .unwrap();
+    std::fs::write(
+        kind_dir.join("200.json"),
+        header(200, None).encode(b"not json"),
+    )
+    .unwrap();
+    std::fs::write(kind_dir.join("300.json"), r#"{"1": 2.5}"#).unwrap();
+    std::fs::write(
+        kind_dir.join("400.json"),
+        header(400, Some(409)).encode(br#"{"1": 3.5}"#),
+    )
+    .unwrap();
+    set_calibration_dir(Some(dir.clone()));
+
+    assert!(try_external_calibration("test/external", "json", 99, parse)
+        .unwrap()
+        .is_none());
+    let calibration = try_external_calibration("test/external", "json", 150, parse)
+        .unwrap()
+        .unwrap();
+    assert_eq!(calibration.header.first_run, 100);
+    assert_eq!(calibration.header.last_run, Some(199));
+    assert_eq!(calibration.data.get(&1), Some(&2.5));
+    assert!(matches!(
+        try_external_calibration("test/external", "json", 200, parse),
+        Err(LoadExternalCalibrationError::BadFormat {
+            source: ParseCalibrationFileError::BadData(_),
+            ..
+        })
+    ));
+    // Files without a header are rejected.
+    assert!(matches!(
+        try_external_calibration("test/external", "json", 300, parse),
+        Err(LoadExternalCalibrationError::BadFormat { .. })
+    ));
+    // Files are ignored outside of the run range in their header.
+    assert!(
+        try_external_calibration("test/external", "json", 405, parse)
+            .unwrap()
+            .is_some()
+    );
+    assert!(
+        try_external_calibration("test/external", "json", 410, parse)
+            .unwrap()
+            .is_none()
+    );
+
+    set_calibration_dir(None);
+    assert!(
+        try_external_calibration("test/external", "json", 150, parse)
+            .unwrap()
+            .is_none()
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn embedded_calibration_headers() {
+    for run_number in (0..12000).chain([u32::MAX]) {
+        for header in calibration_headers(run_number) {
+            assert!(header.contains(run_number));
+        }
+    }
+    assert_eq!(calibration_headers(u32::MAX).len(), 7);
+}
+
+#[test]
+fn declared_simulated_runs() {
+    fn parse(bytes: &[u8]) -> Result<HashMap<u32, f64>, String> {
+        serde_json::from_slice(bytes).map_err(|e| e.to_string())
+    }
+    // Use run numbers that are not used by any other test. Otherwise this
+    // could interfere with other tests running in parallel.
+    let ideal = 4_000_000_000..=4_000_000_009;
+    let custom = 4_000_000_010..=4_000_000_019;
+
+    assert!(!is_simulated_run(4_000_000_005));
+    declare_simulated_runs(ideal.clone(), SimulationCalibration::Ideal).unwrap();
+    assert!(is_simulated_run(4_000_000_005));
+    assert!(is_simulated_run(u32::MAX));
+    assert!(!is_simulated_run(100));
+    assert!(declare_simulated_runs(4_000_000_009..=4_000_000_010, Default::default()).is_err());
+    // Simulated runs use the same (embedded) calibration as `u32::MAX`.
+    assert_eq!(
+        calibration_headers(4_000_000_005),
+        calibration_headers(u32::MAX)
+    );
+
+    let dir = temp_calibration_dir("declared_simulation");
+    let kind_dir = dir.join("test/simulation");
+    std::fs::create_dir_all(&kind_dir).unwrap();
+    std::fs::write(
+        kind_dir.join("simulation.json"),
+        CalibrationHeader::new("test/simulation", u32::MAX, Some(u32::MAX), "test", None)
+            .encode(br#"{"1": 2.5}"#),
+    )
+    .unwrap();
+    declare_simulated_runs(custom, SimulationCalibration::Directory(dir.clone())).unwrap();
+
+    let calibration = try_external_calibration("test/simulation", "json", 4_000_000_015, parse)
+        .unwrap()
+        .unwrap();
+    assert_eq!(calibration.data.get(&1), Some(&2.5));
+    assert!(calibration.header.contains(4_000_000_015));
+    assert!(!calibration.header.contains(100));
+    for run_number in [4_000_000_005, 100] {
+        assert!(
+            try_external_calibration("test/simulation", "json", run_number, parse)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,115 @@
+use crate::calibration::format::{Calibration, CalibrationHeader};
+use crate::calibration::{try_external_calibration, LoadExternalCalibrationError};
+use alpha_g_detector::alpha16::aw_map::TpcWirePosition;
+use alpha_g_detector::padwing::map::TpcPadPosition;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+use thiserror::Error;
+
+// Software amplitude thresholds are given in the same (calibrated) units as the
+// amplitudes of the avalanches. A wire (or pad) hit is only used to form
+// SpacePoints if its amplitude is above the threshold of its channel.
+//
+// There are no embedded threshold files. Thresholds are only meant to be
+// provided in the external calibration directory (e.g. to reprocess a noisy
+// campaign with tighter cuts), and runs without a threshold file keep every
+// positive amplitude.
+#[derive(Debug, Deserialize)]
+#[serde(
+    deny_unknown_fields,
+    bound(deserialize = "K: Eq + Hash + Deserialize<'de>")
+)]
+struct Thresholds<K: Eq + Hash> {
+    // Threshold of every channel that is not explicitly listed.
+    default: f64,
+    #[serde(default)]
+    channels: HashMap<K, f64>,
+}
+
+impl<K: Eq + Hash> Thresholds<K> {
+    fn get(&self, channel: &K) -> f64 {
+        self.channels.get(channel).copied().unwrap_or(self.default)
+    }
+}
+
+fn try_wire_calibration(
+    run_number: u32,
+) -> Result<Arc<Calibration<Thresholds<TpcWirePosition>>>, MapWireThresholdError> {
+    try_external_calibration(WIRES_KIND, "json", run_number, wires_try_data_from_bytes)?
+        .ok_or(MapWireThresholdError::MissingMap { run_number })
+}
+
+fn try_pad_calibration(
+    run_number: u32,
+) -> Result<Arc<Calibration<Thresholds<TpcPadPosition>>>, MapPadThresholdError> {
+    try_external_calibration(PADS_KIND, "ron", run_number, pads_try_data_from_bytes)?
+        .ok_or(MapPadThresholdError::MissingMap { run_number })
+}
+
+const WIRES_KIND: &str = "wires/threshold";
+const PADS_KIND: &str = "pads/threshold";
+
+/// Try to get the software amplitude threshold for a given wire. Return an
+/// error if there is no map available for the given run number.
+pub(crate) fn try_wire_threshold(
+    run_number: u32,
+    wire: TpcWirePosition,
+) -> Result<f64, MapWireThresholdError> {
+    Ok(try_wire_calibration(run_number)?.data.get(&wire))
+}
+
+/// Try to get the header of the wire threshold calibration used for a given
+/// run number.
+pub(crate) fn try_wire_threshold_header(
+    run_number: u32,
+) -> Result<CalibrationHeader, MapWireThresholdError> {
+    Ok(try_wire_calibration(run_number)?.header.clone())
+}
+
+/// Try to get the software amplitude threshold for a given pad. Return an
+/// error if there is no map available for the given run number.
+pub(crate) fn try_pad_threshold(
+    run_number: u32,
+    pad: TpcPadPosition,
+) -> Result<f64, MapPadThresholdError> {
+    Ok(try_pad_calibration(run_number)?.data.get(&pad))
+}
+
+/// Try to get the header of the pad threshold calibration used for a given run
+/// number.
+pub(crate) fn try_pad_threshold_header(
+    run_number: u32,
+) -> Result<CalibrationHeader, MapPadThresholdError> {
+    Ok(try_pad_calibration(run_number)?.header.clone())
+}
+
+/// The error type returned when the wire threshold map is not available.
+#[derive(Debug, Error)]
+pub enum MapWireThresholdError {
+    #[error("no wire threshold available for run number `{run_number}`")]
+    MissingMap { run_number: u32 },
+    #[error("failed to load external wire threshold calibration")]
+    External(#[from] LoadExternalCalibrationError),
+}
+
+/// The error type returned when the pad threshold map is not available.
+#[derive(Debug, Error)]
+pub enum MapPadThresholdError {
+    #[error("no pad threshold available for run number `{run_number}`")]
+    MissingMap { run_number: u32 },
+    #[error("failed to load external pad threshold calibration")]
+    External(#[from] LoadExternalCalibrationError),
+}
+
+fn wires_try_data_from_bytes(bytes: &[u8]) -> Result<Thresholds<TpcWirePosition>, String> {
+    serde_json::from_slice(bytes).map_err(|e| e.to_string())
+}
+
+fn pads_try_data_from_bytes(bytes: &[u8]) -> Result<Thresholds<TpcPadPosition>, String> {
+    ron::de::from_bytes(bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests;
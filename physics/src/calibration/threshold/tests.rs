@@ -0,0 +1,60 @@
+use super::*;
+use alpha_g_detector::padwing::map::{TpcPadColumn, TpcPadRow};
+
+#[test]
+fn try_wire_threshold_map_error() {
+    for run_number in [0, 9277, 11186, 11506] {
+        let wire = TpcWirePosition::try_from(0).unwrap();
+        assert!(matches!(
+            try_wire_threshold(run_number, wire),
+            Err(MapWireThresholdError::MissingMap { .. })
+        ));
+    }
+}
+
+#[test]
+fn try_pad_threshold_map_error() {
+    for run_number in [0, 9277, 11186] {
+        let pad = TpcPadPosition {
+            row: TpcPadRow::try_from(0).unwrap(),
+            column: TpcPadColumn::try_from(0).unwrap(),
+        };
+        assert!(matches!(
+            try_pad_threshold(run_number, pad),
+            Err(MapPadThresholdError::MissingMap { .. })
+        ));
+    }
+}
+
+#[test]
+fn wire_thresholds_from_bytes() {
+    let thresholds =
+        wires_try_data_from_bytes(br#"{"default":50.0,"channels":{"7":200.0}}"#).unwrap();
+
+    let wire = TpcWirePosition::try_from(7).unwrap();
+    assert_eq!(thresholds.get(&wire), 200.0);
+    let wire = TpcWirePosition::try_from(8).unwrap();
+    assert_eq!(thresholds.get(&wire), 50.0);
+
+    let thresholds = wires_try_data_from_bytes(br#"{"default":50.0}"#).unwrap();
+    assert_eq!(thresholds.get(&wire), 50.0);
+
+    assert!(wires_try_data_from_bytes(br#"{"channels":{}}"#).is_err());
+}
+
+#[test]
+fn pad_thresholds_from_bytes() {
+    let thresholds =
+        pads_try_data_from_bytes(b"(default:10.0,channels:{(column:3,row:100):40.0})").unwrap();
+
+    let pad = TpcPadPosition {
+        column: TpcPadColumn::try_from(3).unwrap(),
+        row: TpcPadRow::try_from(100).unwrap(),
+    };
+    assert_eq!(thresholds.get(&pad), 40.0);
+    let pad = TpcPadPosition {
+        column: TpcPadColumn::try_from(3).unwrap(),
+        row: TpcPadRow::try_from(101).unwrap(),
+    };
+    assert_eq!(thresholds.get(&pad), 10.0);
+}
@@ -0,0 +1,192 @@
+use crate::calibration::format::{Calibration, CalibrationHeader};
+use crate::calibration::{
+    is_simulated_run, try_external_calibration, LoadExternalCalibrationError,
+};
+use alpha_g_detector::alpha16::aw_map::TpcWirePosition;
+use alpha_g_detector::padwing::map::TpcPadPosition;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+// Timing offsets are given in nanoseconds. A positive offset means that the
+// signals of a channel arrive later than they should, i.e. the offset has to be
+// subtracted from the measured times.
+//
+// There is no binary that produces these files. The only maps so far are the
+// handwritten (all zero) simulation maps. A map for real data is obtained by
+// collecting the time of the earliest pulse of each channel over many events
+// (e.g. with a `ConstantFractionDiscriminator`), and then passing these to
+// `signal_processing::time_offsets`. Write the result with a
+// `CalibrationHeader` (the `producer` is whatever script did the above), and
+// test it with `set_calibration_dir` before embedding it below.
+
+includes! {
+    WIRES_DATA_PATH = "../../data/calibration/wires/time/";
+    // All the following files are embedded at compile time.
+    // Add new files to the list below to include them.
+    WIRE_BYTES_SIMULATION = "simulation_complete.json",
+}
+
+includes! {
+    PADS_DATA_PATH = "../../data/calibration/pads/time/";
+    // All the following files are embedded at compile time.
+    // Add new files to the list below to include them.
+    PAD_BYTES_SIMULATION = "simulation_complete.ron",
+}
+
+lazy_static! {
+    // Whenever a new file is added, generate the appropriate new HashMap.
+    // Do not delete any of the existing maps.
+    //
+    // Adding a new map is as simple as:
+    // wires_complete_from_bytes(WIRE_BYTES_NUMBER)
+    // or
+    // pads_complete_from_bytes(PAD_BYTES_NUMBER)
+    static ref WIRE_MAP_SIMULATION: Arc<Calibration<HashMap<TpcWirePosition, f64>>> =
+        wires_complete_from_bytes(WIRE_BYTES_SIMULATION);
+    static ref PAD_MAP_SIMULATION: Arc<Calibration<HashMap<TpcPadPosition, f64>>> =
+        pads_complete_from_bytes(PAD_BYTES_SIMULATION);
+}
+
+fn try_wire_calibration(
+    run_number: u32,
+) -> Result<Arc<Calibration<HashMap<TpcWirePosition, f64>>>, MapWireTimeOffsetError> {
+    if let Some(calibration) =
+        try_external_calibration(WIRES_KIND, "json", run_number, wires_try_data_from_bytes)?
+    {
+        return Ok(calibration);
+    }
+    // This map should be updated whenever a new file is added.
+    // The run range in the header of each file has to agree with this match
+    // (checked by unit tests).
+    let calibration = match run_number {
+        // Simulated runs (see `declare_simulated_runs`).
+        run if is_simulated_run(run) => &*WIRE_MAP_SIMULATION,
+        _ => return Err(MapWireTimeOffsetError::MissingMap { run_number }),
+    };
+
+    Ok(Arc::clone(calibration))
+}
+
+fn try_pad_calibration(
+    run_number: u32,
+) -> Result<Arc<Calibration<HashMap<TpcPadPosition, f64>>>, MapPadTimeOffsetError> {
+    if let Some(calibration) =
+        try_external_calibration(PADS_KIND, "ron", run_number, pads_try_data_from_bytes)?
+    {
+        return Ok(calibration);
+    }
+    // This map should be updated whenever a new file is added.
+    // The run range in the header of each file has to agree with this match
+    // (checked by unit tests).
+    let calibration = match run_number {
+        // Simulated runs (see `declare_simulated_runs`).
+        run if is_simulated_run(run) => &*PAD_MAP_SIMULATION,
+        _ => return Err(MapPadTimeOffsetError::MissingMap { run_number }),
+    };
+
+    Ok(Arc::clone(calibration))
+}
+
+// Nothing below this line needs to be changed when new files are added.
+
+const WIRES_KIND: &str = "wires/time";
+const PADS_KIND: &str = "pads/time";
+
+/// Try to get the timing offset (in nanoseconds) for a given wire. Return an
+/// error if there is no map available for the given run number or if there is
+/// no offset for a given wire in the map.
+pub(crate) fn try_wire_time_offset(
+    run_number: u32,
+    wire: TpcWirePosition,
+) -> Result<f64, MapWireTimeOffsetError> {
+    try_wire_calibration(run_number)?
+        .data
+        .get(&wire)
+        .copied()
+        .ok_or(MapWireTimeOffsetError::MissingWire { run_number, wire })
+}
+
+/// Try to get the header of the wire timing calibration used for a given run
+/// number.
+pub(crate) fn try_wire_time_offset_header(
+    run_number: u32,
+) -> Result<CalibrationHeader, MapWireTimeOffsetError> {
+    Ok(try_wire_calibration(run_number)?.header.clone())
+}
+
+/// Try to get the timing offset (in nanoseconds) for a given pad. Return an
+/// error if there is no map available for the given run number or if there is
+/// no offset for a given pad in the map.
+pub(crate) fn try_pad_time_offset(
+    run_number: u32,
+    pad: TpcPadPosition,
+) -> Result<f64, MapPadTimeOffsetError> {
+    try_pad_calibration(run_number)?
+        .data
+        .get(&pad)
+        .copied()
+        .ok_or(MapPadTimeOffsetError::MissingPad { run_number, pad })
+}
+
+/// Try to get the header of the pad timing calibration used for a given run
+/// number.
+pub(crate) fn try_pad_time_offset_header(
+    run_number: u32,
+) -> Result<CalibrationHeader, MapPadTimeOffsetError> {
+    Ok(try_pad_calibration(run_number)?.header.clone())
+}
+
+/// The error type returned when the wire timing calibration map is not
+/// available.
+#[derive(Debug, Error)]
+pub enum MapWireTimeOffsetError {
+    #[error("no wire timing calibration available for run number `{run_number}`")]
+    MissingMap { run_number: u32 },
+    #[error(
+        "no wire timing calibration available for wire `{wire:?}` in run number `{run_number}`"
+    )]
+    MissingWire {
+        run_number: u32,
+        wire: TpcWirePosition,
+    },
+    #[error("failed to load external wire timing calibration")]
+    External(#[from] LoadExternalCalibrationError),
+}
+
+/// The error type returned when the pad timing calibration map is not
+/// available.
+#[derive(Debug, Error)]
+pub enum MapPadTimeOffsetError {
+    #[error("no pad timing calibration available for run number `{run_number}`")]
+    MissingMap { run_number: u32 },
+    #[error("no pad timing calibration available for pad `{pad:?}` in run number `{run_number}`")]
+    MissingPad {
+        run_number: u32,
+        pad: TpcPadPosition,
+    },
+    #[error("failed to load external pad timing calibration")]
+    External(#[from] LoadExternalCalibrationError),
+}
+
+fn wires_complete_from_bytes(bytes: &[u8]) -> Arc<Calibration<HashMap<TpcWirePosition, f64>>> {
+    // Correctness of the format is checked by unit tests.
+    Arc::new(Calibration::try_from_bytes(WIRES_KIND, bytes, wires_try_data_from_bytes).unwrap())
+}
+
+fn wires_try_data_from_bytes(bytes: &[u8]) -> Result<HashMap<TpcWirePosition, f64>, String> {
+    serde_json::from_slice(bytes).map_err(|e| e.to_string())
+}
+
+fn pads_complete_from_bytes(bytes: &[u8]) -> Arc<Calibration<HashMap<TpcPadPosition, f64>>> {
+    // Correctness of the format is checked by unit tests.
+    Arc::new(Calibration::try_from_bytes(PADS_KIND, bytes, pads_try_data_from_bytes).unwrap())
+}
+
+fn pads_try_data_from_bytes(bytes: &[u8]) -> Result<HashMap<TpcPadPosition, f64>, String> {
+    ron::de::from_bytes(bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests;
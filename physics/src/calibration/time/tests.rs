@@ -0,0 +1,52 @@
+use super::*;
+use crate::calibration::format::assert_run_range_matches_header;
+use alpha_g_detector::alpha16::aw_map::TPC_ANODE_WIRES;
+use alpha_g_detector::padwing::map::{TpcPadColumn, TpcPadRow, TPC_PAD_COLUMNS, TPC_PAD_ROWS};
+
+#[test]
+fn try_wire_time_offset_map_error() {
+    for run_number in [0, 9277, 11186, 11506] {
+        let wire = TpcWirePosition::try_from(0).unwrap();
+        assert!(try_wire_time_offset(run_number, wire).is_err());
+    }
+}
+
+#[test]
+fn try_wire_time_offset_correctness_sim() {
+    for i in 0..TPC_ANODE_WIRES {
+        let wire = TpcWirePosition::try_from(i).unwrap();
+        let offset = try_wire_time_offset(u32::MAX, wire).unwrap();
+        assert_eq!(offset, 0.0);
+    }
+}
+
+#[test]
+fn try_pad_time_offset_map_error() {
+    for run_number in [0, 9277, 11186] {
+        let pad_position = TpcPadPosition {
+            row: TpcPadRow::try_from(0).unwrap(),
+            column: TpcPadColumn::try_from(0).unwrap(),
+        };
+        assert!(try_pad_time_offset(run_number, pad_position).is_err());
+    }
+}
+
+#[test]
+fn try_pad_time_offset_correctness_sim() {
+    for row in 0..TPC_PAD_ROWS {
+        let row = TpcPadRow::try_from(row).unwrap();
+        for column in 0..TPC_PAD_COLUMNS {
+            let column = TpcPadColumn::try_from(column).unwrap();
+            let pad_position = TpcPadPosition { row, column };
+
+            let offset = try_pad_time_offset(u32::MAX, pad_position).unwrap();
+            assert_eq!(offset, 0.0);
+        }
+    }
+}
+
+#[test]
+fn run_ranges_match_headers() {
+    assert_run_range_matches_header(&WIRE_MAP_SIMULATION, try_wire_calibration);
+    assert_run_range_matches_header(&PAD_MAP_SIMULATION, try_pad_calibration);
+}
@@ -1,6 +1,11 @@
+use crate::calibration::format::{Calibration, CalibrationHeader};
+use crate::calibration::{
+    is_simulated_run, try_external_calibration, LoadExternalCalibrationError,
+};
 use alpha_g_detector::alpha16::aw_map::TpcWirePosition;
-use lazy_static::{__Deref, lazy_static};
+use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::sync::Arc;
 use thiserror::Error;
 
 includes! {
@@ -19,9 +24,37 @@ lazy_static! {
     // complete_from_bytes(BYTES_NUMBER)
     // or
     // update_previous_from_bytes(&PREVIOUS_HASHMAP, BYTES_NUMBER)
-    static ref MAP_SIMULATION: HashMap<TpcWirePosition, i16> = complete_from_bytes(BYTES_SIMULATION);
-    static ref MAP_7026: HashMap<TpcWirePosition, i16> = complete_from_bytes(BYTES_7026);
+    static ref MAP_SIMULATION: Arc<Calibration<HashMap<TpcWirePosition, i16>>> =
+        complete_from_bytes(BYTES_SIMULATION);
+    static ref MAP_7026: Arc<Calibration<HashMap<TpcWirePosition, i16>>> =
+        complete_from_bytes(BYTES_7026);
 }
+
+fn try_calibration(
+    run_number: u32,
+) -> Result<Arc<Calibration<HashMap<TpcWirePosition, i16>>>, MapWireBaselineError> {
+    if let Some(calibration) =
+        try_external_calibration(KIND, "json", run_number, try_data_from_bytes)?
+    {
+        return Ok(calibration);
+    }
+    // This map should be updated whenever a new file is added.
+    // The run range in the header of each file has to agree with this match
+    // (checked by unit tests).
+    let calibration = match run_number {
+        // Simulated runs (see `declare_simulated_runs`).
+        run if is_simulated_run(run) => &*MAP_SIMULATION,
+        7026.. => &*MAP_7026,
+        _ => return Err(MapWireBaselineError::MissingMap { run_number }),
+    };
+
+    Ok(Arc::clone(calibration))
+}
+
+// Nothing below this line needs to be changed when new files are added.
+
+const KIND: &str = "wires/baseline";
+
 /// Try to get the baseline for a given wire. Return an error if there is no map
 /// available for the given run number or if there is no baseline for a given
 /// wire in the map.
@@ -29,20 +62,20 @@ pub(crate) fn try_wire_baseline(
     run_number: u32,
     wire: TpcWirePosition,
 ) -> Result<i16, MapWireBaselineError> {
-    // This map should be updated whenever a new file is added.
-    let map = match run_number {
-        // u32::MAX corresponds to a simulation run.
-        u32::MAX => MAP_SIMULATION.deref(),
-        7026.. => MAP_7026.deref(),
-        _ => return Err(MapWireBaselineError::MissingMap { run_number }),
-    };
-
-    map.get(&wire)
+    try_calibration(run_number)?
+        .data
+        .get(&wire)
         .copied()
         .ok_or(MapWireBaselineError::MissingWire { run_number, wire })
 }
 
-// Nothing below this line needs to be changed when new files are added.
+/// Try to get the header of the wire baseline calibration used for a given run
+/// number.
+pub(crate) fn try_wire_baseline_header(
+    run_number: u32,
+) -> Result<CalibrationHeader, MapWireBaselineError> {
+    Ok(try_calibration(run_number)?.header.clone())
+}
 
 /// The error type returned when the baseline calibration map is not available.
 #[derive(Debug, Error)]
@@ -54,36 +87,52 @@ pub enum MapWireBaselineError {
         run_number: u32,
         wire: TpcWirePosition,
     },
+    #[error("failed to load external wire baseline calibration")]
+    External(#[from] LoadExternalCalibrationError),
 }
 
-fn complete_from_bytes(bytes: &[u8]) -> HashMap<TpcWirePosition, i16> {
+fn complete_from_bytes(bytes: &[u8]) -> Arc<Calibration<HashMap<TpcWirePosition, i16>>> {
     // Correctness of the format is checked by unit tests.
-    let map: HashMap<TpcWirePosition, (f64, f64, usize)> = serde_json::from_slice(bytes).unwrap();
-    map.into_iter()
+    Arc::new(Calibration::try_from_bytes(KIND, bytes, try_data_from_bytes).unwrap())
+}
+
+fn try_data_from_bytes(bytes: &[u8]) -> Result<HashMap<TpcWirePosition, i16>, String> {
+    let map: HashMap<TpcWirePosition, (f64, f64, usize)> =
+        serde_json::from_slice(bytes).map_err(|e| e.to_string())?;
+    Ok(map
+        .into_iter()
         .map(|(wire, (baseline, _, _))| (wire, baseline.round() as i16))
-        .collect()
+        .collect())
 }
 
 fn _update_previous_from_bytes(
-    previous: &HashMap<TpcWirePosition, i16>,
+    previous: &Calibration<HashMap<TpcWirePosition, i16>>,
     bytes: &[u8],
-) -> HashMap<TpcWirePosition, i16> {
+) -> Arc<Calibration<HashMap<TpcWirePosition, i16>>> {
     // Correctness of the format is checked by unit tests.
-    let update: HashMap<TpcWirePosition, Option<(f64, f64, usize)>> =
-        serde_json::from_slice(bytes).unwrap();
-    let update: HashMap<_, _> = update
-        .into_iter()
-        .map(|(k, v)| (k, v.map(|(baseline, _, _)| baseline.round() as i16)))
-        .collect();
+    let update = Calibration::try_from_bytes(KIND, bytes, |bytes| {
+        let update: HashMap<TpcWirePosition, Option<(f64, f64, usize)>> =
+            serde_json::from_slice(bytes).map_err(|e| e.to_string())?;
+        Ok(update
+            .into_iter()
+            .map(|(wire, value)| (wire, value.map(|(baseline, _, _)| baseline.round() as i16)))
+            .collect::<HashMap<_, _>>())
+    })
+    .unwrap();
 
-    let mut map = previous.clone();
-    for (wire, value) in update {
+    let mut map = previous.data.clone();
+    for (wire, value) in update.data {
         match value {
-            Some(value) => map.insert(wire, value),
+            Some(baseline) => map.insert(wire, baseline),
             None => map.remove(&wire),
         };
     }
-    map
+    // The provenance of an update is the update itself. The previous file can
+    // be found from the run range.
+    Arc::new(Calibration {
+        header: update.header,
+        data: map,
+    })
 }
 
 #[cfg(test)]
@@ -1,4 +1,5 @@
 use super::*;
+use crate::calibration::format::assert_run_range_matches_header;
 use alpha_g_detector::alpha16::{aw_map::TPC_ANODE_WIRES, ADC_MAX, ADC_MIN};
 
 fn all_within_limits(run_number: u32) -> bool {
@@ -81,3 +82,9 @@ fn try_wire_baseline_correctness_sim() {
         assert_eq!(baseline, 3000);
     }
 }
+
+#[test]
+fn run_ranges_match_headers() {
+    assert_run_range_matches_header(&MAP_SIMULATION, try_calibration);
+    assert_run_range_matches_header(&MAP_7026, try_calibration);
+}
@@ -1,9 +1,10 @@
+use crate::calibration::is_simulated_run;
 use thiserror::Error;
 
 pub(crate) fn try_wire_delay(run_number: u32) -> Result<usize, MapWireDelayError> {
     match run_number {
-        // u32::MAX corresponds to a simulation run.
-        u32::MAX => Ok(100),
+        // Simulated runs (see `declare_simulated_runs`).
+        run if is_simulated_run(run) => Ok(100),
         // This is basically a fixed value, but it is still good to check it
         // every once in a while. This will change if e.g. the main trigger
         // changes from the MLU2+ that has been used since forever.
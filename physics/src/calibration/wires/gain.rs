@@ -1,6 +1,12 @@
+use crate::calibration::format::{Calibration, CalibrationHeader};
+use crate::calibration::{
+    gain_with_fallback, is_simulated_run, try_external_calibration, GainFallback,
+    LoadExternalCalibrationError,
+};
 use alpha_g_detector::alpha16::aw_map::TpcWirePosition;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::sync::Arc;
 use thiserror::Error;
 
 includes! {
@@ -19,22 +25,30 @@ lazy_static! {
     //
     // Adding a new map is as simple as:
     // complete_from_bytes(BYTES_NUMBER)
-    static ref MAP_SIMULATION: HashMap<TpcWirePosition, f64> = complete_from_bytes(BYTES_SIMULATION);
-    static ref MAP_9277: HashMap<TpcWirePosition, f64> = complete_from_bytes(BYTES_9277);
-    static ref MAP_11186: HashMap<TpcWirePosition, f64> = complete_from_bytes(BYTES_11186);
-    static ref MAP_11506: HashMap<TpcWirePosition, f64> = complete_from_bytes(BYTES_11506);
+    static ref MAP_SIMULATION: Arc<Calibration<HashMap<TpcWirePosition, f64>>> =
+        complete_from_bytes(BYTES_SIMULATION);
+    static ref MAP_9277: Arc<Calibration<HashMap<TpcWirePosition, f64>>> =
+        complete_from_bytes(BYTES_9277);
+    static ref MAP_11186: Arc<Calibration<HashMap<TpcWirePosition, f64>>> =
+        complete_from_bytes(BYTES_11186);
+    static ref MAP_11506: Arc<Calibration<HashMap<TpcWirePosition, f64>>> =
+        complete_from_bytes(BYTES_11506);
 }
-/// Try to get the gain for a given wire. Return an error if there is no map
-/// available for the given run number or if there is no gain for a given
-/// wire in the map.
-pub(crate) fn try_wire_gain(
+
+fn try_calibration(
     run_number: u32,
-    wire: TpcWirePosition,
-) -> Result<f64, MapWireGainError> {
+) -> Result<Arc<Calibration<HashMap<TpcWirePosition, f64>>>, MapWireGainError> {
+    if let Some(calibration) =
+        try_external_calibration(KIND, "json", run_number, try_data_from_bytes)?
+    {
+        return Ok(calibration);
+    }
     // This map should be updated whenever a new file is added.
-    let map = match run_number {
-        // u32::MAX corresponds to a simulation run.
-        u32::MAX => &*MAP_SIMULATION,
+    // The run range in the header of each file has to agree with this match
+    // (checked by unit tests).
+    let calibration = match run_number {
+        // Simulated runs (see `declare_simulated_runs`).
+        run if is_simulated_run(run) => &*MAP_SIMULATION,
         // A calibration might be done some time after the detector is in a
         // given state. That's why some times the map is valid for runs before
         // the calibration run.
@@ -44,12 +58,48 @@ pub(crate) fn try_wire_gain(
         _ => return Err(MapWireGainError::MissingMap { run_number }),
     };
 
-    map.get(&wire)
+    Ok(Arc::clone(calibration))
+}
+
+// Nothing below this line needs to be changed when new files are added.
+
+const KIND: &str = "wires/gain";
+
+/// Try to get the gain for a given wire. Return an error if there is no map
+/// available for the given run number or if there is no gain for a given
+/// wire in the map.
+pub(crate) fn try_wire_gain(
+    run_number: u32,
+    wire: TpcWirePosition,
+) -> Result<f64, MapWireGainError> {
+    try_calibration(run_number)?
+        .data
+        .get(&wire)
         .copied()
         .ok_or(MapWireGainError::MissingWire { run_number, wire })
 }
 
-// Nothing below this line needs to be changed when new files are added.
+/// Same as [`try_wire_gain`], but a wire that is missing from the map is
+/// handled according to the given fallback policy.
+pub(crate) fn try_wire_gain_with_fallback(
+    run_number: u32,
+    wire: TpcWirePosition,
+    fallback: GainFallback,
+) -> Result<f64, MapWireGainError> {
+    gain_with_fallback(
+        run_number,
+        &wire,
+        fallback,
+        try_calibration,
+        MapWireGainError::MissingWire { run_number, wire },
+    )
+}
+
+/// Try to get the header of the wire gain calibration used for a given run
+/// number.
+pub(crate) fn try_wire_gain_header(run_number: u32) -> Result<CalibrationHeader, MapWireGainError> {
+    Ok(try_calibration(run_number)?.header.clone())
+}
 
 /// The error type returned when the gain calibration map is not available.
 #[derive(Debug, Error)]
@@ -61,11 +111,17 @@ pub enum MapWireGainError {
         run_number: u32,
         wire: TpcWirePosition,
     },
+    #[error("failed to load external wire gain calibration")]
+    External(#[from] LoadExternalCalibrationError),
 }
 
-fn complete_from_bytes(bytes: &[u8]) -> HashMap<TpcWirePosition, f64> {
+fn complete_from_bytes(bytes: &[u8]) -> Arc<Calibration<HashMap<TpcWirePosition, f64>>> {
     // Correctness of the format is checked by unit tests.
-    serde_json::from_slice(bytes).unwrap()
+    Arc::new(Calibration::try_from_bytes(KIND, bytes, try_data_from_bytes).unwrap())
+}
+
+fn try_data_from_bytes(bytes: &[u8]) -> Result<HashMap<TpcWirePosition, f64>, String> {
+    serde_json::from_slice(bytes).map_err(|e| e.to_string())
 }
 
 #[cfg(test)]
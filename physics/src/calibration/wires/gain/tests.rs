@@ -1,4 +1,5 @@
 use super::*;
+use crate::calibration::format::assert_run_range_matches_header;
 use alpha_g_detector::alpha16::aw_map::TPC_ANODE_WIRES;
 
 #[test]
@@ -99,3 +100,51 @@ fn try_wire_gain_correctness_sim() {
         assert_eq!(gain, 1.0);
     }
 }
+
+#[test]
+fn try_wire_gain_with_fallback_11084() {
+    let wire = TpcWirePosition::try_from(111).unwrap();
+    let previous = try_wire_gain(9277, wire).unwrap();
+    let next = try_wire_gain(11356, wire).unwrap();
+
+    assert!(try_wire_gain_with_fallback(11084, wire, GainFallback::Error).is_err());
+    assert_eq!(
+        try_wire_gain_with_fallback(11084, wire, GainFallback::Previous).unwrap(),
+        previous
+    );
+    assert_eq!(
+        try_wire_gain_with_fallback(11084, wire, GainFallback::Next).unwrap(),
+        next
+    );
+    for run_number in [11084, 11200, 11355] {
+        let fraction = f64::from(run_number - 9277) / f64::from(11356 - 9277);
+        let gain =
+            try_wire_gain_with_fallback(run_number, wire, GainFallback::Interpolate).unwrap();
+        assert!((gain - (previous + fraction * (next - previous))).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn try_wire_gain_with_fallback_present() {
+    let wire = TpcWirePosition::try_from(224).unwrap();
+    for fallback in [
+        GainFallback::Error,
+        GainFallback::Previous,
+        GainFallback::Next,
+        GainFallback::Interpolate,
+    ] {
+        assert_eq!(
+            try_wire_gain_with_fallback(11084, wire, fallback).unwrap(),
+            try_wire_gain(11084, wire).unwrap()
+        );
+        assert!(try_wire_gain_with_fallback(0, wire, fallback).is_err());
+    }
+}
+
+#[test]
+fn run_ranges_match_headers() {
+    assert_run_range_matches_header(&MAP_SIMULATION, try_calibration);
+    assert_run_range_matches_header(&MAP_9277, try_calibration);
+    assert_run_range_matches_header(&MAP_11186, try_calibration);
+    assert_run_range_matches_header(&MAP_11506, try_calibration);
+}
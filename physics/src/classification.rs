@@ -0,0 +1,379 @@
+use crate::bv::BvHit;
+use crate::reconstruction::{ReconstructionConfig, Track, VertexingResult};
+use crate::{Avalanche, SpacePoint};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uom::si::f64::{Length, Time};
+use uom::si::length::meter;
+use uom::si::time::second;
+
+/// Discriminating variables of a reconstructed event.
+///
+/// Optional values are `None` when they are not defined for a given event (e.g.
+/// there is no primary vertex).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EventFeatures {
+    /// Number of tracks (excluding cosmic rays).
+    pub num_tracks: usize,
+    /// Number of tracks tagged as cosmic rays.
+    pub num_cosmics: usize,
+    /// Number of tracks associated to the primary vertex.
+    pub num_vertex_tracks: usize,
+    /// Chi-squared of the primary vertex fit divided by the number of tracks
+    /// associated to it.
+    pub vertex_chi2_per_track: Option<f64>,
+    /// Radial position of the primary vertex.
+    pub vertex_r: Option<Length>,
+    /// Mean chi-squared per degree of freedom (i.e. the normalized fit
+    /// residuals) of all tracks (excluding cosmic rays).
+    pub track_chi2_per_ndf: Option<f64>,
+    /// Number of Barrel Veto hits.
+    pub num_bv_hits: usize,
+    /// Time difference between the first and last Barrel Veto hits.
+    pub bv_time_spread: Option<Time>,
+}
+
+// Number of variables in `EventFeatures`. Keep in sync with
+// `EventFeatures::values`.
+const NUM_FEATURES: usize = 8;
+
+impl EventFeatures {
+    /// Extract the discriminating variables of an event from the output of the
+    /// different reconstruction stages.
+    pub fn new(vertexing: &VertexingResult, cosmics: &[Track], bv_hits: &[BvHit]) -> Self {
+        let tracks: Vec<_> = vertexing
+            .primary
+            .iter()
+            .chain(&vertexing.secondaries)
+            .flat_map(|vertex| vertex.tracks.iter().map(|(track, _)| track))
+            .chain(&vertexing.remainder)
+            .collect();
+        let track_chi2_per_ndf = (!tracks.is_empty()).then(|| {
+            tracks
+                .iter()
+                .map(|track| track.chi2() / track.ndf().max(1) as f64)
+                .sum::<f64>()
+                / tracks.len() as f64
+        });
+        let primary = vertexing.primary.as_ref();
+        let bv_time_spread = (bv_hits.len() >= 2).then(|| {
+            let times = bv_hits.iter().map(|hit| hit.t.get::<second>());
+            let min = times.clone().fold(f64::INFINITY, f64::min);
+            let max = times.fold(f64::NEG_INFINITY, f64::max);
+
+            Time::new::<second>(max - min)
+        });
+
+        Self {
+            num_tracks: tracks.len(),
+            num_cosmics: cosmics.len(),
+            num_vertex_tracks: primary.map_or(0, |vertex| vertex.tracks.len()),
+            vertex_chi2_per_track: primary.map(|vertex| vertex.chi2 / vertex.tracks.len() as f64),
+            vertex_r: primary.map(|vertex| vertex.position.x.hypot(vertex.position.y)),
+            track_chi2_per_ndf,
+            num_bv_hits: bv_hits.len(),
+            bv_time_spread,
+        }
+    }
+    // Missing values are NaN.
+    fn values(&self) -> [f64; NUM_FEATURES] {
+        [
+            self.num_tracks as f64,
+            self.num_cosmics as f64,
+            self.num_vertex_tracks as f64,
+            self.vertex_chi2_per_track.unwrap_or(f64::NAN),
+            self.vertex_r.map_or(f64::NAN, |r| r.get::<meter>()),
+            self.track_chi2_per_ndf.unwrap_or(f64::NAN),
+            self.num_bv_hits as f64,
+            self.bv_time_spread.map_or(f64::NAN, |t| t.get::<second>()),
+        ]
+    }
+}
+
+/// Summary of the energy deposited by an event in the detector.
+///
+/// These are fast discriminating variables (i.e. they require neither track
+/// finding nor fitting) intended for online monitoring and skimming. All
+/// amplitudes and energies are in arbitrary units.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnergyDeposits {
+    /// Sum of the [`Avalanche::wire_amplitude`] of all avalanches.
+    pub wire_charge: f64,
+    /// Sum of the [`Avalanche::pad_amplitude`] of all avalanches.
+    pub pad_charge: f64,
+    /// Number of avalanches.
+    pub num_avalanches: usize,
+    /// Sum of the [`BvHit::energy`] of all Barrel Veto hits.
+    pub bv_energy: f64,
+}
+
+impl EnergyDeposits {
+    /// Aggregate the energy deposited by an event from its avalanches and
+    /// Barrel Veto hits.
+    pub fn new(avalanches: &[Avalanche], bv_hits: &[BvHit]) -> Self {
+        Self {
+            wire_charge: avalanches.iter().map(|a| a.wire_amplitude).sum(),
+            pad_charge: avalanches.iter().map(|a| a.pad_amplitude).sum(),
+            num_avalanches: avalanches.len(),
+            bv_energy: bv_hits.iter().map(|hit| hit.energy).sum(),
+        }
+    }
+}
+
+/// Topology of a reconstructed event.
+///
+/// This is a coarse classification intended to let downstream analyses branch
+/// on the type of event without re-implementing the same selection logic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventTopology {
+    /// There are no [`SpacePoint`]s.
+    Empty,
+    /// There are [`SpacePoint`]s, but neither a primary vertex nor a cosmic ray
+    /// were reconstructed.
+    NoiseOnly,
+    /// At least one track was tagged as a cosmic ray, and there is no primary
+    /// vertex.
+    Cosmic,
+    /// There are too many [`SpacePoint`]s to be a single annihilation (e.g.
+    /// a flash of annihilations on the electrodes during mixing).
+    BackgroundFlash,
+    /// There is a primary vertex.
+    AnnihilationCandidate,
+}
+
+/// Classify the topology of an event from the output of the different
+/// reconstruction stages. This is the same as [`event_topology_with_config`]
+/// with the default configuration.
+pub fn event_topology(
+    spacepoints: &[SpacePoint],
+    cosmics: &[Track],
+    vertexing: &VertexingResult,
+) -> EventTopology {
+    event_topology_with_config(
+        spacepoints,
+        cosmics,
+        vertexing,
+        &ReconstructionConfig::default(),
+    )
+}
+
+/// Classify the topology of an event with a custom configuration. See
+/// [`TopologyConfig`](crate::reconstruction::TopologyConfig) for the available
+/// parameters.
+///
+/// A background flash takes precedence over any other topology; a primary
+/// vertex takes precedence over cosmic rays.
+pub fn event_topology_with_config(
+    spacepoints: &[SpacePoint],
+    cosmics: &[Track],
+    vertexing: &VertexingResult,
+    config: &ReconstructionConfig,
+) -> EventTopology {
+    if spacepoints.is_empty() {
+        EventTopology::Empty
+    } else if spacepoints.len() > config.topology.max_num_spacepoints {
+        EventTopology::BackgroundFlash
+    } else if vertexing.primary.is_some() {
+        EventTopology::AnnihilationCandidate
+    } else if !cosmics.is_empty() {
+        EventTopology::Cosmic
+    } else {
+        EventTopology::NoiseOnly
+    }
+}
+
+/// The error type returned when training a [`LikelihoodClassifier`] fails.
+#[derive(Debug, Error)]
+pub enum TrainClassifierError {
+    /// The signal or background training sample is empty.
+    #[error("empty training sample")]
+    EmptySample,
+    /// The number of bins is zero.
+    #[error("zero number of bins")]
+    ZeroBins,
+}
+
+/// The error type returned when loading a [`LikelihoodClassifier`] (e.g. one
+/// trained with a different version of this crate) fails.
+#[derive(Debug, Error)]
+pub enum InvalidClassifierError {
+    /// The number of histograms doesn't match the number of features in
+    /// [`EventFeatures`].
+    #[error("wrong number of features (found `{found}`, expected `{expected}`)")]
+    WrongNumberOfFeatures { found: usize, expected: usize },
+    /// The histogram of a feature has no bins, a different number of signal
+    /// and background bins, or a non-positive probability density.
+    #[error("invalid histogram for feature `{0}`")]
+    InvalidHistogram(usize),
+}
+
+// Binned probability density of a single feature. The last bin is reserved for
+// missing values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Histogram {
+    min: f64,
+    max: f64,
+    signal: Vec<f64>,
+    background: Vec<f64>,
+}
+
+impl Histogram {
+    // At least one bin (plus the bin for missing values) with a strictly
+    // positive density. Otherwise `bin` panics or the score is not finite.
+    fn is_valid(&self) -> bool {
+        self.signal.len() >= 2
+            && self.signal.len() == self.background.len()
+            && self
+                .signal
+                .iter()
+                .chain(&self.background)
+                .all(|&p| p > 0.0 && p.is_finite())
+    }
+    fn bin(&self, value: f64) -> usize {
+        let num_bins = self.signal.len() - 1;
+        if value.is_nan() {
+            num_bins
+        } else if self.max <= self.min {
+            0
+        } else {
+            // Values outside of the training range go to the edge bins.
+            let fraction = (value - self.min) / (self.max - self.min);
+            ((fraction * num_bins as f64) as usize).min(num_bins - 1)
+        }
+    }
+}
+
+/// Projective likelihood classifier of annihilation (signal) vs background
+/// (e.g. cosmic rays) events.
+///
+/// Each feature of an [`EventFeatures`] is assumed to be independent, and its
+/// probability density for signal and background is estimated from binned
+/// training samples. The classifier can be stored and loaded with any serde
+/// format (e.g. JSON). Deserialization fails with an
+/// [`InvalidClassifierError`] if the classifier is not valid.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedLikelihoodClassifier")]
+pub struct LikelihoodClassifier {
+    histograms: Vec<Histogram>,
+}
+
+// Deserialized classifier before validation.
+#[derive(Deserialize)]
+struct UncheckedLikelihoodClassifier {
+    histograms: Vec<Histogram>,
+}
+
+impl TryFrom<UncheckedLikelihoodClassifier> for LikelihoodClassifier {
+    type Error = InvalidClassifierError;
+
+    fn try_from(unchecked: UncheckedLikelihoodClassifier) -> Result<Self, Self::Error> {
+        let histograms = unchecked.histograms;
+        if histograms.len() != NUM_FEATURES {
+            return Err(InvalidClassifierError::WrongNumberOfFeatures {
+                found: histograms.len(),
+                expected: NUM_FEATURES,
+            });
+        }
+        if let Some(i) = histograms.iter().position(|h| !h.is_valid()) {
+            return Err(InvalidClassifierError::InvalidHistogram(i));
+        }
+
+        Ok(Self { histograms })
+    }
+}
+
+impl LikelihoodClassifier {
+    /// Train a classifier from samples of signal and background events. Each
+    /// continuous feature is binned into `num_bins` between its minimum and
+    /// maximum values in the training samples.
+    pub fn train(
+        signal: &[EventFeatures],
+        background: &[EventFeatures],
+        num_bins: usize,
+    ) -> Result<Self, TrainClassifierError> {
+        if signal.is_empty() || background.is_empty() {
+            return Err(TrainClassifierError::EmptySample);
+        }
+        if num_bins == 0 {
+            return Err(TrainClassifierError::ZeroBins);
+        }
+
+        let signal: Vec<_> = signal.iter().map(EventFeatures::values).collect();
+        let background: Vec<_> = background.iter().map(EventFeatures::values).collect();
+        let histograms = (0..NUM_FEATURES)
+            .map(|i| {
+                let values = signal.iter().chain(&background).map(|v| v[i]);
+                let min = values
+                    .clone()
+                    .filter(|v| !v.is_nan())
+                    .fold(f64::INFINITY, f64::min);
+                let max = values
+                    .filter(|v| !v.is_nan())
+                    .fold(f64::NEG_INFINITY, f64::max);
+                let mut histogram = Histogram {
+                    min,
+                    max,
+                    signal: vec![0.0; num_bins + 1],
+                    background: vec![0.0; num_bins + 1],
+                };
+
+                let density = |sample: &[[f64; NUM_FEATURES]]| -> Vec<f64> {
+                    // Start every bin with a single entry. Otherwise, a single
+                    // empty bin is enough to veto an event.
+                    let mut counts = vec![1.0; num_bins + 1];
+                    for v in sample {
+                        counts[histogram.bin(v[i])] += 1.0;
+                    }
+                    let total: f64 = counts.iter().sum();
+                    counts.iter().map(|c| c / total).collect()
+                };
+                let (signal, background) = (density(&signal[..]), density(&background[..]));
+                histogram.signal = signal;
+                histogram.background = background;
+
+                histogram
+            })
+            .collect();
+
+        Ok(Self { histograms })
+    }
+    /// Return the signal score of an event. This is a number between 0
+    /// (background-like) and 1 (signal-like).
+    pub fn score(&self, features: &EventFeatures) -> f64 {
+        // Sum logarithms to avoid underflow.
+        let log_ratio: f64 = self
+            .histograms
+            .iter()
+            .zip(features.values())
+            .map(|(histogram, value)| {
+                let bin = histogram.bin(value);
+                histogram.background[bin].ln() - histogram.signal[bin].ln()
+            })
+            .sum();
+
+        1.0 / (1.0 + log_ratio.exp())
+    }
+    /// Return the fraction of signal events that are accepted, and the
+    /// fraction of background events that are rejected, when keeping all events
+    /// with a score greater than or equal to `threshold`.
+    pub fn evaluate(
+        &self,
+        signal: &[EventFeatures],
+        background: &[EventFeatures],
+        threshold: f64,
+    ) -> (f64, f64) {
+        let accepted = |sample: &[EventFeatures]| {
+            sample
+                .iter()
+                .filter(|features| self.score(features) >= threshold)
+                .count() as f64
+                / sample.len() as f64
+        };
+
+        (accepted(signal), 1.0 - accepted(background))
+    }
+}
+
+#[cfg(test)]
+mod tests;
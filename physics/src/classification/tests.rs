@@ -0,0 +1,211 @@
+use super::*;
+use uom::si::angle::radian;
+use uom::si::f64::Angle;
+use uom::si::length::centimeter;
+use uom::si::time::nanosecond;
+
+fn signal_like(i: usize) -> EventFeatures {
+    EventFeatures {
+        num_tracks: 3 + i % 3,
+        num_cosmics: 0,
+        num_vertex_tracks: 2 + i % 3,
+        vertex_chi2_per_track: Some(1.0 + 0.1 * (i % 5) as f64),
+        vertex_r: Some(Length::new::<centimeter>(2.0 + (i % 4) as f64)),
+        track_chi2_per_ndf: Some(1.0 + 0.1 * (i % 4) as f64),
+        num_bv_hits: 3 + i % 4,
+        bv_time_spread: Some(Time::new::<nanosecond>(0.5 + 0.1 * (i % 3) as f64)),
+    }
+}
+
+fn background_like(i: usize) -> EventFeatures {
+    EventFeatures {
+        num_tracks: i % 2,
+        num_cosmics: 1,
+        num_vertex_tracks: 0,
+        vertex_chi2_per_track: None,
+        vertex_r: None,
+        track_chi2_per_ndf: None,
+        num_bv_hits: 2,
+        bv_time_spread: Some(Time::new::<nanosecond>(3.0 + 0.2 * (i % 5) as f64)),
+    }
+}
+
+#[test]
+fn empty_vertexing_event_features() {
+    let vertexing = VertexingResult {
+        primary: None,
+        secondaries: Vec::new(),
+        remainder: Vec::new(),
+    };
+    let features = EventFeatures::new(&vertexing, &[], &[]);
+
+    assert_eq!(features.num_tracks, 0);
+    assert_eq!(features.num_cosmics, 0);
+    assert_eq!(features.num_vertex_tracks, 0);
+    assert!(features.vertex_chi2_per_track.is_none());
+    assert!(features.vertex_r.is_none());
+    assert!(features.track_chi2_per_ndf.is_none());
+    assert_eq!(features.num_bv_hits, 0);
+    assert!(features.bv_time_spread.is_none());
+}
+
+#[test]
+fn event_topology_without_tracks() {
+    let vertexing = VertexingResult {
+        primary: None,
+        secondaries: Vec::new(),
+        remainder: Vec::new(),
+    };
+    let sp = SpacePoint {
+        r: Length::new::<centimeter>(15.0),
+        phi: Angle::new::<radian>(0.0),
+        z: Length::new::<centimeter>(0.0),
+        wire_amplitude: 1.0,
+        pad_amplitude: 1.0,
+        sigma_r: Length::new::<centimeter>(0.1),
+        sigma_rphi: Length::new::<centimeter>(0.1),
+        sigma_z: Length::new::<centimeter>(0.1),
+    };
+    let mut config = ReconstructionConfig::default();
+    config.topology.max_num_spacepoints = 2;
+
+    assert_eq!(
+        event_topology_with_config(&[], &[], &vertexing, &config),
+        EventTopology::Empty
+    );
+    assert_eq!(
+        event_topology_with_config(&[sp; 2], &[], &vertexing, &config),
+        EventTopology::NoiseOnly
+    );
+    assert_eq!(
+        event_topology_with_config(&[sp; 3], &[], &vertexing, &config),
+        EventTopology::BackgroundFlash
+    );
+}
+
+#[test]
+fn train_classifier_errors() {
+    let signal: Vec<_> = (0..10).map(signal_like).collect();
+    let background: Vec<_> = (0..10).map(background_like).collect();
+
+    assert!(matches!(
+        LikelihoodClassifier::train(&[], &background, 10),
+        Err(TrainClassifierError::EmptySample)
+    ));
+    assert!(matches!(
+        LikelihoodClassifier::train(&signal, &[], 10),
+        Err(TrainClassifierError::EmptySample)
+    ));
+    assert!(matches!(
+        LikelihoodClassifier::train(&signal, &background, 0),
+        Err(TrainClassifierError::ZeroBins)
+    ));
+}
+
+#[test]
+fn trivial_likelihood_classifier() {
+    let signal: Vec<_> = (0..100).map(signal_like).collect();
+    let background: Vec<_> = (0..100).map(background_like).collect();
+    let classifier = LikelihoodClassifier::train(&signal, &background, 20).unwrap();
+
+    for features in signal.iter() {
+        let score = classifier.score(features);
+        assert!(score > 0.9 && score <= 1.0, "{score}");
+    }
+    for features in background.iter() {
+        let score = classifier.score(features);
+        assert!((0.0..0.1).contains(&score), "{score}");
+    }
+
+    let (efficiency, rejection) = classifier.evaluate(&signal, &background, 0.5);
+    assert_eq!(efficiency, 1.0);
+    assert_eq!(rejection, 1.0);
+}
+
+#[test]
+fn likelihood_classifier_out_of_range() {
+    let signal: Vec<_> = (0..100).map(signal_like).collect();
+    let background: Vec<_> = (0..100).map(background_like).collect();
+    let classifier = LikelihoodClassifier::train(&signal, &background, 20).unwrap();
+
+    let features = EventFeatures {
+        num_tracks: 100,
+        vertex_r: Some(Length::new::<centimeter>(-100.0)),
+        ..signal[0]
+    };
+    assert!(classifier.score(&features) > 0.5);
+}
+
+#[test]
+fn likelihood_classifier_serde_round_trip() {
+    let signal: Vec<_> = (0..10).map(signal_like).collect();
+    let background: Vec<_> = (0..10).map(background_like).collect();
+    let classifier = LikelihoodClassifier::train(&signal, &background, 5).unwrap();
+
+    let json = serde_json::to_string(&classifier).unwrap();
+    let loaded: LikelihoodClassifier = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, classifier);
+}
+
+#[test]
+fn likelihood_classifier_invalid_deserialize() {
+    let signal: Vec<_> = (0..10).map(signal_like).collect();
+    let background: Vec<_> = (0..10).map(background_like).collect();
+    let classifier = LikelihoodClassifier::train(&signal, &background, 5).unwrap();
+    let json = serde_json::to_value(&classifier).unwrap();
+
+    let mut missing_feature = json.clone();
+    missing_feature["histograms"].as_array_mut().unwrap().pop();
+    let err = serde_json::from_value::<LikelihoodClassifier>(missing_feature).unwrap_err();
+    assert!(
+        err.to_string().contains("wrong number of features"),
+        "{err}"
+    );
+
+    let mut empty_histogram = json.clone();
+    empty_histogram["histograms"][1]["signal"] = serde_json::json!([]);
+    empty_histogram["histograms"][1]["background"] = serde_json::json!([]);
+    let err = serde_json::from_value::<LikelihoodClassifier>(empty_histogram).unwrap_err();
+    assert!(err.to_string().contains("feature `1`"), "{err}");
+
+    let mut mismatched_bins = json;
+    mismatched_bins["histograms"][2]["background"]
+        .as_array_mut()
+        .unwrap()
+        .pop();
+    let err = serde_json::from_value::<LikelihoodClassifier>(mismatched_bins).unwrap_err();
+    assert!(err.to_string().contains("feature `2`"), "{err}");
+}
+
+#[test]
+fn energy_deposits() {
+    let avalanche = |wire_amplitude: f64, pad_amplitude: f64| Avalanche {
+        t: Time::new::<nanosecond>(1000.0),
+        phi: Angle::new::<radian>(0.0),
+        z: Length::new::<centimeter>(0.0),
+        wire_amplitude,
+        pad_amplitude,
+        pad_multiplicity: 1,
+    };
+    let hit = |energy: f64| BvHit {
+        bar: crate::bv::BarPosition::try_from(0).unwrap(),
+        z: Length::new::<centimeter>(0.0),
+        t: Time::new::<nanosecond>(0.0),
+        energy,
+    };
+
+    let deposits = EnergyDeposits::new(&[], &[]);
+    assert_eq!(deposits.wire_charge, 0.0);
+    assert_eq!(deposits.pad_charge, 0.0);
+    assert_eq!(deposits.num_avalanches, 0);
+    assert_eq!(deposits.bv_energy, 0.0);
+
+    let deposits = EnergyDeposits::new(
+        &[avalanche(1.0, 10.0), avalanche(2.5, 0.0)],
+        &[hit(3.0), hit(4.0), hit(0.5)],
+    );
+    assert_eq!(deposits.wire_charge, 3.5);
+    assert_eq!(deposits.pad_charge, 10.0);
+    assert_eq!(deposits.num_avalanches, 2);
+    assert_eq!(deposits.bv_energy, 7.5);
+}
@@ -10,7 +10,7 @@ const RESPONSE_BYTES: &[u8] = include_bytes!("../../data/simulation/tpc_response
 lazy_static! {
     // The format of the file is a serialized vector with the response every
     // nanosecond. Need to re-bin (by accumulating) by `BIN_WIDTH`.
-    static ref PAD_RESPONSE: Vec<f64> = {
+    pub(crate) static ref PAD_RESPONSE: Vec<f64> = {
         let raw: Vec<f64> = serde_json::from_slice(RESPONSE_BYTES).unwrap();
 
         raw
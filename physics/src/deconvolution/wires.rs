@@ -14,7 +14,7 @@ const RESPONSE_BYTES: &[u8] = include_bytes!("../../data/simulation/tpc_response
 lazy_static! {
     // The format of the file is a serialized vector with the response every
     // nanosecond. Need to re-bin (by accumulating) by `BIN_WIDTH`.
-    static ref WIRE_RESPONSE: Vec<f64> = {
+    pub(crate) static ref WIRE_RESPONSE: Vec<f64> = {
         let raw: Vec<f64> = serde_json::from_slice(RESPONSE_BYTES).unwrap();
 
         raw
@@ -24,7 +24,7 @@ lazy_static! {
     };
 }
 // "Strength" of the signal induced on a neighboring wire.
-const NEIGHBOR_FACTORS: [f64; 5] = [1.0, -0.1275, -0.0365, -0.012, -0.0042];
+pub(crate) const NEIGHBOR_FACTORS: [f64; 5] = [1.0, -0.1275, -0.0365, -0.012, -0.0042];
 
 // Identify all the contiguous `Some` signals.
 // Return, in an arbitrary order, a vector with the (half-open) intervals of the
@@ -1,7 +1,15 @@
+use crate::by_run::ByRun;
+use crate::gas::GasProperties;
+use alpha_g_detector::alpha16::aw_map::{ANODE_WIRES_RADIUS, INNER_CATHODE_RADIUS};
+use alpha_g_detector::padwing::map::DETECTOR_LENGTH;
 use lazy_static::lazy_static;
 use serde::Deserialize;
+use std::path::Path;
 use thiserror::Error;
+use uom::si::angle::radian;
 use uom::si::f64::{Angle, Length, Time};
+use uom::si::length::meter;
+use uom::si::time::{nanosecond, second};
 
 /// The error type returned when a drift time lookup fails.
 #[derive(Debug, Error)]
@@ -12,10 +20,31 @@ pub enum TryDriftLookupError {
     AxialPositionOutOfRange(Length),
 }
 
+/// The error type returned when loading [`DriftTables`] fails.
+#[derive(Debug, Error)]
+pub enum LoadDriftTablesError {
+    /// Error reading the file.
+    #[error("io error")]
+    IoError(#[from] std::io::Error),
+    /// The contents are not valid JSON drift tables.
+    #[error("bad drift tables format")]
+    BadFormat(#[from] serde_json::Error),
+    /// There are no drift tables.
+    #[error("empty drift tables")]
+    Empty,
+    /// A drift table has less than 2 entries or the drift times are not in
+    /// ascending order.
+    #[error("bad drift table at index `{index}`")]
+    BadTable { index: usize },
+    /// The `z` upper bounds are not positive and in ascending order.
+    #[error("bad axial upper bounds")]
+    BadUpperBounds,
+}
+
 // Represents the radius and Lorentz correction as a function of drift time.
-// The drift time is in ascending order. Given that this structure only exists
-// in a static lookup table loaded at compile time, this is guaranteed by unit
-// tests.
+// The drift time is in ascending order. This is guaranteed by unit tests for
+// the static lookup table loaded at compile time, and checked when loading
+// external tables.
 #[derive(Clone, Debug, Deserialize)]
 pub(crate) struct DriftTable(Vec<(Time, Length, Angle)>);
 
@@ -43,6 +72,28 @@ impl DriftTable {
 
         Ok((radius, correction))
     }
+    // Inverse of `at`. Return the drift time and Lorentz correction of an
+    // ionization at a given radius. The radius decreases with drift time, so
+    // the first bracketing pair of points is used.
+    fn drift_time(&self, r: Length) -> Option<(Time, Angle)> {
+        self.0.windows(2).find_map(|w| {
+            let (lhs_time, lhs_radius, lhs_correction) = w[0];
+            let (rhs_time, rhs_radius, rhs_correction) = w[1];
+            if r > lhs_radius.max(rhs_radius) || r < lhs_radius.min(rhs_radius) {
+                return None;
+            }
+
+            let fraction = if lhs_radius == rhs_radius {
+                0.0
+            } else {
+                ((r - lhs_radius) / (rhs_radius - lhs_radius)).value
+            };
+            let time = lhs_time + fraction * (rhs_time - lhs_time);
+            let correction = lhs_correction + fraction * (rhs_correction - lhs_correction);
+
+            Some((time, correction))
+        })
+    }
 }
 
 // The magnetic field is not uniform throughout the full detector length. Hence
@@ -51,12 +102,105 @@ impl DriftTable {
 // up to half the detector length). The negative `z` region is symmetric to the
 // positive `z` region.
 // Same as `DriftTable`, the `z` upper bound is in ascending order and checked
-// by unit tests.
+// by unit tests (or when loading external tables).
+/// Space-time relation i.e. radius and Lorentz angle correction as a function
+/// of `z` and drift time.
+///
+/// The default drift tables are obtained from a simulation with a uniform 1 T
+/// magnetic field and an Ar/CO2 (70/30) gas mixture. Run-dependent tables can
+/// be loaded from JSON files with the same format as the default table:
+///
+/// ```json
+/// [
+///     [[[0.0, 0.1819, 0.0], [8e-9, 0.1815, 0.0]], 0.6975],
+///     [[[0.0, 0.1819, 0.0], [8e-9, 0.1814, 0.0]], 1.152]
+/// ]
+/// ```
+///
+/// Each element is a `(table, z_upper_bound)` pair. Each table is a list of
+/// `(drift_time, radius, lorentz_correction)` entries in seconds, meters, and
+/// radians respectively.
 #[derive(Clone, Debug, Deserialize)]
-pub(crate) struct DriftTables(Vec<(DriftTable, Length)>);
+pub struct DriftTables(Vec<(DriftTable, Length)>);
+
+impl Default for DriftTables {
+    /// Return the embedded (simulated) drift tables.
+    fn default() -> Self {
+        DRIFT_TABLES.clone()
+    }
+}
+
+impl TryFrom<&[u8]> for DriftTables {
+    type Error = LoadDriftTablesError;
+
+    /// Parse drift tables from the bytes of a JSON file.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let tables: Self = serde_json::from_slice(bytes)?;
+        if tables.0.is_empty() {
+            return Err(LoadDriftTablesError::Empty);
+        }
+        for (index, (table, _)) in tables.0.iter().enumerate() {
+            if table.0.len() < 2 || table.0.windows(2).any(|w| w[0].0 >= w[1].0) {
+                return Err(LoadDriftTablesError::BadTable { index });
+            }
+        }
+        if tables.0[0].1.value <= 0.0 || tables.0.windows(2).any(|w| w[0].1 >= w[1].1) {
+            return Err(LoadDriftTablesError::BadUpperBounds);
+        }
+
+        Ok(tables)
+    }
+}
 
 impl DriftTables {
-    pub(crate) fn at(&self, z: Length, t: Time) -> Result<(Length, Angle), TryDriftLookupError> {
+    /// Read and parse drift tables from a JSON file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, LoadDriftTablesError> {
+        let contents = std::fs::read(path)?;
+
+        Self::try_from(&contents[..])
+    }
+    /// Return a simple space-time relation given the properties of the gas.
+    ///
+    /// The electrons drift radially (from the inner cathode to the anode
+    /// wires) at a constant velocity, and at a constant Lorentz angle with
+    /// respect to the electric field. The magnetic field is assumed to be
+    /// uniform along `z`. Returns [`None`] if the drift velocity is not
+    /// positive.
+    pub fn from_gas_properties(gas: &GasProperties) -> Option<Self> {
+        let velocity = gas.drift_velocity.value;
+        if !(velocity > 0.0 && velocity.is_finite()) {
+            return None;
+        }
+        let max_drift_time = (ANODE_WIRES_RADIUS - INNER_CATHODE_RADIUS) / velocity;
+        // Same time step as the embedded drift tables.
+        let step = Time::new::<nanosecond>(8.0).get::<second>();
+        let tan_lorentz = gas.lorentz_angle.get::<radian>().tan();
+
+        let num_entries = (max_drift_time / step).ceil() as usize + 1;
+        let table = (0..num_entries)
+            .map(|i| {
+                let t = (i as f64 * step).min(max_drift_time);
+                let r = ANODE_WIRES_RADIUS - velocity * t;
+                // An electron that moves `dr` radially also moves
+                // `tan(lorentz_angle) * dr` azimuthally.
+                let correction = tan_lorentz * (ANODE_WIRES_RADIUS / r).ln();
+
+                (
+                    Time::new::<second>(t),
+                    Length::new::<meter>(r),
+                    Angle::new::<radian>(correction),
+                )
+            })
+            .collect();
+
+        Some(Self(vec![(
+            DriftTable(table),
+            Length::new::<meter>(0.5 * DETECTOR_LENGTH),
+        )]))
+    }
+    /// Return the radius and Lorentz angle correction given the axial position
+    /// and drift time of an avalanche.
+    pub fn at(&self, z: Length, t: Time) -> Result<(Length, Angle), TryDriftLookupError> {
         let z_abs = z.abs();
         if z_abs > self.0[self.0.len() - 1].1 {
             return Err(TryDriftLookupError::AxialPositionOutOfRange(z));
@@ -70,6 +214,28 @@ impl DriftTables {
 
         table.at(t)
     }
+    // Return the maximum drift time (i.e. the last entry of the table) at a
+    // given axial position. `None` if the position is outside the range of the
+    // tables.
+    pub(crate) fn max_drift_time(&self, z: Length) -> Option<Time> {
+        let (table, _) = self
+            .0
+            .iter()
+            .find(|(_, z_upper_bound)| z_upper_bound >= &z.abs())?;
+
+        table.0.last().map(|&(time, _, _)| time)
+    }
+    // Return the drift time and Lorentz angle correction given the axial
+    // position and radius of an ionization. `None` if the position is outside
+    // the range of the tables.
+    pub(crate) fn drift_time(&self, z: Length, r: Length) -> Option<(Time, Angle)> {
+        let (table, _) = self
+            .0
+            .iter()
+            .find(|(_, z_upper_bound)| z_upper_bound >= &z.abs())?;
+
+        table.drift_time(r)
+    }
 }
 
 const TABLE_BYTES: &[u8] =
@@ -79,5 +245,12 @@ lazy_static! {
     pub(crate) static ref DRIFT_TABLES: DriftTables = serde_json::from_slice(TABLE_BYTES).unwrap();
 }
 
+/// Collection of [`DriftTables`] for different ranges of run numbers.
+///
+/// The gas mixture and drift field have changed between data taking campaigns.
+/// The default (embedded) drift tables are used for any run number not
+/// explicitly added.
+pub type DriftTablesByRun = ByRun<DriftTables>;
+
 #[cfg(test)]
 mod tests;
@@ -117,3 +117,103 @@ fn valid_drift_time_lookup() {
         z += Length::new::<meter>(0.1);
     }
 }
+
+#[test]
+fn default_drift_tables() {
+    let tables = DriftTables::default();
+    let z = Length::new::<meter>(0.3);
+    let t = Time::new::<microsecond>(1.0);
+
+    assert_eq!(tables.at(z, t).unwrap(), DRIFT_TABLES.at(z, t).unwrap());
+}
+
+#[test]
+fn drift_tables_try_from_bytes() {
+    let tables = DriftTables::try_from(TABLE_BYTES).unwrap();
+    let z = Length::new::<meter>(-0.8);
+    let t = Time::new::<microsecond>(2.0);
+    assert_eq!(tables.at(z, t).unwrap(), DRIFT_TABLES.at(z, t).unwrap());
+
+    let bytes = br#"[[[[0.0, 0.18, 0.0], [1e-6, 0.11, 0.1]], 1.2]]"#;
+    let tables = DriftTables::try_from(&bytes[..]).unwrap();
+    let (radius, correction) = tables
+        .at(Length::new::<meter>(1.0), Time::new::<microsecond>(0.5))
+        .unwrap();
+    assert!((radius - Length::new::<meter>(0.145)).abs() < Length::new::<meter>(1e-12));
+    assert!((correction - Angle::new::<radian>(0.05)).abs() < Angle::new::<radian>(1e-12));
+    assert!(tables
+        .at(Length::new::<meter>(1.3), Time::new::<microsecond>(0.5))
+        .is_err());
+}
+
+#[test]
+fn drift_tables_try_from_bad_bytes() {
+    match DriftTables::try_from(&b"[[1, 2]"[..]) {
+        Err(LoadDriftTablesError::BadFormat(_)) => {}
+        _ => unreachable!(),
+    }
+    match DriftTables::try_from(&b"[]"[..]) {
+        Err(LoadDriftTablesError::Empty) => {}
+        _ => unreachable!(),
+    }
+    let bytes = br#"[[[[0.0, 0.18, 0.0]], 1.2]]"#;
+    match DriftTables::try_from(&bytes[..]) {
+        Err(LoadDriftTablesError::BadTable { index: 0 }) => {}
+        _ => unreachable!(),
+    }
+    let bytes = br#"[[[[0.0, 0.18, 0.0], [1e-6, 0.11, 0.1]], 0.5], [[[1e-6, 0.18, 0.0], [0.0, 0.11, 0.1]], 1.2]]"#;
+    match DriftTables::try_from(&bytes[..]) {
+        Err(LoadDriftTablesError::BadTable { index: 1 }) => {}
+        _ => unreachable!(),
+    }
+    let bytes = br#"[[[[0.0, 0.18, 0.0], [1e-6, 0.11, 0.1]], 1.2], [[[0.0, 0.18, 0.0], [1e-6, 0.11, 0.1]], 0.5]]"#;
+    match DriftTables::try_from(&bytes[..]) {
+        Err(LoadDriftTablesError::BadUpperBounds) => {}
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn drift_tables_by_run() {
+    let bytes = br#"[[[[0.0, 0.18, 0.0], [1e-6, 0.11, 0.1]], 1.2]]"#;
+    let custom = DriftTables::try_from(&bytes[..]).unwrap();
+
+    let mut by_run = DriftTablesByRun::new();
+    by_run.insert(100..=200, custom).unwrap();
+    assert!(by_run.insert(200..=300, DriftTables::default()).is_err());
+    assert!(by_run.insert(50..=100, DriftTables::default()).is_err());
+    assert!(by_run.insert(120..=130, DriftTables::default()).is_err());
+    by_run.insert(201..=300, DriftTables::default()).unwrap();
+
+    let z = Length::new::<meter>(0.0);
+    let t = Time::new::<microsecond>(0.5);
+    let default = DRIFT_TABLES.at(z, t).unwrap();
+    let (radius, _) = by_run.get(150).at(z, t).unwrap();
+    assert!((radius - Length::new::<meter>(0.145)).abs() < Length::new::<meter>(1e-12));
+    for run_number in [0, 99, 201, 250, 301, u32::MAX] {
+        assert_eq!(by_run.get(run_number).at(z, t).unwrap(), default);
+    }
+}
+
+#[test]
+fn drift_tables_drift_time_inverse() {
+    for z in [0.0, 0.5, -1.0] {
+        let z = Length::new::<meter>(z);
+        for t in [0.1, 0.5, 1.0, 2.0] {
+            let t = Time::new::<microsecond>(t);
+            let (r, correction) = DRIFT_TABLES.at(z, t).unwrap();
+            let (time, inverse_correction) = DRIFT_TABLES.drift_time(z, r).unwrap();
+
+            assert!((time - t).abs() < Time::new::<microsecond>(1e-6));
+            assert!((inverse_correction - correction).abs() < Angle::new::<radian>(1e-9));
+        }
+    }
+
+    let z = Length::new::<meter>(0.0);
+    assert!(DRIFT_TABLES
+        .drift_time(z, Length::new::<meter>(ANODE_WIRES_RADIUS + 0.01))
+        .is_none());
+    assert!(DRIFT_TABLES
+        .drift_time(Length::new::<meter>(DETECTOR_LENGTH), z)
+        .is_none());
+}
@@ -0,0 +1,185 @@
+use crate::reconstruction::Coordinate;
+use serde::Deserialize;
+use std::path::Path;
+use thiserror::Error;
+use uom::si::f64::{Length, MagneticFluxDensity};
+use uom::si::magnetic_flux_density::tesla;
+
+/// Nominal magnetic field of the ALPHA-g solenoid.
+pub const NOMINAL_FIELD: MagneticFluxDensity = MagneticFluxDensity {
+    dimension: uom::lib::marker::PhantomData,
+    units: uom::lib::marker::PhantomData,
+    value: 1.0,
+};
+
+/// Magnetic field vector at a given point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldVector {
+    pub x: MagneticFluxDensity,
+    pub y: MagneticFluxDensity,
+    pub z: MagneticFluxDensity,
+}
+
+/// The error type returned when a magnetic field lookup fails.
+#[derive(Debug, Error)]
+#[error("coordinate `{coordinate:?}` is outside of the field map")]
+pub struct FieldOutOfRangeError {
+    coordinate: Coordinate,
+}
+
+/// The error type returned when loading a [`FieldMap`] fails.
+#[derive(Debug, Error)]
+pub enum LoadFieldMapError {
+    /// Error reading the file.
+    #[error("io error")]
+    IoError(#[from] std::io::Error),
+    /// The contents are not a valid JSON field map.
+    #[error("bad field map format")]
+    BadFormat(#[from] serde_json::Error),
+    /// A grid has less than 2 points or is not in ascending order.
+    #[error("bad `{axis}` grid (expected at least 2 points in ascending order)")]
+    BadGrid { axis: char },
+    /// The dimensions of a field table do not match the grid.
+    #[error("dimension mismatch (expected `{expected:?}`, found `{found:?}`)")]
+    DimensionMismatch {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+}
+
+/// Measured (or simulated) axially symmetric magnetic field map.
+///
+/// The map is a regular grid in `r` and `z`, and the field is linearly
+/// interpolated between grid points. The expected JSON format is:
+///
+/// ```json
+/// {
+///     "r": [0.0, 0.1, 0.2],
+///     "z": [-1.2, 0.0, 1.2],
+///     "b_r": [[0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 0.0]],
+///     "b_z": [[0.9, 0.9, 0.9], [1.0, 1.0, 1.0], [0.9, 0.9, 0.9]]
+/// }
+/// ```
+///
+/// Where `r` and `z` are in meters, and `b_r[i][j]` and `b_z[i][j]` (in tesla)
+/// are the radial and axial components of the field at `z[i]` and `r[j]`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FieldMap {
+    r: Vec<Length>,
+    z: Vec<Length>,
+    b_r: Vec<Vec<MagneticFluxDensity>>,
+    b_z: Vec<Vec<MagneticFluxDensity>>,
+}
+
+impl TryFrom<&[u8]> for FieldMap {
+    type Error = LoadFieldMapError;
+
+    /// Parse a field map from the bytes of a JSON file.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let map: Self = serde_json::from_slice(bytes)?;
+
+        for (axis, grid) in [('r', &map.r), ('z', &map.z)] {
+            if grid.len() < 2 || grid.windows(2).any(|w| w[0] >= w[1]) {
+                return Err(LoadFieldMapError::BadGrid { axis });
+            }
+        }
+        for table in [&map.b_r, &map.b_z] {
+            let expected = (map.z.len(), map.r.len());
+            let found = (
+                table.len(),
+                table
+                    .iter()
+                    .map(Vec::len)
+                    .find(|&len| len != map.r.len())
+                    .unwrap_or(map.r.len()),
+            );
+            if found != expected {
+                return Err(LoadFieldMapError::DimensionMismatch { expected, found });
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+// Return the index of the lower grid point and the fractional distance to the
+// upper grid point. The grid is guaranteed to have at least 2 points in
+// ascending order.
+fn bracket(grid: &[Length], value: Length) -> Option<(usize, f64)> {
+    if value < grid[0] || value > grid[grid.len() - 1] {
+        return None;
+    }
+    let upper = grid
+        .iter()
+        .position(|&g| g > value)
+        // If value is the last element, the last two elements bracket it.
+        .unwrap_or(grid.len() - 1);
+    let lower = upper - 1;
+    let fraction = ((value - grid[lower]) / (grid[upper] - grid[lower])).value;
+
+    Some((lower, fraction))
+}
+
+impl FieldMap {
+    /// Read and parse a field map from a JSON file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, LoadFieldMapError> {
+        let contents = std::fs::read(path)?;
+
+        Self::try_from(&contents[..])
+    }
+    /// Return the magnetic field at a given coordinate.
+    pub fn at(&self, coordinate: Coordinate) -> Result<FieldVector, FieldOutOfRangeError> {
+        let r = coordinate.x.hypot(coordinate.y);
+        let (Some((i, fz)), Some((j, fr))) = (bracket(&self.z, coordinate.z), bracket(&self.r, r))
+        else {
+            return Err(FieldOutOfRangeError { coordinate });
+        };
+        let interpolate = |table: &[Vec<MagneticFluxDensity>]| {
+            let low = table[i][j] + fr * (table[i][j + 1] - table[i][j]);
+            let high = table[i + 1][j] + fr * (table[i + 1][j + 1] - table[i + 1][j]);
+            low + fz * (high - low)
+        };
+        let b_r = interpolate(&self.b_r);
+        let b_z = interpolate(&self.b_z);
+
+        let phi = coordinate.y.atan2(coordinate.x);
+        Ok(FieldVector {
+            x: b_r * phi.cos(),
+            y: b_r * phi.sin(),
+            z: b_z,
+        })
+    }
+}
+
+/// Magnetic field in the ALPHA-g detector.
+#[derive(Clone, Debug)]
+pub enum MagneticField {
+    /// Uniform field along the `z` axis.
+    Uniform(MagneticFluxDensity),
+    /// Field given by a [`FieldMap`].
+    Map(FieldMap),
+}
+
+impl Default for MagneticField {
+    /// Uniform field equal to [`NOMINAL_FIELD`].
+    fn default() -> Self {
+        Self::Uniform(NOMINAL_FIELD)
+    }
+}
+
+impl MagneticField {
+    /// Return the magnetic field at a given coordinate.
+    pub fn at(&self, coordinate: Coordinate) -> Result<FieldVector, FieldOutOfRangeError> {
+        match self {
+            Self::Uniform(b) => Ok(FieldVector {
+                x: MagneticFluxDensity::new::<tesla>(0.0),
+                y: MagneticFluxDensity::new::<tesla>(0.0),
+                z: *b,
+            }),
+            Self::Map(map) => map.at(coordinate),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
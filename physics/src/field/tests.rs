@@ -0,0 +1,115 @@
+use super::*;
+use uom::si::length::meter;
+
+const MAP: &str = r#"{
+    "r": [0.0, 0.1, 0.2],
+    "z": [-1.0, 0.0, 1.0],
+    "b_r": [[0.0, -0.1, -0.2], [0.0, 0.0, 0.0], [0.0, 0.1, 0.2]],
+    "b_z": [[0.5, 0.5, 0.5], [1.0, 1.0, 1.0], [0.5, 0.5, 0.5]]
+}"#;
+
+fn coordinate(x: f64, y: f64, z: f64) -> Coordinate {
+    Coordinate {
+        x: Length::new::<meter>(x),
+        y: Length::new::<meter>(y),
+        z: Length::new::<meter>(z),
+    }
+}
+
+#[test]
+fn nominal_field() {
+    assert_eq!(NOMINAL_FIELD, MagneticFluxDensity::new::<tesla>(1.0));
+}
+
+#[test]
+fn uniform_field_at() {
+    let field = MagneticField::default();
+    for c in [coordinate(0.0, 0.0, 0.0), coordinate(10.0, -3.0, 100.0)] {
+        let b = field.at(c).unwrap();
+        assert_eq!(b.x, MagneticFluxDensity::new::<tesla>(0.0));
+        assert_eq!(b.y, MagneticFluxDensity::new::<tesla>(0.0));
+        assert_eq!(b.z, NOMINAL_FIELD);
+    }
+}
+
+#[test]
+fn field_map_at_grid_points() {
+    let map = FieldMap::try_from(MAP.as_bytes()).unwrap();
+
+    let b = map.at(coordinate(0.0, 0.0, 0.0)).unwrap();
+    assert_eq!(b.z, MagneticFluxDensity::new::<tesla>(1.0));
+    let b = map.at(coordinate(0.0, 0.0, -1.0)).unwrap();
+    assert_eq!(b.z, MagneticFluxDensity::new::<tesla>(0.5));
+    let b = map.at(coordinate(0.2, 0.0, 1.0)).unwrap();
+    assert_eq!(b.x, MagneticFluxDensity::new::<tesla>(0.2));
+    assert_eq!(b.z, MagneticFluxDensity::new::<tesla>(0.5));
+}
+
+#[test]
+fn field_map_interpolation() {
+    let map = FieldMap::try_from(MAP.as_bytes()).unwrap();
+
+    let b = map.at(coordinate(0.0, 0.15, 0.5)).unwrap();
+    assert!((b.x.get::<tesla>()).abs() < 1e-12);
+    assert!((b.y.get::<tesla>() - 0.075).abs() < 1e-12);
+    assert!((b.z.get::<tesla>() - 0.75).abs() < 1e-12);
+
+    let field = MagneticField::Map(map);
+    let b = field.at(coordinate(-0.15, 0.0, -0.5)).unwrap();
+    assert!((b.x.get::<tesla>() - 0.075).abs() < 1e-12);
+    assert!((b.z.get::<tesla>() - 0.75).abs() < 1e-12);
+}
+
+#[test]
+fn field_map_out_of_range() {
+    let map = FieldMap::try_from(MAP.as_bytes()).unwrap();
+
+    assert!(map.at(coordinate(0.0, 0.0, 1.1)).is_err());
+    assert!(map.at(coordinate(0.0, 0.0, -1.1)).is_err());
+    assert!(map.at(coordinate(0.2, 0.1, 0.0)).is_err());
+}
+
+#[test]
+fn field_map_bad_format() {
+    match FieldMap::try_from(&b"not json"[..]) {
+        Err(LoadFieldMapError::BadFormat(_)) => {}
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn field_map_bad_grid() {
+    let bad = r#"{"r": [0.0, 0.2, 0.1], "z": [0.0, 1.0], "b_r": [[0, 0, 0], [0, 0, 0]], "b_z": [[0, 0, 0], [0, 0, 0]]}"#;
+    match FieldMap::try_from(bad.as_bytes()) {
+        Err(LoadFieldMapError::BadGrid { axis: 'r' }) => {}
+        _ => unreachable!(),
+    }
+
+    let bad = r#"{"r": [0.0, 0.1], "z": [0.0], "b_r": [[0, 0]], "b_z": [[0, 0]]}"#;
+    match FieldMap::try_from(bad.as_bytes()) {
+        Err(LoadFieldMapError::BadGrid { axis: 'z' }) => {}
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn field_map_dimension_mismatch() {
+    let bad =
+        r#"{"r": [0.0, 0.1], "z": [0.0, 1.0], "b_r": [[0, 0], [0, 0]], "b_z": [[0, 0], [0]]}"#;
+    match FieldMap::try_from(bad.as_bytes()) {
+        Err(LoadFieldMapError::DimensionMismatch {
+            expected: (2, 2),
+            found: (2, 1),
+        }) => {}
+        _ => unreachable!(),
+    }
+
+    let bad = r#"{"r": [0.0, 0.1], "z": [0.0, 1.0], "b_r": [[0, 0]], "b_z": [[0, 0], [0, 0]]}"#;
+    match FieldMap::try_from(bad.as_bytes()) {
+        Err(LoadFieldMapError::DimensionMismatch {
+            expected: (2, 2),
+            found: (1, 2),
+        }) => {}
+        _ => unreachable!(),
+    }
+}
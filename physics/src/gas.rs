@@ -0,0 +1,234 @@
+use crate::by_run::ByRun;
+use crate::field::NOMINAL_FIELD;
+use crate::timing::T0;
+use crate::{Avalanche, DriftTables};
+use alpha_g_detector::alpha16::aw_map::{ANODE_WIRES_RADIUS, INNER_CATHODE_RADIUS};
+use serde::{Deserialize, Serialize};
+use uom::si::angle::radian;
+use uom::si::f64::*;
+use uom::si::length::{meter, millimeter};
+use uom::si::magnetic_flux_density::tesla;
+use uom::si::time::second;
+use uom::si::velocity::meter_per_second;
+
+/// Reference properties of the rTPC gas for a given set of running conditions.
+///
+/// The default values correspond to an Ar/CO2 (70/30) gas mixture in a uniform
+/// 1 T magnetic field i.e. the same conditions as the embedded
+/// [`DriftTables`]. These are the values used by the
+/// simulation (see
+/// [`DriftConfig::from_gas_properties`](crate::simulation::DriftConfig::from_gas_properties)),
+/// and can be used to build a simple space-time relation (see
+/// [`DriftTables::from_gas_properties`](crate::DriftTables::from_gas_properties))
+/// for conditions without a dedicated drift table.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GasProperties {
+    /// Fraction (between `0` and `1`) of CO2 in the Ar/CO2 gas mixture.
+    pub co2_fraction: f64,
+    /// Axial magnetic field.
+    pub magnetic_field: MagneticFluxDensity,
+    /// Average radial drift velocity of the ionization electrons.
+    pub drift_velocity: Velocity,
+    /// Angle between the drift velocity of the electrons and the (radial)
+    /// electric field.
+    pub lorentz_angle: Angle,
+    /// Mean gas gain relative to the default conditions.
+    pub relative_gain: f64,
+    /// Shape parameter `theta` of the Polya distribution of the gas gain.
+    pub polya_theta: f64,
+    /// Mean distance between primary ionization clusters along the track of a
+    /// minimum ionizing particle.
+    pub cluster_spacing: Length,
+    /// Longitudinal (radial) diffusion after drifting 1 cm.
+    pub longitudinal_diffusion: Length,
+    /// Transverse (azimuthal and axial) diffusion after drifting 1 cm.
+    pub transverse_diffusion: Length,
+}
+
+impl Default for GasProperties {
+    fn default() -> Self {
+        Self {
+            co2_fraction: 0.3,
+            magnetic_field: NOMINAL_FIELD,
+            // Average of the embedded drift tables: electrons drift from the
+            // inner cathode to the anode wires in about 4.3 us.
+            drift_velocity: Velocity::new::<meter_per_second>(17_000.0),
+            // Gives the same Lorentz angle correction at the inner cathode as
+            // the embedded drift tables (at `z = 0`).
+            lorentz_angle: Angle::new::<radian>(0.29),
+            relative_gain: 1.0,
+            polya_theta: 0.5,
+            // About 30 primary clusters per cm for a minimum ionizing particle
+            // in Ar/CO2 (70/30).
+            cluster_spacing: Length::new::<millimeter>(0.33),
+            // Roughly what Magboltz gives for Ar/CO2 (70/30) at the rTPC
+            // drift field. The transverse diffusion is further reduced by the
+            // magnetic field, but this is a good enough upper bound.
+            longitudinal_diffusion: Length::new::<millimeter>(0.15),
+            transverse_diffusion: Length::new::<millimeter>(0.15),
+        }
+    }
+}
+
+/// Collection of [`GasProperties`] for different ranges of run numbers.
+///
+/// The CO2 fraction and the magnetic field have changed between data taking
+/// campaigns. The default properties are used for any run number not
+/// explicitly added.
+pub type GasPropertiesByRun = ByRun<GasProperties>;
+
+/// Drift velocity and global T0 of a run extracted from cosmic tracks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DriftCalibration {
+    /// Time of the avalanches generated at the anode wires (i.e. zero drift
+    /// time) in the same reference as [`Avalanche::t`].
+    pub t0: T0,
+    /// Drift time of the ionization at the inner cathode.
+    pub max_drift_time: Time,
+    /// Average radial drift velocity of the ionization electrons.
+    pub drift_velocity: Velocity,
+}
+
+impl DriftCalibration {
+    /// Return the given [`GasProperties`] with the measured drift velocity.
+    /// These are the conditions of a field-off run i.e. there is no magnetic
+    /// field nor Lorentz angle.
+    ///
+    /// Use e.g. [`DriftTables::from_gas_properties`](crate::DriftTables::from_gas_properties)
+    /// to get the corresponding space-time relation.
+    pub fn gas_properties(&self, gas: GasProperties) -> GasProperties {
+        GasProperties {
+            magnetic_field: MagneticFluxDensity::new::<tesla>(0.0),
+            drift_velocity: self.drift_velocity,
+            lorentz_angle: Angle::new::<radian>(0.0),
+            ..gas
+        }
+    }
+    /// Return the space-time relation given by
+    /// [`DriftCalibration::gas_properties`] (e.g. with the properties of the
+    /// run from [`GasPropertiesByRun::get`]). Insert it into a
+    /// [`DriftTablesByRun`](crate::DriftTablesByRun) to use it for the
+    /// reconstruction of the run. Returns [`None`] under the same conditions
+    /// as [`DriftTables::from_gas_properties`].
+    pub fn drift_tables(&self, gas: GasProperties) -> Option<DriftTables> {
+        DriftTables::from_gas_properties(&self.gas_properties(gas))
+    }
+}
+
+// Quantiles of the drift time distribution used to estimate the height of the
+// plateau next to the leading and trailing edges.
+const LEADING_PLATEAU: (f64, f64) = (0.1, 0.3);
+const TRAILING_PLATEAU: (f64, f64) = (0.7, 0.9);
+// Maximum number of bins in the histogram of avalanche times. This is more
+// than enough for any reasonable bin width (the drift time is a few us).
+const MAX_NUM_BINS: usize = 100_000;
+
+/// Extract the [`DriftCalibration`] of a run from the avalanches of cosmic
+/// tracks recorded without magnetic field.
+///
+/// Cosmic rays cross the full drift region, so the distribution of the time
+/// of all their avalanches is a plateau between the avalanches at the anode
+/// wires (the global T0) and those at the inner cathode (T0 plus the maximum
+/// drift time). Each edge is located at half the height of the adjacent
+/// plateau in a histogram of the avalanche times with bins of `bin_width`
+/// (e.g. the sampling period of the anode wires). The drift velocity is then
+/// the distance between the anode wires and the inner cathode divided by the
+/// maximum drift time.
+///
+/// Avalanches far away from the plateau (further than the width of the
+/// plateau) are ignored. Avalanches should be accumulated over many events of
+/// the same run. Returns [`None`] if there are not enough avalanches to find
+/// both edges, or if the bins are too small for the plateau.
+pub fn fit_drift_calibration(
+    avalanches: &[Avalanche],
+    bin_width: Time,
+) -> Option<DriftCalibration> {
+    let bin_width = bin_width.get::<second>();
+    if !(bin_width > 0.0 && bin_width.is_finite()) {
+        return None;
+    }
+    let mut times: Vec<f64> = avalanches
+        .iter()
+        .map(|avalanche| avalanche.t.get::<second>())
+        .filter(|t| t.is_finite())
+        .collect();
+    times.sort_unstable_by(f64::total_cmp);
+    if times.is_empty() {
+        return None;
+    }
+    // A few outliers (e.g. noise far away from the trigger) would otherwise
+    // stretch the histogram arbitrarily.
+    let quantile = |q: f64| times[((times.len() - 1) as f64 * q) as usize];
+    let width = quantile(TRAILING_PLATEAU.1) - quantile(LEADING_PLATEAU.0);
+    let (min, max) = (
+        quantile(LEADING_PLATEAU.0) - width,
+        quantile(TRAILING_PLATEAU.1) + width,
+    );
+    times.retain(|&t| t >= min && t <= max);
+    let (first, last) = (*times.first()?, *times.last()?);
+
+    let num_bins = ((last - first) / bin_width).floor() as usize + 1;
+    if num_bins > MAX_NUM_BINS {
+        return None;
+    }
+    let mut counts = vec![0.0; num_bins];
+    for t in &times {
+        counts[((t - first) / bin_width) as usize] += 1.0;
+    }
+    let bin =
+        |q: f64| ((times[((times.len() - 1) as f64 * q) as usize] - first) / bin_width) as usize;
+    let height = |(q_min, q_max): (f64, f64)| {
+        let (min, max) = (bin(q_min), bin(q_max));
+        counts[min..=max].iter().sum::<f64>() / (max - min + 1) as f64
+    };
+    let center = |i: usize| first + (i as f64 + 0.5) * bin_width;
+    // Time at which the counts cross half the plateau height (linearly
+    // interpolated between bin centers), walking away from the plateau towards
+    // the edge of the histogram.
+    let edge = |start: usize, half: f64, backwards: bool| {
+        let step = |i: usize| {
+            if backwards {
+                i.checked_sub(1)
+            } else {
+                Some(i + 1).filter(|&i| i < num_bins)
+            }
+        };
+        if counts[start] < half {
+            return None;
+        }
+        let mut i = start;
+        loop {
+            let next = step(i)?;
+            if counts[next] < half {
+                let fraction = (counts[i] - half) / (counts[i] - counts[next]);
+                break Some(center(i) + fraction * (center(next) - center(i)));
+            }
+            i = next;
+        }
+    };
+
+    let leading = edge(bin(LEADING_PLATEAU.1), 0.5 * height(LEADING_PLATEAU), true)?;
+    let trailing = edge(
+        bin(TRAILING_PLATEAU.0),
+        0.5 * height(TRAILING_PLATEAU),
+        false,
+    )?;
+    let max_drift_time = trailing - leading;
+    if max_drift_time <= 0.0 {
+        return None;
+    }
+
+    Some(DriftCalibration {
+        t0: T0 {
+            t0: Time::new::<second>(leading),
+            sigma: Time::new::<second>(bin_width / 12.0f64.sqrt()),
+        },
+        max_drift_time: Time::new::<second>(max_drift_time),
+        drift_velocity: Length::new::<meter>(ANODE_WIRES_RADIUS - INNER_CATHODE_RADIUS)
+            / Time::new::<second>(max_drift_time),
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,178 @@
+use super::*;
+use crate::simulation::DriftConfig;
+use crate::DriftTables;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::StandardNormal;
+use uom::si::time::nanosecond;
+
+fn avalanche(t: Time) -> Avalanche {
+    Avalanche {
+        t,
+        phi: Angle::new::<radian>(0.0),
+        z: Length::new::<meter>(0.0),
+        wire_amplitude: 1.0,
+        pad_amplitude: 1.0,
+        pad_multiplicity: 1,
+    }
+}
+
+#[test]
+fn gas_properties_default_round_trip() {
+    let gas = GasProperties::default();
+    let json = serde_json::to_string(&gas).unwrap();
+    assert_eq!(serde_json::from_str::<GasProperties>(&json).unwrap(), gas);
+    assert_eq!(serde_json::from_str::<GasProperties>("{}").unwrap(), gas);
+}
+
+#[test]
+fn gas_properties_default_drift_tables() {
+    let linear = DriftTables::from_gas_properties(&GasProperties::default()).unwrap();
+    let embedded = DriftTables::default();
+
+    let z = Length::new::<meter>(0.0);
+    let r = Length::new::<meter>(INNER_CATHODE_RADIUS + 0.001);
+    let (linear_t, linear_correction) = linear.drift_time(z, r).unwrap();
+    let (embedded_t, embedded_correction) = embedded.drift_time(z, r).unwrap();
+    assert!(((linear_t - embedded_t) / embedded_t).value.abs() < 0.02);
+    assert!(
+        ((linear_correction - embedded_correction) / embedded_correction)
+            .value
+            .abs()
+            < 0.05
+    );
+
+    let max_drift_time = linear.max_drift_time(z).unwrap();
+    let (r, _) = linear.at(z, max_drift_time).unwrap();
+    assert!((r - Length::new::<meter>(INNER_CATHODE_RADIUS)).abs() < Length::new::<meter>(1e-9));
+}
+
+#[test]
+fn gas_properties_bad_drift_velocity() {
+    for velocity in [0.0, -1.0, f64::NAN] {
+        let gas = GasProperties {
+            drift_velocity: Velocity::new::<meter_per_second>(velocity),
+            ..Default::default()
+        };
+        assert!(DriftTables::from_gas_properties(&gas).is_none());
+    }
+}
+
+#[test]
+fn gas_properties_drift_config() {
+    assert_eq!(
+        DriftConfig::from_gas_properties(&GasProperties::default()),
+        DriftConfig::default()
+    );
+
+    let gas = GasProperties {
+        relative_gain: 2.0,
+        polya_theta: 1.0,
+        ..Default::default()
+    };
+    let config = DriftConfig::from_gas_properties(&gas);
+    assert_eq!(config.relative_gain, 2.0);
+    assert_eq!(config.polya_theta, 1.0);
+}
+
+#[test]
+fn gas_properties_by_run() {
+    let custom = GasProperties {
+        co2_fraction: 0.2,
+        magnetic_field: MagneticFluxDensity::new::<tesla>(0.65),
+        ..Default::default()
+    };
+
+    let mut by_run = GasPropertiesByRun::new();
+    by_run.insert(100..=200, custom).unwrap();
+    assert!(by_run.insert(200..=300, GasProperties::default()).is_err());
+    assert!(by_run.insert(50..=100, GasProperties::default()).is_err());
+    assert!(by_run.insert(120..=130, GasProperties::default()).is_err());
+    by_run.insert(201..=300, GasProperties::default()).unwrap();
+
+    assert_eq!(*by_run.get(150), custom);
+    for run_number in [0, 99, 201, 250, 301, u32::MAX] {
+        assert_eq!(*by_run.get(run_number), GasProperties::default());
+    }
+
+    assert_eq!(
+        DriftConfig::for_run(&by_run, 150),
+        DriftConfig::from_gas_properties(&custom)
+    );
+    assert_eq!(DriftConfig::for_run(&by_run, 0), DriftConfig::default());
+}
+
+#[test]
+fn fit_drift_calibration_cosmics() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let t0 = Time::new::<nanosecond>(1000.0);
+    let max_drift_time = Time::new::<nanosecond>(4282.0);
+    let smearing = Time::new::<nanosecond>(10.0);
+    let mut avalanches: Vec<_> = (0..20000)
+        .map(|_| {
+            let normal: f64 = rng.sample(StandardNormal);
+            avalanche(t0 + rng.gen_range(0.0..1.0) * max_drift_time + normal * smearing)
+        })
+        .collect();
+    // Random noise.
+    avalanches.extend((0..200).map(|_| avalanche(rng.gen_range(0.0..2.0) * (t0 + max_drift_time))));
+
+    let bin_width = Time::new::<nanosecond>(16.0);
+    let calibration = fit_drift_calibration(&avalanches, bin_width).unwrap();
+    assert!((calibration.t0.t0 - t0).abs() < bin_width);
+    assert!((calibration.max_drift_time - max_drift_time).abs() < 2.0 * bin_width);
+    let velocity = Velocity::new::<meter_per_second>(17_000.0);
+    assert!(
+        ((calibration.drift_velocity - velocity) / velocity)
+            .value
+            .abs()
+            < 0.01
+    );
+
+    let gas = calibration.gas_properties(GasProperties::default());
+    assert_eq!(gas.magnetic_field, MagneticFluxDensity::new::<tesla>(0.0));
+    assert_eq!(gas.lorentz_angle, Angle::new::<radian>(0.0));
+    let tables = calibration.drift_tables(GasProperties::default()).unwrap();
+    let z = Length::new::<meter>(0.0);
+    assert!(
+        (tables.max_drift_time(z).unwrap() - calibration.max_drift_time).abs()
+            < Time::new::<nanosecond>(1e-6)
+    );
+    let corrected = calibration.t0.correct(avalanche(t0));
+    assert!(corrected.t.abs() < bin_width);
+}
+
+#[test]
+fn fit_drift_calibration_outliers() {
+    let t0 = Time::new::<nanosecond>(1000.0);
+    let max_drift_time = Time::new::<nanosecond>(4282.0);
+    let mut avalanches: Vec<_> = (0..10000)
+        .map(|i| avalanche(t0 + max_drift_time * (i as f64 / 10000.0)))
+        .chain((0..100).map(|i| avalanche(Time::new::<nanosecond>(70.0 * i as f64))))
+        .collect();
+    // Would need ~1e14 bins if the histogram covered all avalanches.
+    avalanches.push(avalanche(Time::new::<second>(1e6)));
+    avalanches.push(avalanche(Time::new::<second>(-1e6)));
+
+    let bin_width = Time::new::<nanosecond>(16.0);
+    let calibration = fit_drift_calibration(&avalanches, bin_width).unwrap();
+    assert!((calibration.t0.t0 - t0).abs() < bin_width);
+    assert!((calibration.max_drift_time - max_drift_time).abs() < 2.0 * bin_width);
+
+    // Bins that are too small for the plateau.
+    assert!(fit_drift_calibration(&avalanches, Time::new::<nanosecond>(1e-3)).is_none());
+}
+
+#[test]
+fn fit_drift_calibration_not_enough_avalanches() {
+    let bin_width = Time::new::<nanosecond>(16.0);
+    assert!(fit_drift_calibration(&[], bin_width).is_none());
+
+    let avalanches = [avalanche(Time::new::<nanosecond>(100.0))];
+    assert!(fit_drift_calibration(&avalanches, bin_width).is_none());
+
+    let avalanches: Vec<_> = (0..1000)
+        .map(|i| avalanche(Time::new::<nanosecond>(i as f64)))
+        .collect();
+    assert!(fit_drift_calibration(&avalanches, Time::new::<nanosecond>(0.0)).is_none());
+}
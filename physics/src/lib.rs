@@ -1,14 +1,23 @@
-use crate::calibration::pads::baseline::try_pad_baseline;
-use crate::calibration::pads::delay::try_pad_delay;
-use crate::calibration::pads::gain::try_pad_gain;
-use crate::calibration::wires::baseline::try_wire_baseline;
-use crate::calibration::wires::delay::try_wire_delay;
-use crate::calibration::wires::gain::try_wire_gain;
+use crate::calibration::phi_correction::PhiCorrection;
+use crate::classification::{event_topology_with_config, EventTopology};
 use crate::deconvolution::pads::pad_deconvolution;
 use crate::deconvolution::wires::{contiguous_ranges, wire_range_deconvolution};
 use crate::drift::DRIFT_TABLES;
-use crate::matching::{match_column_inputs, pad_column_to_wires, wire_to_pad_column};
-use crate::reconstruction::{cluster_spacepoints, find_vertices, Coordinate};
+use crate::field::MagneticField;
+use crate::matching::{
+    cfd_avalanche_times, match_inputs, neighbor_pad_columns, pad_only_avalanches,
+    wire_to_pad_column, Thresholds,
+};
+use crate::reconstruction::{
+    cluster_spacepoints_with_config, estimate_vertex_z, find_vertices_with_config,
+    fit_tracks_with_failures, tag_cosmics_in_field, tag_spirals_with_config, Cluster,
+    ClusteringAlgorithm, Coordinate, ReconstructionConfig, Track, TryTrackFromClusterError,
+    VertexingResult,
+};
+use crate::signal_processing::{
+    common_mode_noise, pad_response_deconvolution, BaselineRestoration, CommonModeCorrection,
+    ConstantFractionDiscriminator, IonTailCancellation,
+};
 use alpha_g_detector::alpha16::aw_map::{
     self, MapTpcWirePositionError, TpcWirePosition, TPC_ANODE_WIRES,
 };
@@ -17,25 +26,37 @@ use alpha_g_detector::midas::{
     Adc32BankName, Alpha16BankName, MainEventBankName, ParseMainEventBankNameError,
 };
 use alpha_g_detector::padwing::map::{
-    MapTpcPadPositionError, TpcPadPosition, TPC_PAD_COLUMNS, TPC_PAD_ROWS,
+    MapTpcPadPositionError, TpcPadPosition, PAD_PITCH_PHI, TPC_PAD_COLUMNS, TPC_PAD_ROWS,
 };
 use alpha_g_detector::padwing::{
     self, Chunk, PwbPacket, TryChunkFromSliceError, TryPwbPacketFromChunksError,
 };
 use alpha_g_detector::trigger::TryTrgPacketFromSliceError;
 use alpha_g_detector::trigger::{self, TrgPacket};
-use std::collections::{BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use uom::si::angle::radian;
 use uom::si::f64::*;
+use uom::si::length::{meter, millimeter};
 use uom::typenum::P2;
 
+pub use crate::by_run::{ByRun, OverlappingRunsError};
+pub use crate::calibration::bv::gain::MapBvGainError;
+pub use crate::calibration::format::{CalibrationHeader, ParseCalibrationFileError};
 pub use crate::calibration::pads::baseline::MapPadBaselineError;
 pub use crate::calibration::pads::delay::MapPadDelayError;
 pub use crate::calibration::pads::gain::MapPadGainError;
+pub use crate::calibration::phi_correction::MapPhiCorrectionError;
+pub use crate::calibration::set::{CalibrationSet, LoadCalibrationSetError};
+pub use crate::calibration::threshold::{MapPadThresholdError, MapWireThresholdError};
+pub use crate::calibration::time::{MapPadTimeOffsetError, MapWireTimeOffsetError};
 pub use crate::calibration::wires::baseline::MapWireBaselineError;
 pub use crate::calibration::wires::delay::MapWireDelayError;
 pub use crate::calibration::wires::gain::MapWireGainError;
-pub use crate::drift::TryDriftLookupError;
+pub use crate::calibration::{GainFallback, LoadExternalCalibrationError, SimulationCalibration};
+pub use crate::drift::{DriftTables, DriftTablesByRun, LoadDriftTablesError, TryDriftLookupError};
 
 // Calibration
 //
@@ -47,20 +68,45 @@ pub use crate::drift::TryDriftLookupError;
 //
 // If there is ever a compelling reason to expose calibration to the user, I
 // believe it should be moved to a separate `alpha_g_calibration` crate.
+//
+// The only exceptions are `set_calibration_dir`, which allows testing a new
+// calibration before it is embedded, `declare_simulated_runs`, which allows
+// multiple simulation productions (each with its own calibration) to coexist,
+// and `calibration_provenance` together with the calibration file format (to
+// know which calibration produced a result).
 mod calibration;
+// Values (e.g. drift tables or gas properties) keyed by ranges of run numbers.
+mod by_run;
 // Map, as a function of `z` (given that the B field is non-homogeneous through
 // the entire rTPC volume), a given drift time to a radius and Lorentz angle
 // correction.
 mod drift;
 // Extract avalanche time and amplitude information from the wire and pad
 // signals.
+/// Barrel Veto.
+pub mod bv;
 mod deconvolution;
 // Match wire and pad signals to obtain Avalanches.
 /// Chronobox.
 pub mod chronobox;
+/// Event classification.
+pub mod classification;
+/// Magnetic field.
+pub mod field;
+/// Run-dependent properties of the rTPC gas.
+pub mod gas;
 mod matching;
+/// Reconstruction inputs and outputs as plain `f64`s in fixed units.
+#[cfg(feature = "plain")]
+pub mod plain;
 /// Vertex reconstruction.
 pub mod reconstruction;
+/// Signal processing of raw waveforms.
+pub mod signal_processing;
+/// Toy simulation of the detector response.
+pub mod simulation;
+/// Event time reconstruction.
+pub mod timing;
 
 /// Townsend avalanche generated in the multiplying region near an anode wire
 /// surface.
@@ -85,6 +131,8 @@ pub struct Avalanche {
     pub wire_amplitude: f64,
     /// Same as `wire_amplitude`, but for the induced pad signal.
     pub pad_amplitude: f64,
+    /// Number of contiguous pad rows with an induced signal.
+    pub pad_multiplicity: usize,
 }
 
 /// Radial position of the anode wires.
@@ -102,7 +150,7 @@ pub const TRG_CLOCK_FREQ: Frequency = Frequency {
 };
 
 /// Reconstructed ionization position.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SpacePoint {
     /// Radial position of the ionization.
     pub r: Length,
@@ -111,23 +159,101 @@ pub struct SpacePoint {
     /// Axial position of the ionization. The center of the detector is at
     /// `z = 0`.
     pub z: Length,
+    /// Amplitude of the corresponding [`Avalanche`] as perceived by the anode
+    /// wires. Same caveats as [`Avalanche::wire_amplitude`] apply.
+    pub wire_amplitude: f64,
+    /// Same as `wire_amplitude`, but for the induced pad signal.
+    pub pad_amplitude: f64,
+    /// Uncertainty of `r`. This has to be positive.
+    pub sigma_r: Length,
+    /// Uncertainty of the arc length `r * phi`. This has to be positive.
+    pub sigma_rphi: Length,
+    /// Uncertainty of `z`. This has to be positive.
+    pub sigma_z: Length,
+}
+
+// Amplitude below which the resolution starts degrading (as 1/sqrt(amplitude))
+// due to noise. This depends on the (arbitrary) scale of the avalanche
+// amplitudes; update it if that ever changes.
+const REFERENCE_AMPLITUDE: f64 = 500.0;
+// Maximum degradation of the resolution due to a small amplitude.
+const MAX_AMPLITUDE_SCALE: f64 = 5.0;
+// Number of pad rows used to calculate the `z` of an avalanche. A larger pad
+// multiplicity means that the induced charge is spread out (e.g. noisy pads or
+// overlapping avalanches), hence a worse `z` resolution.
+const NOMINAL_PAD_MULTIPLICITY: usize = 3;
+
+fn amplitude_scale(amplitude: f64) -> f64 {
+    if amplitude.is_nan() || amplitude <= 0.0 {
+        MAX_AMPLITUDE_SCALE
+    } else {
+        (REFERENCE_AMPLITUDE / amplitude)
+            .sqrt()
+            .clamp(1.0, MAX_AMPLITUDE_SCALE)
+    }
 }
 
 impl TryFrom<Avalanche> for SpacePoint {
     type Error = TryDriftLookupError;
 
+    /// Convert an [`Avalanche`] to a [`SpacePoint`] using the default
+    /// [`DriftTables`].
     fn try_from(avalanche: Avalanche) -> Result<Self, Self::Error> {
-        let (r, lorentz_correction) = DRIFT_TABLES.at(avalanche.z, avalanche.t)?;
+        Self::try_from_avalanche(avalanche, &DRIFT_TABLES)
+    }
+}
+
+impl SpacePoint {
+    /// Convert an [`Avalanche`] to a [`SpacePoint`] using the given
+    /// [`DriftTables`] (e.g. run-dependent tables from [`DriftTablesByRun`]).
+    pub fn try_from_avalanche(
+        avalanche: Avalanche,
+        tables: &DriftTables,
+    ) -> Result<Self, TryDriftLookupError> {
+        let (r, lorentz_correction) = tables.at(avalanche.z, avalanche.t)?;
+        // Resolution with a large (i.e. not noise-dominated) signal. Radial
+        // resolution is dominated by the drift time measurement. The azimuthal
+        // and axial resolutions are roughly the wire and pad pitch divided by
+        // sqrt(12).
+        let sigma_r = Length::new::<millimeter>(2.0);
+        let sigma_rphi = Length::new::<millimeter>(1.3);
+        let sigma_z = Length::new::<millimeter>(1.2);
 
         Ok(SpacePoint {
             r,
             phi: avalanche.phi - lorentz_correction,
             z: avalanche.z,
+            wire_amplitude: avalanche.wire_amplitude,
+            pad_amplitude: avalanche.pad_amplitude,
+            sigma_r: sigma_r * amplitude_scale(avalanche.wire_amplitude),
+            sigma_rphi: sigma_rphi * amplitude_scale(avalanche.wire_amplitude),
+            sigma_z: sigma_z
+                * amplitude_scale(avalanche.pad_amplitude)
+                * (avalanche.pad_multiplicity.max(NOMINAL_PAD_MULTIPLICITY) as f64
+                    / NOMINAL_PAD_MULTIPLICITY as f64),
         })
     }
-}
+    /// Same as [`SpacePoint::try_from_avalanche`], but for an [`Avalanche`]
+    /// reconstructed without anode wire information (see
+    /// [`MainEvent::pad_avalanches`]). The azimuthal resolution is given by the
+    /// width of a pad column, and the radial resolution by the pad amplitude.
+    pub fn try_from_pad_avalanche(
+        avalanche: Avalanche,
+        tables: &DriftTables,
+    ) -> Result<Self, TryDriftLookupError> {
+        // The radial resolution is given by the pad amplitude instead.
+        let mut sp = Self::try_from_avalanche(
+            Avalanche {
+                wire_amplitude: avalanche.pad_amplitude,
+                ..avalanche
+            },
+            tables,
+        )?;
+        sp.wire_amplitude = avalanche.wire_amplitude;
+        sp.sigma_rphi = sp.r * PAD_PITCH_PHI / 12.0f64.sqrt();
 
-impl SpacePoint {
+        Ok(sp)
+    }
     /// Return the `x` coordinate of the ionization position.
     pub fn x(self) -> Length {
         self.r * self.phi.cos()
@@ -136,6 +262,12 @@ impl SpacePoint {
     pub fn y(self) -> Length {
         self.r * self.phi.sin()
     }
+    /// Return the `z` coordinate of the ionization position. This is the same
+    /// as the `z` field; it is here for consistency with [`SpacePoint::x`] and
+    /// [`SpacePoint::y`].
+    pub fn z(self) -> Length {
+        self.z
+    }
     /// Calculate the distance between two points.
     pub fn distance(self, other: Self) -> Length {
         ((self.x() - other.x()).powi(P2::new())
@@ -145,6 +277,68 @@ impl SpacePoint {
     }
 }
 
+/// Plain representation of a [`SpacePoint`] for external tools (e.g. plotting
+/// or machine learning pipelines) that don't use [`uom`].
+///
+/// All lengths are in meters and angles in radians. It contains both the
+/// cylindrical and cartesian coordinates of the ionization position.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpacePointRecord {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub r: f64,
+    pub phi: f64,
+    pub wire_amplitude: f64,
+    pub pad_amplitude: f64,
+    pub sigma_r: f64,
+    pub sigma_rphi: f64,
+    pub sigma_z: f64,
+}
+
+impl From<SpacePoint> for SpacePointRecord {
+    fn from(sp: SpacePoint) -> Self {
+        Self {
+            x: sp.x().get::<meter>(),
+            y: sp.y().get::<meter>(),
+            z: sp.z.get::<meter>(),
+            r: sp.r.get::<meter>(),
+            phi: sp.phi.get::<radian>(),
+            wire_amplitude: sp.wire_amplitude,
+            pad_amplitude: sp.pad_amplitude,
+            sigma_r: sp.sigma_r.get::<meter>(),
+            sigma_rphi: sp.sigma_rphi.get::<meter>(),
+            sigma_z: sp.sigma_z.get::<meter>(),
+        }
+    }
+}
+
+// Shift a signal by a (fractional) number of samples i.e. the output at index
+// `i` is the (linearly interpolated) input at `i + samples`. The output has the
+// same length as the input, and samples outside of the input are zero.
+fn shift_signal(signal: Vec<f64>, samples: f64) -> Vec<f64> {
+    if samples == 0.0 {
+        return signal;
+    }
+
+    let whole = samples.floor();
+    let fraction = samples - whole;
+    let at = |i: f64| {
+        if i < 0.0 {
+            0.0
+        } else {
+            signal.get(i as usize).copied().unwrap_or(0.0)
+        }
+    };
+
+    (0..signal.len())
+        .map(|i| {
+            let i = i as f64 + whole;
+            (1.0 - fraction) * at(i) + fraction * at(i + 1.0)
+        })
+        .collect()
+}
+
 /// The error type returned when conversion from data banks to a [`MainEvent`]
 /// fails.
 #[derive(Error, Debug)]
@@ -217,6 +411,177 @@ pub enum TryMainEventFromDataBanksError {
     /// Pad gain calibration failed.
     #[error("pad gain calibration failed")]
     PadGainError(#[from] MapPadGainError),
+    /// Wire timing calibration failed.
+    #[error("wire timing calibration failed")]
+    WireTimeOffsetError(#[from] MapWireTimeOffsetError),
+    /// Pad timing calibration failed.
+    #[error("pad timing calibration failed")]
+    PadTimeOffsetError(#[from] MapPadTimeOffsetError),
+    /// Resolving the calibration of the run failed.
+    #[error("calibration set failed")]
+    CalibrationSetError(#[from] LoadCalibrationSetError),
+    /// Subtracting the baseline from a pad sample overflows. This can only
+    /// happen with a bad pad baseline calibration.
+    #[error("pad baseline subtraction overflow in position `{position:?}`")]
+    PadBaselineOverflow { position: TpcPadPosition },
+}
+
+/// Set a directory with calibration files that take precedence over the
+/// calibration embedded in the library. Use `None` to only use the embedded
+/// calibration.
+///
+/// The directory is initialized from the `ALPHA_G_CALIBRATION_DIR` environment
+/// variable. It has the same layout as the `data/calibration` directory of the
+/// repository, e.g. `<dir>/wires/gain/`, and each file is named after the
+/// first run number for which it is valid (or `simulation` for simulated
+/// runs). A file is valid until the next run number for which there is a file.
+/// The format of each file is the same as the format of the embedded
+/// calibration files (see [`CalibrationHeader`]).
+pub fn set_calibration_dir(dir: Option<std::path::PathBuf>) {
+    calibration::set_calibration_dir(dir);
+}
+
+/// Declare a range of run numbers as simulated runs with a given
+/// [`SimulationCalibration`]. Returns an error if the range overlaps with a
+/// previously declared range.
+///
+/// Run number [`u32::MAX`] is always a simulated run with the
+/// [`SimulationCalibration::Ideal`] calibration, unless it is part of a
+/// declared range. Simulated runs only use simulation calibrations i.e. they
+/// never use (nor fall back to) the calibration of real data runs.
+pub fn declare_simulated_runs(
+    runs: std::ops::RangeInclusive<u32>,
+    calibration: SimulationCalibration,
+) -> Result<(), OverlappingRunsError> {
+    calibration::declare_simulated_runs(runs, calibration)
+}
+
+/// Remove all the ranges of simulated runs added with
+/// [`declare_simulated_runs`]. Only run number [`u32::MAX`] remains a
+/// simulated run.
+pub fn clear_simulated_runs() {
+    calibration::clear_simulated_runs();
+}
+
+/// Return `true` if the run number is a simulated run (see
+/// [`declare_simulated_runs`]).
+pub fn is_simulated_run(run_number: u32) -> bool {
+    calibration::is_simulated_run(run_number)
+}
+
+/// Return the header of all the calibration files used for a given run number.
+/// Calibrations that are not available for the run number are skipped.
+///
+/// This allows to know exactly which calibration was used to produce a given
+/// result.
+pub fn calibration_provenance(run_number: u32) -> Vec<CalibrationHeader> {
+    calibration::calibration_headers(run_number)
+}
+
+// Apply the baseline, gain, delay, and timing calibration to a raw anode wire
+// waveform. The ion tail cancellation is applied to the calibrated signal.
+fn wire_signal(
+    calibration: &CalibrationSet,
+    wire_position: TpcWirePosition,
+    waveform: &[i16],
+    options: &SignalOptions,
+) -> Result<Vec<f64>, TryMainEventFromDataBanksError> {
+    let baseline = calibration.wire_baseline(wire_position)?;
+    let gain = calibration.wire_gain_with_fallback(wire_position, options.gain_fallback)?;
+    let delay = calibration.wire_delay()?;
+    let offset = match calibration.wire_time_offset(wire_position) {
+        Ok(offset) => offset,
+        // The timing calibration is optional. Without it, all channels are
+        // assumed to be in time.
+        Err(MapWireTimeOffsetError::MissingMap { .. }) => 0.0,
+        Err(e) => return Err(e.into()),
+    };
+
+    let signal: Vec<_> = waveform
+        .iter()
+        .skip(delay)
+        // Convert to i32 to avoid overflow
+        .map(|&v| f64::from(i32::from(v) - i32::from(baseline)) * gain)
+        .collect();
+    let signal = options.wire_ion_tail.apply(&signal);
+
+    Ok(shift_signal(signal, offset * 1e-9 * alpha16::ADC32_RATE))
+}
+
+// Same as `wire_signal`, but for a raw pad waveform. The `common_mode` noise
+// (indexed as the raw waveform, and missing samples are `0.0`) is subtracted
+// before the gain calibration, and the baseline restoration is applied to the
+// calibrated signal.
+fn pad_signal(
+    calibration: &CalibrationSet,
+    pad_position: TpcPadPosition,
+    waveform: &[i16],
+    common_mode: &[f64],
+    options: &SignalOptions,
+) -> Result<Vec<f64>, TryMainEventFromDataBanksError> {
+    let baseline = calibration.pad_baseline(pad_position)?;
+    let gain = calibration.pad_gain_with_fallback(pad_position, options.gain_fallback)?;
+    let delay = calibration.pad_delay()?;
+    let offset = match calibration.pad_time_offset(pad_position) {
+        Ok(offset) => offset,
+        // Same as for the wires.
+        Err(MapPadTimeOffsetError::MissingMap { .. }) => 0.0,
+        Err(e) => return Err(e.into()),
+    };
+
+    let signal = waveform
+        .iter()
+        .enumerate()
+        .skip(delay)
+        .map(|(i, &v)| {
+            let v = v.checked_sub(baseline).ok_or(
+                TryMainEventFromDataBanksError::PadBaselineOverflow {
+                    position: pad_position,
+                },
+            )?;
+            Ok((f64::from(v) - common_mode.get(i).copied().unwrap_or(0.0)) * gain)
+        })
+        .collect::<Result<Vec<_>, TryMainEventFromDataBanksError>>()?;
+    let signal = options.pad_baseline_restoration.apply(&signal);
+
+    Ok(shift_signal(signal, offset * 1e-9 * padwing::PWB_RATE))
+}
+
+/// Options to convert the raw waveforms of a [`MainEvent`] into signals.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SignalOptions {
+    /// Policy for channels that are missing from the gain calibration.
+    pub gain_fallback: GainFallback,
+    /// Baseline restoration applied to all pad signals (after calibration)
+    /// before any amplitude is extracted.
+    pub pad_baseline_restoration: BaselineRestoration,
+    /// Common-mode noise correction applied to all pad waveforms (before
+    /// calibration).
+    pub pad_common_mode: CommonModeCorrection,
+    /// Ion tail cancellation applied to all anode wire signals (after
+    /// calibration) before any avalanche is reconstructed.
+    pub wire_ion_tail: IonTailCancellation,
+    /// Remove the charge induced on the neighboring pad rows (see
+    /// [`pad_response_deconvolution`](signal_processing::pad_response_deconvolution))
+    /// from the pad signals before they are matched into avalanches. The pad
+    /// amplitude of each avalanche is then only the charge in front of its
+    /// pad, but this is significantly slower.
+    pub pad_response_deconvolution: bool,
+    /// Constant fraction discriminator used to measure the time of each
+    /// avalanche from the leading edge of its anode wire signal. If `None`,
+    /// the time of an avalanche is the time bin of the deconvolved wire signal
+    /// in which it was found.
+    pub wire_timing: Option<ConstantFractionDiscriminator>,
+}
+
+type PadSignals = Box<[[Option<Vec<f64>>; TPC_PAD_ROWS]; TPC_PAD_COLUMNS]>;
+
+// All pad channels without a signal. This is built directly on the heap.
+pub(crate) fn empty_pad_signals() -> PadSignals {
+    vec![[const { None }; TPC_PAD_ROWS]; TPC_PAD_COLUMNS]
+        .into_boxed_slice()
+        .try_into()
+        .unwrap()
 }
 
 /// ALPHA-g main event.
@@ -233,7 +598,20 @@ pub struct MainEvent {
     // with a `TpcWirePosition` key. (As long as we are careful about the
     // 0th wire channel.)
     wire_signals: [Option<Vec<f64>>; TPC_ANODE_WIRES],
-    pad_signals: [[Option<Vec<f64>>; TPC_PAD_ROWS]; TPC_PAD_COLUMNS],
+    // Boxed given that it is too large for the stack.
+    pad_signals: PadSignals,
+    // Software amplitude thresholds of the run (applied when matching wire and
+    // pad hits into avalanches).
+    thresholds: Thresholds,
+    // Azimuthal correction of the run (applied to all SpacePoints before track
+    // finding and fitting).
+    phi_correction: PhiCorrection,
+    // See `SignalOptions::pad_response_deconvolution`. This is applied when
+    // the avalanches are reconstructed (i.e. all pad rows of a column are
+    // needed at once).
+    pad_response_deconvolution: bool,
+    // See `SignalOptions::wire_timing`.
+    wire_timing: Option<ConstantFractionDiscriminator>,
     trigger_timestamp: u32,
 }
 impl MainEvent {
@@ -247,9 +625,58 @@ impl MainEvent {
     where
         I: IntoIterator<Item = (&'a str, &'a [u8])>,
     {
+        Self::try_from_banks_with_gain_fallback(run_number, banks, GainFallback::Error)
+    }
+    /// Same as [`MainEvent::try_from_banks`], but channels that are missing
+    /// from the gain calibration of the given run number are handled according
+    /// to the `gain_fallback` policy instead of returning an error.
+    pub fn try_from_banks_with_gain_fallback<'a, I>(
+        run_number: u32,
+        banks: I,
+        gain_fallback: GainFallback,
+    ) -> Result<Self, TryMainEventFromDataBanksError>
+    where
+        I: IntoIterator<Item = (&'a str, &'a [u8])>,
+    {
+        let options = SignalOptions {
+            gain_fallback,
+            ..Default::default()
+        };
+
+        Self::try_from_banks_with_options(run_number, banks, &options)
+    }
+    /// Same as [`MainEvent::try_from_banks`], but the raw waveforms are
+    /// converted into signals according to arbitrary [`SignalOptions`].
+    ///
+    /// The [`CalibrationSet`] of the run number is resolved in every call.
+    /// Use [`MainEvent::try_from_banks_with_calibration`] (or a [`RunContext`])
+    /// to resolve it only once when processing many events of the same run.
+    pub fn try_from_banks_with_options<'a, I>(
+        run_number: u32,
+        banks: I,
+        options: &SignalOptions,
+    ) -> Result<Self, TryMainEventFromDataBanksError>
+    where
+        I: IntoIterator<Item = (&'a str, &'a [u8])>,
+    {
+        let calibration = CalibrationSet::for_run(run_number)?;
+
+        Self::try_from_banks_with_calibration(&calibration, banks, options)
+    }
+    /// Same as [`MainEvent::try_from_banks_with_options`], but with an
+    /// explicit [`CalibrationSet`] (which also determines the run number).
+    pub fn try_from_banks_with_calibration<'a, I>(
+        calibration: &CalibrationSet,
+        banks: I,
+        options: &SignalOptions,
+    ) -> Result<Self, TryMainEventFromDataBanksError>
+    where
+        I: IntoIterator<Item = (&'a str, &'a [u8])>,
+    {
+        let run_number = calibration.run_number();
         // I didn't find another way to initialize such large arrays.
         let mut wire_signals = [(); TPC_ANODE_WIRES].map(|_| None);
-        let mut pad_signals = [(); TPC_PAD_COLUMNS].map(|_| [(); TPC_PAD_ROWS].map(|_| None));
+        let mut pad_signals = empty_pad_signals();
         let mut trigger_timestamp = None;
         // Need to group chunks by board and chip.
         let mut pwb_chunks_map: HashMap<_, Vec<_>> = HashMap::new();
@@ -284,16 +711,7 @@ impl MainEvent {
                             bank_name,
                         });
                     } else {
-                        let baseline = try_wire_baseline(run_number, wire_position)?;
-                        let gain = try_wire_gain(run_number, wire_position)?;
-                        let delay = try_wire_delay(run_number)?;
-
-                        let signal: Vec<_> = waveform
-                            .iter()
-                            .skip(delay)
-                            // Convert to i32 to avoid overflow
-                            .map(|&v| f64::from(i32::from(v) - i32::from(baseline)) * gain)
-                            .collect();
+                        let signal = wire_signal(calibration, wire_position, waveform, options)?;
                         if !signal.is_empty() {
                             wire_signals[wire_index] = Some(signal);
                         }
@@ -323,41 +741,74 @@ impl MainEvent {
             }
         }
 
+        // The common-mode noise is shared by all channels in the same group
+        // of packets.
+        let mut pwb_packets_map: HashMap<_, Vec<_>> = HashMap::new();
         for chunks in pwb_chunks_map.into_values() {
             let packet = PwbPacket::try_from(chunks)?;
-            let board_id = packet.board_id();
-            let after_id = packet.after_id();
-            for &channel_id in packet.channels_sent() {
-                if let padwing::ChannelId::Pad(pad_channel_id) = channel_id {
-                    // A waveform is guaranteed to exist and not be empty if the
-                    // channel was sent. It is safe to unwrap.
-                    let waveform = packet.waveform_at(channel_id).unwrap();
-
-                    let pad_position =
-                        TpcPadPosition::try_new(run_number, board_id, after_id, pad_channel_id)?;
-                    let pad_index = (
-                        usize::from(pad_position.column),
-                        usize::from(pad_position.row),
-                    );
-                    if pad_signals[pad_index.0][pad_index.1].is_some() {
-                        return Err(TryMainEventFromDataBanksError::DuplicatePadSignal {
-                            position: pad_position,
-                        });
-                    } else {
-                        let baseline = try_pad_baseline(run_number, pad_position)?;
-                        let gain = try_pad_gain(run_number, pad_position)?;
-                        let delay = try_pad_delay(run_number)?;
-
-                        let signal: Vec<_> = waveform
-                            .iter()
-                            .skip(delay)
-                            // Given the ranges of PWB samples, overflow is
-                            // not possible.
-                            .map(|&v| f64::from(v.checked_sub(baseline).unwrap()) * gain)
-                            .collect();
-                        if !signal.is_empty() {
-                            pad_signals[pad_index.0][pad_index.1] = Some(signal);
-                        }
+            let key = match options.pad_common_mode {
+                CommonModeCorrection::Pwb { .. } => (packet.board_id(), None),
+                _ => (packet.board_id(), Some(packet.after_id())),
+            };
+
+            pwb_packets_map.entry(key).or_default().push(packet);
+        }
+
+        for packets in pwb_packets_map.into_values() {
+            let mut pads = Vec::new();
+            for packet in &packets {
+                let board_id = packet.board_id();
+                let after_id = packet.after_id();
+                for &channel_id in packet.channels_sent() {
+                    if let padwing::ChannelId::Pad(pad_channel_id) = channel_id {
+                        // A waveform is guaranteed to exist and not be empty if
+                        // the channel was sent. It is safe to unwrap.
+                        let waveform = packet.waveform_at(channel_id).unwrap();
+                        let pad_position = TpcPadPosition::try_new(
+                            run_number,
+                            board_id,
+                            after_id,
+                            pad_channel_id,
+                        )?;
+
+                        pads.push((pad_position, waveform));
+                    }
+                }
+            }
+
+            let common_mode = match options.pad_common_mode {
+                CommonModeCorrection::Disabled => Vec::new(),
+                CommonModeCorrection::AfterChip { min_channels }
+                | CommonModeCorrection::Pwb { min_channels } => {
+                    let mut signals: Vec<Vec<f64>> = Vec::with_capacity(pads.len());
+                    for &(pad_position, waveform) in &pads {
+                        let baseline = calibration.pad_baseline(pad_position)?;
+                        signals.push(
+                            waveform
+                                .iter()
+                                .map(|&v| f64::from(v) - f64::from(baseline))
+                                .collect(),
+                        );
+                    }
+
+                    common_mode_noise(&signals, min_channels)
+                }
+            };
+
+            for (pad_position, waveform) in pads {
+                let pad_index = (
+                    usize::from(pad_position.column),
+                    usize::from(pad_position.row),
+                );
+                if pad_signals[pad_index.0][pad_index.1].is_some() {
+                    return Err(TryMainEventFromDataBanksError::DuplicatePadSignal {
+                        position: pad_position,
+                    });
+                } else {
+                    let signal =
+                        pad_signal(calibration, pad_position, waveform, &common_mode, options)?;
+                    if !signal.is_empty() {
+                        pad_signals[pad_index.0][pad_index.1] = Some(signal);
                     }
                 }
             }
@@ -366,6 +817,10 @@ impl MainEvent {
         Ok(Self {
             wire_signals,
             pad_signals,
+            thresholds: calibration.thresholds(),
+            phi_correction: calibration.phi_correction_map(),
+            pad_response_deconvolution: options.pad_response_deconvolution,
+            wire_timing: options.wire_timing,
             trigger_timestamp: trigger_timestamp
                 .ok_or(TryMainEventFromDataBanksError::MissingTrgBank)?,
         })
@@ -373,20 +828,112 @@ impl MainEvent {
     /// Return the reconstructed primary vertex position.
     ///
     /// This is a convenience method for using [`MainEvent::avalanches`],
-    /// [`cluster_spacepoints`] and [`find_vertices`] with fewer imports and
+    /// [`cluster_spacepoints`](reconstruction::cluster_spacepoints),
+    /// [`tag_cosmics`](reconstruction::tag_cosmics),
+    /// [`fit_tracks`](reconstruction::fit_tracks), and
+    /// [`find_vertices`](reconstruction::find_vertices) with fewer imports and
     /// without intermediate variables.
     pub fn vertex(&self) -> Option<Coordinate> {
-        let points = self
-            .avalanches()
+        self.vertex_with_config(&ReconstructionConfig::default())
+    }
+    /// Same as [`MainEvent::vertex`], but with an arbitrary
+    /// [`ReconstructionConfig`].
+    pub fn vertex_with_config(&self, config: &ReconstructionConfig) -> Option<Coordinate> {
+        self.reconstruct_with_config(config)
+            .vertexing
+            .primary
+            .map(|info| info.position)
+    }
+    /// Run the full reconstruction chain on the event i.e. avalanches,
+    /// [`SpacePoint`]s, track finding, cosmic ray tagging, track fitting, and
+    /// vertexing.
+    ///
+    /// The [`SpacePoint`]s are corrected by the azimuthal correction map of
+    /// the run (see [`CalibrationSet::phi_correction`]) before track finding.
+    /// The magnetic field is assumed to be uniform and equal to
+    /// [`NOMINAL_FIELD`](field::NOMINAL_FIELD); use a [`RunContext`] to
+    /// reconstruct the event with e.g. a measured field map.
+    pub fn reconstruct(&self) -> ReconstructedEvent {
+        self.reconstruct_with_config(&ReconstructionConfig::default())
+    }
+    /// Same as [`MainEvent::reconstruct`], but with an arbitrary
+    /// [`ReconstructionConfig`].
+    pub fn reconstruct_with_config(&self, config: &ReconstructionConfig) -> ReconstructedEvent {
+        self.reconstruct_with_diagnostics(config).0
+    }
+    /// Same as [`MainEvent::reconstruct_with_config`], but also return the
+    /// [`Diagnostics`] of each reconstruction stage.
+    pub fn reconstruct_with_diagnostics(
+        &self,
+        config: &ReconstructionConfig,
+    ) -> (ReconstructedEvent, Diagnostics) {
+        self.reconstruct_with_tables(config, &DRIFT_TABLES, &MagneticField::default())
+    }
+    // Same as `reconstruct_with_diagnostics`, but with arbitrary drift tables
+    // to obtain the SpacePoints, and an arbitrary magnetic field to fit the
+    // tracks.
+    fn reconstruct_with_tables(
+        &self,
+        config: &ReconstructionConfig,
+        tables: &DriftTables,
+        field: &MagneticField,
+    ) -> (ReconstructedEvent, Diagnostics) {
+        let mut diagnostics = Diagnostics::default();
+
+        // Pad-only track finding ignores the anode wires.
+        let pad_only = config.clustering.algorithm == ClusteringAlgorithm::PadOnly;
+
+        let start = Instant::now();
+        let avalanches = if pad_only {
+            self.pad_avalanches_with_config(config)
+        } else {
+            self.avalanches_with_config(config)
+        };
+        diagnostics.durations.avalanches = start.elapsed();
+        diagnostics.num_avalanches = avalanches.len();
+
+        let start = Instant::now();
+        let spacepoints: Vec<SpacePoint> = avalanches
             .into_iter()
-            .filter_map(|avalanche| avalanche.try_into().ok())
+            .filter_map(|avalanche| {
+                if pad_only {
+                    SpacePoint::try_from_pad_avalanche(avalanche, tables).ok()
+                } else {
+                    SpacePoint::try_from_avalanche(avalanche, tables).ok()
+                }
+            })
+            .map(|sp| self.phi_correction.apply(sp))
             .collect();
-        let tracks = cluster_spacepoints(points)
-            .clusters
-            .into_iter()
-            .filter_map(|cluster| cluster.try_into().ok())
+        diagnostics.durations.spacepoints = start.elapsed();
+        diagnostics.num_spacepoints = spacepoints.len();
+
+        let event = reconstruct_spacepoints(
+            self.trigger_timestamp,
+            spacepoints,
+            field,
+            config,
+            &mut diagnostics,
+        );
+
+        (event, diagnostics)
+    }
+    /// Return a fast estimate of the `z` of the primary vertex.
+    ///
+    /// This uses only the pad signals (no track finding nor fitting), and it is
+    /// intended for online monitoring. See
+    /// [`estimate_vertex_z`](reconstruction::estimate_vertex_z) for details.
+    pub fn vertex_z_estimate(&self) -> Option<Length> {
+        let pad_charges: Vec<[f64; TPC_PAD_ROWS]> = self
+            .pad_signals
+            .iter()
+            .map(|column| {
+                column
+                    .each_ref()
+                    .map(|signal| signal.iter().flatten().filter(|&&v| v > 0.0).sum::<f64>())
+            })
             .collect();
-        find_vertices(tracks).primary.map(|info| info.position)
+
+        estimate_vertex_z(&pad_charges)
     }
     /// Return the trigger timestamp of the event. This is a counter that
     /// increments at a frequency of [`TRG_CLOCK_FREQ`].
@@ -397,6 +944,13 @@ impl MainEvent {
     }
     /// Return all reconstructed avalanches in the event.
     pub fn avalanches(&self) -> Vec<Avalanche> {
+        self.avalanches_with_config(&ReconstructionConfig::default())
+    }
+    /// Same as [`MainEvent::avalanches`], but with an arbitrary
+    /// [`ReconstructionConfig`] (e.g. to integrate the pad charge of each
+    /// avalanche within a
+    /// [`pad_integration_window`](ReconstructionConfig::pad_integration_window)).
+    pub fn avalanches_with_config(&self, config: &ReconstructionConfig) -> Vec<Avalanche> {
         // We would only want to deconvolve pad columns that have wire signals.
         // Furthermore, to make the output deterministic, we need to iterate
         // over the pad columns in a deterministic order.
@@ -410,25 +964,345 @@ impl MainEvent {
             }
         }
 
-        let mut avalanches = Vec::new();
-        for column in pad_columns {
-            let mut pad_inputs_column = [(); TPC_PAD_ROWS].map(|_| Vec::new());
-            for (row, input) in pad_inputs_column.iter_mut().enumerate() {
-                if let Some(signal) = self.pad_signals[column][row].as_ref() {
-                    *input = pad_deconvolution(signal);
-                }
-            }
+        // The neighboring pad columns are also needed to account for the charge
+        // shared between pad columns.
+        let mut pad_inputs = BTreeMap::new();
+        for column in pad_columns
+            .into_iter()
+            .flat_map(|column| [column].into_iter().chain(neighbor_pad_columns(column)))
+        {
+            pad_inputs
+                .entry(column)
+                .or_insert_with(|| self.pad_column_inputs(column));
+        }
 
-            let wire_indices = pad_column_to_wires(column);
-            avalanches.extend(match_column_inputs(
-                wire_indices.clone().collect::<Vec<_>>().try_into().unwrap(),
-                wire_inputs[wire_indices].try_into().unwrap(),
-                &pad_inputs_column,
-            ));
+        let mut avalanches = match_inputs(
+            &wire_inputs,
+            &pad_inputs,
+            config.pad_integration_window,
+            &self.thresholds,
+        );
+        if let Some(cfd) = self.wire_timing {
+            cfd_avalanche_times(&mut avalanches, &self.wire_signals, cfd);
         }
 
         avalanches
     }
+    /// Return all avalanches in the event reconstructed only from the pad
+    /// signals i.e. ignoring all anode wires. This is useful for runs in which
+    /// several anode wire preamps were dead.
+    ///
+    /// The azimuthal angle of each avalanche is the center of its pad column,
+    /// and its wire amplitude is zero. Use
+    /// [`SpacePoint::try_from_pad_avalanche`] to get the corresponding
+    /// [`SpacePoint`]s.
+    pub fn pad_avalanches(&self) -> Vec<Avalanche> {
+        self.pad_avalanches_with_config(&ReconstructionConfig::default())
+    }
+    /// Same as [`MainEvent::pad_avalanches`], but with an arbitrary
+    /// [`ReconstructionConfig`].
+    pub fn pad_avalanches_with_config(&self, config: &ReconstructionConfig) -> Vec<Avalanche> {
+        let pad_inputs: BTreeMap<_, _> = self
+            .pad_signals
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| column.iter().any(Option::is_some))
+            .map(|(column, _)| (column, self.pad_column_inputs(column)))
+            .collect();
+
+        pad_only_avalanches(&pad_inputs, config.pad_integration_window, &self.thresholds)
+    }
+    // Deconvolved inputs of all the pad rows in a pad column. Rows without a
+    // signal are empty.
+    fn pad_column_inputs(&self, column: usize) -> [Vec<f64>; TPC_PAD_ROWS] {
+        let inputs = self.pad_signals[column].each_ref().map(|signal| {
+            signal
+                .as_ref()
+                .map(|signal| pad_deconvolution(signal))
+                .unwrap_or_default()
+        });
+
+        if self.pad_response_deconvolution {
+            // Same number of rows as the input.
+            pad_response_deconvolution(&inputs).try_into().unwrap()
+        } else {
+            inputs
+        }
+    }
+}
+
+/// Per-run state needed to reconstruct [`MainEvent`]s.
+///
+/// Everything that only depends on the run (i.e. the [`CalibrationSet`], the
+/// [`DriftTables`], the masks of hot channels, and all the options) is resolved
+/// once when the context is created. Individual events are then reconstructed
+/// from a shared reference to the context; it is `Send` and `Sync`, so a single
+/// context can be used by multiple threads e.g. in a long-lived online monitor.
+#[derive(Clone, Debug)]
+pub struct RunContext {
+    calibration: CalibrationSet,
+    /// Drift tables used to convert avalanches into [`SpacePoint`]s.
+    pub drift_tables: DriftTables,
+    /// Magnetic field used to fit the [`Track`]s (see
+    /// [`fit_track_in_field`](reconstruction::fit_track_in_field)).
+    pub magnetic_field: MagneticField,
+    /// Options to convert the raw waveforms into signals.
+    pub signal_options: SignalOptions,
+    /// Configuration of all the reconstruction stages.
+    pub config: ReconstructionConfig,
+    /// Anode wires that are ignored in all events (e.g. hot channels).
+    pub masked_wires: HashSet<TpcWirePosition>,
+    /// Pads that are ignored in all events (e.g. hot channels).
+    pub masked_pads: HashSet<TpcPadPosition>,
+}
+
+impl RunContext {
+    /// Resolve the [`CalibrationSet`] and the [`DriftTables`] of a given run
+    /// number. All other fields have their default values (i.e. nominal
+    /// uniform magnetic field and no masked channels) and can be modified
+    /// before reconstructing any event.
+    pub fn new(
+        run_number: u32,
+        drift_tables: &DriftTablesByRun,
+    ) -> Result<Self, LoadCalibrationSetError> {
+        Ok(Self::from_calibration(
+            CalibrationSet::for_run(run_number)?,
+            drift_tables,
+        ))
+    }
+    /// Same as [`RunContext::new`], but with an explicit [`CalibrationSet`]
+    /// (which also determines the run number).
+    pub fn from_calibration(calibration: CalibrationSet, drift_tables: &DriftTablesByRun) -> Self {
+        Self {
+            drift_tables: drift_tables.get(calibration.run_number()).clone(),
+            calibration,
+            magnetic_field: MagneticField::default(),
+            signal_options: SignalOptions::default(),
+            config: ReconstructionConfig::default(),
+            masked_wires: HashSet::new(),
+            masked_pads: HashSet::new(),
+        }
+    }
+    /// Return the run number of the context.
+    pub fn run_number(&self) -> u32 {
+        self.calibration.run_number()
+    }
+    /// Return the [`CalibrationSet`] of the run.
+    pub fn calibration(&self) -> &CalibrationSet {
+        &self.calibration
+    }
+    /// Try to convert data banks to a [`MainEvent`]. This is the same as
+    /// [`MainEvent::try_from_banks_with_calibration`] with the calibration and
+    /// signal options of the context, but the signals of all masked channels
+    /// are removed.
+    pub fn main_event<'a, I>(&self, banks: I) -> Result<MainEvent, TryMainEventFromDataBanksError>
+    where
+        I: IntoIterator<Item = (&'a str, &'a [u8])>,
+    {
+        let mut event = MainEvent::try_from_banks_with_calibration(
+            &self.calibration,
+            banks,
+            &self.signal_options,
+        )?;
+        for &wire in &self.masked_wires {
+            event.wire_signals[usize::from(wire)] = None;
+        }
+        for &pad in &self.masked_pads {
+            event.pad_signals[usize::from(pad.column)][usize::from(pad.row)] = None;
+        }
+
+        Ok(event)
+    }
+    /// Run the full reconstruction chain on an event with the drift tables,
+    /// magnetic field, and configuration of the context.
+    pub fn reconstruct(&self, event: &MainEvent) -> ReconstructedEvent {
+        self.reconstruct_with_diagnostics(event).0
+    }
+    /// Same as [`RunContext::reconstruct`], but also return the
+    /// [`Diagnostics`] of each reconstruction stage.
+    pub fn reconstruct_with_diagnostics(
+        &self,
+        event: &MainEvent,
+    ) -> (ReconstructedEvent, Diagnostics) {
+        event.reconstruct_with_tables(&self.config, &self.drift_tables, &self.magnetic_field)
+    }
+    /// Reconstruct an event from its data banks. This is a convenience method
+    /// for using [`RunContext::main_event`] and [`RunContext::reconstruct`].
+    pub fn reconstruct_banks<'a, I>(
+        &self,
+        banks: I,
+    ) -> Result<ReconstructedEvent, TryMainEventFromDataBanksError>
+    where
+        I: IntoIterator<Item = (&'a str, &'a [u8])>,
+    {
+        Ok(self.reconstruct(&self.main_event(banks)?))
+    }
+}
+
+/// Output of the full reconstruction of a [`MainEvent`].
+#[derive(Clone, Debug)]
+pub struct ReconstructedEvent {
+    /// Trigger timestamp of the event (see [`MainEvent::timestamp`]).
+    pub timestamp: u32,
+    /// All the [`SpacePoint`]s in the event.
+    pub spacepoints: Vec<SpacePoint>,
+    /// [`SpacePoint`]s of spiraling electrons. These are not used for track
+    /// finding.
+    pub spirals: Vec<Cluster>,
+    /// [`Track`]s tagged as cosmic rays. These are not used for vertexing.
+    pub cosmics: Vec<Track>,
+    /// Reconstructed vertices and all remaining [`Track`]s.
+    pub vertexing: VertexingResult,
+    /// Topology of the event.
+    pub topology: EventTopology,
+}
+
+// Reconstruct an event from its SpacePoints. The wall time and output size of
+// all the stages after the SpacePoints are recorded in the diagnostics.
+pub(crate) fn reconstruct_spacepoints(
+    timestamp: u32,
+    spacepoints: Vec<SpacePoint>,
+    field: &MagneticField,
+    config: &ReconstructionConfig,
+    diagnostics: &mut Diagnostics,
+) -> ReconstructedEvent {
+    // Spirals would otherwise fragment into many small clusters.
+    let start = Instant::now();
+    let spiral_tagging = tag_spirals_with_config(spacepoints.clone(), config);
+    diagnostics.durations.spiral_tagging = start.elapsed();
+    diagnostics.num_spirals = spiral_tagging.spirals.len();
+
+    let start = Instant::now();
+    let clustering = cluster_spacepoints_with_config(spiral_tagging.remainder, config);
+    diagnostics.durations.clustering = start.elapsed();
+    diagnostics.num_clusters = clustering.clusters.len();
+    diagnostics.num_unclustered_spacepoints = clustering.remainder.len();
+
+    // Cosmic rays would otherwise bias the vertex distribution.
+    let start = Instant::now();
+    let cosmic_tagging = tag_cosmics_in_field(clustering.clusters, field, config);
+    diagnostics.durations.cosmic_tagging = start.elapsed();
+    diagnostics.num_cosmics = cosmic_tagging.cosmics.len();
+
+    let start = Instant::now();
+    let (tracks, failures) = fit_tracks_with_failures(cosmic_tagging.remainder, field, config);
+    diagnostics.durations.track_fitting = start.elapsed();
+    diagnostics.num_tracks = tracks.len();
+    diagnostics.num_fit_iter = tracks.iter().map(Track::num_iter).sum();
+    diagnostics.track_fit_failures = failures;
+
+    let start = Instant::now();
+    let vertexing = find_vertices_with_config(tracks, config);
+    diagnostics.durations.vertexing = start.elapsed();
+    diagnostics.num_vertices = vertexing.vertices().count();
+
+    let topology =
+        event_topology_with_config(&spacepoints, &cosmic_tagging.cosmics, &vertexing, config);
+
+    ReconstructedEvent {
+        timestamp,
+        spacepoints,
+        spirals: spiral_tagging.spirals,
+        cosmics: cosmic_tagging.cosmics,
+        vertexing,
+        topology,
+    }
+}
+
+/// Wall time spent on each stage of the reconstruction of a [`MainEvent`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StageDurations {
+    /// Deconvolution and matching of the wire and pad signals into
+    /// [`Avalanche`]s (see [`MainEvent::avalanches`]).
+    pub avalanches: Duration,
+    /// Conversion of [`Avalanche`]s into [`SpacePoint`]s.
+    pub spacepoints: Duration,
+    /// Spiraling electron tagging.
+    pub spiral_tagging: Duration,
+    /// Clustering of [`SpacePoint`]s.
+    pub clustering: Duration,
+    /// Cosmic ray tagging.
+    pub cosmic_tagging: Duration,
+    /// Track fitting (and merging).
+    pub track_fitting: Duration,
+    /// Vertex finding and fitting.
+    pub vertexing: Duration,
+}
+
+/// Diagnostics of the full reconstruction of a [`MainEvent`] (see
+/// [`MainEvent::reconstruct_with_diagnostics`]).
+///
+/// This is intended to make performance and quality regressions visible e.g.
+/// when reprocessing a run with a new version or configuration.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    /// Wall time spent on each stage.
+    pub durations: StageDurations,
+    /// Number of reconstructed [`Avalanche`]s.
+    pub num_avalanches: usize,
+    /// Number of [`SpacePoint`]s. Avalanches outside the range of the drift
+    /// tables can not be converted into a [`SpacePoint`].
+    pub num_spacepoints: usize,
+    /// Number of spiraling electrons. These [`SpacePoint`]s are removed before
+    /// clustering.
+    pub num_spirals: usize,
+    /// Number of [`SpacePoint`]s that are not part of any
+    /// [`Cluster`](reconstruction::Cluster).
+    pub num_unclustered_spacepoints: usize,
+    /// Number of [`Cluster`](reconstruction::Cluster)s found.
+    pub num_clusters: usize,
+    /// Number of cosmic ray [`Track`]s. Each one consumes two clusters.
+    pub num_cosmics: usize,
+    /// Number of fitted [`Track`]s (after merging, and excluding cosmic rays).
+    pub num_tracks: usize,
+    /// Total number of minimizer iterations of all fitted [`Track`]s (see
+    /// [`Track::num_iter`]).
+    pub num_fit_iter: u64,
+    /// Reason why each rejected [`Cluster`](reconstruction::Cluster) could not
+    /// be fitted to a [`Track`].
+    pub track_fit_failures: Vec<TryTrackFromClusterError>,
+    /// Number of reconstructed vertices (primary plus secondaries).
+    pub num_vertices: usize,
+}
+
+/// Given a run number, reconstruct a main event from its data banks. The data
+/// banks are provided as an iterator over tuples of bank name and data slice.
+///
+/// This is a convenience function for using [`MainEvent::try_from_banks`] and
+/// [`MainEvent::reconstruct`].
+pub fn reconstruct<'a, I>(
+    run_number: u32,
+    banks: I,
+) -> Result<ReconstructedEvent, TryMainEventFromDataBanksError>
+where
+    I: IntoIterator<Item = (&'a str, &'a [u8])>,
+{
+    reconstruct_with_config(run_number, banks, &ReconstructionConfig::default())
+}
+
+/// Same as [`reconstruct`], but with an arbitrary [`ReconstructionConfig`].
+pub fn reconstruct_with_config<'a, I>(
+    run_number: u32,
+    banks: I,
+    config: &ReconstructionConfig,
+) -> Result<ReconstructedEvent, TryMainEventFromDataBanksError>
+where
+    I: IntoIterator<Item = (&'a str, &'a [u8])>,
+{
+    Ok(MainEvent::try_from_banks(run_number, banks)?.reconstruct_with_config(config))
+}
+
+/// Same as [`reconstruct_with_config`], but also return the [`Diagnostics`] of
+/// each reconstruction stage.
+pub fn reconstruct_with_diagnostics<'a, I>(
+    run_number: u32,
+    banks: I,
+    config: &ReconstructionConfig,
+) -> Result<(ReconstructedEvent, Diagnostics), TryMainEventFromDataBanksError>
+where
+    I: IntoIterator<Item = (&'a str, &'a [u8])>,
+{
+    Ok(MainEvent::try_from_banks(run_number, banks)?.reconstruct_with_diagnostics(config))
 }
 
 #[cfg(test)]
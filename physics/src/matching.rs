@@ -1,7 +1,15 @@
+use crate::deconvolution::wires::WIRE_RESPONSE;
+use crate::reconstruction::IntegrationWindow;
+use crate::signal_processing::{ConstantFractionDiscriminator, PAD_RESPONSE_FUNCTION};
 use crate::Avalanche;
-use alpha_g_detector::alpha16::aw_map::{TpcWirePosition, TPC_ANODE_WIRES};
+use alpha_g_detector::alpha16::aw_map::{TpcWirePosition, ANODE_WIRE_PITCH_PHI, TPC_ANODE_WIRES};
 use alpha_g_detector::alpha16::ADC32_RATE;
-use alpha_g_detector::padwing::map::{TpcPadRow, PAD_PITCH_Z, TPC_PAD_COLUMNS, TPC_PAD_ROWS};
+use alpha_g_detector::padwing::map::{
+    TpcPadColumn, TpcPadRow, CATHODE_PADS_RADIUS, PAD_PITCH_Z, TPC_PAD_COLUMNS, TPC_PAD_ROWS,
+};
+use alpha_g_detector::padwing::PWB_RATE;
+use std::collections::{BTreeMap, HashMap};
+use std::f64::consts::{SQRT_2, TAU};
 use std::ops::Range;
 use uom::si::angle::radian;
 use uom::si::f64::*;
@@ -11,7 +19,7 @@ use uom::typenum::P2;
 
 const WIRES_PER_COLUMN: usize = TPC_ANODE_WIRES / TPC_PAD_COLUMNS;
 // From `alpha_g_detector` internal mapping.
-const WIRE_SHIFT: usize = 8;
+pub(crate) const WIRE_SHIFT: usize = 8;
 
 // Map a wire index to the pad column index that contains it.
 //
@@ -38,6 +46,80 @@ pub(crate) fn pad_column_to_wires(pad_column: usize) -> Range<usize> {
     first..first + WIRES_PER_COLUMN
 }
 
+// Return the index of the wire closest to a given azimuthal angle.
+pub(crate) fn wire_index(phi: Angle) -> usize {
+    let phi = phi.get::<radian>().rem_euclid(TAU);
+    let shifted_index = ((phi / ANODE_WIRE_PITCH_PHI) as usize).min(TPC_ANODE_WIRES - 1);
+
+    (shifted_index + WIRE_SHIFT) % TPC_ANODE_WIRES
+}
+
+// Return the [previous, next] pad column indices of a given `pad_column`.
+pub(crate) fn neighbor_pad_columns(pad_column: usize) -> [usize; 2] {
+    [
+        (pad_column + TPC_PAD_COLUMNS - 1) % TPC_PAD_COLUMNS,
+        (pad_column + 1) % TPC_PAD_COLUMNS,
+    ]
+}
+
+// Complementary error function. The approximation 7.1.26 from "Handbook of
+// Mathematical Functions" by Abramowitz and Stegun (maximum absolute error of
+// 1.5e-7) is more than enough here.
+fn erfc(x: f64) -> f64 {
+    if x < 0.0 {
+        return 2.0 - erfc(-x);
+    }
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+
+    poly * (-x * x).exp()
+}
+
+// Fraction of the charge induced by an avalanche in front of the `n`th wire of
+// a pad column (i.e. `n` is in the range [0, WIRES_PER_COLUMN)) that is shared
+// with each of its [previous, next] neighboring pad columns.
+//
+// The pad response function is roughly Gaussian. Its width is taken to be the
+// same along `phi` as along `z` (determined from the charge induced on the
+// nearest pad row).
+fn shared_fractions(n: usize) -> [f64; 2] {
+    let sigma =
+        PAD_PITCH_Z / (2.0 * (PAD_RESPONSE_FUNCTION[0] / PAD_RESPONSE_FUNCTION[1]).ln()).sqrt();
+    let wire_arc = ANODE_WIRE_PITCH_PHI * CATHODE_PADS_RADIUS;
+    let to_previous = (n as f64 + 0.5) * wire_arc;
+    let to_next = WIRES_PER_COLUMN as f64 * wire_arc - to_previous;
+
+    [to_previous, to_next].map(|distance| 0.5 * erfc(distance / (SQRT_2 * sigma)))
+}
+
+// Maximum tolerated ratio between the observed and expected charge shared with
+// a neighboring pad column. Deconvolved amplitudes are noisy, so this needs to
+// be generous.
+const SHARING_TOLERANCE: f64 = 2.0;
+
+// Software amplitude threshold of each channel (see the `threshold`
+// calibration). A wire or pad hit is only used if its amplitude is above the
+// threshold of its channel.
+// The pad thresholds are boxed to keep a `MainEvent` small enough to be moved
+// around on the stack.
+#[derive(Clone, Debug)]
+pub(crate) struct Thresholds {
+    pub(crate) wires: [f64; TPC_ANODE_WIRES],
+    pub(crate) pads: Box<[[f64; TPC_PAD_ROWS]; TPC_PAD_COLUMNS]>,
+}
+
+impl Default for Thresholds {
+    // Keep every positive amplitude.
+    fn default() -> Self {
+        Self {
+            wires: [0.0; TPC_ANODE_WIRES],
+            pads: Box::new([[0.0; TPC_PAD_ROWS]; TPC_PAD_COLUMNS]),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 struct WireHit {
     phi: Angle,
@@ -45,29 +127,51 @@ struct WireHit {
 }
 
 fn wire_hits_at_t(
-    wire_indices: [usize; WIRES_PER_COLUMN],
-    wire_inputs: &[Vec<f64>; WIRES_PER_COLUMN],
+    wire_indices: Range<usize>,
+    wire_inputs: &[Vec<f64>; TPC_ANODE_WIRES],
     t: usize,
+    thresholds: &[f64; TPC_ANODE_WIRES],
 ) -> Vec<WireHit> {
     wire_indices
-        .iter()
-        .zip(wire_inputs)
-        .filter_map(|(index, input)| {
-            input.get(t).copied().filter(|v| v > &0.0).map(|v| WireHit {
-                phi: Angle::new::<radian>(TpcWirePosition::try_from(*index).unwrap().phi()),
-                amplitude: v,
-            })
+        .filter_map(|index| {
+            wire_inputs[index]
+                .get(t)
+                .copied()
+                .filter(|v| v > &0.0 && v > &thresholds[index])
+                .map(|v| WireHit {
+                    phi: Angle::new::<radian>(TpcWirePosition::try_from(index).unwrap().phi()),
+                    amplitude: v,
+                })
         })
         .collect()
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 struct PadHit {
     z: Length,
     amplitude: f64,
+    multiplicity: usize,
 }
 
-fn pad_hits_at_t(pad_column_inputs: &[Vec<f64>; TPC_PAD_ROWS], t: usize) -> Vec<PadHit> {
+// Number of contiguous pad rows (around `row`) with a positive input at time
+// `t`.
+fn pad_multiplicity(pad_column_inputs: &[Vec<f64>; TPC_PAD_ROWS], row: usize, t: usize) -> usize {
+    let is_positive = |row: &usize| pad_column_inputs[*row].get(t).is_some_and(|&v| v > 0.0);
+    let below = (0..row).rev().take_while(is_positive).count();
+    let above = (row + 1..TPC_PAD_ROWS).take_while(is_positive).count();
+
+    below + 1 + above
+}
+
+// The amplitude of each hit is the input at time `t` or, if there is a
+// `window`, the input integrated within the window. Hits with an amplitude
+// below the threshold of their pad are ignored.
+fn pad_hits_at_t(
+    pad_column_inputs: &[Vec<f64>; TPC_PAD_ROWS],
+    t: usize,
+    window: Option<IntegrationWindow>,
+    thresholds: &[f64; TPC_PAD_ROWS],
+) -> Vec<PadHit> {
     let mut pad_hits = Vec::new();
 
     let mut first = pad_column_inputs[0].get(t).copied().unwrap_or(0.0);
@@ -82,8 +186,17 @@ fn pad_hits_at_t(pad_column_inputs: &[Vec<f64>; TPC_PAD_ROWS], t: usize) -> Vec<
             let z = Length::new::<meter>(TpcPadRow::try_from(row - 1).unwrap().z())
                 + (sigma_squared / (2.0 * width)) * (last / first).ln();
 
-            let amplitude = middle;
-            pad_hits.push(PadHit { z, amplitude });
+            let amplitude = window.map_or(middle, |window| {
+                window.integrate(&pad_column_inputs[row - 1], t)
+            });
+            if amplitude > thresholds[row - 1] {
+                let multiplicity = pad_multiplicity(pad_column_inputs, row - 1, t);
+                pad_hits.push(PadHit {
+                    z,
+                    amplitude,
+                    multiplicity,
+                });
+            }
         }
 
         first = middle;
@@ -93,40 +206,268 @@ fn pad_hits_at_t(pad_column_inputs: &[Vec<f64>; TPC_PAD_ROWS], t: usize) -> Vec<
     pad_hits
 }
 
-// Match the inputs from all the wires in a pad column to the input from the
-// pad column to reconstruct avalanches.
-pub(crate) fn match_column_inputs(
-    wire_indices: [usize; WIRES_PER_COLUMN],
-    wire_inputs: &[Vec<f64>; WIRES_PER_COLUMN],
-    pad_column_inputs: &[Vec<f64>; TPC_PAD_ROWS],
+// Pad hits of all the pad columns at each time bin.
+type PadHits = BTreeMap<usize, Vec<Vec<PadHit>>>;
+
+fn all_pad_hits(
+    pad_inputs: &BTreeMap<usize, [Vec<f64>; TPC_PAD_ROWS]>,
+    window: Option<IntegrationWindow>,
+    thresholds: &Thresholds,
+) -> PadHits {
+    pad_inputs
+        .iter()
+        .map(|(&column, inputs)| {
+            let t_max = inputs.iter().map(|input| input.len()).max().unwrap();
+            (
+                column,
+                (0..t_max)
+                    .map(|t| pad_hits_at_t(inputs, t, window, &thresholds.pads[column]))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+fn pad_hits_in(pad_hits: &PadHits, pad_column: usize, t: usize) -> &[PadHit] {
+    pad_hits
+        .get(&pad_column)
+        .and_then(|hits| hits.get(t))
+        .map_or(&[], Vec::as_slice)
+}
+
+// Find the hit (pad column and index) in a neighboring pad column from which
+// the charge of `hit` was induced. Returns `None` if `hit` is an independent
+// avalanche.
+//
+// The charge is considered to be shared if a wire in the neighboring pad column
+// has a hit at the same time, the neighboring pad hit has a similar `z`, and
+// the amplitude of `hit` is compatible with the pad response function. Without
+// `wire_inputs` (i.e. pad-only reconstruction), any wire in the neighboring pad
+// column could have the hit.
+fn shared_with(
+    pad_column: usize,
+    hit: &PadHit,
+    t: usize,
+    wire_inputs: Option<&[Vec<f64>; TPC_ANODE_WIRES]>,
+    pad_hits: &PadHits,
+) -> Option<(usize, usize)> {
+    let width = Length::new::<meter>(PAD_PITCH_Z);
+
+    neighbor_pad_columns(pad_column)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(side, neighbor)| {
+            // The neighbor shares charge with this column through its opposite
+            // side i.e. if the neighbor is the previous column, the charge is
+            // shared with its next column and vice versa.
+            let fraction = pad_column_to_wires(neighbor)
+                .enumerate()
+                .filter(|(_, wire)| {
+                    wire_inputs.is_none_or(|inputs| inputs[*wire].get(t).is_some_and(|&v| v > 0.0))
+                })
+                .map(|(n, _)| shared_fractions(n)[1 - side])
+                .reduce(f64::max)?;
+            let max_ratio = SHARING_TOLERANCE * fraction / (1.0 - fraction);
+
+            pad_hits_in(pad_hits, neighbor, t)
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| {
+                    (other.z - hit.z).abs() < width
+                        && hit.amplitude < other.amplitude
+                        && hit.amplitude <= max_ratio * other.amplitude
+                })
+                // The charge can only come from an independent avalanche.
+                // Recursion is guaranteed to end because the amplitude is
+                // strictly increasing.
+                .filter(|(_, other)| {
+                    shared_with(neighbor, other, t, wire_inputs, pad_hits).is_none()
+                })
+                .map(|(index, other)| (neighbor, index, other.amplitude))
+                .max_by(|a, b| a.2.total_cmp(&b.2))
+        })
+        .max_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|(neighbor, index, _)| (neighbor, index))
+}
+
+// Pad hits of a pad column at time `t` after taking into account the charge
+// sharing between neighboring pad columns.
+//
+// An avalanche in front of a wire close to the edge of a pad column also
+// induces a charge on the neighboring pad column. Otherwise, this induced
+// charge would be seen as an independent pad hit (and could be matched to an
+// unrelated wire hit in the neighboring column). Instead, shared charges are
+// added to the avalanche they were induced by, and its `z` is the amplitude
+// weighted centroid.
+fn shared_pad_hits_at_t(
+    pad_column: usize,
+    t: usize,
+    wire_inputs: Option<&[Vec<f64>; TPC_ANODE_WIRES]>,
+    pad_hits: &PadHits,
+) -> Vec<PadHit> {
+    pad_hits_in(pad_hits, pad_column, t)
+        .iter()
+        .enumerate()
+        .filter(|(_, hit)| shared_with(pad_column, hit, t, wire_inputs, pad_hits).is_none())
+        .map(|(index, hit)| {
+            let mut merged = *hit;
+            let mut weighted_z = hit.z * hit.amplitude;
+            for neighbor in neighbor_pad_columns(pad_column) {
+                for other in pad_hits_in(pad_hits, neighbor, t) {
+                    if shared_with(neighbor, other, t, wire_inputs, pad_hits)
+                        == Some((pad_column, index))
+                    {
+                        merged.amplitude += other.amplitude;
+                        merged.multiplicity = merged.multiplicity.max(other.multiplicity);
+                        weighted_z += other.z * other.amplitude;
+                    }
+                }
+            }
+            merged.z = weighted_z / merged.amplitude;
+
+            merged
+        })
+        .collect()
+}
+
+// Match the inputs from all the wires to the inputs from all the pads to
+// reconstruct avalanches.
+//
+// Each element of `pad_inputs` is the input of all the pad rows in a pad
+// column. Only the pad columns in `pad_inputs` are matched, hence it should
+// also include the neighbors of all columns with wire inputs (to account for
+// charge sharing). The pad amplitude of each avalanche is integrated within the
+// `window` (if any), and hits below the `thresholds` of their channel are
+// ignored.
+pub(crate) fn match_inputs(
+    wire_inputs: &[Vec<f64>; TPC_ANODE_WIRES],
+    pad_inputs: &BTreeMap<usize, [Vec<f64>; TPC_PAD_ROWS]>,
+    window: Option<IntegrationWindow>,
+    thresholds: &Thresholds,
 ) -> Vec<Avalanche> {
-    let t_max = wire_inputs.iter().map(|input| input.len()).max().unwrap();
+    let all_pad_hits = all_pad_hits(pad_inputs, window, thresholds);
 
     let mut avalanches = Vec::new();
-    for t in 0..t_max {
-        let mut wire_hits = wire_hits_at_t(wire_indices, wire_inputs, t);
-        if wire_hits.is_empty() {
+    for &column in pad_inputs.keys() {
+        let t_max = pad_column_to_wires(column)
+            .map(|index| wire_inputs[index].len())
+            .max()
+            .unwrap();
+
+        for t in 0..t_max {
+            let mut wire_hits = wire_hits_at_t(
+                pad_column_to_wires(column),
+                wire_inputs,
+                t,
+                &thresholds.wires,
+            );
+            if wire_hits.is_empty() {
+                continue;
+            }
+            let mut pad_hits = shared_pad_hits_at_t(column, t, Some(wire_inputs), &all_pad_hits);
+            // Sort by amplitude (descending order) before matching. This
+            // matches together largest avalanches first and tries to fix the
+            // ghosting problem by taking into account the avalanches
+            // amplitudes.
+            wire_hits.sort_unstable_by(|a, b| b.amplitude.partial_cmp(&a.amplitude).unwrap());
+            pad_hits.sort_unstable_by(|a, b| b.amplitude.partial_cmp(&a.amplitude).unwrap());
+
+            avalanches.extend(
+                wire_hits
+                    .into_iter()
+                    .zip(pad_hits)
+                    .map(|(wire_hit, pad_hit)| Avalanche {
+                        t: Time::new::<second>(t as f64 / ADC32_RATE),
+                        phi: wire_hit.phi,
+                        z: pad_hit.z,
+                        wire_amplitude: wire_hit.amplitude,
+                        pad_amplitude: pad_hit.amplitude,
+                        pad_multiplicity: pad_hit.multiplicity,
+                    }),
+            );
+        }
+    }
+
+    avalanches
+}
+
+// Replace the time of each avalanche by the time that the constant fraction
+// discriminator gives for the closest pulse (within a single sample) in its
+// anode wire signal. The time of each pulse is corrected by the time that the
+// discriminator gives for the wire response itself, i.e. the time bin at which
+// the deconvolution finds an avalanche. Avalanches without such a pulse (e.g.
+// a second avalanche in the same pulse) keep their time bin.
+pub(crate) fn cfd_avalanche_times(
+    avalanches: &mut [Avalanche],
+    wire_signals: &[Option<Vec<f64>>; TPC_ANODE_WIRES],
+    cfd: ConstantFractionDiscriminator,
+) {
+    let response_cfd = ConstantFractionDiscriminator {
+        threshold: 0.0,
+        ..cfd
+    };
+    // The wire response starts with its leading edge; prepend the baseline
+    // sample that precedes it in a signal.
+    let response: Vec<_> = std::iter::once(0.0)
+        .chain(WIRE_RESPONSE.iter().copied())
+        .collect();
+    let Some(offset) = response_cfd.pulse_times(&response).first().map(|t| t - 1.0) else {
+        return;
+    };
+
+    let mut pulse_times = HashMap::new();
+    for avalanche in avalanches {
+        let wire = wire_index(avalanche.phi);
+        let Some(signal) = &wire_signals[wire] else {
             continue;
+        };
+        let times = pulse_times
+            .entry(wire)
+            .or_insert_with(|| cfd.pulse_times(signal));
+
+        let bin = avalanche.t.get::<second>() * ADC32_RATE;
+        if let Some(t) = times
+            .iter()
+            .map(|t| t - offset)
+            .filter(|t| (t - bin).abs() <= 1.0)
+            .min_by(|a, b| (a - bin).abs().total_cmp(&(b - bin).abs()))
+        {
+            avalanche.t = Time::new::<second>(t / ADC32_RATE);
+        }
+    }
+}
+
+// Reconstruct avalanches from the pad inputs alone i.e. without any anode wire
+// information (e.g. runs with dead anode wire preamps).
+//
+// Every independent pad hit (after charge sharing) is an avalanche. Its
+// azimuthal position is the center of its pad column, and its wire amplitude is
+// zero.
+pub(crate) fn pad_only_avalanches(
+    pad_inputs: &BTreeMap<usize, [Vec<f64>; TPC_PAD_ROWS]>,
+    window: Option<IntegrationWindow>,
+    thresholds: &Thresholds,
+) -> Vec<Avalanche> {
+    let all_pad_hits = all_pad_hits(pad_inputs, window, thresholds);
+
+    let mut avalanches = Vec::new();
+    for (&column, hits) in &all_pad_hits {
+        let phi = Angle::new::<radian>(TpcPadColumn::try_from(column).unwrap().phi());
+
+        for t in 0..hits.len() {
+            avalanches.extend(
+                shared_pad_hits_at_t(column, t, None, &all_pad_hits)
+                    .into_iter()
+                    .map(|pad_hit| Avalanche {
+                        t: Time::new::<second>(t as f64 / PWB_RATE),
+                        phi,
+                        z: pad_hit.z,
+                        wire_amplitude: 0.0,
+                        pad_amplitude: pad_hit.amplitude,
+                        pad_multiplicity: pad_hit.multiplicity,
+                    }),
+            );
         }
-        let mut pad_hits = pad_hits_at_t(pad_column_inputs, t);
-        // Sort by amplitude (descending order) before matching. This matches
-        // together largest avalanches first and tries to fix the ghosting
-        // problem by taking into account the avalanches amplitudes.
-        wire_hits.sort_unstable_by(|a, b| b.amplitude.partial_cmp(&a.amplitude).unwrap());
-        pad_hits.sort_unstable_by(|a, b| b.amplitude.partial_cmp(&a.amplitude).unwrap());
-
-        avalanches.extend(
-            wire_hits
-                .into_iter()
-                .zip(pad_hits)
-                .map(|(wire_hit, pad_hit)| Avalanche {
-                    t: Time::new::<second>(t as f64 / ADC32_RATE),
-                    phi: wire_hit.phi,
-                    z: pad_hit.z,
-                    wire_amplitude: wire_hit.amplitude,
-                    pad_amplitude: pad_hit.amplitude,
-                }),
-        );
     }
 
     avalanches
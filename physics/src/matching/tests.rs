@@ -1,5 +1,4 @@
 use super::*;
-use alpha_g_detector::padwing::map::TpcPadColumn;
 use std::collections::HashSet;
 use std::f64::consts::PI;
 
@@ -37,3 +36,205 @@ fn pad_column_index_to_wire_indices() {
 
     assert_eq!(seen.len(), TPC_ANODE_WIRES);
 }
+
+#[test]
+fn pad_multiplicity_contiguous_rows() {
+    let mut inputs = [(); TPC_PAD_ROWS].map(|_| Vec::new());
+    for row in [10, 11, 12, 13, 15] {
+        inputs[row] = vec![0.0, 1.0];
+    }
+
+    assert_eq!(pad_multiplicity(&inputs, 11, 1), 4);
+    assert_eq!(pad_multiplicity(&inputs, 15, 1), 1);
+    // Nothing at time 0 other than the row itself.
+    assert_eq!(pad_multiplicity(&inputs, 11, 0), 1);
+
+    inputs[0] = vec![0.0, 1.0];
+    inputs[1] = vec![0.0, 1.0];
+    assert_eq!(pad_multiplicity(&inputs, 0, 1), 2);
+    inputs[TPC_PAD_ROWS - 1] = vec![0.0, 1.0];
+    assert_eq!(pad_multiplicity(&inputs, TPC_PAD_ROWS - 1, 1), 1);
+}
+
+#[test]
+fn neighbor_pad_columns_wrap_around() {
+    assert_eq!(neighbor_pad_columns(5), [4, 6]);
+    assert_eq!(neighbor_pad_columns(0), [TPC_PAD_COLUMNS - 1, 1]);
+    assert_eq!(
+        neighbor_pad_columns(TPC_PAD_COLUMNS - 1),
+        [TPC_PAD_COLUMNS - 2, 0]
+    );
+}
+
+#[test]
+fn shared_fractions_symmetry() {
+    for n in 0..WIRES_PER_COLUMN {
+        let [previous, next] = shared_fractions(n);
+        let [mirror_previous, mirror_next] = shared_fractions(WIRES_PER_COLUMN - 1 - n);
+
+        assert!((previous - mirror_next).abs() < 1e-12);
+        assert!((next - mirror_previous).abs() < 1e-12);
+        assert!(previous > 0.0 && previous < 0.5);
+        assert!(next > 0.0 && next < 0.5);
+    }
+    // Only the wires at the edge of a pad column share a significant charge.
+    let [edge, _] = shared_fractions(0);
+    let [inner, _] = shared_fractions(1);
+    assert!(edge > 0.1);
+    assert!(inner < 0.01);
+}
+
+// Wire and pad inputs (same as the arguments of `match_inputs`).
+type Inputs = (
+    [Vec<f64>; TPC_ANODE_WIRES],
+    BTreeMap<usize, [Vec<f64>; TPC_PAD_ROWS]>,
+);
+
+// Inputs with a single time bin. The pad column inputs are given as
+// (column, [(row, amplitude)]).
+fn single_bin_inputs(wires: &[usize], pads: &[(usize, [(usize, f64); 3])]) -> Inputs {
+    let mut wire_inputs = [(); TPC_ANODE_WIRES].map(|_| Vec::new());
+    for &wire in wires {
+        wire_inputs[wire] = vec![1.0];
+    }
+    let mut pad_inputs = BTreeMap::new();
+    for (column, rows) in pads {
+        let inputs: &mut [Vec<f64>; TPC_PAD_ROWS] = pad_inputs
+            .entry(*column)
+            .or_insert_with(|| [(); TPC_PAD_ROWS].map(|_| vec![0.0]));
+        for &(row, amplitude) in rows {
+            inputs[row] = vec![amplitude];
+        }
+    }
+
+    (wire_inputs, pad_inputs)
+}
+
+#[test]
+fn match_inputs_shared_charge() {
+    // Avalanche at the last wire of column 5. A small fraction of its charge
+    // is induced on column 6, which also has an unrelated wire hit.
+    let wires = [
+        pad_column_to_wires(5).last().unwrap(),
+        pad_column_to_wires(6).start + 3,
+    ];
+    let (wire_inputs, pad_inputs) = single_bin_inputs(
+        &wires,
+        &[
+            (5, [(10, 0.3), (11, 1.0), (12, 0.3)]),
+            (6, [(10, 0.03), (11, 0.1), (12, 0.03)]),
+        ],
+    );
+
+    let avalanches = match_inputs(&wire_inputs, &pad_inputs, None, &Thresholds::default());
+    assert_eq!(avalanches.len(), 1);
+    let avalanche = avalanches[0];
+    assert!((avalanche.pad_amplitude - 1.1).abs() < 1e-12);
+    let z = Length::new::<meter>(TpcPadRow::try_from(11).unwrap().z());
+    assert!((avalanche.z - z).abs() < Length::new::<meter>(1e-9));
+}
+
+#[test]
+fn match_inputs_independent_neighbors() {
+    // The charge in column 6 is too large to be induced by the avalanche in
+    // column 5.
+    let wires = [
+        pad_column_to_wires(5).last().unwrap(),
+        pad_column_to_wires(6).start + 3,
+    ];
+    let (wire_inputs, pad_inputs) = single_bin_inputs(
+        &wires,
+        &[
+            (5, [(10, 0.3), (11, 1.0), (12, 0.3)]),
+            (6, [(10, 0.24), (11, 0.8), (12, 0.24)]),
+        ],
+    );
+
+    let avalanches = match_inputs(&wire_inputs, &pad_inputs, None, &Thresholds::default());
+    assert_eq!(avalanches.len(), 2);
+    assert_eq!(avalanches[0].pad_amplitude, 1.0);
+    assert_eq!(avalanches[1].pad_amplitude, 0.8);
+}
+
+#[test]
+fn match_inputs_no_neighbor_wire_hit() {
+    // Without a wire hit at the edge of column 5, the charge in column 6 can
+    // not be induced by it.
+    let wires = [
+        pad_column_to_wires(5).start + 3,
+        pad_column_to_wires(6).start + 3,
+    ];
+    let (wire_inputs, pad_inputs) = single_bin_inputs(
+        &wires,
+        &[
+            (5, [(10, 0.3), (11, 1.0), (12, 0.3)]),
+            (6, [(10, 0.03), (11, 0.1), (12, 0.03)]),
+        ],
+    );
+
+    let avalanches = match_inputs(&wire_inputs, &pad_inputs, None, &Thresholds::default());
+    assert_eq!(avalanches.len(), 2);
+}
+
+#[test]
+fn match_inputs_thresholds() {
+    // Same as `match_inputs_independent_neighbors`.
+    let wires = [
+        pad_column_to_wires(5).last().unwrap(),
+        pad_column_to_wires(6).start + 3,
+    ];
+    let (wire_inputs, pad_inputs) = single_bin_inputs(
+        &wires,
+        &[
+            (5, [(10, 0.3), (11, 1.0), (12, 0.3)]),
+            (6, [(10, 0.24), (11, 0.8), (12, 0.24)]),
+        ],
+    );
+
+    let mut thresholds = Thresholds::default();
+    thresholds.pads[6][11] = 0.9;
+    let avalanches = match_inputs(&wire_inputs, &pad_inputs, None, &thresholds);
+    assert_eq!(avalanches.len(), 1);
+    assert_eq!(avalanches[0].pad_amplitude, 1.0);
+
+    let mut thresholds = Thresholds::default();
+    thresholds.wires[wires[0]] = 1.0;
+    let avalanches = match_inputs(&wire_inputs, &pad_inputs, None, &thresholds);
+    assert_eq!(avalanches.len(), 1);
+    assert_eq!(avalanches[0].pad_amplitude, 0.8);
+}
+
+#[test]
+fn pad_only_avalanches_shared_charge() {
+    // Same as `match_inputs_shared_charge`, but without any wire.
+    let (_, pad_inputs) = single_bin_inputs(
+        &[],
+        &[
+            (5, [(10, 0.3), (11, 1.0), (12, 0.3)]),
+            (6, [(10, 0.03), (11, 0.1), (12, 0.03)]),
+        ],
+    );
+
+    let avalanches = pad_only_avalanches(&pad_inputs, None, &Thresholds::default());
+    assert_eq!(avalanches.len(), 1);
+    let avalanche = avalanches[0];
+    assert!((avalanche.pad_amplitude - 1.1).abs() < 1e-12);
+    assert_eq!(avalanche.wire_amplitude, 0.0);
+    let phi = Angle::new::<radian>(TpcPadColumn::try_from(5).unwrap().phi());
+    assert_eq!(avalanche.phi, phi);
+}
+
+#[test]
+fn pad_only_avalanches_independent_neighbors() {
+    let (_, pad_inputs) = single_bin_inputs(
+        &[],
+        &[
+            (5, [(10, 0.3), (11, 1.0), (12, 0.3)]),
+            (6, [(10, 0.24), (11, 0.8), (12, 0.24)]),
+        ],
+    );
+
+    let avalanches = pad_only_avalanches(&pad_inputs, None, &Thresholds::default());
+    assert_eq!(avalanches.len(), 2);
+    assert_ne!(avalanches[0].phi, avalanches[1].phi);
+}
@@ -0,0 +1,243 @@
+use crate::classification::EventTopology;
+use crate::field::MagneticField;
+use crate::reconstruction::{ReconstructionConfig, Track, VertexInfo};
+use crate::{reconstruct_spacepoints, Avalanche, Diagnostics, ReconstructedEvent, SpacePoint};
+use serde::{Deserialize, Serialize};
+use uom::si::angle::radian;
+use uom::si::area::square_meter;
+use uom::si::f64::*;
+use uom::si::length::meter;
+use uom::si::reciprocal_length::reciprocal_meter;
+use uom::si::time::second;
+
+pub use crate::SpacePointRecord;
+
+// All the types in this module mirror a type of the main API without any `uom`
+// quantity. Units are fixed: seconds, meters, and radians (and the
+// corresponding derived units). Keep the documentation of each field explicit
+// about its unit.
+
+/// Plain representation of an [`Avalanche`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AvalancheRecord {
+    /// Time in seconds (see [`Avalanche::t`]).
+    pub t: f64,
+    /// Azimuthal angle in radians.
+    pub phi: f64,
+    /// Axial position in meters.
+    pub z: f64,
+    /// Same as [`Avalanche::wire_amplitude`].
+    pub wire_amplitude: f64,
+    /// Same as [`Avalanche::pad_amplitude`].
+    pub pad_amplitude: f64,
+    /// Same as [`Avalanche::pad_multiplicity`].
+    pub pad_multiplicity: usize,
+}
+
+impl From<Avalanche> for AvalancheRecord {
+    fn from(avalanche: Avalanche) -> Self {
+        Self {
+            t: avalanche.t.get::<second>(),
+            phi: avalanche.phi.get::<radian>(),
+            z: avalanche.z.get::<meter>(),
+            wire_amplitude: avalanche.wire_amplitude,
+            pad_amplitude: avalanche.pad_amplitude,
+            pad_multiplicity: avalanche.pad_multiplicity,
+        }
+    }
+}
+
+impl From<AvalancheRecord> for Avalanche {
+    fn from(record: AvalancheRecord) -> Self {
+        Self {
+            t: Time::new::<second>(record.t),
+            phi: Angle::new::<radian>(record.phi),
+            z: Length::new::<meter>(record.z),
+            wire_amplitude: record.wire_amplitude,
+            pad_amplitude: record.pad_amplitude,
+            pad_multiplicity: record.pad_multiplicity,
+        }
+    }
+}
+
+impl From<SpacePointRecord> for SpacePoint {
+    /// The position is given by the cylindrical coordinates of the record
+    /// (i.e. `x` and `y` are ignored).
+    fn from(record: SpacePointRecord) -> Self {
+        Self {
+            r: Length::new::<meter>(record.r),
+            phi: Angle::new::<radian>(record.phi),
+            z: Length::new::<meter>(record.z),
+            wire_amplitude: record.wire_amplitude,
+            pad_amplitude: record.pad_amplitude,
+            sigma_r: Length::new::<meter>(record.sigma_r),
+            sigma_rphi: Length::new::<meter>(record.sigma_rphi),
+            sigma_z: Length::new::<meter>(record.sigma_z),
+        }
+    }
+}
+
+/// Plain representation of a [`Track`] given by its
+/// [`Perigee`](crate::reconstruction::Perigee) parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrackRecord {
+    /// Signed transverse distance to the beamline in meters.
+    pub d0: f64,
+    /// Axial position of the perigee in meters.
+    pub z0: f64,
+    /// Azimuthal angle of the direction at the perigee in radians.
+    pub phi0: f64,
+    /// Signed curvature in 1/meter.
+    pub curvature: f64,
+    /// Tangent of the dip angle.
+    pub tan_lambda: f64,
+    /// Covariance of `(d0, z0, phi0, curvature, tan_lambda)` in the units
+    /// above (see [`Track::covariance`]).
+    pub covariance: Option<[[f64; 5]; 5]>,
+    /// Same as [`Track::dedx`] in amplitude units per meter.
+    pub dedx: f64,
+    /// Same as [`Track::chi2`].
+    pub chi2: f64,
+    /// Same as [`Track::ndf`].
+    pub ndf: usize,
+    /// Same as [`Track::is_cosmic`].
+    pub is_cosmic: bool,
+}
+
+impl From<&Track> for TrackRecord {
+    fn from(track: &Track) -> Self {
+        let perigee = track.perigee();
+
+        Self {
+            d0: perigee.d0.get::<meter>(),
+            z0: perigee.z0.get::<meter>(),
+            phi0: perigee.phi0.get::<radian>(),
+            curvature: perigee.curvature.get::<reciprocal_meter>(),
+            tan_lambda: perigee.tan_lambda,
+            covariance: track.covariance(),
+            dedx: track.dedx().get::<reciprocal_meter>(),
+            chi2: track.chi2(),
+            ndf: track.ndf(),
+            is_cosmic: track.is_cosmic(),
+        }
+    }
+}
+
+/// Plain representation of a [`VertexInfo`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VertexRecord {
+    /// Position `x` in meters.
+    pub x: f64,
+    /// Position `y` in meters.
+    pub y: f64,
+    /// Position `z` in meters.
+    pub z: f64,
+    /// Covariance matrix of `(x, y, z)` in square meters.
+    pub covariance: Option<[[f64; 3]; 3]>,
+    /// Same as [`VertexInfo::chi2`].
+    pub chi2: f64,
+    /// Same as [`VertexInfo::ndf`].
+    pub ndf: usize,
+    /// Same as [`VertexInfo::probability`].
+    pub probability: f64,
+    /// Tracks associated to the vertex.
+    pub tracks: Vec<TrackRecord>,
+    /// Same as [`VertexInfo::weights`].
+    pub weights: Vec<f64>,
+}
+
+impl From<&VertexInfo> for VertexRecord {
+    fn from(vertex: &VertexInfo) -> Self {
+        Self {
+            x: vertex.position.x.get::<meter>(),
+            y: vertex.position.y.get::<meter>(),
+            z: vertex.position.z.get::<meter>(),
+            covariance: vertex
+                .covariance
+                .map(|c| c.map(|row| row.map(|v| v.get::<square_meter>()))),
+            chi2: vertex.chi2,
+            ndf: vertex.ndf,
+            probability: vertex.probability,
+            tracks: vertex
+                .tracks
+                .iter()
+                .map(|(track, _)| track.into())
+                .collect(),
+            weights: vertex.weights.clone(),
+        }
+    }
+}
+
+/// Plain representation of a [`ReconstructedEvent`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EventRecord {
+    /// Same as [`ReconstructedEvent::timestamp`].
+    pub timestamp: u32,
+    /// All the [`SpacePoint`]s in the event.
+    pub spacepoints: Vec<SpacePointRecord>,
+    /// [`SpacePoint`]s of each spiraling electron.
+    pub spirals: Vec<Vec<SpacePointRecord>>,
+    /// Tracks tagged as cosmic rays.
+    pub cosmics: Vec<TrackRecord>,
+    /// Primary signal vertex.
+    pub primary: Option<VertexRecord>,
+    /// Secondary vertices.
+    pub secondaries: Vec<VertexRecord>,
+    /// Tracks that were not associated to any vertex.
+    pub remainder: Vec<TrackRecord>,
+    /// Same as [`ReconstructedEvent::topology`].
+    pub topology: EventTopology,
+}
+
+impl From<&ReconstructedEvent> for EventRecord {
+    fn from(event: &ReconstructedEvent) -> Self {
+        Self {
+            timestamp: event.timestamp,
+            spacepoints: event.spacepoints.iter().map(|&sp| sp.into()).collect(),
+            spirals: event
+                .spirals
+                .iter()
+                .map(|spiral| spiral.iter().map(|&sp| sp.into()).collect())
+                .collect(),
+            cosmics: event.cosmics.iter().map(Into::into).collect(),
+            primary: event.vertexing.primary.as_ref().map(Into::into),
+            secondaries: event.vertexing.secondaries.iter().map(Into::into).collect(),
+            remainder: event.vertexing.remainder.iter().map(Into::into).collect(),
+            topology: event.topology,
+        }
+    }
+}
+
+/// Convert [`AvalancheRecord`]s into [`SpacePointRecord`]s with the default
+/// [`DriftTables`](crate::DriftTables). Avalanches outside the range of the
+/// drift tables are skipped.
+pub fn spacepoints(avalanches: &[AvalancheRecord]) -> Vec<SpacePointRecord> {
+    avalanches
+        .iter()
+        .filter_map(|&avalanche| SpacePoint::try_from(Avalanche::from(avalanche)).ok())
+        .map(Into::into)
+        .collect()
+}
+
+/// Reconstruct an event from its [`SpacePointRecord`]s. This is the same as
+/// [`MainEvent::reconstruct_with_config`](crate::MainEvent::reconstruct_with_config)
+/// after the [`SpacePoint`]s are obtained.
+///
+/// The [`ReconstructionConfig`] can be parsed from a TOML string (see its
+/// [`FromStr`](std::str::FromStr) implementation) without using any `uom`
+/// type.
+pub fn reconstruct(spacepoints: &[SpacePointRecord], config: &ReconstructionConfig) -> EventRecord {
+    let spacepoints = spacepoints.iter().map(|&sp| sp.into()).collect();
+    let event = reconstruct_spacepoints(
+        0,
+        spacepoints,
+        &MagneticField::default(),
+        config,
+        &mut Diagnostics::default(),
+    );
+
+    (&event).into()
+}
+
+#[cfg(test)]
+mod tests;
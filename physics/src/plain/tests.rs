@@ -0,0 +1,92 @@
+use super::*;
+use crate::classification::event_topology;
+use crate::reconstruction::{
+    cluster_spacepoints, find_vertices, fit_tracks, tag_cosmics, tag_spirals, Coordinate,
+};
+use crate::simulation::{annihilation_tracks, drift_spacepoints, DriftConfig};
+use crate::DriftTables;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[test]
+fn avalanche_record_round_trip() {
+    let record = AvalancheRecord {
+        t: 1e-6,
+        phi: 1.5,
+        z: -0.3,
+        wire_amplitude: 10.0,
+        pad_amplitude: 20.0,
+        pad_multiplicity: 3,
+    };
+    let avalanche = Avalanche::from(record);
+    assert_eq!(avalanche.t, Time::new::<second>(1e-6));
+    assert_eq!(avalanche.z, Length::new::<meter>(-0.3));
+    assert_eq!(AvalancheRecord::from(avalanche), record);
+}
+
+#[test]
+fn spacepoint_record_round_trip() {
+    let records = spacepoints(&[AvalancheRecord {
+        t: 1e-6,
+        phi: 1.5,
+        z: -0.3,
+        wire_amplitude: 10.0,
+        pad_amplitude: 20.0,
+        pad_multiplicity: 3,
+    }]);
+    assert_eq!(records.len(), 1);
+
+    let record = records[0];
+    let sp = SpacePoint::from(record);
+    assert_eq!(SpacePointRecord::from(sp), record);
+    assert!((record.x.hypot(record.y) - record.r).abs() < 1e-12);
+}
+
+#[test]
+fn reconstruct_empty_event() {
+    let event = reconstruct(&[], &ReconstructionConfig::default());
+
+    assert!(event.spacepoints.is_empty());
+    assert!(event.spirals.is_empty());
+    assert!(event.cosmics.is_empty());
+    assert!(event.primary.is_none());
+    assert!(event.secondaries.is_empty());
+    assert!(event.remainder.is_empty());
+}
+
+#[test]
+fn reconstruct_same_as_main_api() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let vertex = Coordinate {
+        x: Length::new::<meter>(0.0),
+        y: Length::new::<meter>(0.0),
+        z: Length::new::<meter>(0.1),
+    };
+    let tracks = annihilation_tracks(vertex, 3, 100.0, &mut rng);
+    let spacepoints = drift_spacepoints(
+        &tracks,
+        &DriftTables::default(),
+        &DriftConfig::default(),
+        &mut rng,
+    )
+    .unwrap();
+    let records: Vec<SpacePointRecord> = spacepoints.iter().map(|&sp| sp.into()).collect();
+
+    let event = reconstruct(&records, &ReconstructionConfig::default());
+    assert_eq!(event.spacepoints, records);
+
+    let spiral_tagging = tag_spirals(spacepoints.clone());
+    let clustering = cluster_spacepoints(spiral_tagging.remainder);
+    let cosmic_tagging = tag_cosmics(clustering.clusters);
+    let vertexing = find_vertices(fit_tracks(cosmic_tagging.remainder));
+    let topology = event_topology(&spacepoints, &cosmic_tagging.cosmics, &vertexing);
+    let expected = EventRecord::from(&ReconstructedEvent {
+        timestamp: 0,
+        spacepoints,
+        spirals: spiral_tagging.spirals,
+        cosmics: cosmic_tagging.cosmics,
+        vertexing,
+        topology,
+    });
+    assert_eq!(event, expected);
+}
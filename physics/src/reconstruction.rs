@@ -1,19 +1,57 @@
+use crate::bv::BvHit;
+use crate::field::{FieldOutOfRangeError, MagneticField};
 use crate::SpacePoint;
+use alpha_g_detector::alpha16::aw_map::INNER_CATHODE_RADIUS;
+use alpha_g_detector::padwing::map::{
+    TpcPadRow, CATHODE_PADS_RADIUS, DETECTOR_LENGTH, TPC_PAD_ROWS,
+};
 use core::slice::Iter;
-use std::f64::consts::PI;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::{PI, TAU};
 use thiserror::Error;
 use uom::si::angle::radian;
-use uom::si::f64::{Angle, Length, Ratio};
-use uom::si::length::{centimeter, meter};
+use uom::si::area::square_meter;
+use uom::si::f64::{Angle, Area, Length, Momentum, Ratio, ReciprocalLength, Time};
+use uom::si::length::meter;
+use uom::si::magnetic_flux_density::tesla;
+use uom::si::momentum::kilogram_meter_per_second;
 use uom::si::ratio::ratio;
+use uom::si::reciprocal_length::reciprocal_meter;
 use uom::typenum::P2;
 
+pub use config::{
+    AnnealingSchedule, BeamSpotConstraint, BvMatchingConfig, ClusteringAlgorithm, ClusteringConfig,
+    CosmicTaggingConfig, HoughPrecision, IntegrationWindow, KalmanFitConfig,
+    LoadReconstructionConfigError, ReconstructionConfig, SpiralTaggingConfig, TopologyConfig,
+    TrackFitAlgorithm, TrackFitConfig, TrackMergingConfig, VertexFitConfig,
+};
+pub use scoring::{
+    filter_clusters, filter_spacepoints, ClusterScorer, IsolationScorer, RadialExtentScorer,
+    ScoringError, SpacePointScorer,
+};
+#[cfg(feature = "ml")]
+pub use scoring::{LoadOnnxScorerError, OnnxScorer};
+
+// Tunable parameters of all the reconstruction stages.
+mod config;
 // Identify groups of SpacePoints that belong together to potential tracks.
 mod track_finding;
 // Fit a group of SpacePoints to a track.
 mod track_fitting;
+// Merge Clusters that are part of the same track.
+mod track_merging;
+// Identify pairs of Clusters that are a single cosmic ray.
+mod cosmic_tagging;
+// Identify groups of SpacePoints left by low momentum spiraling electrons.
+mod spiral_tagging;
 // Fit Tracks from an event to vertices.
 mod vertex_fitting;
+// Associate Tracks to Barrel Veto hits.
+mod bv_matching;
+// Classify SpacePoints and Clusters as signal or noise.
+mod scoring;
+// Small matrix operations needed by the track and vertex fitting.
+mod matrix;
 
 /// Collection of [`SpacePoint`]s.
 ///
@@ -61,24 +99,104 @@ pub struct ClusteringResult {
 /// Given a collection of [`SpacePoint`]s, cluster them into groups that
 /// are potentially part of the same track.
 pub fn cluster_spacepoints(sp: Vec<SpacePoint>) -> ClusteringResult {
-    track_finding::cluster_spacepoints(
-        sp,
-        // Minimum number of SpacePoints per Cluster.
-        // We need at least 3 points to get an accurate initial guess for the
-        // helix through a cluster.
-        // Track fitting will panic if this is set to less than 3.
-        13,
-        // Number of bins along `rho` in Hough space.
-        250,
-        // Number of bins along `theta` in Hough space.
-        230,
-        // Maximum clustering distance in Euclidean space.
-        Length::new::<centimeter>(3.0),
-    )
+    cluster_spacepoints_with_config(sp, &ReconstructionConfig::default())
+}
+
+/// Same as [`cluster_spacepoints`], but with an arbitrary
+/// [`ReconstructionConfig`].
+pub fn cluster_spacepoints_with_config(
+    sp: Vec<SpacePoint>,
+    config: &ReconstructionConfig,
+) -> ClusteringResult {
+    cluster_spacepoints_with_vetoes(sp, config, |_| false, |_| false)
+}
+
+/// Same as [`cluster_spacepoints_with_config`], but with user callbacks to
+/// veto individual [`SpacePoint`]s and [`Cluster`]s (e.g. fiducial cuts or
+/// masks of hot channels).
+///
+/// A [`SpacePoint`] for which `veto_spacepoint` returns `true` is not used for
+/// clustering, and a [`Cluster`] for which `veto_cluster` returns `true` is
+/// discarded. In both cases, the [`SpacePoint`]s are part of the
+/// [`ClusteringResult::remainder`].
+pub fn cluster_spacepoints_with_vetoes<S, C>(
+    sp: Vec<SpacePoint>,
+    config: &ReconstructionConfig,
+    mut veto_spacepoint: S,
+    mut veto_cluster: C,
+) -> ClusteringResult
+where
+    S: FnMut(&SpacePoint) -> bool,
+    C: FnMut(&Cluster) -> bool,
+{
+    let (vetoed, sp): (Vec<_>, Vec<_>) = sp.into_iter().partition(|p| veto_spacepoint(p));
+
+    let mut result = cluster_spacepoints_unvetoed(sp, &config.clustering);
+    let (vetoed_clusters, clusters): (Vec<_>, Vec<_>) =
+        result.clusters.into_iter().partition(|c| veto_cluster(c));
+    result.clusters = clusters;
+    result
+        .remainder
+        .extend(vetoed_clusters.into_iter().flatten().chain(vetoed));
+
+    result
+}
+
+// Cluster all the SpacePoints with the algorithm selected in the config.
+fn cluster_spacepoints_unvetoed(
+    sp: Vec<SpacePoint>,
+    config: &ClusteringConfig,
+) -> ClusteringResult {
+    let max_distance = track_finding::NeighborDistance::new(
+        config.max_neighbor_distance,
+        config.max_neighbor_distance_z,
+    );
+    match config.algorithm {
+        ClusteringAlgorithm::Hough => {
+            let (rho_bins, theta_bins) = if config.adaptive_hough_bins {
+                track_finding::adaptive_hough_bins(
+                    &sp,
+                    config.min_num_points_per_cluster,
+                    config.rho_bins,
+                    config.theta_bins,
+                    config.hough_precision,
+                )
+            } else {
+                (config.rho_bins, config.theta_bins)
+            };
+
+            track_finding::cluster_spacepoints(
+                sp,
+                config.min_num_points_per_cluster,
+                config.max_num_clusters,
+                rho_bins,
+                theta_bins,
+                config.hough_precision,
+                max_distance,
+            )
+        }
+        ClusteringAlgorithm::Dbscan => track_finding::dbscan_cluster_spacepoints(
+            sp,
+            config.min_num_points_per_cluster,
+            config.max_num_clusters,
+            config.min_num_neighbors,
+            max_distance,
+        ),
+        ClusteringAlgorithm::PadOnly => track_finding::pad_only_cluster_spacepoints(
+            sp,
+            config.min_num_points_per_cluster,
+            config.max_num_clusters,
+            config.min_num_neighbors,
+            config.max_neighbor_phi,
+            config
+                .max_neighbor_distance_z
+                .unwrap_or(config.max_neighbor_distance),
+        ),
+    }
 }
 
 /// A point in 3D space.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Coordinate {
     pub x: Length,
     pub y: Length,
@@ -210,13 +328,73 @@ impl Helix {
         // If t is within the range, then it is the actual global minimum.
         t.get::<radian>().clamp(-PI, PI)
     }
+    // Return the value of `t` of the closest point on the helix to the
+    // beamline.
+    fn closest_t_to_beamline(&self) -> f64 {
+        let c = self.at(0.0);
+        angle_between_vectors((c.x - self.x0, c.y - self.y0), (-self.x0, -self.y0)).get::<radian>()
+    }
     // Return the coordinate of the closest point on the helix to the beamline.
     fn closest_to_beamline(&self) -> Coordinate {
-        let c = self.at(0.0);
-        let t = angle_between_vectors((c.x - self.x0, c.y - self.y0), (-self.x0, -self.y0))
-            .get::<radian>();
+        self.at(self.closest_t_to_beamline())
+    }
+    // Return the (not normalized) tangent vector `dx/dt, dy/dt, dz/dt` in
+    // meters.
+    fn tangent(&self, t: f64) -> [f64; 3] {
+        let angle = Angle::new::<radian>(t) + self.phi0;
+
+        [
+            -(self.r * angle.sin()).get::<meter>(),
+            (self.r * angle.cos()).get::<meter>(),
+            self.h.get::<meter>() / (2.0 * PI),
+        ]
+    }
+    // Return the value of `t` at which the helix has a given `z`. Returns
+    // `None` if the helix has a constant `z`.
+    fn t_at_z(&self, z: Length) -> Option<f64> {
+        let t = (2.0 * PI * (z - self.z0) / self.h).get::<ratio>();
+
+        t.is_finite().then_some(t)
+    }
+    // Return the value of `t` (within a single revolution) of the closest point
+    // on the helix to a line (given by a point and a direction).
+    fn closest_t_to_line(&self, point: Coordinate, direction: [f64; 3]) -> f64 {
+        let norm = direction.iter().map(|d| d.powi(2)).sum::<f64>().sqrt();
+        let u = direction.map(|d| d / norm);
+        let distance_sqr = |t: f64| {
+            let c = self.at(t);
+            let d = [
+                (c.x - point.x).get::<meter>(),
+                (c.y - point.y).get::<meter>(),
+                (c.z - point.z).get::<meter>(),
+            ];
+            let along = d[0] * u[0] + d[1] * u[1] + d[2] * u[2];
+
+            d[0].powi(2) + d[1].powi(2) + d[2].powi(2) - along.powi(2)
+        };
+        // The distance can have multiple local minima within a revolution. Use
+        // a coarse scan to bracket the global minimum, and then refine it with
+        // a golden-section search.
+        const NUM_STEPS: usize = 360;
+        let step = 2.0 * PI / NUM_STEPS as f64;
+        let best = (0..=NUM_STEPS)
+            .map(|i| -PI + i as f64 * step)
+            .min_by(|&a, &b| distance_sqr(a).total_cmp(&distance_sqr(b)))
+            .unwrap();
+
+        let inv_phi = (5.0f64.sqrt() - 1.0) / 2.0;
+        let (mut a, mut b) = ((best - step).max(-PI), (best + step).min(PI));
+        while b - a > 1e-12 {
+            let c = b - inv_phi * (b - a);
+            let d = a + inv_phi * (b - a);
+            if distance_sqr(c) < distance_sqr(d) {
+                b = d;
+            } else {
+                a = c;
+            }
+        }
 
-        self.at(t)
+        (a + b) / 2.0
     }
     // Return the arc length of the helix between the two points.
     fn arc_length(&self, t1: f64, t2: f64) -> Length {
@@ -226,6 +404,186 @@ impl Helix {
 
         s.hypot(delta_z)
     }
+    // Return the values of `t` at which the helix crosses a cylinder (centered
+    // on the beamline) with a given radius. There are at most 2 crossings in a
+    // single revolution.
+    fn t_at_radius(&self, radius: Length) -> Vec<f64> {
+        let r = self.r.abs();
+        let d = self.x0.hypot(self.y0);
+        if d.value == 0.0 || d > r + radius || d < (r - radius).abs() {
+            return Vec::new();
+        }
+        // Intersection of two circles in the x-y plane. The crossings are
+        // symmetric with respect to the line that joins both centers.
+        let a = (radius.powi(P2::new()) - r.powi(P2::new()) + d.powi(P2::new())) / (2.0 * d);
+        let half_chord = radius.powi(P2::new()) - a.powi(P2::new());
+        // Rounding errors can make it slightly negative for tangent circles.
+        let half_chord = if half_chord.value > 0.0 {
+            half_chord.sqrt()
+        } else {
+            Length::new::<meter>(0.0)
+        };
+        let (ux, uy) = (self.x0 / d, self.y0 / d);
+
+        let c = self.at(0.0);
+        [1.0, -1.0]
+            .into_iter()
+            .map(|sign| {
+                let x = a * ux - sign * half_chord * uy;
+                let y = a * uy + sign * half_chord * ux;
+                angle_between_vectors((c.x - self.x0, c.y - self.y0), (x - self.x0, y - self.y0))
+                    .get::<radian>()
+            })
+            .collect()
+    }
+}
+
+/// Position and direction of a [`Track`] at a given `t`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackState {
+    /// Value of the track parameter `t`.
+    pub t: f64,
+    /// Position of the track.
+    pub position: Coordinate,
+    /// Unit vector (`x`, `y`, `z`) tangent to the track. It points in the
+    /// direction in which the particle moves outwards i.e. from
+    /// [`Track::t_inner`] towards [`Track::t_outer`].
+    pub direction: [f64; 3],
+}
+
+/// Closest approach between two [`Track`]s.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClosestApproach {
+    /// [`TrackState`] at the point of closest approach on the first track.
+    pub first: TrackState,
+    /// [`TrackState`] at the point of closest approach on the second track.
+    pub second: TrackState,
+    /// Distance of closest approach between both tracks.
+    pub distance: Length,
+    /// Midpoint between the points of closest approach on both tracks.
+    pub midpoint: Coordinate,
+}
+
+/// Residual of a [`SpacePoint`] with respect to a [`Track`].
+///
+/// The distance between the [`SpacePoint`] and the closest point on the track
+/// is decomposed along the radial, azimuthal, and axial directions at the
+/// [`SpacePoint`]. All components are the [`SpacePoint`] minus the track.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Residual {
+    /// Value of `t` at which the track is closest to the [`SpacePoint`].
+    pub t: f64,
+    /// Radial component of the residual.
+    pub radial: Length,
+    /// Azimuthal (arc length) component of the residual.
+    pub azimuthal: Length,
+    /// Axial component of the residual.
+    pub axial: Length,
+    /// Squared distance in units of the [`SpacePoint`] resolution i.e. the
+    /// contribution of the [`SpacePoint`] to the chi-squared of the
+    /// least-squares fit.
+    pub chi2: f64,
+}
+
+/// Momentum of a [`Track`] assuming a singly charged particle.
+///
+/// The axial component is signed, and it is positive if the particle moves
+/// (outwards, see [`TrackState::direction`]) towards positive `z`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackMomentum {
+    /// Transverse momentum.
+    pub pt: Momentum,
+    /// Axial momentum.
+    pub pz: Momentum,
+    /// Magnitude of the momentum.
+    pub p: Momentum,
+    /// Standard deviation of [`TrackMomentum::pt`]. This is [`None`] if the
+    /// track has no covariance (see [`Track::covariance`]).
+    pub sigma_pt: Option<Momentum>,
+    /// Standard deviation of [`TrackMomentum::pz`].
+    pub sigma_pz: Option<Momentum>,
+    /// Standard deviation of [`TrackMomentum::p`].
+    pub sigma_p: Option<Momentum>,
+}
+
+/// Sign of the electric charge of a particle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChargeSign {
+    Positive,
+    Negative,
+}
+
+/// Minimum curvature significance (see [`ChargeSignEstimate::significance`])
+/// for [`Track::charge_sign`] to assign a sign to a track.
+pub const MIN_CHARGE_SIGN_SIGNIFICANCE: f64 = 2.0;
+
+/// Charge sign estimate of a [`Track`] assuming a singly charged particle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChargeSignEstimate {
+    /// Most likely sign of the charge. This is [`None`] if the
+    /// [`ChargeSignEstimate::significance`] is less than
+    /// [`MIN_CHARGE_SIGN_SIGNIFICANCE`] (e.g. a nearly straight, high momentum
+    /// track) because the bending direction can not be trusted.
+    pub sign: Option<ChargeSign>,
+    /// Absolute value of the curvature divided by its uncertainty. This is
+    /// infinite for a curved track without covariance (e.g.
+    /// [`Track::from_perigee`]).
+    pub significance: f64,
+    /// Probability (between `0.5` and `1.0`) that the most likely sign is
+    /// correct, assuming a Gaussian uncertainty of the curvature.
+    pub confidence: f64,
+}
+
+// Elementary charge in coulomb.
+const ELEMENTARY_CHARGE: f64 = 1.602_176_634e-19;
+// Number of points along a track at which the magnetic field is averaged to
+// estimate its momentum and charge sign.
+const MOMENTUM_FIELD_SAMPLES: usize = 9;
+
+/// Perigee parameters of a [`Track`].
+///
+/// The perigee is the point of the track closest to the beamline in the `x-y`
+/// plane. Given the direction of the track at the perigee (see
+/// [`TrackState::direction`]), the parameters are:
+///
+/// - `d0`: Signed transverse distance to the beamline. The perigee is at
+///   `x = -d0 * sin(phi0)` and `y = d0 * cos(phi0)`.
+/// - `z0`: Axial position of the perigee.
+/// - `phi0`: Azimuthal angle of the direction at the perigee.
+/// - `curvature`: Signed inverse of the radius of the track in the `x-y`
+///   plane. It is positive if the track bends counter-clockwise (looking from
+///   positive `z`).
+/// - `tan_lambda`: Tangent of the dip angle i.e. `dz/ds` with `s` the arc
+///   length in the `x-y` plane.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Perigee {
+    pub d0: Length,
+    pub z0: Length,
+    pub phi0: Angle,
+    pub curvature: ReciprocalLength,
+    pub tan_lambda: f64,
+}
+
+// Straight tracks (zero curvature) are approximated by this huge radius.
+const MAX_PERIGEE_RADIUS: f64 = 1e9;
+
+impl Perigee {
+    /// Return the position of the perigee.
+    pub fn position(&self) -> Coordinate {
+        Coordinate {
+            x: -self.d0 * self.phi0.sin(),
+            y: self.d0 * self.phi0.cos(),
+            z: self.z0,
+        }
+    }
+    /// Return the unit vector (`x`, `y`, `z`) tangent to the track at the
+    /// perigee.
+    pub fn direction(&self) -> [f64; 3] {
+        let (sin, cos) = self.phi0.get::<radian>().sin_cos();
+        let norm = (1.0 + self.tan_lambda.powi(2)).sqrt();
+
+        [cos / norm, sin / norm, self.tan_lambda / norm]
+    }
 }
 
 /// Trajectory of a charged particle through the detector volume.
@@ -251,6 +609,38 @@ pub struct Track {
     // It is just an arbitrary parametrization.
     t_inner: f64,
     t_outer: f64,
+    dedx: ReciprocalLength,
+    cosmic: bool,
+    // Covariance of the perigee parameters (see `Track::covariance`).
+    covariance: Option<[[f64; 5]; 5]>,
+    chi2: f64,
+    ndf: usize,
+    // Number of iterations of the minimizer (see `Track::num_iter`).
+    num_iter: u64,
+}
+
+// Truncated mean of the wire amplitudes of all SpacePoints in a track,
+// normalized by the length of the track. Only the lowest `kept_fraction` of
+// amplitudes contribute to the mean (the energy loss distribution has a long
+// Landau tail).
+// Pad amplitudes are deliberately not used. Within a pad column, pad hits are
+// assigned to wire hits at the same time only by the order of their amplitudes
+// (i.e. the `pad_amplitude` of a SpacePoint can belong to a different
+// avalanche), and the charge induced on a pad column depends on the position
+// of the avalanche with respect to the column edges (part of it is shared with
+// the neighboring column). Mixing them in would only add noise to the wire
+// amplitudes, which are unambiguous.
+fn truncated_mean_dedx(
+    mut wire_amplitudes: Vec<f64>,
+    track_length: Length,
+    kept_fraction: f64,
+) -> ReciprocalLength {
+    wire_amplitudes.sort_unstable_by(|a, b| a.total_cmp(b));
+    let num_kept = ((wire_amplitudes.len() as f64 * kept_fraction).ceil() as usize)
+        .clamp(1, wire_amplitudes.len());
+    let mean = wire_amplitudes[..num_kept].iter().sum::<f64>() / num_kept as f64;
+    // Each SpacePoint samples (on average) an equal fraction of the track.
+    mean * wire_amplitudes.len() as f64 / track_length
 }
 
 impl Track {
@@ -268,51 +658,960 @@ impl Track {
     pub fn t_outer(&self) -> f64 {
         self.t_outer
     }
+    /// Return the truncated mean energy loss per unit length (in arbitrary
+    /// units) of the track.
+    ///
+    /// This is calculated from the wire amplitudes of all the [`SpacePoint`]s
+    /// associated to the track; only the lowest amplitudes contribute to the
+    /// mean in order to remove the long tail of the energy loss distribution.
+    /// The pad amplitudes are not used because the matching between wire and
+    /// pad hits of simultaneous avalanches is ambiguous, and part of the
+    /// induced charge is shared with the neighboring pad column.
+    /// It is useful to separate e.g. protons, pions, and cosmics within the
+    /// same data set, but the absolute scale is subject to change (see
+    /// [`Avalanche::wire_amplitude`](crate::Avalanche::wire_amplitude)).
+    pub fn dedx(&self) -> ReciprocalLength {
+        self.dedx
+    }
+    /// Return `true` if the track has been tagged as a through-going cosmic
+    /// ray (see [`tag_cosmics`]).
+    pub fn is_cosmic(&self) -> bool {
+        self.cosmic
+    }
+    /// Return the covariance matrix of the [`Perigee`] parameters of the track.
+    ///
+    /// Parameters are in (`d0`, `z0`, `phi0`, `curvature`, `tan_lambda`) order,
+    /// and all values are in SI units (i.e. meter, radian, and 1/meter). This
+    /// is [`None`] if the track was not obtained from a fit (e.g.
+    /// [`Track::from_perigee`]), or if the fit does not constrain all the
+    /// parameters.
+    pub fn covariance(&self) -> Option<[[f64; 5]; 5]> {
+        self.covariance
+    }
+    /// Return the chi-squared of the track fit.
+    ///
+    /// For the least-squares fit, this is the sum of [`Residual::chi2`] over
+    /// all the [`SpacePoint`]s in the fitted [`Cluster`]. For the Kalman filter
+    /// fit, this is the sum of the chi-squared increments of all the filter
+    /// updates (using the resolution in the [`KalmanFitConfig`]).
+    pub fn chi2(&self) -> f64 {
+        self.chi2
+    }
+    /// Return the number of degrees of freedom of the track fit.
+    ///
+    /// Each [`SpacePoint`] is compared to the closest point on the track, so it
+    /// only contributes with two degrees of freedom (the third one is absorbed
+    /// by its position along the track). Hence this is `2 * n - 5` for a
+    /// [`Cluster`] with `n` [`SpacePoint`]s and the 5 [`Perigee`] parameters.
+    ///
+    /// Tracks that were not obtained from a fit (e.g. [`Track::from_perigee`])
+    /// have zero chi-squared and zero degrees of freedom.
+    pub fn ndf(&self) -> usize {
+        self.ndf
+    }
+    /// Return the number of iterations of the minimizer used by the
+    /// least-squares fit.
+    ///
+    /// This is zero for tracks fitted with a Kalman filter (which is not
+    /// iterative) and for tracks that were not obtained from a fit (e.g.
+    /// [`Track::from_perigee`]).
+    pub fn num_iter(&self) -> u64 {
+        self.num_iter
+    }
+    /// Return the [`Residual`] of a [`SpacePoint`] with respect to the track.
+    ///
+    /// This is useful e.g. for quality cuts or residual-based alignment studies
+    /// with the same [`SpacePoint`]s that were used in the fit.
+    pub fn residual(&self, sp: SpacePoint) -> Residual {
+        self.residual_with_config(sp, &ReconstructionConfig::default())
+    }
+    /// Same as [`Track::residual`], but with an arbitrary
+    /// [`ReconstructionConfig`] (e.g. the one used to fit the track).
+    pub fn residual_with_config(&self, sp: SpacePoint, config: &ReconstructionConfig) -> Residual {
+        let config = &config.track_fit;
+        let t = self.helix.closest_t(
+            sp,
+            config.closest_t_tolerance,
+            config.max_num_closest_t_iter,
+        );
+        let [radial, azimuthal, axial] = track_fitting::residual_components(sp, self.helix.at(t));
+
+        Residual {
+            t,
+            radial,
+            azimuthal,
+            axial,
+            chi2: (radial / sp.sigma_r).get::<ratio>().powi(2)
+                + (azimuthal / sp.sigma_rphi).get::<ratio>().powi(2)
+                + (axial / sp.sigma_z).get::<ratio>().powi(2),
+        }
+    }
+    /// Return the momentum of the track in a given magnetic field.
+    ///
+    /// The transverse momentum is obtained from the fitted curvature and the
+    /// axial component of the field averaged along the track (between
+    /// [`Track::t_inner`] and [`Track::t_outer`]). The axial momentum follows
+    /// from the dip angle. Uncertainties are propagated from the
+    /// [`Track::covariance`] of the `curvature` and `tan_lambda` parameters
+    /// (the uncertainty of the field itself is ignored).
+    ///
+    /// Returns an error if the field is not known along the track.
+    pub fn momentum(&self, field: &MagneticField) -> Result<TrackMomentum, FieldOutOfRangeError> {
+        let b_z = self.mean_field_z(field)?.abs();
+
+        let perigee = self.perigee();
+        let curvature = perigee.curvature.get::<reciprocal_meter>();
+        let tan_lambda = perigee.tan_lambda;
+        let sec_lambda = (1.0 + tan_lambda.powi(2)).sqrt();
+        // Momentum in kg m/s of a singly charged particle in a helix.
+        let pt = ELEMENTARY_CHARGE * b_z / curvature.abs();
+        let pz = pt * tan_lambda;
+        let p = pt * sec_lambda;
+        // Error propagation with the Jacobian with respect to (curvature,
+        // tan_lambda).
+        let sigma = |jacobian: [f64; 2]| {
+            self.covariance.map(|cov| {
+                let c = [[cov[3][3], cov[3][4]], [cov[4][3], cov[4][4]]];
+                let variance = (0..2)
+                    .flat_map(|i| (0..2).map(move |j| (i, j)))
+                    .map(|(i, j)| jacobian[i] * c[i][j] * jacobian[j])
+                    .sum::<f64>();
+
+                Momentum::new::<kilogram_meter_per_second>(variance.max(0.0).sqrt())
+            })
+        };
+
+        Ok(TrackMomentum {
+            pt: Momentum::new::<kilogram_meter_per_second>(pt),
+            pz: Momentum::new::<kilogram_meter_per_second>(pz),
+            p: Momentum::new::<kilogram_meter_per_second>(p),
+            sigma_pt: sigma([-pt / curvature, 0.0]),
+            sigma_pz: sigma([-pz / curvature, pt]),
+            sigma_p: sigma([-p / curvature, pt * tan_lambda / sec_lambda]),
+        })
+    }
+    /// Return the charge sign estimate of the track in a given magnetic field.
+    ///
+    /// The sign is given by the bending direction of the track (i.e. the sign
+    /// of the fitted curvature) and the direction of the axial component of
+    /// the field averaged along the track. Its confidence is derived from the
+    /// significance of the curvature given the [`Track::covariance`].
+    ///
+    /// Returns an error if the field is not known along the track.
+    pub fn charge_sign(
+        &self,
+        field: &MagneticField,
+    ) -> Result<ChargeSignEstimate, FieldOutOfRangeError> {
+        let b_z = self.mean_field_z(field)?;
+        let curvature = self.perigee().curvature.get::<reciprocal_meter>();
+        // A positive particle bends clockwise in a field along positive `z`.
+        let product = -curvature * b_z;
+        if product == 0.0 {
+            return Ok(ChargeSignEstimate {
+                sign: None,
+                significance: 0.0,
+                confidence: 0.5,
+            });
+        }
+
+        let significance = match self.covariance {
+            Some(cov) => curvature.abs() / cov[3][3].sqrt(),
+            None => f64::INFINITY,
+        };
+        // The probability of getting the wrong sign is half the probability of
+        // a Gaussian variable being more than `significance` standard
+        // deviations away from its mean.
+        let confidence = if significance.is_finite() {
+            1.0 - 0.5 * vertex_fitting::chi2_probability(significance.powi(2), 1)
+        } else {
+            1.0
+        };
+        let sign = if product > 0.0 {
+            ChargeSign::Positive
+        } else {
+            ChargeSign::Negative
+        };
+
+        Ok(ChargeSignEstimate {
+            sign: (significance >= MIN_CHARGE_SIGN_SIGNIFICANCE).then_some(sign),
+            significance,
+            confidence,
+        })
+    }
+    // Axial component of the magnetic field (in tesla) averaged along the
+    // track.
+    fn mean_field_z(&self, field: &MagneticField) -> Result<f64, FieldOutOfRangeError> {
+        let mut b_z = 0.0;
+        for i in 0..MOMENTUM_FIELD_SAMPLES {
+            let fraction = i as f64 / (MOMENTUM_FIELD_SAMPLES - 1) as f64;
+            let t = self.t_inner + fraction * (self.t_outer - self.t_inner);
+            b_z += field.at(self.at(t))?.z.get::<tesla>();
+        }
+
+        Ok(b_z / MOMENTUM_FIELD_SAMPLES as f64)
+    }
+    /// Create a track from its [`Perigee`] parameters. This is useful e.g. to
+    /// use the extrapolation methods with externally provided parameters.
+    ///
+    /// The resulting track has no associated dE/dx (i.e. zero), no covariance,
+    /// no chi-squared (see [`Track::ndf`]), and it is not tagged as a cosmic.
+    pub fn from_perigee(perigee: Perigee) -> Self {
+        let position = perigee.position();
+        let curvature = perigee.curvature.get::<reciprocal_meter>();
+        let radius = Length::new::<meter>((1.0 / curvature.abs()).min(MAX_PERIGEE_RADIUS));
+        // Particles bending counter-clockwise move towards increasing `t`.
+        let sign = if curvature >= 0.0 { 1.0 } else { -1.0 };
+        let (sin, cos) = (perigee.phi0.sin(), perigee.phi0.cos());
+        let (x0, y0) = (
+            position.x - sign * radius * sin,
+            position.y + sign * radius * cos,
+        );
+        let helix = Helix {
+            x0,
+            y0,
+            z0: perigee.z0,
+            r: radius,
+            phi0: (position.y - y0).atan2(position.x - x0),
+            h: sign * TAU * radius * perigee.tan_lambda,
+        };
+        // First crossing of a given radius when moving outwards from the
+        // perigee (at `t = 0`).
+        let first_crossing = |radius: f64| {
+            helix
+                .t_at_radius(Length::new::<meter>(radius))
+                .into_iter()
+                .map(|t| (t * sign).rem_euclid(TAU))
+                .min_by(|a, b| a.total_cmp(b))
+                .map(|distance| sign * distance)
+        };
+
+        Self {
+            helix,
+            // The perigee itself is the closest to the inner cathode if the
+            // track never crosses it.
+            t_inner: first_crossing(INNER_CATHODE_RADIUS).unwrap_or(0.0),
+            t_outer: first_crossing(CATHODE_PADS_RADIUS).unwrap_or(sign * PI),
+            dedx: ReciprocalLength::new::<reciprocal_meter>(0.0),
+            cosmic: false,
+            covariance: None,
+            chi2: 0.0,
+            ndf: 0,
+            num_iter: 0,
+        }
+    }
+    /// Return the [`Perigee`] parameters of the track.
+    pub fn perigee(&self) -> Perigee {
+        let state = self.state(self.helix.closest_t_to_beamline());
+        let [ux, uy, uz] = state.direction;
+        let phi0 = Angle::new::<radian>(uy.atan2(ux));
+        let (px, py) = (state.position.x, state.position.y);
+        // The track bends counter-clockwise if the center of the circle is to
+        // the left of the direction of motion.
+        let left = (ux * (self.helix.y0 - py) - uy * (self.helix.x0 - px)).get::<meter>();
+        let sign = if left >= 0.0 { 1.0 } else { -1.0 };
+
+        Perigee {
+            d0: -px * phi0.sin() + py * phi0.cos(),
+            z0: state.position.z,
+            phi0,
+            curvature: sign / self.helix.r.abs(),
+            tan_lambda: uz / ux.hypot(uy),
+        }
+    }
+    /// Return the signed transverse impact parameter of the track with respect
+    /// to the nominal trap axis (`x = y = 0`). This is the same as the `d0` of
+    /// [`Track::perigee`].
+    ///
+    /// Use its absolute value for cuts like "the track must point within 1 cm
+    /// of the trap axis".
+    pub fn d0(&self) -> Length {
+        self.perigee().d0
+    }
+    /// Return the axial position of the point of closest approach of the track
+    /// to the nominal trap axis (`x = y = 0`). This is the same as the `z0` of
+    /// [`Track::perigee`].
+    pub fn z0(&self) -> Length {
+        self.perigee().z0
+    }
+    /// Return the [`TrackState`] (position and direction) at a given `t`.
+    pub fn state(&self, t: f64) -> TrackState {
+        let sign = if self.t_outer >= self.t_inner {
+            1.0
+        } else {
+            -1.0
+        };
+        let tangent = self.helix.tangent(t);
+        let norm = tangent.iter().map(|v| v.powi(2)).sum::<f64>().sqrt();
+
+        TrackState {
+            t,
+            position: self.helix.at(t),
+            direction: tangent.map(|v| sign * v / norm),
+        }
+    }
+    /// Extrapolate the track to a cylinder (centered on the beamline) with a
+    /// given radius.
+    ///
+    /// A track crosses a cylinder at most twice within a single revolution.
+    /// Returns an empty vector if the track never reaches the given radius.
+    pub fn extrapolate_to_radius(&self, radius: Length) -> Vec<TrackState> {
+        self.helix
+            .t_at_radius(radius)
+            .into_iter()
+            .map(|t| self.state(t))
+            .collect()
+    }
+    /// Extrapolate the track to a plane perpendicular to the beamline at a
+    /// given `z`. Returns [`None`] if the track has a constant `z`.
+    ///
+    /// Note that this is not restricted to a single revolution; low momentum
+    /// tracks can go around multiple times before reaching `z`.
+    pub fn extrapolate_to_z(&self, z: Length) -> Option<TrackState> {
+        self.helix.t_at_z(z).map(|t| self.state(t))
+    }
+    /// Return the [`TrackState`] at the point of closest approach to a given
+    /// [`Coordinate`] (within a single revolution).
+    pub fn closest_to_point(&self, point: Coordinate) -> TrackState {
+        self.closest_to_point_with_config(point, &ReconstructionConfig::default())
+    }
+    /// Same as [`Track::closest_to_point`], but with an arbitrary
+    /// [`ReconstructionConfig`] (e.g. the one used to fit the track).
+    pub fn closest_to_point_with_config(
+        &self,
+        point: Coordinate,
+        config: &ReconstructionConfig,
+    ) -> TrackState {
+        let config = &config.track_fit;
+        // There is already a method in the Helix to calculate the closest_t
+        // to a SpacePoint. Just use that.
+        let sp = SpacePoint {
+            r: point.x.hypot(point.y),
+            phi: point.y.atan2(point.x),
+            z: point.z,
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            // Irrelevant to find the closest point on a track.
+            sigma_r: Length::new::<meter>(0.0),
+            sigma_rphi: Length::new::<meter>(0.0),
+            sigma_z: Length::new::<meter>(0.0),
+        };
+        let t = self.helix.closest_t(
+            sp,
+            config.closest_t_tolerance,
+            config.max_num_closest_t_iter,
+        );
+
+        self.state(t)
+    }
+    /// Return the [`TrackState`] at the point of closest approach to a straight
+    /// line (within a single revolution). The line is given by any point on it
+    /// and a (not necessarily normalized) direction vector (`x`, `y`, `z`).
+    pub fn closest_to_line(&self, point: Coordinate, direction: [f64; 3]) -> TrackState {
+        self.state(self.helix.closest_t_to_line(point, direction))
+    }
+    /// Return the [`ClosestApproach`] between this track and another track
+    /// (within a single revolution of each track).
+    ///
+    /// This is the same distance of closest approach used to seed the vertex
+    /// fit, and it is useful e.g. to look for secondary vertices or to diagnose
+    /// vertexing failures. Nearly straight tracks (e.g. [`Track::from_perigee`]
+    /// with zero curvature) behave as lines. Note that the search starts from
+    /// the closest approach of this track to the beamline, and it can converge
+    /// to a local minimum for tracks that cross each other more than once.
+    pub fn closest_approach(&self, other: &Track) -> ClosestApproach {
+        self.closest_approach_with_config(other, &ReconstructionConfig::default())
+    }
+    /// Same as [`Track::closest_approach`], but with an arbitrary
+    /// [`ReconstructionConfig`] (e.g. the one used to fit the tracks).
+    pub fn closest_approach_with_config(
+        &self,
+        other: &Track,
+        config: &ReconstructionConfig,
+    ) -> ClosestApproach {
+        let config = &config.track_fit;
+        let (t_first, t_second) = vertex_fitting::closest_approach(
+            self,
+            other,
+            config.closest_t_tolerance,
+            config.max_num_closest_t_iter,
+        );
+        let (first, second) = (self.state(t_first), other.state(t_second));
+        let (a, b) = (first.position, second.position);
+
+        ClosestApproach {
+            first,
+            second,
+            distance: (a.x - b.x).hypot(a.y - b.y).hypot(a.z - b.z),
+            midpoint: Coordinate {
+                x: 0.5 * (a.x + b.x),
+                y: 0.5 * (a.y + b.y),
+                z: 0.5 * (a.z + b.z),
+            },
+        }
+    }
+    /// Return the arc length of the track between two cylinders (centered on
+    /// the beamline) with the given radii.
+    ///
+    /// Only the outgoing half revolution of the track is considered i.e. from
+    /// the perigee towards [`Track::t_outer`]. The radius of the track is
+    /// monotonically increasing along this path, so this is the distance
+    /// traveled by a particle produced at the beamline between both
+    /// cylinders. Returns zero if the track never gets in between the two radii.
+    pub fn path_length(&self, r1: Length, r2: Length) -> Length {
+        let (start, end) = self.outgoing_offsets(r1, r2);
+
+        self.helix.arc_length(0.0, end - start)
+    }
+    /// Return the arc length of the track inside the rTPC volume i.e. between
+    /// the inner cathode and the cathode pads, and within the length of the
+    /// detector.
+    ///
+    /// This is the outgoing half revolution of the track (see
+    /// [`Track::path_length`]). Tracks tagged as cosmic rays (see
+    /// [`Track::is_cosmic`]) go through the detector, so the incoming half
+    /// revolution is also included.
+    pub fn length_in_tpc(&self) -> Length {
+        let (start, end) = self.outgoing_offsets(
+            Length::new::<meter>(INNER_CATHODE_RADIUS),
+            Length::new::<meter>(CATHODE_PADS_RADIUS),
+        );
+        let outgoing_sign = if self.t_outer >= self.t_inner {
+            1.0
+        } else {
+            -1.0
+        };
+        let signs: &[f64] = if self.cosmic {
+            &[outgoing_sign, -outgoing_sign]
+        } else {
+            &[outgoing_sign]
+        };
+
+        let t_perigee = self.helix.closest_t_to_beamline();
+        let half_length = Length::new::<meter>(DETECTOR_LENGTH / 2.0);
+        signs
+            .iter()
+            .map(|sign| {
+                let (z_start, z_end) = (
+                    self.helix.at(t_perigee + sign * start).z,
+                    self.helix.at(t_perigee + sign * end).z,
+                );
+                // The axial position is linear in `t`. Clip the range of
+                // offsets to the length of the detector.
+                let (start, end) = if z_start == z_end {
+                    if z_start.abs() > half_length {
+                        (0.0, 0.0)
+                    } else {
+                        (start, end)
+                    }
+                } else {
+                    let offset_at = |z: Length| {
+                        start + (end - start) * ((z - z_start) / (z_end - z_start)).get::<ratio>()
+                    };
+                    let (a, b) = (offset_at(-half_length), offset_at(half_length));
+                    (start.max(a.min(b)), end.min(a.max(b)))
+                };
+
+                self.helix.arc_length(0.0, (end - start).max(0.0))
+            })
+            .sum()
+    }
+    // Return the range of (unsigned) offsets in `t` from the perigee, along the
+    // outgoing half revolution, in which the track is between two radii.
+    //
+    // The distance from the beamline to a point at an offset `u` from the
+    // perigee is given by the law of cosines:
+    //    r^2 = d^2 + R^2 - 2 * d * R * cos(u)
+    // where `d` is the distance from the beamline to the center of the helix,
+    // and `R` is the radius of the helix.
+    fn outgoing_offsets(&self, r1: Length, r2: Length) -> (f64, f64) {
+        let d = self.helix.x0.hypot(self.helix.y0);
+        let r = self.helix.r.abs();
+        let offset_at = |radius: Length| {
+            if radius <= (d - r).abs() {
+                0.0
+            } else if radius >= d + r {
+                PI
+            } else {
+                let cos = (d.powi(P2::new()) + r.powi(P2::new()) - radius.powi(P2::new()))
+                    / (2.0 * d * r);
+                cos.get::<ratio>().clamp(-1.0, 1.0).acos()
+            }
+        };
+
+        (offset_at(r1.min(r2)), offset_at(r1.max(r2)))
+    }
 }
 
 /// The error type returned when conversion from a [`Cluster`] to a [`Track`]
 /// fails.
 #[derive(Debug, Error)]
 pub enum TryTrackFromClusterError {
+    /// The [`Cluster`] has less than the minimum number of [`SpacePoint`]s
+    /// (three) required to fit a [`Track`].
+    #[error("not enough spacepoints (found `{0}`, expected at least `3`)")]
+    NotEnoughPoints(usize),
     /// Unable to produce initial fit parameters.
     #[error("unable to produce initial fit parameters")]
     NoInitialParameters,
+    /// The magnetic field is not known along the track.
+    #[error("magnetic field lookup failed")]
+    FieldOutOfRange(#[from] FieldOutOfRangeError),
 }
 
 impl TryFrom<Cluster> for Track {
     type Error = TryTrackFromClusterError;
 
     fn try_from(cluster: Cluster) -> Result<Self, Self::Error> {
-        track_fitting::fit_cluster_to_helix(
-            cluster,
-            // Maximum number of Nelder-Mead iterations.
-            100,
-            // Nelder-Mead standard deviation tolerance.
-            f64::EPSILON,
-            // Delta from the initial guess for each simplex vertex.
-            // I just stuck to the default value used by scipy's implementation
-            // of Nelder-Mead. It has worked well.
-            // See:
-            // https://github.com/scipy/scipy/blob/v1.11.2/scipy/optimize/_optimize.py#L833
-            0.05,
-            // Maximum number of iterations to find the closest point on the
-            // helix given a SpacePoint.
-            20,
-            // Tolerance for finding the `t` parameter of the closest point on
-            // the helix given a SpacePoint.
-            f64::EPSILON,
-        )
+        fit_track_with_config(cluster, &ReconstructionConfig::default())
     }
 }
 
+/// Same as [`Track::try_from`], but with an arbitrary [`ReconstructionConfig`].
+///
+/// The [`Cluster`] is fitted with the [`TrackFitConfig::algorithm`] of the
+/// configuration; this is the fit used by all the other steps of the
+/// reconstruction (e.g. [`fit_tracks_with_config`]).
+///
+/// The magnetic field is assumed to be uniform and equal to
+/// [`NOMINAL_FIELD`](crate::field::NOMINAL_FIELD). See [`fit_track_in_field`]
+/// to use e.g. a measured field map.
+pub fn fit_track_with_config(
+    cluster: Cluster,
+    config: &ReconstructionConfig,
+) -> Result<Track, TryTrackFromClusterError> {
+    fit_track_in_field(cluster, &MagneticField::default(), config)
+}
+
+/// Same as [`fit_track_with_config`], but with an arbitrary [`MagneticField`].
+///
+/// Only the Kalman filter depends on the magnetic field (see
+/// [`fit_track_kalman_with_config`]); the least-squares fit is purely
+/// geometrical.
+pub fn fit_track_in_field(
+    cluster: Cluster,
+    field: &MagneticField,
+    config: &ReconstructionConfig,
+) -> Result<Track, TryTrackFromClusterError> {
+    if config.track_fit.algorithm == TrackFitAlgorithm::Kalman {
+        return fit_track_kalman_with_config(cluster, field, config);
+    }
+
+    let seed = config.seed;
+    let config = &config.track_fit;
+    track_fitting::fit_cluster_to_helix(
+        cluster,
+        config.max_num_iter,
+        config.sd_tolerance,
+        config.initial_simplex_delta,
+        config.max_num_closest_t_iter,
+        config.closest_t_tolerance,
+        config.dedx_kept_fraction,
+        config.hessian_step,
+        config.max_num_resample,
+        seed,
+    )
+}
+
+/// Result of tagging cosmic rays in a collection of [`Cluster`]s.
+#[derive(Clone, Debug)]
+pub struct CosmicTaggingResult {
+    /// Through-going cosmic ray [`Track`]s. Each one is the combined fit of two
+    /// back-to-back [`Cluster`]s, and [`Track::is_cosmic`] returns `true`.
+    pub cosmics: Vec<Track>,
+    /// Remaining [`Cluster`]s that have not been identified as part of a
+    /// cosmic ray.
+    pub remainder: Vec<Cluster>,
+}
+
+/// Given a collection of [`Cluster`]s, identify pairs of back-to-back clusters
+/// that are a single straight through-going cosmic ray.
+///
+/// A cosmic ray crossing the detector leaves two clusters on opposite sides of
+/// the inner cathode. If these are fitted individually, they look like two
+/// annihilation tracks and bias the vertex distribution.
+pub fn tag_cosmics(clusters: Vec<Cluster>) -> CosmicTaggingResult {
+    tag_cosmics_with_config(clusters, &ReconstructionConfig::default())
+}
+
+/// Same as [`tag_cosmics`], but with an arbitrary [`ReconstructionConfig`].
+pub fn tag_cosmics_with_config(
+    clusters: Vec<Cluster>,
+    config: &ReconstructionConfig,
+) -> CosmicTaggingResult {
+    tag_cosmics_in_field(clusters, &MagneticField::default(), config)
+}
+
+/// Same as [`tag_cosmics_with_config`], but with an arbitrary
+/// [`MagneticField`] (see [`fit_track_in_field`]).
+pub fn tag_cosmics_in_field(
+    clusters: Vec<Cluster>,
+    field: &MagneticField,
+    config: &ReconstructionConfig,
+) -> CosmicTaggingResult {
+    cosmic_tagging::tag_cosmics(
+        clusters,
+        |cluster| fit_track_in_field(cluster, field, config),
+        config.cosmic_tagging.min_radius,
+        config.cosmic_tagging.max_rms_residual,
+        config.track_fit.max_num_closest_t_iter,
+        config.track_fit.closest_t_tolerance,
+    )
+}
+
+/// Result of tagging spiraling electrons in a collection of [`SpacePoint`]s.
+#[derive(Clone, Debug)]
+pub struct SpiralTaggingResult {
+    /// Groups of [`SpacePoint`]s that make at least one full turn around a
+    /// small circle in the `x-y` plane.
+    pub spirals: Vec<Cluster>,
+    /// Remaining [`SpacePoint`]s.
+    pub remainder: Vec<SpacePoint>,
+}
+
+/// Identify the [`SpacePoint`]s left by low momentum electrons (e.g. delta
+/// rays) spiraling in the magnetic field.
+///
+/// These make one or more full turns around a small circle, and would
+/// otherwise fragment into many small clusters that are either discarded or
+/// fitted to nonsensical tracks. Remove them before clustering.
+pub fn tag_spirals(spacepoints: Vec<SpacePoint>) -> SpiralTaggingResult {
+    tag_spirals_with_config(spacepoints, &ReconstructionConfig::default())
+}
+
+/// Same as [`tag_spirals`], but with an arbitrary [`ReconstructionConfig`].
+pub fn tag_spirals_with_config(
+    spacepoints: Vec<SpacePoint>,
+    config: &ReconstructionConfig,
+) -> SpiralTaggingResult {
+    let config = &config.spiral_tagging;
+    spiral_tagging::tag_spirals(
+        spacepoints,
+        config.min_num_points,
+        config.max_neighbor_distance,
+        config.max_radius,
+        config.max_rms_residual,
+        config.max_phase_gap,
+    )
+}
+
+/// Fit a collection of [`Cluster`]s to [`Track`]s, merging the clusters that
+/// are part of the same track.
+///
+/// A single track can be split into multiple clusters (e.g. by a dead region of
+/// the detector). Clusters with compatible individual fits are fitted together,
+/// and merged if the combined fit is good. Clusters that can not be fitted are
+/// dropped.
+pub fn fit_tracks(clusters: Vec<Cluster>) -> Vec<Track> {
+    fit_tracks_with_config(clusters, &ReconstructionConfig::default())
+}
+
+/// Same as [`fit_tracks`], but with an arbitrary [`ReconstructionConfig`].
+pub fn fit_tracks_with_config(clusters: Vec<Cluster>, config: &ReconstructionConfig) -> Vec<Track> {
+    fit_tracks_in_field(clusters, &MagneticField::default(), config)
+}
+
+/// Same as [`fit_tracks_with_config`], but with an arbitrary [`MagneticField`]
+/// (see [`fit_track_in_field`]).
+pub fn fit_tracks_in_field(
+    clusters: Vec<Cluster>,
+    field: &MagneticField,
+    config: &ReconstructionConfig,
+) -> Vec<Track> {
+    fit_tracks_with_failures(clusters, field, config).0
+}
+
+/// Same as [`fit_tracks_with_config`], but with user callbacks to veto
+/// individual [`Cluster`]s before fitting, and [`Track`]s after fitting and
+/// merging (e.g. fiducial cuts).
+///
+/// A [`Cluster`] for which `veto_cluster` returns `true` is dropped without
+/// being fitted, and a [`Track`] for which `veto_track` returns `true` is
+/// dropped from the output.
+pub fn fit_tracks_with_vetoes<C, T>(
+    clusters: Vec<Cluster>,
+    config: &ReconstructionConfig,
+    mut veto_cluster: C,
+    mut veto_track: T,
+) -> Vec<Track>
+where
+    C: FnMut(&Cluster) -> bool,
+    T: FnMut(&Track) -> bool,
+{
+    let clusters = clusters.into_iter().filter(|c| !veto_cluster(c)).collect();
+    let (mut tracks, _) = fit_tracks_with_failures(clusters, &MagneticField::default(), config);
+    tracks.retain(|t| !veto_track(t));
+
+    tracks
+}
+
+// Same as `fit_tracks_in_field`, but also return the reason why each of the
+// dropped clusters could not be fitted.
+pub(crate) fn fit_tracks_with_failures(
+    clusters: Vec<Cluster>,
+    field: &MagneticField,
+    config: &ReconstructionConfig,
+) -> (Vec<Track>, Vec<TryTrackFromClusterError>) {
+    track_merging::fit_and_merge_clusters(
+        clusters,
+        |cluster| fit_track_in_field(cluster, field, config),
+        config.track_merging.max_relative_difference,
+        config.track_merging.max_slope_difference,
+        config.track_merging.max_rms_residual,
+        config.track_fit.max_num_closest_t_iter,
+        config.track_fit.closest_t_tolerance,
+    )
+}
+
+/// Fit a [`Cluster`] to a [`Track`] using a Kalman filter.
+///
+/// This is an alternative to the global least-squares fit used by
+/// [`Track::try_from`]. The Kalman filter takes into account the spatial
+/// resolution of each [`SpacePoint`] and the multiple scattering in the rTPC
+/// gas, which improves the extrapolation of low momentum tracks towards the
+/// vertex. The full reconstruction uses this fit if
+/// [`TrackFitConfig::algorithm`] is [`TrackFitAlgorithm::Kalman`].
+///
+/// The magnetic field is assumed to be uniform and equal to
+/// [`NOMINAL_FIELD`](crate::field::NOMINAL_FIELD).
+/// See [`fit_track_kalman_in_field`] to use e.g. a measured field map.
+pub fn fit_track_kalman(cluster: Cluster) -> Result<Track, TryTrackFromClusterError> {
+    fit_track_kalman_in_field(cluster, &MagneticField::default())
+}
+
+/// Same as [`fit_track_kalman`], but with an arbitrary [`MagneticField`].
+pub fn fit_track_kalman_in_field(
+    cluster: Cluster,
+    field: &MagneticField,
+) -> Result<Track, TryTrackFromClusterError> {
+    fit_track_kalman_with_config(cluster, field, &ReconstructionConfig::default())
+}
+
+/// Same as [`fit_track_kalman_in_field`], but with an arbitrary
+/// [`ReconstructionConfig`].
+pub fn fit_track_kalman_with_config(
+    cluster: Cluster,
+    field: &MagneticField,
+    config: &ReconstructionConfig,
+) -> Result<Track, TryTrackFromClusterError> {
+    let seed = config.seed;
+    let config = &config.kalman_fit;
+    track_fitting::kalman_fit_cluster_to_helix(
+        cluster,
+        field,
+        config.radiation_length,
+        config.max_num_closest_t_iter,
+        config.closest_t_tolerance,
+        config.dedx_kept_fraction,
+        config.max_num_resample,
+        seed,
+    )
+}
+
+/// Estimate the `z` of the primary vertex from the charge induced on the cathode
+/// pads.
+///
+/// This is a fast alternative to [`find_vertices`] (i.e. no track finding nor
+/// fitting) intended for online monitoring. Each element of `pad_charges` is
+/// the total charge (in arbitrary units) of all the pads in a single pad
+/// column, indexed by pad row.
+///
+/// The charge-weighted `z` centroid of a column is where the tracks cross that
+/// column. Annihilation products go in all directions, hence the
+/// charge-weighted median of all centroids is an estimate of the vertex `z`.
+/// Return `None` if there is no positive charge.
+pub fn estimate_vertex_z(pad_charges: &[[f64; TPC_PAD_ROWS]]) -> Option<Length> {
+    let mut centroids: Vec<(f64, f64)> = pad_charges
+        .iter()
+        .filter_map(|column| {
+            let (total, moment) = column
+                .iter()
+                .enumerate()
+                // Negative charges are just noise.
+                .filter(|(_, &q)| q > 0.0)
+                .fold((0.0, 0.0), |(total, moment), (row, &q)| {
+                    let z = TpcPadRow::try_from(row).unwrap().z();
+                    (total + q, moment + q * z)
+                });
+
+            (total > 0.0).then_some((moment / total, total))
+        })
+        .collect();
+    centroids.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let half = centroids.iter().map(|(_, q)| q).sum::<f64>() / 2.0;
+    let mut cumulative = 0.0;
+    centroids
+        .into_iter()
+        .find(|(_, q)| {
+            cumulative += q;
+            cumulative >= half
+        })
+        .map(|(z, _)| Length::new::<meter>(z))
+}
+
+/// Barrel Veto hits associated to a [`Track`].
+#[derive(Clone, Debug)]
+pub struct BvTrackMatch {
+    /// Hits matched to the positions where the extrapolated track crosses the
+    /// Barrel Veto, sorted by time. An annihilation product crosses the Barrel
+    /// Veto once, whereas a cosmic ray (see [`Track::is_cosmic`]) crosses it
+    /// twice.
+    pub hits: Vec<BvHit>,
+    /// Time of flight between both matched hits. This is only available if
+    /// the track has 2 matched hits.
+    pub tof: Option<Time>,
+    /// Length of the track between both matched hits. This is only available
+    /// if the track has 2 matched hits.
+    ///
+    /// A cosmic ray has `path_length / tof` close to the speed of light.
+    pub path_length: Option<Length>,
+}
+
+/// Associate each [`Track`] to the [`BvHit`]s where it crosses the Barrel Veto.
+///
+/// Tracks are extrapolated to the radius of the Barrel Veto bars, and matched
+/// to the hit with the closest axial position in the same (or a neighboring)
+/// bar. The output has one element per track, in the same order as the input.
+///
+/// The time of flight of a cosmic ray between both sides of the Barrel Veto
+/// is a few nanoseconds, whereas all annihilation products reach the Barrel
+/// Veto at (roughly) the same time. This can be used to reject cosmic rays by
+/// timing.
+pub fn match_bv_hits(tracks: &[Track], hits: &[BvHit]) -> Vec<BvTrackMatch> {
+    match_bv_hits_with_config(tracks, hits, &ReconstructionConfig::default())
+}
+
+/// Same as [`match_bv_hits`], but with an arbitrary [`ReconstructionConfig`].
+pub fn match_bv_hits_with_config(
+    tracks: &[Track],
+    hits: &[BvHit],
+    config: &ReconstructionConfig,
+) -> Vec<BvTrackMatch> {
+    bv_matching::match_bv_hits(
+        tracks,
+        hits,
+        config.bv_matching.max_bar_distance,
+        config.bv_matching.max_z_distance,
+    )
+}
+
+/// Resolution of a vertex position estimated from the covariance of its
+/// [`Track`]s (see [`VertexInfo::resolution`]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VertexResolution {
+    /// Covariance matrix of the vertex position (in `x`, `y`, `z` order).
+    pub covariance: [[Area; 3]; 3],
+    /// Standard deviation of the vertex position along `x`.
+    pub sigma_x: Length,
+    /// Standard deviation of the vertex position along `y`.
+    pub sigma_y: Length,
+    /// Standard deviation of the vertex position along `z`.
+    pub sigma_z: Length,
+}
+
 /// Information about a reconstructed vertex.
 #[derive(Clone, Debug)]
 pub struct VertexInfo {
     /// Position of the vertex.
     pub position: Coordinate,
+    /// Covariance matrix of the vertex position (in `x`, `y`, `z` order). This
+    /// is `None` if the tracks do not constrain the vertex position along all
+    /// directions (e.g. all tracks are parallel).
+    pub covariance: Option<[[Area; 3]; 3]>,
+    /// Chi-squared of the vertex fit i.e. the sum of squared distances of
+    /// closest approach between the vertex and each track, relative to the
+    /// expected track resolution.
+    pub chi2: f64,
+    /// Number of degrees of freedom of the vertex fit. Each track constrains
+    /// the vertex along the 2 directions perpendicular to it (plus 2 from the
+    /// beam spot constraint, if any), minus the 3 fitted coordinates.
+    pub ndf: usize,
+    /// Probability of getting a chi-squared larger than [`VertexInfo::chi2`]
+    /// by chance (given [`VertexInfo::ndf`]). This is `1.0` if there are no
+    /// degrees of freedom.
+    pub probability: f64,
     /// [`Track`]s associated to the vertex. Each track is paired with the value
     /// of `t` at which it is closest to the vertex.
     pub tracks: Vec<(Track, f64)>,
+    /// Weight (between `0` and `1`) of each track in
+    /// [`VertexInfo::tracks`] (same order), given its contribution to the
+    /// chi-squared. Tracks with a contribution equal to
+    /// [`VertexFitConfig::max_track_chi2`] have a weight of `0.5`, and the
+    /// weight quickly goes to `0` (`1`) for larger (smaller) contributions.
+    pub weights: Vec<f64>,
+}
+
+impl VertexInfo {
+    /// Return `true` if at least one of the tracks associated to the vertex is
+    /// incompatible with it (i.e. has a weight smaller than `0.5`). Such a
+    /// vertex was most likely formed from tracks that do not come from a
+    /// common point.
+    pub fn has_incompatible_tracks(&self) -> bool {
+        self.weights.iter().any(|&weight| weight < 0.5)
+    }
+    /// Estimate the resolution of the vertex position from the covariance of
+    /// each of its tracks (see [`Track::covariance`]) and their geometry.
+    ///
+    /// Unlike [`VertexInfo::covariance`], which assumes the same
+    /// [`VertexFitConfig::track_resolution`] for all tracks, each track
+    /// constrains the vertex along the 2 directions perpendicular to it with
+    /// its own uncertainty (extrapolated from the perigee to the vertex), and
+    /// scaled by its weight in [`VertexInfo::weights`]. Then, e.g. a vertex
+    /// made of tracks at a small opening angle, or of poorly measured tracks,
+    /// has a large resolution.
+    ///
+    /// The beam spot constraint (if any) is not included. Returns [`None`] if
+    /// there are less than 2 tracks, or if any track has no covariance.
+    pub fn resolution(&self) -> Option<VertexResolution> {
+        // A single track never constrains the vertex along its direction.
+        if self.tracks.len() < 2 {
+            return None;
+        }
+        let mut information = [[0.0; 3]; 3];
+        for ((track, _), weight) in self.tracks.iter().zip(&self.weights) {
+            // Parameters are in (d0, z0, phi0, curvature, tan_lambda) order.
+            let cov = track.covariance()?;
+            let perigee = track.perigee();
+            let start = perigee.position();
+            let lever_arm = (self.position.x - start.x)
+                .hypot(self.position.y - start.y)
+                .get::<meter>();
+            let [ux, uy, uz] = perigee.direction();
+            let (sin, cos) = perigee.phi0.get::<radian>().sin_cos();
+            // Transverse direction perpendicular to the track. An uncertainty
+            // in `d0` moves the track along this direction.
+            let transverse = [-sin, cos, 0.0];
+            let transverse_var = cov[0][0] + lever_arm.powi(2) * cov[2][2];
+            // Component of the `z` axis perpendicular to the track. An
+            // uncertainty in `z0` moves the track along `z`, but only this
+            // component matters.
+            let axial_norm = (1.0 - uz.powi(2)).sqrt();
+            let axial = [-uz * ux, -uz * uy, 1.0 - uz.powi(2)].map(|v| v / axial_norm);
+            let axial_var = (cov[1][1] + lever_arm.powi(2) * cov[4][4]) * axial_norm.powi(2);
+
+            for (direction, variance) in [(transverse, transverse_var), (axial, axial_var)] {
+                if variance <= 0.0 {
+                    return None;
+                }
+                for (row, d_i) in information.iter_mut().zip(direction) {
+                    for (value, d_j) in row.iter_mut().zip(direction) {
+                        *value += weight * d_i * d_j / variance;
+                    }
+                }
+            }
+        }
+
+        let covariance = matrix::inverse_3x3(&information)
+            .filter(|inverse| (0..3).all(|i| inverse[i][i] > 0.0))?
+            .map(|row| row.map(Area::new::<square_meter>));
+
+        Some(VertexResolution {
+            covariance,
+            sigma_x: covariance[0][0].sqrt(),
+            sigma_y: covariance[1][1].sqrt(),
+            sigma_z: covariance[2][2].sqrt(),
+        })
+    }
 }
 
 /// Result of reconstructing the vertices of an event from a set of [`Track`]s.
@@ -320,41 +1619,59 @@ pub struct VertexInfo {
 pub struct VertexingResult {
     /// Primary signal vertex.
     pub primary: Option<VertexInfo>,
-    /// Secondary vertices.
+    /// Secondary vertices (e.g. pileup) ranked in the same way as the primary
+    /// vertex i.e. from the most to the least likely to be the signal vertex.
     pub secondaries: Vec<VertexInfo>,
     /// Remaining [`Track`]s that were not associated to any vertex.
     pub remainder: Vec<Track>,
 }
 
+impl VertexingResult {
+    /// Return an iterator over all the vertices ranked from the most to the
+    /// least likely to be the signal vertex (i.e. the primary vertex first,
+    /// followed by the secondary vertices).
+    pub fn vertices(&self) -> impl Iterator<Item = &VertexInfo> {
+        self.primary.iter().chain(&self.secondaries)
+    }
+}
+
 /// Given a collection of [`Track`]s, reconstruct the vertices of an event.
+///
+/// Tracks are grouped by the axial position of their closest approach to the
+/// beamline, and a vertex is fitted to each group with enough tracks. Vertices
+/// are ranked by their number of tracks; the best one is the primary vertex
+/// and all others are secondary vertices (e.g. pileup of an annihilation and a
+/// cosmic ray).
 pub fn find_vertices(tracks: Vec<Track>) -> VertexingResult {
+    find_vertices_with_config(tracks, &ReconstructionConfig::default())
+}
+
+/// Same as [`find_vertices`], but with an arbitrary [`ReconstructionConfig`].
+pub fn find_vertices_with_config(
+    tracks: Vec<Track>,
+    config: &ReconstructionConfig,
+) -> VertexingResult {
+    let config = &config.vertex_fit;
     vertex_fitting::find_vertices(
         tracks,
-        // Minimum track length to be considered for vertexing.
-        Length::new::<centimeter>(3.5),
-        // Maximum distance of closest approach to the beamline to be considered
-        // for primary vertex seed.
-        Length::new::<centimeter>(5.3),
-        // Maximum clustering distance along the beamline for primary vertex
-        // cluster.
-        Length::new::<centimeter>(3.4),
-        // Delta from the initial guess for each initial simplex vertex.
-        // I just stuck to the default value used by scipy's implementation
-        // of Nelder-Mead. It has worked well.
-        // See:
-        // https://github.com/scipy/scipy/blob/v1.11.2/scipy/optimize/_optimize.py#L833
-        0.05,
-        // Maximum number of iterations to find the closest point on the
-        // helix given a SpacePoint.
-        20,
-        // Tolerance for finding the `t` parameter of the closest point on
-        // the helix given a SpacePoint.
-        f64::EPSILON,
-        // Maximum number of Nelder-Mead iterations per vertex distance
-        // minimization.
-        100,
-        // Nelder-Mead standard deviation tolerance.
-        f64::EPSILON,
+        config.min_track_length,
+        config.max_beamline_distance,
+        config.max_axial_distance,
+        config.initial_simplex_delta,
+        config.max_num_closest_t_iter,
+        config.closest_t_tolerance,
+        config.max_num_iter,
+        config.sd_tolerance,
+        config.track_resolution,
+        config.hessian_step,
+        config.max_track_chi2,
+        config
+            .beam_spot
+            .map(|beam_spot| (beam_spot.sigma_x, beam_spot.sigma_y)),
+        config.max_num_vertices,
+        config
+            .annealing
+            .map(|annealing| (annealing.initial_temperature, annealing.ratio)),
     )
 }
 
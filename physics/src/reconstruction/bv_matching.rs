@@ -0,0 +1,97 @@
+use crate::bv::{BvHit, BAR_RADIUS, BV_BARS};
+use crate::reconstruction::{BvTrackMatch, Coordinate, Track};
+use std::f64::consts::TAU;
+use uom::si::angle::radian;
+use uom::si::f64::Length;
+use uom::si::length::meter;
+use uom::si::time::second;
+
+// Extrapolate each track to the radius of the Barrel Veto, and match each
+// crossing to the hit (in the same or a neighboring bar) with the closest `z`.
+//
+// The helix of an annihilation product crosses the Barrel Veto radius twice,
+// but only one of these is physical: the first crossing after leaving the rTPC
+// through the outer cathode. Cosmic rays go straight through the whole
+// detector, hence both crossings are physical.
+//
+// Tracks are matched independently, i.e. the same hit can be matched to
+// multiple tracks.
+pub(crate) fn match_bv_hits(
+    tracks: &[Track],
+    hits: &[BvHit],
+    max_bar_distance: usize,
+    max_z_distance: Length,
+) -> Vec<BvTrackMatch> {
+    tracks
+        .iter()
+        .map(|track| {
+            let mut crossings = track.helix.t_at_radius(BAR_RADIUS);
+            if !track.is_cosmic() {
+                // `t` increases or decreases when going outwards depending on
+                // the direction of rotation.
+                let direction = if track.t_outer >= track.t_inner {
+                    1.0
+                } else {
+                    -1.0
+                };
+                let distance = |t: f64| ((t - track.t_outer) * direction).rem_euclid(TAU);
+                crossings = crossings
+                    .into_iter()
+                    .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+                    .into_iter()
+                    .collect();
+            }
+
+            let matched: Vec<_> = crossings
+                .into_iter()
+                .filter_map(|t| {
+                    closest_hit(track.helix.at(t), hits, max_bar_distance, max_z_distance)
+                        .map(|hit| (t, hit))
+                })
+                .collect();
+            let (tof, path_length) = match matched[..] {
+                [(t1, hit1), (t2, hit2)] => (
+                    Some((hit2.t - hit1.t).abs()),
+                    Some(track.helix.arc_length(t1, t2)),
+                ),
+                _ => (None, None),
+            };
+
+            let mut hits: Vec<_> = matched.into_iter().map(|(_, hit)| hit).collect();
+            hits.sort_by(|a, b| a.t.get::<second>().total_cmp(&b.t.get::<second>()));
+
+            BvTrackMatch {
+                hits,
+                tof,
+                path_length,
+            }
+        })
+        .collect()
+}
+
+// Find the hit closest (in `z`) to a coordinate at the radius of the Barrel
+// Veto.
+fn closest_hit(
+    c: Coordinate,
+    hits: &[BvHit],
+    max_bar_distance: usize,
+    max_z_distance: Length,
+) -> Option<BvHit> {
+    let bar_pitch = TAU / BV_BARS as f64;
+    let bar = (c.y.atan2(c.x).get::<radian>() / bar_pitch)
+        .round()
+        .rem_euclid(BV_BARS as f64) as usize;
+
+    hits.iter()
+        .filter(|hit| {
+            let diff = usize::from(hit.bar).abs_diff(bar);
+            diff.min(BV_BARS - diff) <= max_bar_distance
+        })
+        .filter(|hit| (hit.z - c.z).abs() <= max_z_distance)
+        .min_by(|a, b| {
+            let dz_a = (a.z - c.z).abs();
+            let dz_b = (b.z - c.z).abs();
+            dz_a.get::<meter>().total_cmp(&dz_b.get::<meter>())
+        })
+        .copied()
+}
@@ -0,0 +1,627 @@
+use alpha_g_detector::padwing::map::PAD_PITCH_PHI;
+use alpha_g_detector::padwing::PWB_RATE;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::FRAC_PI_2;
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+use uom::si::angle::radian;
+use uom::si::f64::{Angle, Length, Time};
+use uom::si::length::{centimeter, meter, millimeter};
+use uom::si::time::{nanosecond, second};
+
+/// The error type returned when loading a [`ReconstructionConfig`] fails.
+#[derive(Debug, Error)]
+pub enum LoadReconstructionConfigError {
+    /// Error reading the file.
+    #[error("io error")]
+    IoError(#[from] std::io::Error),
+    /// The contents are not a valid TOML reconstruction configuration.
+    #[error("bad reconstruction config format")]
+    BadFormat(#[from] toml::de::Error),
+    /// The minimum number of points per cluster is less than 3.
+    #[error("too few points per cluster (expected at least 3, found `{0}`)")]
+    TooFewPointsPerCluster(usize),
+    /// A fraction of wire amplitudes used for dE/dx is not in `(0, 1]`.
+    #[error("bad dE/dx kept fraction `{0}` (expected a value in (0, 1])")]
+    BadDedxKeptFraction(f64),
+    /// A width of the beam spot constraint is not positive.
+    #[error("bad beam spot width `{0:?}` (expected a positive value)")]
+    BadBeamSpotWidth(Length),
+    /// The annealing schedule of the adaptive vertex fit never reaches unit
+    /// temperature.
+    #[error("bad annealing schedule `{0:?}` (expected an initial temperature of at least 1 and a ratio in (0, 1))")]
+    BadAnnealingSchedule(AnnealingSchedule),
+    /// The pad charge integration window does not have a finite positive
+    /// length or a finite start offset.
+    #[error(
+        "bad pad integration window `{0:?}` (expected a finite start offset and a positive length)"
+    )]
+    BadIntegrationWindow(IntegrationWindow),
+}
+
+/// Algorithm used to cluster [`SpacePoint`](crate::SpacePoint)s.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusteringAlgorithm {
+    /// Find lines in the Hough space of the conformal mapping of the `x-y`
+    /// plane. This assumes that tracks originate close to the beamline.
+    #[default]
+    Hough,
+    /// Density-based clustering (DBSCAN). This makes no assumptions about the
+    /// shape or origin of the tracks, which helps with short tracks far from
+    /// the beamline.
+    Dbscan,
+    /// Density-based clustering (same as [`ClusteringAlgorithm::Dbscan`]) in
+    /// the `z-phi` plane of [`SpacePoint`](crate::SpacePoint)s reconstructed
+    /// only from the pad signals. This is meant for runs in which several
+    /// anode wire preamps were dead.
+    ///
+    /// The full reconstruction of a [`MainEvent`](crate::MainEvent) uses
+    /// [`MainEvent::pad_avalanches`](crate::MainEvent::pad_avalanches) with
+    /// this algorithm.
+    PadOnly,
+}
+
+/// Floating point precision used to vote in Hough space (see
+/// [`ClusteringAlgorithm::Hough`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoughPrecision {
+    /// Double precision (`f64`).
+    #[default]
+    Double,
+    /// Single precision (`f32`). This only changes the vote of
+    /// [`SpacePoint`](crate::SpacePoint)s that are (almost exactly) at the edge
+    /// of a bin, and it is noticeably faster when processing a large number of
+    /// events.
+    Single,
+}
+
+/// Parameters used to cluster [`SpacePoint`](crate::SpacePoint)s into
+/// potential tracks.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClusteringConfig {
+    /// Clustering algorithm.
+    pub algorithm: ClusteringAlgorithm,
+    /// Minimum number of points per cluster. Track fitting fails for all
+    /// clusters with less than 3 points.
+    pub min_num_points_per_cluster: usize,
+    /// Maximum number of clusters per event. There is no limit if `None`.
+    pub max_num_clusters: Option<usize>,
+    /// Number of bins along `rho` in Hough space.
+    pub rho_bins: u32,
+    /// Number of bins along `theta` in Hough space.
+    pub theta_bins: u32,
+    /// Choose the number of bins in Hough space for each event, from
+    /// `rho_bins / 4` (and `theta_bins / 4`) up to 4 times `rho_bins` (and
+    /// `theta_bins`). The binning is refined for as long as the most popular
+    /// bin has at least `min_num_points_per_cluster` points i.e. busy events
+    /// get finer bins than sparse events.
+    pub adaptive_hough_bins: bool,
+    /// Floating point precision used to vote in Hough space.
+    pub hough_precision: HoughPrecision,
+    /// Minimum number of points (including itself) within
+    /// `max_neighbor_distance` for a point to be a core point in DBSCAN.
+    pub min_num_neighbors: usize,
+    /// Maximum distance between neighboring points in the same cluster.
+    pub max_neighbor_distance: Length,
+    /// Maximum distance along `z` between neighboring points in the same
+    /// cluster. If set, [`ClusteringConfig::max_neighbor_distance`] only
+    /// applies to the transverse (`x-y`) distance, and two points are neighbors
+    /// if `(dt / max_neighbor_distance)^2 + (dz / max_neighbor_distance_z)^2 <=
+    /// 1`. Otherwise, the same (Euclidean) distance cut applies along all
+    /// directions.
+    pub max_neighbor_distance_z: Option<Length>,
+    /// Maximum azimuthal distance between neighboring points in the same
+    /// cluster with [`ClusteringAlgorithm::PadOnly`]. Along `z`, the same cut
+    /// as with the other algorithms applies.
+    pub max_neighbor_phi: Angle,
+}
+
+impl Default for ClusteringConfig {
+    fn default() -> Self {
+        Self {
+            // We need at least 3 points to get an accurate initial guess for
+            // the helix through a cluster.
+            algorithm: ClusteringAlgorithm::Hough,
+            min_num_points_per_cluster: 13,
+            max_num_clusters: None,
+            rho_bins: 250,
+            theta_bins: 230,
+            adaptive_hough_bins: false,
+            hough_precision: HoughPrecision::Double,
+            min_num_neighbors: 3,
+            max_neighbor_distance: Length::new::<centimeter>(3.0),
+            max_neighbor_distance_z: None,
+            // Points in adjacent pad columns, even after the (small) Lorentz
+            // angle correction.
+            max_neighbor_phi: Angle::new::<radian>(1.5 * PAD_PITCH_PHI),
+        }
+    }
+}
+
+/// Algorithm used to fit a [`Cluster`](crate::reconstruction::Cluster) to a
+/// [`Track`](crate::reconstruction::Track).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackFitAlgorithm {
+    /// Global least-squares fit of a helix to all the
+    /// [`SpacePoint`](crate::SpacePoint)s.
+    #[default]
+    LeastSquares,
+    /// Kalman filter (see
+    /// [`fit_track_kalman`](crate::reconstruction::fit_track_kalman)). The
+    /// magnetic field is assumed to be uniform and equal to
+    /// [`NOMINAL_FIELD`](crate::field::NOMINAL_FIELD).
+    Kalman,
+}
+
+/// Parameters used by the least-squares track fit.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackFitConfig {
+    /// Track fit algorithm. The parameters of the Kalman filter are in
+    /// [`KalmanFitConfig`].
+    pub algorithm: TrackFitAlgorithm,
+    /// Maximum number of Nelder-Mead iterations.
+    pub max_num_iter: u64,
+    /// Nelder-Mead standard deviation tolerance.
+    pub sd_tolerance: f64,
+    /// Relative change from the initial guess for each simplex vertex.
+    pub initial_simplex_delta: f64,
+    /// Maximum number of iterations to find the closest point on the track
+    /// given a [`SpacePoint`](crate::SpacePoint).
+    pub max_num_closest_t_iter: usize,
+    /// Tolerance for finding the `t` parameter of the closest point on the
+    /// track given a [`SpacePoint`](crate::SpacePoint).
+    pub closest_t_tolerance: f64,
+    /// Fraction of the lowest wire amplitudes used to calculate the truncated
+    /// mean dE/dx.
+    pub dedx_kept_fraction: f64,
+    /// Step size used to numerically calculate the Hessian of the chi-squared
+    /// with respect to the [`Perigee`](crate::reconstruction::Perigee)
+    /// parameters (used to get the track covariance). The same step is used
+    /// for all parameters in SI units (i.e. meter, radian, and 1/meter).
+    pub hessian_step: f64,
+    /// Maximum number of random triplets of
+    /// [`SpacePoint`](crate::SpacePoint)s tried for the initial guess of the
+    /// fit when the default triplet does not define a circle (e.g. collinear
+    /// or repeated points). See [`ReconstructionConfig::seed`].
+    pub max_num_resample: usize,
+}
+
+impl Default for TrackFitConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: TrackFitAlgorithm::LeastSquares,
+            max_num_iter: 100,
+            sd_tolerance: f64::EPSILON,
+            // I just stuck to the default value used by scipy's implementation
+            // of Nelder-Mead. It has worked well.
+            // See:
+            // https://github.com/scipy/scipy/blob/v1.11.2/scipy/optimize/_optimize.py#L833
+            initial_simplex_delta: 0.05,
+            max_num_closest_t_iter: 20,
+            closest_t_tolerance: f64::EPSILON,
+            dedx_kept_fraction: 0.7,
+            // Small compared to the track resolution (and the typical
+            // curvature), but large enough for the chi-squared differences to
+            // be well above the numerical noise.
+            hessian_step: 1e-5,
+            max_num_resample: 10,
+        }
+    }
+}
+
+/// Parameters used by the Kalman filter track fit.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KalmanFitConfig {
+    /// Radiation length of the rTPC gas.
+    pub radiation_length: Length,
+    /// Same as [`TrackFitConfig::max_num_closest_t_iter`].
+    pub max_num_closest_t_iter: usize,
+    /// Same as [`TrackFitConfig::closest_t_tolerance`].
+    pub closest_t_tolerance: f64,
+    /// Same as [`TrackFitConfig::dedx_kept_fraction`].
+    pub dedx_kept_fraction: f64,
+    /// Same as [`TrackFitConfig::max_num_resample`].
+    pub max_num_resample: usize,
+}
+
+impl Default for KalmanFitConfig {
+    fn default() -> Self {
+        Self {
+            // Ar/CO2 (70/30) at atmospheric pressure.
+            radiation_length: Length::new::<meter>(134.0),
+            max_num_closest_t_iter: 20,
+            closest_t_tolerance: f64::EPSILON,
+            dedx_kept_fraction: 0.7,
+            max_num_resample: 10,
+        }
+    }
+}
+
+/// Parameters used to merge [`Cluster`](crate::reconstruction::Cluster)s that
+/// belong to the same track.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackMergingConfig {
+    /// Maximum difference between the centers (and radii) of the two circles
+    /// in the `x-y` plane, relative to the largest of the two radii.
+    pub max_relative_difference: f64,
+    /// Maximum difference between the `dz/ds` of the two tracks, where `s` is
+    /// the arc length in the `x-y` plane.
+    pub max_slope_difference: f64,
+    /// Maximum RMS distance between the combined track and all its
+    /// [`SpacePoint`](crate::SpacePoint)s.
+    pub max_rms_residual: Length,
+}
+
+impl Default for TrackMergingConfig {
+    fn default() -> Self {
+        Self {
+            max_relative_difference: 0.2,
+            max_slope_difference: 0.2,
+            // A few times the resolution of a single SpacePoint.
+            max_rms_residual: Length::new::<millimeter>(5.0),
+        }
+    }
+}
+
+/// Parameters used to identify cosmic rays.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CosmicTaggingConfig {
+    /// Minimum radius of curvature (in the `x-y` plane) of a cosmic ray.
+    pub min_radius: Length,
+    /// Maximum RMS distance between the cosmic ray track and all its
+    /// [`SpacePoint`](crate::SpacePoint)s.
+    pub max_rms_residual: Length,
+}
+
+impl Default for CosmicTaggingConfig {
+    fn default() -> Self {
+        Self {
+            // Roughly a 450 MeV/c particle in the 1 T field. Annihilation
+            // products are typically below this.
+            min_radius: Length::new::<meter>(1.5),
+            max_rms_residual: Length::new::<millimeter>(5.0),
+        }
+    }
+}
+
+/// Parameters used to identify the [`SpacePoint`](crate::SpacePoint)s of low
+/// momentum spiraling electrons (e.g. delta rays).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpiralTaggingConfig {
+    /// Minimum number of points in a spiral.
+    pub min_num_points: usize,
+    /// Maximum distance between neighboring points in the same spiral.
+    pub max_neighbor_distance: Length,
+    /// Maximum radius of curvature (in the `x-y` plane) of a spiral.
+    pub max_radius: Length,
+    /// Maximum RMS distance (in the `x-y` plane) between the circle of a
+    /// spiral and all its points.
+    pub max_rms_residual: Length,
+    /// Maximum azimuthal gap (around the center of its circle) between
+    /// consecutive points of a spiral. Smaller values require a more complete
+    /// turn.
+    pub max_phase_gap: Angle,
+}
+
+impl Default for SpiralTaggingConfig {
+    fn default() -> Self {
+        Self {
+            min_num_points: 13,
+            max_neighbor_distance: Length::new::<centimeter>(1.5),
+            // A spiral needs to fit in the 8 cm between the inner and outer
+            // cathodes.
+            max_radius: Length::new::<centimeter>(4.0),
+            max_rms_residual: Length::new::<millimeter>(5.0),
+            max_phase_gap: Angle::new::<radian>(FRAC_PI_2),
+        }
+    }
+}
+
+/// Parameters used to classify the topology of a reconstructed event (see
+/// [`EventTopology`](crate::classification::EventTopology)).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TopologyConfig {
+    /// Maximum number of [`SpacePoint`](crate::SpacePoint)s in an event before
+    /// it is classified as a background flash.
+    pub max_num_spacepoints: usize,
+}
+
+impl Default for TopologyConfig {
+    fn default() -> Self {
+        Self {
+            // A single annihilation rarely leaves more than a few hundred
+            // SpacePoints. Flashes during mixing saturate the detector.
+            max_num_spacepoints: 2000,
+        }
+    }
+}
+
+/// Parameters used to match [`Track`](crate::reconstruction::Track)s to Barrel
+/// Veto hits.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BvMatchingConfig {
+    /// Maximum number of bars between the extrapolated track and a hit.
+    pub max_bar_distance: usize,
+    /// Maximum axial distance between the extrapolated track and a hit.
+    pub max_z_distance: Length,
+}
+
+impl Default for BvMatchingConfig {
+    fn default() -> Self {
+        Self {
+            // The extrapolation is good enough to point at the right bar, but
+            // a track can also cross the edge between two bars.
+            max_bar_distance: 1,
+            // Dominated by the BV timing resolution.
+            max_z_distance: Length::new::<centimeter>(30.0),
+        }
+    }
+}
+
+/// Gaussian constraint of a vertex position to the trap axis (`x = y = 0`).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BeamSpotConstraint {
+    /// Width of the beam spot along `x`.
+    pub sigma_x: Length,
+    /// Width of the beam spot along `y`.
+    pub sigma_y: Length,
+}
+
+/// Deterministic annealing schedule of an adaptive vertex fit.
+///
+/// The vertex is fitted repeatedly at decreasing temperatures
+/// `initial_temperature * ratio^i` (down to `1`). Before each fit, every track
+/// is weighted by `exp(-chi2 / 2T) / (exp(-chi2 / 2T) + exp(-max_track_chi2 /
+/// 2T))` given its chi-squared contribution to the previous vertex. Outlier
+/// tracks (e.g. a cosmic ray crossing close to an annihilation vertex) are
+/// smoothly down-weighted instead of pulling the vertex towards them.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnnealingSchedule {
+    /// Temperature of the first re-weighting. This has to be at least `1`.
+    pub initial_temperature: f64,
+    /// Ratio between consecutive temperatures. This has to be in `(0, 1)`.
+    pub ratio: f64,
+}
+
+impl Default for AnnealingSchedule {
+    fn default() -> Self {
+        // Common choice of adaptive vertex fitters in high energy physics.
+        Self {
+            initial_temperature: 256.0,
+            ratio: 0.25,
+        }
+    }
+}
+
+/// Time window used to integrate the charge of a pad pulse.
+///
+/// The optimal window depends on the running conditions e.g. a short window
+/// reduces the contribution of pile-up in high rate runs, whereas a long
+/// window collects all the charge of each pulse in low rate runs.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IntegrationWindow {
+    /// Start of the window relative to the time of the pulse. A negative offset
+    /// starts the window before the pulse.
+    pub start_offset: Time,
+    /// Length of the window. This has to be positive.
+    pub length: Time,
+}
+
+impl Default for IntegrationWindow {
+    fn default() -> Self {
+        // 2 samples before and 4 samples after the pulse.
+        Self {
+            start_offset: Time::new::<nanosecond>(-32.0),
+            length: Time::new::<nanosecond>(112.0),
+        }
+    }
+}
+
+impl IntegrationWindow {
+    /// Return the charge of a pulse at sample `t` of a deconvolved pad
+    /// `signal` (e.g. the output of
+    /// [`pad_amplifier_deconvolution`](crate::signal_processing::pad_amplifier_deconvolution))
+    /// i.e. the sum of all the samples within the window. Samples outside of
+    /// the signal are zero. The window is rounded to whole samples, and it is
+    /// never shorter than a single sample.
+    ///
+    /// This is the pad amplitude of each [`Avalanche`](crate::Avalanche) when
+    /// the window is set in [`ReconstructionConfig::pad_integration_window`].
+    pub fn integrate(&self, signal: &[f64], t: usize) -> f64 {
+        let first = t as isize + (self.start_offset.get::<second>() * PWB_RATE).round() as isize;
+        let length = ((self.length.get::<second>() * PWB_RATE).round() as isize).max(1);
+
+        (first..first + length)
+            .filter_map(|i| usize::try_from(i).ok())
+            .filter_map(|i| signal.get(i))
+            .sum()
+    }
+}
+
+/// Parameters used to find and fit vertices.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VertexFitConfig {
+    /// Minimum track length to be considered for vertexing.
+    pub min_track_length: Length,
+    /// Maximum distance of closest approach to the beamline to be considered
+    /// for the primary vertex seed.
+    pub max_beamline_distance: Length,
+    /// Maximum clustering distance along the beamline for the primary vertex
+    /// cluster.
+    pub max_axial_distance: Length,
+    /// Relative change from the initial guess for each simplex vertex.
+    pub initial_simplex_delta: f64,
+    /// Same as [`TrackFitConfig::max_num_closest_t_iter`].
+    pub max_num_closest_t_iter: usize,
+    /// Same as [`TrackFitConfig::closest_t_tolerance`].
+    pub closest_t_tolerance: f64,
+    /// Maximum number of Nelder-Mead iterations per vertex distance
+    /// minimization.
+    pub max_num_iter: u64,
+    /// Nelder-Mead standard deviation tolerance.
+    pub sd_tolerance: f64,
+    /// Expected distance of closest approach between a track and the true
+    /// vertex (resolution of a track extrapolated to the beamline).
+    pub track_resolution: Length,
+    /// Step size to numerically calculate the Hessian of the cost function
+    /// (used to get the vertex covariance).
+    pub hessian_step: Length,
+    /// Contribution of a single track to the vertex chi-squared above which
+    /// the track is flagged as incompatible with the vertex.
+    pub max_track_chi2: f64,
+    /// Maximum number of vertices (primary plus secondaries) per event. There
+    /// is no limit if `None`.
+    pub max_num_vertices: Option<usize>,
+    /// Constrain the vertex to the trap axis. This allows fitting a vertex
+    /// with a single track. There is no constraint if `None`.
+    pub beam_spot: Option<BeamSpotConstraint>,
+    /// Fit the vertex with an adaptive fitter. All tracks have the same weight
+    /// if `None`.
+    pub annealing: Option<AnnealingSchedule>,
+}
+
+impl Default for VertexFitConfig {
+    fn default() -> Self {
+        Self {
+            min_track_length: Length::new::<centimeter>(3.5),
+            max_beamline_distance: Length::new::<centimeter>(5.3),
+            max_axial_distance: Length::new::<centimeter>(3.4),
+            // Same as in the track fit.
+            initial_simplex_delta: 0.05,
+            max_num_closest_t_iter: 20,
+            closest_t_tolerance: f64::EPSILON,
+            max_num_iter: 100,
+            sd_tolerance: f64::EPSILON,
+            track_resolution: Length::new::<millimeter>(15.0),
+            hessian_step: Length::new::<millimeter>(0.1),
+            // A 3 sigma distance of closest approach.
+            max_track_chi2: 9.0,
+            max_num_vertices: None,
+            beam_spot: None,
+            annealing: None,
+        }
+    }
+}
+
+/// Tunable parameters of the full reconstruction pipeline.
+///
+/// The [`Default`] configuration is the one used by e.g.
+/// [`cluster_spacepoints`](crate::reconstruction::cluster_spacepoints) and
+/// [`find_vertices`](crate::reconstruction::find_vertices). A custom
+/// configuration can be loaded from a TOML file. Missing fields take their
+/// default value, and all lengths are in meters:
+///
+/// ```toml
+/// seed = 42
+///
+/// [clustering]
+/// min_num_points_per_cluster = 10
+/// max_num_clusters = 8
+/// max_neighbor_distance = 0.025
+///
+/// [vertex_fit]
+/// track_resolution = 0.01
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReconstructionConfig {
+    /// Seed of the random number generator used by any step that resamples
+    /// [`SpacePoint`](crate::SpacePoint)s (e.g. the initial guess of the track
+    /// fits). Reconstructing the same event with the same seed always gives
+    /// the same result.
+    pub seed: u64,
+    pub clustering: ClusteringConfig,
+    pub track_fit: TrackFitConfig,
+    pub kalman_fit: KalmanFitConfig,
+    pub track_merging: TrackMergingConfig,
+    pub cosmic_tagging: CosmicTaggingConfig,
+    pub spiral_tagging: SpiralTaggingConfig,
+    pub vertex_fit: VertexFitConfig,
+    pub bv_matching: BvMatchingConfig,
+    pub topology: TopologyConfig,
+    /// Window used to integrate the charge of each pad hit (i.e. the pad
+    /// amplitude of each [`Avalanche`](crate::Avalanche)). `None` uses the
+    /// deconvolved charge at the time of the hit.
+    pub pad_integration_window: Option<IntegrationWindow>,
+}
+
+impl FromStr for ReconstructionConfig {
+    type Err = LoadReconstructionConfigError;
+
+    /// Parse a reconstruction configuration from the contents of a TOML file.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let config: Self = toml::from_str(s)?;
+
+        if config.clustering.min_num_points_per_cluster < 3 {
+            // Track fitting fails with less than 3 points.
+            return Err(LoadReconstructionConfigError::TooFewPointsPerCluster(
+                config.clustering.min_num_points_per_cluster,
+            ));
+        }
+        for fraction in [
+            config.track_fit.dedx_kept_fraction,
+            config.kalman_fit.dedx_kept_fraction,
+        ] {
+            if fraction.is_nan() || fraction <= 0.0 || fraction > 1.0 {
+                return Err(LoadReconstructionConfigError::BadDedxKeptFraction(fraction));
+            }
+        }
+
+        if let Some(beam_spot) = config.vertex_fit.beam_spot {
+            for sigma in [beam_spot.sigma_x, beam_spot.sigma_y] {
+                if sigma.value.is_nan() || sigma.value <= 0.0 {
+                    return Err(LoadReconstructionConfigError::BadBeamSpotWidth(sigma));
+                }
+            }
+        }
+        if let Some(annealing) = config.vertex_fit.annealing {
+            if !(annealing.initial_temperature >= 1.0
+                && annealing.initial_temperature.is_finite()
+                && annealing.ratio > 0.0
+                && annealing.ratio < 1.0)
+            {
+                return Err(LoadReconstructionConfigError::BadAnnealingSchedule(
+                    annealing,
+                ));
+            }
+        }
+        if let Some(window) = config.pad_integration_window {
+            if !(window.start_offset.value.is_finite()
+                && window.length.value.is_finite()
+                && window.length.value > 0.0)
+            {
+                return Err(LoadReconstructionConfigError::BadIntegrationWindow(window));
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+impl ReconstructionConfig {
+    /// Read and parse a reconstruction configuration from a TOML file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, LoadReconstructionConfigError> {
+        let contents = std::fs::read_to_string(path)?;
+
+        contents.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,231 @@
+use super::*;
+
+#[test]
+fn empty_reconstruction_config() {
+    let config: ReconstructionConfig = "".parse().unwrap();
+
+    assert_eq!(config, ReconstructionConfig::default());
+}
+
+#[test]
+fn partial_reconstruction_config() {
+    let contents = r#"
+[clustering]
+min_num_points_per_cluster = 10
+max_num_clusters = 8
+max_neighbor_distance = 0.025
+
+[vertex_fit]
+track_resolution = 0.01
+"#;
+    let config: ReconstructionConfig = contents.parse().unwrap();
+
+    let mut expected = ReconstructionConfig::default();
+    expected.clustering.min_num_points_per_cluster = 10;
+    expected.clustering.max_num_clusters = Some(8);
+    expected.clustering.max_neighbor_distance = Length::new::<centimeter>(2.5);
+    expected.vertex_fit.track_resolution = Length::new::<millimeter>(10.0);
+
+    assert_eq!(config.clustering.min_num_points_per_cluster, 10);
+    assert_eq!(config.clustering.max_num_clusters, Some(8));
+    let diff = config.clustering.max_neighbor_distance - expected.clustering.max_neighbor_distance;
+    assert!(diff.abs() < Length::new::<meter>(1e-12));
+    let diff = config.vertex_fit.track_resolution - expected.vertex_fit.track_resolution;
+    assert!(diff.abs() < Length::new::<meter>(1e-12));
+    assert_eq!(config.track_fit, expected.track_fit);
+    assert_eq!(config.kalman_fit, expected.kalman_fit);
+}
+
+#[test]
+fn default_reconstruction_config_round_trip() {
+    let config = ReconstructionConfig::default();
+    let contents = toml::to_string(&config).unwrap();
+
+    assert_eq!(contents.parse::<ReconstructionConfig>().unwrap(), config);
+}
+
+#[test]
+fn unknown_field_reconstruction_config() {
+    let contents = r#"
+[clustering]
+min_num_point_per_cluster = 10
+"#;
+
+    assert!(matches!(
+        contents.parse::<ReconstructionConfig>(),
+        Err(LoadReconstructionConfigError::BadFormat(_))
+    ));
+}
+
+#[test]
+fn too_few_points_reconstruction_config() {
+    let contents = r#"
+[clustering]
+min_num_points_per_cluster = 2
+"#;
+
+    assert!(matches!(
+        contents.parse::<ReconstructionConfig>(),
+        Err(LoadReconstructionConfigError::TooFewPointsPerCluster(2))
+    ));
+}
+
+#[test]
+fn bad_dedx_fraction_reconstruction_config() {
+    for fraction in ["0.0", "1.5", "-0.5", "nan"] {
+        let contents = format!("[kalman_fit]\ndedx_kept_fraction = {fraction}");
+
+        assert!(matches!(
+            contents.parse::<ReconstructionConfig>(),
+            Err(LoadReconstructionConfigError::BadDedxKeptFraction(_))
+        ));
+    }
+}
+
+#[test]
+fn clustering_algorithm_reconstruction_config() {
+    let contents = r#"
+[clustering]
+algorithm = "dbscan"
+"#;
+    let config: ReconstructionConfig = contents.parse().unwrap();
+
+    assert_eq!(config.clustering.algorithm, ClusteringAlgorithm::Dbscan);
+
+    let contents = r#"
+[clustering]
+algorithm = "k_means"
+"#;
+
+    assert!(matches!(
+        contents.parse::<ReconstructionConfig>(),
+        Err(LoadReconstructionConfigError::BadFormat(_))
+    ));
+}
+
+#[test]
+fn beam_spot_reconstruction_config() {
+    let contents = r#"
+[vertex_fit.beam_spot]
+sigma_x = 0.003
+sigma_y = 0.004
+"#;
+    let config: ReconstructionConfig = contents.parse().unwrap();
+    let beam_spot = config.vertex_fit.beam_spot.unwrap();
+
+    let diff = beam_spot.sigma_x - Length::new::<millimeter>(3.0);
+    assert!(diff.abs() < Length::new::<meter>(1e-12));
+    let diff = beam_spot.sigma_y - Length::new::<millimeter>(4.0);
+    assert!(diff.abs() < Length::new::<meter>(1e-12));
+
+    let contents = r#"
+[vertex_fit.beam_spot]
+sigma_x = 0.003
+sigma_y = 0.0
+"#;
+
+    assert!(matches!(
+        contents.parse::<ReconstructionConfig>(),
+        Err(LoadReconstructionConfigError::BadBeamSpotWidth(_))
+    ));
+}
+
+#[test]
+fn annealing_reconstruction_config() {
+    let contents = r#"
+[vertex_fit.annealing]
+initial_temperature = 64.0
+ratio = 0.5
+"#;
+    let config: ReconstructionConfig = contents.parse().unwrap();
+    assert_eq!(
+        config.vertex_fit.annealing,
+        Some(AnnealingSchedule {
+            initial_temperature: 64.0,
+            ratio: 0.5,
+        })
+    );
+
+    let contents = r#"
+[vertex_fit.annealing]
+initial_temperature = 64.0
+ratio = 1.0
+"#;
+
+    assert!(matches!(
+        contents.parse::<ReconstructionConfig>(),
+        Err(LoadReconstructionConfigError::BadAnnealingSchedule(_))
+    ));
+}
+
+#[test]
+fn pad_integration_window_reconstruction_config() {
+    let contents = r#"
+[pad_integration_window]
+start_offset = -16e-9
+length = 64e-9
+"#;
+    let config: ReconstructionConfig = contents.parse().unwrap();
+    let window = config.pad_integration_window.unwrap();
+
+    let diff = window.start_offset - Time::new::<nanosecond>(-16.0);
+    assert!(diff.abs() < Time::new::<second>(1e-15));
+    let diff = window.length - Time::new::<nanosecond>(64.0);
+    assert!(diff.abs() < Time::new::<second>(1e-15));
+
+    let contents = r#"
+[pad_integration_window]
+start_offset = 0.0
+length = 0.0
+"#;
+
+    assert!(matches!(
+        contents.parse::<ReconstructionConfig>(),
+        Err(LoadReconstructionConfigError::BadIntegrationWindow(_))
+    ));
+}
+
+#[test]
+fn integration_window_charge() {
+    let signal = [1.0, 2.0, 4.0, 8.0, 16.0, 32.0];
+    // One sample before and two samples after the pulse.
+    let window = IntegrationWindow {
+        start_offset: Time::new::<nanosecond>(-16.0),
+        length: Time::new::<nanosecond>(64.0),
+    };
+
+    assert_eq!(window.integrate(&signal, 2), 2.0 + 4.0 + 8.0 + 16.0);
+    // Samples outside of the signal are zero.
+    assert_eq!(window.integrate(&signal, 0), 1.0 + 2.0 + 4.0);
+    assert_eq!(window.integrate(&signal, 5), 16.0 + 32.0);
+
+    let window = IntegrationWindow {
+        start_offset: Time::new::<nanosecond>(0.0),
+        length: Time::new::<nanosecond>(1.0),
+    };
+    assert_eq!(window.integrate(&signal, 3), 8.0);
+}
+
+#[test]
+fn track_fit_algorithm_reconstruction_config() {
+    let config: ReconstructionConfig = "".parse().unwrap();
+    assert_eq!(config.track_fit.algorithm, TrackFitAlgorithm::LeastSquares);
+
+    let contents = r#"
+[track_fit]
+algorithm = "kalman"
+"#;
+    let config: ReconstructionConfig = contents.parse().unwrap();
+
+    assert_eq!(config.track_fit.algorithm, TrackFitAlgorithm::Kalman);
+
+    let contents = r#"
+[track_fit]
+algorithm = "gauss_newton"
+"#;
+
+    assert!(matches!(
+        contents.parse::<ReconstructionConfig>(),
+        Err(LoadReconstructionConfigError::BadFormat(_))
+    ));
+}
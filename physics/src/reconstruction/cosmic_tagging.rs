@@ -0,0 +1,79 @@
+use crate::reconstruction::track_merging::{crosses_inner_cathode, rms_residual};
+use crate::reconstruction::{Cluster, CosmicTaggingResult, Track, TryTrackFromClusterError};
+use itertools::Itertools;
+use uom::si::f64::Length;
+
+// A cosmic ray goes straight through the detector, leaving two clusters on
+// opposite sides of the inner cathode. This is the same topology as two
+// annihilation tracks going in opposite directions from a vertex close to the
+// beamline. The difference is that a cosmic ray is a single (very high
+// momentum) trajectory i.e. both clusters are well described by a single helix
+// with a large radius.
+//
+// Greedily tag the pair of clusters with the best combined fit. Repeat until no
+// more pairs satisfy the requirements.
+pub(crate) fn tag_cosmics<F>(
+    clusters: Vec<Cluster>,
+    fit: F,
+    min_radius: Length,
+    max_rms_residual: Length,
+    // See Helix::closest_t for details on these 2 parameters.
+    max_num_closest_t_iter: usize,
+    closest_t_tolerance: f64,
+) -> CosmicTaggingResult
+where
+    F: Fn(Cluster) -> Result<Track, TryTrackFromClusterError>,
+{
+    let mut remainder = clusters;
+    let mut cosmics = Vec::new();
+
+    loop {
+        let mut best: Option<(usize, usize, Track, Length)> = None;
+        for (i, j) in (0..remainder.len()).tuple_combinations() {
+            let merged = Cluster(remainder[i].iter().chain(&remainder[j]).copied().collect());
+            let Ok(track) = fit(merged.clone()) else {
+                continue;
+            };
+            if track.helix.r.abs() < min_radius {
+                continue;
+            }
+            let residual = rms_residual(
+                &track.helix,
+                &merged.0,
+                max_num_closest_t_iter,
+                closest_t_tolerance,
+            );
+            // If the combined track does not go through the inner cathode,
+            // then this is a single track split in two (not back-to-back).
+            if residual > max_rms_residual
+                || !crosses_inner_cathode(
+                    &track.helix,
+                    &remainder[i].0,
+                    &remainder[j].0,
+                    max_num_closest_t_iter,
+                    closest_t_tolerance,
+                )
+            {
+                continue;
+            }
+
+            if best.as_ref().is_none_or(|b| residual < b.3) {
+                best = Some((i, j, track, residual));
+            }
+        }
+
+        let Some((i, j, track, _)) = best else {
+            break;
+        };
+        // `j` is always greater than `i`; remove it first so that `i` is not
+        // invalidated.
+        remainder.swap_remove(j);
+        remainder.swap_remove(i);
+        cosmics.push(Track {
+            cosmic: true,
+            ..track
+        });
+    }
+
+    CosmicTaggingResult { cosmics, remainder }
+}
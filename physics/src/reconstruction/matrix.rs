@@ -0,0 +1,90 @@
+// Minimal operations on small, fixed-size matrices of `f64`.
+// These are small enough (at most 6x6) that a general purpose linear algebra
+// library is not worth it.
+pub(crate) type Matrix<const R: usize, const C: usize> = [[f64; C]; R];
+
+pub(crate) fn mat_mul<const R: usize, const N: usize, const C: usize>(
+    a: &Matrix<R, N>,
+    b: &Matrix<N, C>,
+) -> Matrix<R, C> {
+    let mut out = [[0.0; C]; R];
+    for i in 0..R {
+        for j in 0..C {
+            out[i][j] = (0..N).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+
+    out
+}
+
+pub(crate) fn mat_add<const R: usize, const C: usize>(
+    a: &Matrix<R, C>,
+    b: &Matrix<R, C>,
+) -> Matrix<R, C> {
+    let mut out = *a;
+    for i in 0..R {
+        for j in 0..C {
+            out[i][j] += b[i][j];
+        }
+    }
+
+    out
+}
+
+pub(crate) fn transpose<const R: usize, const C: usize>(a: &Matrix<R, C>) -> Matrix<C, R> {
+    let mut out = [[0.0; R]; C];
+    for i in 0..R {
+        for j in 0..C {
+            out[j][i] = a[i][j];
+        }
+    }
+
+    out
+}
+
+// Return `None` if the matrix is singular.
+pub(crate) fn inverse_3x3(a: &Matrix<3, 3>) -> Option<Matrix<3, 3>> {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        a[r0][c0] * a[r1][c1] - a[r0][c1] * a[r1][c0]
+    };
+    let det = (0..3).map(|j| a[0][j] * cofactor(0, j)).sum::<f64>();
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+
+    Some(std::array::from_fn(|i| {
+        std::array::from_fn(|j| cofactor(j, i) / det)
+    }))
+}
+
+// Gauss-Jordan elimination with partial pivoting. Return `None` if the matrix
+// is singular.
+pub(crate) fn inverse<const N: usize>(a: &Matrix<N, N>) -> Option<Matrix<N, N>> {
+    let mut a = *a;
+    let mut out: Matrix<N, N> = std::array::from_fn(|i| std::array::from_fn(|j| f64::from(i == j)));
+    for col in 0..N {
+        let pivot = (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col] == 0.0 || !a[pivot][col].is_finite() {
+            return None;
+        }
+        a.swap(col, pivot);
+        out.swap(col, pivot);
+
+        let scale = a[col][col];
+        for j in 0..N {
+            a[col][j] /= scale;
+            out[col][j] /= scale;
+        }
+        for i in (0..N).filter(|&i| i != col) {
+            let factor = a[i][col];
+            for j in 0..N {
+                a[i][j] -= factor * a[col][j];
+                out[i][j] -= factor * out[col][j];
+            }
+        }
+    }
+
+    Some(out)
+}
@@ -0,0 +1,265 @@
+use crate::reconstruction::Cluster;
+use crate::SpacePoint;
+use thiserror::Error;
+use uom::si::f64::Length;
+use uom::si::length::meter;
+
+#[cfg(feature = "ml")]
+pub use onnx::{LoadOnnxScorerError, OnnxScorer};
+
+/// The error type returned when scoring [`SpacePoint`]s or [`Cluster`]s fails.
+#[derive(Debug, Error)]
+#[error("scoring failed")]
+pub struct ScoringError {
+    #[source]
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+/// A classifier of [`SpacePoint`]s as signal or noise.
+pub trait SpacePointScorer {
+    /// Return one score per [`SpacePoint`] (in the same order). A score is in
+    /// the `[0, 1]` range, where larger values are more signal-like.
+    fn score_spacepoints(&self, spacepoints: &[SpacePoint]) -> Result<Vec<f64>, ScoringError>;
+}
+
+/// A classifier of [`Cluster`]s as real tracks or noise.
+pub trait ClusterScorer {
+    /// Return one score per [`Cluster`] (in the same order). A score is in the
+    /// `[0, 1]` range, where larger values are more track-like.
+    fn score_clusters(&self, clusters: &[Cluster]) -> Result<Vec<f64>, ScoringError>;
+}
+
+/// Remove all the [`SpacePoint`]s with a score below `threshold` e.g. before
+/// clustering.
+pub fn filter_spacepoints<S: SpacePointScorer + ?Sized>(
+    spacepoints: Vec<SpacePoint>,
+    scorer: &S,
+    threshold: f64,
+) -> Result<Vec<SpacePoint>, ScoringError> {
+    let scores = scorer.score_spacepoints(&spacepoints)?;
+
+    Ok(spacepoints
+        .into_iter()
+        .zip(scores)
+        .filter(|(_, score)| *score >= threshold)
+        .map(|(sp, _)| sp)
+        .collect())
+}
+
+/// Remove all the [`Cluster`]s with a score below `threshold` e.g. before
+/// track fitting.
+pub fn filter_clusters<S: ClusterScorer + ?Sized>(
+    clusters: Vec<Cluster>,
+    scorer: &S,
+    threshold: f64,
+) -> Result<Vec<Cluster>, ScoringError> {
+    let scores = scorer.score_clusters(&clusters)?;
+
+    Ok(clusters
+        .into_iter()
+        .zip(scores)
+        .filter(|(_, score)| *score >= threshold)
+        .map(|(cluster, _)| cluster)
+        .collect())
+}
+
+/// Pure-Rust [`SpacePointScorer`] based on the local density of
+/// [`SpacePoint`]s.
+///
+/// Ionization along a track produces many close [`SpacePoint`]s, whereas
+/// random noise is isolated. The score is the number of other
+/// [`SpacePoint`]s within `radius` divided by `min_neighbors` (and clamped
+/// to `1.0`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IsolationScorer {
+    /// Maximum distance to count a neighbor.
+    pub radius: Length,
+    /// Number of neighbors needed for the maximum score.
+    pub min_neighbors: usize,
+}
+
+impl SpacePointScorer for IsolationScorer {
+    fn score_spacepoints(&self, spacepoints: &[SpacePoint]) -> Result<Vec<f64>, ScoringError> {
+        if self.min_neighbors == 0 {
+            return Ok(vec![1.0; spacepoints.len()]);
+        }
+        let radius = self.radius.get::<meter>();
+
+        Ok(spacepoints
+            .iter()
+            .enumerate()
+            .map(|(i, sp)| {
+                let neighbors = spacepoints
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, other)| i != j && distance(sp, other) <= radius)
+                    .count();
+
+                (neighbors as f64 / self.min_neighbors as f64).min(1.0)
+            })
+            .collect())
+    }
+}
+
+fn distance(a: &SpacePoint, b: &SpacePoint) -> f64 {
+    let (dx, dy, dz) = (a.x() - b.x(), a.y() - b.y(), a.z() - b.z());
+
+    (dx * dx + dy * dy + dz * dz).sqrt().get::<meter>()
+}
+
+/// Pure-Rust [`ClusterScorer`] based on the radial extent of a [`Cluster`].
+///
+/// Tracks cross a large fraction of the drift region, whereas coherent noise
+/// (e.g. many channels firing at the same time) produces [`SpacePoint`]s at
+/// the same drift time i.e. the same radius. The score is the difference
+/// between the largest and smallest radius in the cluster divided by
+/// `min_extent` (and clamped to `1.0`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RadialExtentScorer {
+    /// Radial extent needed for the maximum score.
+    pub min_extent: Length,
+}
+
+impl ClusterScorer for RadialExtentScorer {
+    fn score_clusters(&self, clusters: &[Cluster]) -> Result<Vec<f64>, ScoringError> {
+        Ok(clusters
+            .iter()
+            .map(|cluster| {
+                let (min, max) = cluster
+                    .iter()
+                    .map(|sp| sp.r.get::<meter>())
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), r| {
+                        (min.min(r), max.max(r))
+                    });
+                if min > max {
+                    return 0.0;
+                }
+                let min_extent = self.min_extent.get::<meter>();
+                if min_extent <= 0.0 {
+                    return 1.0;
+                }
+
+                ((max - min) / min_extent).min(1.0)
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "ml")]
+mod onnx {
+    use super::{ClusterScorer, ScoringError, SpacePointScorer};
+    use crate::reconstruction::Cluster;
+    use crate::SpacePoint;
+    use std::path::Path;
+    use thiserror::Error;
+    use tract_onnx::prelude::*;
+    use uom::si::length::meter;
+
+    /// The error type returned when loading an [`OnnxScorer`] fails.
+    #[derive(Debug, Error)]
+    #[error("failed to load onnx model")]
+    pub struct LoadOnnxScorerError {
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    }
+
+    /// [`SpacePointScorer`] and [`ClusterScorer`] given by an ONNX model.
+    ///
+    /// The model takes a single `f32` input with shape `[N, F]` and returns a
+    /// single `f32` output with `N` scores (e.g. shape `[N]` or `[N, 1]`). Each
+    /// row of the input has the features of a single [`SpacePoint`] or
+    /// [`Cluster`]:
+    ///
+    /// - [`SpacePoint`]: `[x, y, z, wire_amplitude, pad_amplitude]` (`F = 5`).
+    ///   Positions are in meters.
+    /// - [`Cluster`]: `[num_points, r_min, r_max, z_min, z_max, mean_wire_amplitude]`
+    ///   (`F = 6`). Positions are in meters.
+    pub struct OnnxScorer {
+        model: TypedRunnableModel<TypedModel>,
+    }
+
+    impl OnnxScorer {
+        /// Load an ONNX model from a file.
+        pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, LoadOnnxScorerError> {
+            let model = tract_onnx::onnx()
+                .model_for_path(path)
+                .and_then(|model| model.into_optimized())
+                .and_then(|model| model.into_runnable())
+                .map_err(|e| LoadOnnxScorerError { source: e.into() })?;
+
+            Ok(Self { model })
+        }
+        fn run(&self, features: Vec<f32>, rows: usize) -> Result<Vec<f64>, ScoringError> {
+            if rows == 0 {
+                return Ok(Vec::new());
+            }
+            let columns = features.len() / rows;
+            let scores = tract_ndarray::Array2::from_shape_vec((rows, columns), features)
+                .map_err(|e| ScoringError { source: e.into() })
+                .and_then(|input| {
+                    let input: Tensor = input.into();
+                    self.model
+                        .run(tvec!(input.into()))
+                        .map_err(|e| ScoringError { source: e.into() })
+                })?;
+            let scores = scores[0]
+                .to_array_view::<f32>()
+                .map_err(|e| ScoringError { source: e.into() })?;
+            if scores.len() != rows {
+                return Err(ScoringError {
+                    source: format!("expected `{rows}` scores, found `{}`", scores.len()).into(),
+                });
+            }
+
+            Ok(scores.iter().map(|&s| f64::from(s)).collect())
+        }
+    }
+
+    impl SpacePointScorer for OnnxScorer {
+        fn score_spacepoints(&self, spacepoints: &[SpacePoint]) -> Result<Vec<f64>, ScoringError> {
+            let features = spacepoints
+                .iter()
+                .flat_map(|sp| {
+                    [
+                        sp.x().get::<meter>(),
+                        sp.y().get::<meter>(),
+                        sp.z().get::<meter>(),
+                        sp.wire_amplitude,
+                        sp.pad_amplitude,
+                    ]
+                })
+                .map(|v| v as f32)
+                .collect();
+
+            self.run(features, spacepoints.len())
+        }
+    }
+
+    impl ClusterScorer for OnnxScorer {
+        fn score_clusters(&self, clusters: &[Cluster]) -> Result<Vec<f64>, ScoringError> {
+            let features = clusters
+                .iter()
+                .flat_map(|cluster| {
+                    let fold = |init: f64, f: fn(f64, f64) -> f64, v: fn(&SpacePoint) -> f64| {
+                        cluster.iter().map(v).fold(init, f)
+                    };
+                    let num_points = cluster.0.len() as f64;
+                    [
+                        num_points,
+                        fold(f64::INFINITY, f64::min, |sp| sp.r.get::<meter>()),
+                        fold(f64::NEG_INFINITY, f64::max, |sp| sp.r.get::<meter>()),
+                        fold(f64::INFINITY, f64::min, |sp| sp.z.get::<meter>()),
+                        fold(f64::NEG_INFINITY, f64::max, |sp| sp.z.get::<meter>()),
+                        fold(0.0, |a, b| a + b, |sp| sp.wire_amplitude) / num_points,
+                    ]
+                })
+                .map(|v| v as f32)
+                .collect();
+
+            self.run(features, clusters.len())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
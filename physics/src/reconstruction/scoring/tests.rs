@@ -0,0 +1,82 @@
+use super::*;
+use uom::si::angle::radian;
+use uom::si::f64::Angle;
+use uom::si::length::millimeter;
+
+fn sp(r: f64, phi: f64, z: f64) -> SpacePoint {
+    SpacePoint {
+        r: Length::new::<meter>(r),
+        phi: Angle::new::<radian>(phi),
+        z: Length::new::<meter>(z),
+        wire_amplitude: 1.0,
+        pad_amplitude: 1.0,
+        sigma_r: Length::new::<millimeter>(1.0),
+        sigma_rphi: Length::new::<millimeter>(1.0),
+        sigma_z: Length::new::<millimeter>(1.0),
+    }
+}
+
+#[test]
+fn isolation_scorer() {
+    let scorer = IsolationScorer {
+        radius: Length::new::<millimeter>(5.0),
+        min_neighbors: 2,
+    };
+    let spacepoints = vec![
+        sp(0.15, 0.0, 0.0),
+        sp(0.152, 0.0, 0.0),
+        sp(0.154, 0.0, 0.0),
+        // Isolated noise.
+        sp(0.15, 3.0, 0.5),
+    ];
+
+    let scores = scorer.score_spacepoints(&spacepoints).unwrap();
+    assert_eq!(scores, vec![1.0, 1.0, 1.0, 0.0]);
+
+    let filtered = filter_spacepoints(spacepoints, &scorer, 0.5).unwrap();
+    assert_eq!(filtered.len(), 3);
+    assert!(filtered.iter().all(|sp| sp.z.get::<meter>() == 0.0));
+}
+
+#[test]
+fn isolation_scorer_partial_score() {
+    let scorer = IsolationScorer {
+        radius: Length::new::<millimeter>(5.0),
+        min_neighbors: 4,
+    };
+    let spacepoints = vec![sp(0.15, 0.0, 0.0), sp(0.152, 0.0, 0.0)];
+
+    let scores = scorer.score_spacepoints(&spacepoints).unwrap();
+    assert_eq!(scores, vec![0.25, 0.25]);
+}
+
+#[test]
+fn radial_extent_scorer() {
+    let scorer = RadialExtentScorer {
+        min_extent: Length::new::<meter>(0.04),
+    };
+    let clusters = vec![
+        Cluster(
+            (0..10)
+                .map(|i| sp(0.11 + 0.007 * i as f64, 0.0, 0.0))
+                .collect(),
+        ),
+        // Coherent noise at a single drift time.
+        Cluster((0..10).map(|i| sp(0.15, 0.1 * i as f64, 0.0)).collect()),
+        Cluster(
+            (0..10)
+                .map(|i| sp(0.15 + 0.002 * i as f64, 0.0, 0.0))
+                .collect(),
+        ),
+        Cluster(Vec::new()),
+    ];
+
+    let scores = scorer.score_clusters(&clusters).unwrap();
+    assert_eq!(scores[0], 1.0);
+    assert_eq!(scores[1], 0.0);
+    assert!((scores[2] - 0.45).abs() < 1e-9);
+    assert_eq!(scores[3], 0.0);
+
+    let filtered = filter_clusters(clusters, &scorer, 0.5).unwrap();
+    assert_eq!(filtered.len(), 1);
+}
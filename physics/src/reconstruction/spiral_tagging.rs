@@ -0,0 +1,119 @@
+use crate::reconstruction::matrix::{inverse_3x3, mat_mul};
+use crate::reconstruction::track_finding::{dbscan_cluster_spacepoints, NeighborDistance};
+use crate::reconstruction::SpiralTaggingResult;
+use crate::SpacePoint;
+use std::f64::consts::TAU;
+use uom::si::angle::radian;
+use uom::si::f64::{Angle, Length};
+use uom::si::length::meter;
+
+// Minimum number of neighbors of a core point when grouping SpacePoints. Same
+// as the default of the DBSCAN clustering.
+const MIN_NUM_NEIGHBORS: usize = 3;
+
+// A low momentum electron (e.g. a delta ray) curls up in the magnetic field and
+// spirals along `z` around a fixed axis, leaving a closed loop of SpacePoints
+// in the x-y plane. Annihilation products come from the beamline, so their
+// circle (as seen from the x-y plane) goes through the inner cathode and can
+// never close inside the rTPC.
+//
+// Group SpacePoints by 3D connectivity, and tag as a spiral every group that is
+// a small circle in the x-y plane covering all directions around its center
+// i.e. at least one full turn. Any other group is returned to the remainder
+// untouched.
+pub(crate) fn tag_spirals(
+    sp: Vec<SpacePoint>,
+    min_num_points: usize,
+    max_neighbor_distance: Length,
+    max_radius: Length,
+    max_rms_residual: Length,
+    max_phase_gap: Angle,
+) -> SpiralTaggingResult {
+    let groups = dbscan_cluster_spacepoints(
+        sp,
+        min_num_points,
+        None,
+        MIN_NUM_NEIGHBORS,
+        NeighborDistance::new(max_neighbor_distance, None),
+    );
+
+    let mut spirals = Vec::new();
+    let mut remainder = groups.remainder;
+    for group in groups.clusters {
+        if is_spiral(&group.0, max_radius, max_rms_residual, max_phase_gap) {
+            spirals.push(group);
+        } else {
+            remainder.extend(group.0);
+        }
+    }
+
+    SpiralTaggingResult { spirals, remainder }
+}
+
+fn is_spiral(
+    sp: &[SpacePoint],
+    max_radius: Length,
+    max_rms_residual: Length,
+    max_phase_gap: Angle,
+) -> bool {
+    let points: Vec<_> = sp
+        .iter()
+        .map(|p| (p.x().get::<meter>(), p.y().get::<meter>()))
+        .collect();
+    let Some((center, radius)) = fit_circle(&points) else {
+        return false;
+    };
+    if radius > max_radius.get::<meter>() {
+        return false;
+    }
+    let rms_residual = (points
+        .iter()
+        .map(|&(x, y)| ((x - center.0).hypot(y - center.1) - radius).powi(2))
+        .sum::<f64>()
+        / points.len() as f64)
+        .sqrt();
+    if rms_residual > max_rms_residual.get::<meter>() {
+        return false;
+    }
+
+    let mut phases: Vec<_> = points
+        .iter()
+        .map(|&(x, y)| (y - center.1).atan2(x - center.0))
+        .collect();
+    phases.sort_unstable_by(f64::total_cmp);
+    let largest_gap = phases
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .chain(std::iter::once(phases[0] + TAU - phases[phases.len() - 1]))
+        .fold(0.0, f64::max);
+
+    largest_gap <= max_phase_gap.get::<radian>()
+}
+
+// Algebraic (Kasa) circle fit in the x-y plane i.e. minimize the sum of
+// `(x^2 + y^2 + D x + E y + F)^2`. Coordinates are centered on their mean
+// before the fit for numerical stability. Return the center and radius, or
+// `None` if the points are collinear.
+fn fit_circle(points: &[(f64, f64)]) -> Option<((f64, f64), f64)> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+
+    let mut a = [[0.0; 3]; 3];
+    let mut b = [[0.0]; 3];
+    for &(x, y) in points {
+        let (x, y) = (x - mean_x, y - mean_y);
+        let row = [x, y, 1.0];
+        let rhs = -(x * x + y * y);
+        for (i, &row_i) in row.iter().enumerate() {
+            for (j, &row_j) in row.iter().enumerate() {
+                a[i][j] += row_i * row_j;
+            }
+            b[i][0] += row_i * rhs;
+        }
+    }
+    let [[d], [e], [f]] = mat_mul(&inverse_3x3(&a)?, &b);
+
+    let radius_sqr = 0.25 * (d * d + e * e) - f;
+    (radius_sqr > 0.0).then(|| ((mean_x - 0.5 * d, mean_y - 0.5 * e), radius_sqr.sqrt()))
+}
@@ -1,9 +1,19 @@
 use super::*;
+use crate::bv::{BAR_RADIUS, BV_BARS};
+use crate::field::FieldMap;
+use crate::reconstruction::vertex_fitting::chi2_probability;
 use alpha_g_detector::padwing::map::{CATHODE_PADS_RADIUS, DETECTOR_LENGTH};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::StandardNormal;
 use std::f64::consts::PI;
 use uom::si::angle::radian;
+use uom::si::area::square_meter;
 use uom::si::f64::Angle;
-use uom::si::length::meter;
+use uom::si::f64::MagneticFluxDensity;
+use uom::si::length::{centimeter, meter, millimeter};
+use uom::si::reciprocal_length::reciprocal_meter;
+use uom::si::time::nanosecond;
 
 fn is_within_tpc_volume(p: &SpacePoint) -> bool {
     let detector_half_length = Length::new::<meter>(DETECTOR_LENGTH / 2.0);
@@ -28,6 +38,11 @@ fn single_trivial_track_finding() {
             r: (x * x + y * y).sqrt(),
             phi: y.atan2(x),
             z: Length::new::<meter>(0.0),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
         };
 
         if is_within_tpc_volume(&point) {
@@ -62,6 +77,11 @@ fn two_opposite_tracks() {
             r: (x * x + y * y).sqrt(),
             phi: y.atan2(x),
             z: Length::new::<meter>(0.0),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
         };
 
         if is_within_tpc_volume(&point) {
@@ -84,6 +104,64 @@ fn two_opposite_tracks() {
     }
 }
 
+#[test]
+fn clustering_vetoes() {
+    let mut raw_points = Vec::new();
+
+    let r = Length::new::<centimeter>(20.0);
+    let num_points = 2000;
+    for i in 0..num_points {
+        let theta = Angle::FULL_TURN * i as f64 / num_points as f64;
+        let x = r * theta.cos() + r;
+        let y = r * theta.sin();
+
+        let point = SpacePoint {
+            r: (x * x + y * y).sqrt(),
+            phi: y.atan2(x),
+            z: Length::new::<meter>(0.0),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        };
+
+        if is_within_tpc_volume(&point) {
+            raw_points.push(point);
+        }
+    }
+    let config = ReconstructionConfig::default();
+    let is_below = |p: &SpacePoint| p.y() < Length::new::<meter>(0.0);
+
+    let result = cluster_spacepoints_with_vetoes(raw_points.clone(), &config, is_below, |_| false);
+    assert_eq!(result.clusters.len(), 1);
+    assert!(result.clusters[0].iter().all(|p| !is_below(p)));
+    assert!(result.remainder.iter().all(is_below));
+    assert_eq!(
+        result.clusters[0].0.len() + result.remainder.len(),
+        raw_points.len()
+    );
+
+    let mut num_calls = 0;
+    let result = cluster_spacepoints_with_vetoes(
+        raw_points.clone(),
+        &config,
+        |_| false,
+        |cluster| {
+            num_calls += 1;
+            cluster.iter().all(is_below)
+        },
+    );
+    assert_eq!(num_calls, 2);
+    assert_eq!(result.clusters.len(), 1);
+    assert!(result.clusters[0].iter().all(|p| !is_below(p)));
+    assert!(result.remainder.iter().all(is_below));
+    assert_eq!(
+        result.clusters[0].0.len() + result.remainder.len(),
+        raw_points.len()
+    );
+}
+
 #[test]
 fn two_on_top_tracks() {
     let mut raw_points = Vec::new();
@@ -99,6 +177,11 @@ fn two_on_top_tracks() {
             r: (x * x + y * y).sqrt(),
             phi: y.atan2(x),
             z: Length::new::<meter>(0.5),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
         };
         if is_within_tpc_volume(&point) {
             raw_points.push(point);
@@ -108,6 +191,11 @@ fn two_on_top_tracks() {
             r: (x * x + y * y).sqrt(),
             phi: y.atan2(x),
             z: Length::new::<meter>(-0.5),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
         };
         if is_within_tpc_volume(&point) {
             raw_points.push(point);
@@ -129,6 +217,417 @@ fn two_on_top_tracks() {
     }
 }
 
+#[test]
+fn hough_single_and_double_precision() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut raw_points = Vec::new();
+    // Circles through the beamline with different radii and directions. Smear
+    // the SpacePoints so that some of them are close to the edge of a bin.
+    for i in 0..5 {
+        let angle = Angle::FULL_TURN * i as f64 / 5.0;
+        let r = Length::new::<centimeter>(20.0 + 5.0 * i as f64);
+        let (cx, cy) = (r * angle.cos(), r * angle.sin());
+        let num_points = 1000;
+        for j in 0..num_points {
+            let theta = angle + Angle::HALF_TURN * j as f64 / num_points as f64;
+            let x = r * theta.cos() - cx;
+            let y = r * theta.sin() - cy;
+            let (dx, dy): (f64, f64) = (rng.sample(StandardNormal), rng.sample(StandardNormal));
+            let (x, y) = (
+                x + Length::new::<millimeter>(0.2 * dx),
+                y + Length::new::<millimeter>(0.2 * dy),
+            );
+
+            let point = SpacePoint {
+                r: (x * x + y * y).sqrt(),
+                phi: y.atan2(x),
+                z: Length::new::<meter>(0.0),
+                wire_amplitude: 0.0,
+                pad_amplitude: 0.0,
+                sigma_r: Length::new::<millimeter>(1.0),
+                sigma_rphi: Length::new::<millimeter>(1.0),
+                sigma_z: Length::new::<millimeter>(1.0),
+            };
+            if is_within_tpc_volume(&point) {
+                raw_points.push(point);
+            }
+        }
+    }
+
+    let double = cluster_spacepoints(raw_points.clone());
+    let mut config = ReconstructionConfig::default();
+    config.clustering.hough_precision = HoughPrecision::Single;
+    let single = cluster_spacepoints_with_config(raw_points.clone(), &config);
+
+    assert_eq!(double.clusters.len(), 5);
+    assert_eq!(single.clusters.len(), double.clusters.len());
+    // Only a few SpacePoints (if any) change their vote.
+    let num_clustered = |result: &ClusteringResult| {
+        result
+            .clusters
+            .iter()
+            .map(|cluster| cluster.0.len())
+            .sum::<usize>()
+    };
+    let difference = num_clustered(&single).abs_diff(num_clustered(&double));
+    assert!(difference * 100 <= raw_points.len(), "{difference}");
+}
+
+#[test]
+fn two_crossing_tracks() {
+    let mut raw_points = Vec::new();
+
+    let r = Length::new::<centimeter>(20.0);
+    let num_points = 1000;
+    // Same circle in the x-y plane, but opposite dz/ds. The tracks cross each
+    // other in the middle of the rTPC.
+    let theta_crossing = Angle::new::<radian>(2.3712);
+    for i in 0..num_points {
+        let theta = Angle::HALF_TURN * i as f64 / num_points as f64;
+        let x = r * theta.cos() + r;
+        let y = r * theta.sin();
+        let dz = r * (theta - theta_crossing).get::<radian>();
+
+        // Use the wire amplitude to identify the true track.
+        for (z, track) in [(dz, 1.0), (-dz, 2.0)] {
+            let point = SpacePoint {
+                r: (x * x + y * y).sqrt(),
+                phi: y.atan2(x),
+                z,
+                wire_amplitude: track,
+                pad_amplitude: 0.0,
+                sigma_r: Length::new::<millimeter>(1.0),
+                sigma_rphi: Length::new::<millimeter>(1.0),
+                sigma_z: Length::new::<millimeter>(1.0),
+            };
+            if is_within_tpc_volume(&point) {
+                raw_points.push(point);
+            }
+        }
+    }
+
+    let clustering_result = cluster_spacepoints(raw_points.clone());
+
+    assert!(clustering_result.remainder.is_empty());
+    assert_eq!(clustering_result.clusters.len(), 2);
+
+    let cluster_0 = &clustering_result.clusters[0].0;
+    let cluster_1 = &clustering_result.clusters[1].0;
+    assert_eq!(cluster_0.len(), cluster_1.len());
+    assert_eq!(cluster_0.len() + cluster_1.len(), raw_points.len());
+    for cluster in [cluster_0, cluster_1] {
+        let track = cluster[0].wire_amplitude;
+        assert!(cluster.iter().all(|p| p.wire_amplitude == track));
+    }
+}
+
+#[test]
+fn hough_remainder_partition() {
+    let mut raw_points = Vec::new();
+
+    let r = Length::new::<centimeter>(20.0);
+    let num_points = 1000;
+    for i in 0..num_points {
+        let theta = Angle::HALF_TURN * i as f64 / num_points as f64;
+        let x = r * theta.cos() + r;
+        let y = r * theta.sin();
+
+        let point = SpacePoint {
+            r: (x * x + y * y).sqrt(),
+            phi: y.atan2(x),
+            z: Length::new::<meter>(0.0),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        };
+
+        if is_within_tpc_volume(&point) {
+            raw_points.push(point);
+        }
+    }
+    let track_len = raw_points.len();
+    // Isolated noise points far away from the track and from each other.
+    for i in 0..5 {
+        raw_points.push(SpacePoint {
+            r: Length::new::<centimeter>(15.0),
+            phi: Angle::new::<radian>(-0.5 * i as f64),
+            z: Length::new::<centimeter>(10.0 * (i + 1) as f64),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        });
+    }
+
+    let clustering_result = cluster_spacepoints(raw_points.clone());
+
+    assert_eq!(clustering_result.clusters.len(), 1);
+    assert_eq!(clustering_result.clusters[0].0.len(), track_len);
+    // The remainder keeps the original order.
+    assert_eq!(clustering_result.remainder, raw_points[track_len..]);
+}
+
+#[test]
+fn adaptive_hough_bins_sparse_event() {
+    // Too few points to ever be a cluster; stay at the coarsest binning.
+    let raw_points: Vec<_> = (0..5)
+        .map(|i| SpacePoint {
+            r: Length::new::<centimeter>(15.0),
+            phi: Angle::new::<radian>(0.5 * i as f64),
+            z: Length::new::<meter>(0.0),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        })
+        .collect();
+
+    assert_eq!(
+        track_finding::adaptive_hough_bins(&raw_points, 13, 250, 230, HoughPrecision::Double),
+        (62, 57)
+    );
+}
+
+#[test]
+fn adaptive_hough_bins_single_track() {
+    let mut raw_points = Vec::new();
+
+    let r = Length::new::<centimeter>(20.0);
+    let num_points = 1000;
+    for i in 0..num_points {
+        let theta = Angle::HALF_TURN * i as f64 / num_points as f64;
+        let x = r * theta.cos() + r;
+        let y = r * theta.sin();
+
+        let point = SpacePoint {
+            r: (x * x + y * y).sqrt(),
+            phi: y.atan2(x),
+            z: Length::new::<meter>(0.0),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        };
+
+        if is_within_tpc_volume(&point) {
+            raw_points.push(point);
+        }
+    }
+    // A perfect circle through the origin is a perfect line in Hough space,
+    // so the finest binning still has all the points in a single bin.
+    let (rho_bins, theta_bins) =
+        track_finding::adaptive_hough_bins(&raw_points, 13, 250, 230, HoughPrecision::Double);
+    assert_eq!((rho_bins, theta_bins), (992, 912));
+
+    let mut config = ReconstructionConfig::default();
+    config.clustering.adaptive_hough_bins = true;
+    let clustering_result = cluster_spacepoints_with_config(raw_points.clone(), &config);
+
+    assert!(clustering_result.remainder.is_empty());
+    assert_eq!(clustering_result.clusters.len(), 1);
+    assert_eq!(clustering_result.clusters[0].0.len(), raw_points.len());
+}
+
+fn dbscan_config() -> ReconstructionConfig {
+    let mut config = ReconstructionConfig::default();
+    config.clustering.algorithm = ClusteringAlgorithm::Dbscan;
+
+    config
+}
+
+#[test]
+fn dbscan_two_on_top_tracks() {
+    let mut raw_points = Vec::new();
+
+    let r = Length::new::<centimeter>(20.0);
+    let num_points = 1000;
+    for i in 0..num_points {
+        let theta = Angle::HALF_TURN * i as f64 / num_points as f64;
+        let x = r * theta.cos() + r;
+        let y = r * theta.sin();
+
+        for z in [Length::new::<meter>(0.5), Length::new::<meter>(-0.5)] {
+            let point = SpacePoint {
+                r: (x * x + y * y).sqrt(),
+                phi: y.atan2(x),
+                z,
+                wire_amplitude: 0.0,
+                pad_amplitude: 0.0,
+                sigma_r: Length::new::<millimeter>(1.0),
+                sigma_rphi: Length::new::<millimeter>(1.0),
+                sigma_z: Length::new::<millimeter>(1.0),
+            };
+            if is_within_tpc_volume(&point) {
+                raw_points.push(point);
+            }
+        }
+    }
+
+    let clustering_result = cluster_spacepoints_with_config(raw_points.clone(), &dbscan_config());
+
+    assert!(clustering_result.remainder.is_empty());
+    assert_eq!(clustering_result.clusters.len(), 2);
+
+    let cluster_0 = &clustering_result.clusters[0].0;
+    let cluster_1 = &clustering_result.clusters[1].0;
+    assert_eq!(cluster_0.len(), cluster_1.len());
+    assert_eq!(cluster_0.len() + cluster_1.len(), raw_points.len());
+    for p in cluster_0 {
+        assert!(p.z > Length::new::<meter>(0.0));
+    }
+    for p in cluster_1 {
+        assert!(p.z < Length::new::<meter>(0.0));
+    }
+}
+
+#[test]
+fn dbscan_short_track_far_from_origin() {
+    let mut raw_points = Vec::new();
+    // Straight track that does not point towards the beamline.
+    let num_points = 20;
+    for i in 0..num_points {
+        let x = Length::new::<centimeter>(15.0 + 0.5 * i as f64);
+        let y = Length::new::<centimeter>(10.0);
+
+        raw_points.push(SpacePoint {
+            r: (x * x + y * y).sqrt(),
+            phi: y.atan2(x),
+            z: Length::new::<centimeter>(i as f64),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        });
+    }
+    // Isolated noise point.
+    let noise = SpacePoint {
+        r: Length::new::<centimeter>(18.0),
+        phi: Angle::new::<radian>(-2.0),
+        z: Length::new::<centimeter>(-50.0),
+        wire_amplitude: 0.0,
+        pad_amplitude: 0.0,
+        sigma_r: Length::new::<millimeter>(1.0),
+        sigma_rphi: Length::new::<millimeter>(1.0),
+        sigma_z: Length::new::<millimeter>(1.0),
+    };
+    raw_points.push(noise);
+
+    let clustering_result = cluster_spacepoints_with_config(raw_points, &dbscan_config());
+
+    assert_eq!(clustering_result.clusters.len(), 1);
+    assert_eq!(clustering_result.clusters[0].0.len(), num_points);
+    assert_eq!(clustering_result.remainder, vec![noise]);
+}
+
+#[test]
+fn dbscan_max_num_clusters() {
+    let mut raw_points = Vec::new();
+    for (num_points, z) in [(20, -0.5), (30, 0.5)] {
+        for i in 0..num_points {
+            let phi = Angle::new::<radian>(0.01 * i as f64);
+
+            raw_points.push(SpacePoint {
+                r: Length::new::<centimeter>(15.0),
+                phi,
+                z: Length::new::<meter>(z),
+                wire_amplitude: 0.0,
+                pad_amplitude: 0.0,
+                sigma_r: Length::new::<millimeter>(1.0),
+                sigma_rphi: Length::new::<millimeter>(1.0),
+                sigma_z: Length::new::<millimeter>(1.0),
+            });
+        }
+    }
+
+    let mut config = dbscan_config();
+    config.clustering.max_num_clusters = Some(1);
+    let clustering_result = cluster_spacepoints_with_config(raw_points, &config);
+
+    assert_eq!(clustering_result.clusters.len(), 1);
+    assert_eq!(clustering_result.clusters[0].0.len(), 30);
+    assert_eq!(clustering_result.remainder.len(), 20);
+}
+
+#[test]
+fn dbscan_anisotropic_neighbor_distance() {
+    let mut raw_points = Vec::new();
+    // Two radial tracks 2 cm apart along z. The transverse distance between
+    // consecutive points is 5 mm.
+    for z in [
+        Length::new::<centimeter>(-1.0),
+        Length::new::<centimeter>(1.0),
+    ] {
+        for i in 0..13 {
+            raw_points.push(SpacePoint {
+                r: Length::new::<centimeter>(12.0 + 0.5 * i as f64),
+                phi: Angle::new::<radian>(1.0),
+                z,
+                wire_amplitude: 0.0,
+                pad_amplitude: 0.0,
+                sigma_r: Length::new::<millimeter>(1.0),
+                sigma_rphi: Length::new::<millimeter>(1.0),
+                sigma_z: Length::new::<millimeter>(1.0),
+            });
+        }
+    }
+
+    let mut config = dbscan_config();
+    let clustering_result = cluster_spacepoints_with_config(raw_points.clone(), &config);
+    assert_eq!(clustering_result.clusters.len(), 1);
+    assert_eq!(clustering_result.clusters[0].0.len(), raw_points.len());
+
+    config.clustering.max_neighbor_distance_z = Some(Length::new::<millimeter>(5.0));
+    let clustering_result = cluster_spacepoints_with_config(raw_points, &config);
+    assert!(clustering_result.remainder.is_empty());
+    assert_eq!(clustering_result.clusters.len(), 2);
+    for cluster in clustering_result.clusters {
+        assert_eq!(cluster.0.len(), 13);
+        assert!(cluster.0.windows(2).all(|w| w[0].z == w[1].z));
+    }
+}
+
+#[test]
+fn pad_only_two_tracks() {
+    let mut raw_points = Vec::new();
+    // Two tracks in different pad columns. Their `phi` is only known up to
+    // the center of the pad column (plus the Lorentz angle correction).
+    for (phi, slope) in [(0.3, 1.0), (2.5, -0.5)] {
+        for i in 0..15 {
+            let r = Length::new::<centimeter>(11.0 + 0.5 * i as f64);
+            raw_points.push(SpacePoint {
+                r,
+                phi: Angle::new::<radian>(phi - 0.002 * i as f64),
+                z: slope * r,
+                wire_amplitude: 0.0,
+                pad_amplitude: 0.0,
+                sigma_r: Length::new::<millimeter>(1.0),
+                sigma_rphi: Length::new::<centimeter>(1.0),
+                sigma_z: Length::new::<millimeter>(1.0),
+            });
+        }
+    }
+
+    let mut config = ReconstructionConfig::default();
+    config.clustering.algorithm = ClusteringAlgorithm::PadOnly;
+    let clustering_result = cluster_spacepoints_with_config(raw_points, &config);
+
+    assert!(clustering_result.remainder.is_empty());
+    assert_eq!(clustering_result.clusters.len(), 2);
+    for cluster in clustering_result.clusters {
+        assert_eq!(cluster.0.len(), 15);
+        assert!(cluster
+            .0
+            .windows(2)
+            .all(|w| (w[0].phi - w[1].phi).abs() < Angle::new::<radian>(0.1)));
+    }
+}
+
 fn trivial_helix_fit(x0: Length, y0: Length, z0: Length, r: Length, phi0: Angle, h: Length) {
     let mut raw_points = Vec::new();
     let num_points = 2000;
@@ -144,6 +643,11 @@ fn trivial_helix_fit(x0: Length, y0: Length, z0: Length, r: Length, phi0: Angle,
             r: coord.x.hypot(coord.y),
             phi: coord.y.atan2(coord.x),
             z: coord.z,
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
         };
 
         if is_within_tpc_volume(&point) {
@@ -252,6 +756,50 @@ fn trivial_track_fitting() {
     );
 }
 
+#[test]
+fn track_fitting_weighted_residuals() {
+    let x0 = Length::new::<centimeter>(20.0);
+    let y0 = Length::new::<centimeter>(0.0);
+    let z0 = Length::new::<centimeter>(0.0);
+    let r = Length::new::<centimeter>(20.0);
+    let h = Length::new::<centimeter>(50.0);
+    let points: Vec<_> = helix_points(x0, y0, z0, r, h, (-0.5 * PI, 0.5 * PI))
+        .into_iter()
+        .step_by(10)
+        .collect();
+    // A few noisy points far away from the true track.
+    let outliers: Vec<SpacePoint> = points
+        .iter()
+        .step_by(5)
+        .map(|p| SpacePoint {
+            z: p.z + Length::new::<centimeter>(2.0),
+            ..*p
+        })
+        .collect();
+
+    // Let the fit converge well below the resolution of the SpacePoints.
+    let mut config = ReconstructionConfig::default();
+    config.track_fit.max_num_iter = 400;
+    let residual = |sigma: Length| {
+        let noisy = outliers.iter().map(|p| SpacePoint {
+            sigma_r: sigma,
+            sigma_rphi: sigma,
+            sigma_z: sigma,
+            ..*p
+        });
+        let cluster = Cluster(points.iter().copied().chain(noisy).collect());
+        let track = fit_track_with_config(cluster, &config).unwrap();
+
+        track_merging::rms_residual(&track.helix, &points, 20, f64::EPSILON)
+    };
+    // The fit is pulled much less by the outliers if they have a large
+    // uncertainty.
+    let weighted = residual(Length::new::<meter>(1.0));
+    let unweighted = residual(Length::new::<millimeter>(1.0));
+    assert!(weighted < Length::new::<millimeter>(0.1));
+    assert!(weighted < unweighted / 10.0);
+}
+
 #[test]
 fn track_fitting_h_equal_zero_regression() {
     trivial_helix_fit(
@@ -284,6 +832,11 @@ fn track_fitting_bad_initial_parameters_regression_repeated_points() {
             r: Length::new::<centimeter>(15.0),
             phi: Angle::new::<radian>(0.0),
             z: Length::new::<centimeter>(0.0),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
         });
     }
 
@@ -294,6 +847,40 @@ fn track_fitting_bad_initial_parameters_regression_repeated_points() {
     assert!(Track::try_from(cluster).is_err());
 }
 
+#[test]
+fn track_fitting_resample_initial_parameters() {
+    let point = |r: f64, phi: f64| SpacePoint {
+        r: Length::new::<centimeter>(r),
+        phi: Angle::new::<radian>(phi),
+        z: Length::new::<centimeter>(0.0),
+        wire_amplitude: 0.0,
+        pad_amplitude: 0.0,
+        sigma_r: Length::new::<millimeter>(1.0),
+        sigma_rphi: Length::new::<millimeter>(1.0),
+        sigma_z: Length::new::<millimeter>(1.0),
+    };
+    // The innermost, outermost, and middle points are exactly collinear, but
+    // the rest of the points are not.
+    let mut points = vec![point(10.0, 0.0), point(14.5, 0.0), point(19.0, 0.0)];
+    for i in 0..20 {
+        points.push(point(10.5 + 0.1 * i as f64, 0.0));
+        points.push(point(10.5 + 0.1 * i as f64, 0.01));
+        points.push(point(16.5 + 0.1 * i as f64, 0.0));
+        points.push(point(16.5 + 0.1 * i as f64, 0.01));
+    }
+    let cluster = Cluster(points);
+
+    let mut config = ReconstructionConfig::default();
+    config.track_fit.max_num_resample = 0;
+    assert!(fit_track_with_config(cluster.clone(), &config).is_err());
+
+    config.track_fit.max_num_resample = 10;
+    config.seed = 42;
+    let track = fit_track_with_config(cluster.clone(), &config).unwrap();
+    // Same seed, same result.
+    assert_eq!(fit_track_with_config(cluster, &config).unwrap(), track);
+}
+
 #[test]
 fn track_fitting_bad_initial_parameters_regression_collinear_points() {
     let mut points = Vec::new();
@@ -302,6 +889,11 @@ fn track_fitting_bad_initial_parameters_regression_collinear_points() {
             r: Length::new::<centimeter>(10.0 + 0.09 * i as f64),
             phi: Angle::new::<radian>(0.0),
             z: Length::new::<centimeter>(0.0),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
         });
     }
 
@@ -312,8 +904,135 @@ fn track_fitting_bad_initial_parameters_regression_collinear_points() {
     assert!(Track::try_from(cluster).is_err());
 }
 
-fn test_trivial_vertex(z: Length) {
-    let mut points = Vec::new();
+fn helix_points(
+    x0: Length,
+    y0: Length,
+    z0: Length,
+    r: Length,
+    h: Length,
+    t_range: (f64, f64),
+) -> Vec<SpacePoint> {
+    let num_points = 200;
+    (0..num_points)
+        .map(|i| {
+            let t = t_range.0 + (t_range.1 - t_range.0) * i as f64 / (num_points - 1) as f64;
+            let t = Angle::new::<radian>(t);
+            let x = r * t.cos() + x0;
+            let y = r * t.sin() + y0;
+
+            SpacePoint {
+                r: x.hypot(y),
+                phi: y.atan2(x),
+                z: (h / Angle::FULL_TURN) * t + z0,
+                wire_amplitude: 0.0,
+                pad_amplitude: 0.0,
+                sigma_r: Length::new::<millimeter>(1.0),
+                sigma_rphi: Length::new::<millimeter>(1.0),
+                sigma_z: Length::new::<millimeter>(1.0),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn fit_tracks_merge_split_track() {
+    let x0 = Length::new::<centimeter>(20.0);
+    let y0 = Length::new::<centimeter>(0.0);
+    let z0 = Length::new::<centimeter>(0.0);
+    let r = Length::new::<centimeter>(20.0);
+    let h = Length::new::<centimeter>(50.0);
+    // Same helix with a gap in the middle (e.g. a dead region). A few points
+    // are enough, and keep the fits fast.
+    let first = helix_points(x0, y0, z0, r, h, (-0.5 * PI, -0.1))
+        .into_iter()
+        .step_by(10)
+        .collect();
+    let second = helix_points(x0, y0, z0, r, h, (0.1, 0.5 * PI))
+        .into_iter()
+        .step_by(10)
+        .collect();
+
+    let tracks = fit_tracks(vec![Cluster(first), Cluster(second)]);
+    assert_eq!(tracks.len(), 1);
+    assert!(tracks[0].num_iter() > 0);
+}
+
+#[test]
+fn fit_tracks_failure_reasons() {
+    let x0 = Length::new::<centimeter>(0.0);
+    let y0 = Length::new::<centimeter>(15.0);
+    let z0 = Length::new::<centimeter>(0.0);
+    let r = Length::new::<centimeter>(3.0);
+    let h = Length::new::<centimeter>(20.0);
+    let good = helix_points(x0, y0, z0, r, h, (-0.9 * PI, 0.9 * PI));
+    // Repeated points can not produce initial fit parameters.
+    let bad = vec![good[0]; 100];
+
+    let (tracks, failures) = fit_tracks_with_failures(
+        vec![Cluster(good), Cluster(bad)],
+        &MagneticField::default(),
+        &ReconstructionConfig::default(),
+    );
+    assert_eq!(tracks.len(), 1);
+    assert_eq!(failures.len(), 1);
+    assert!(matches!(
+        failures[0],
+        TryTrackFromClusterError::NoInitialParameters
+    ));
+}
+
+#[test]
+fn fit_track_not_enough_points() {
+    let x0 = Length::new::<centimeter>(0.0);
+    let y0 = Length::new::<centimeter>(15.0);
+    let z0 = Length::new::<centimeter>(0.0);
+    let r = Length::new::<centimeter>(3.0);
+    let h = Length::new::<centimeter>(20.0);
+    let points = helix_points(x0, y0, z0, r, h, (-0.9 * PI, 0.9 * PI));
+    let cluster = Cluster(points[..2].to_vec());
+
+    assert!(matches!(
+        Track::try_from(cluster.clone()),
+        Err(TryTrackFromClusterError::NotEnoughPoints(2))
+    ));
+    assert!(matches!(
+        fit_track_kalman(cluster),
+        Err(TryTrackFromClusterError::NotEnoughPoints(2))
+    ));
+}
+
+#[test]
+fn fit_tracks_vetoes() {
+    let x0 = Length::new::<centimeter>(0.0);
+    let y0 = Length::new::<centimeter>(15.0);
+    let z0 = Length::new::<centimeter>(0.0);
+    let r = Length::new::<centimeter>(3.0);
+    let h = Length::new::<centimeter>(20.0);
+    let good = helix_points(x0, y0, z0, r, h, (-0.9 * PI, 0.9 * PI));
+    let bad = vec![good[0]; 100];
+    let clusters = vec![Cluster(good), Cluster(bad)];
+    let config = ReconstructionConfig::default();
+
+    let mut num_calls = 0;
+    let tracks = fit_tracks_with_vetoes(
+        clusters.clone(),
+        &config,
+        |cluster| {
+            num_calls += 1;
+            cluster.0.windows(2).all(|w| w[0] == w[1])
+        },
+        |_| false,
+    );
+    assert_eq!(num_calls, 2);
+    assert_eq!(tracks.len(), 1);
+
+    let tracks = fit_tracks_with_vetoes(clusters, &config, |_| false, |_| true);
+    assert!(tracks.is_empty());
+}
+
+#[test]
+fn fit_tracks_keep_opposite_tracks() {
+    let mut raw_points = Vec::new();
 
     let r = Length::new::<centimeter>(20.0);
     let num_points = 2000;
@@ -325,21 +1044,305 @@ fn test_trivial_vertex(z: Length) {
         let point = SpacePoint {
             r: (x * x + y * y).sqrt(),
             phi: y.atan2(x),
-            z,
+            z: Length::new::<meter>(0.0),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
         };
 
         if is_within_tpc_volume(&point) {
-            points.push(point);
+            raw_points.push(point);
         }
     }
 
-    let clusters = cluster_spacepoints(points).clusters;
-    let tracks = clusters
-        .into_iter()
-        .map(|cluster| Track::try_from(cluster).unwrap())
-        .collect();
-    let vertex = find_vertices(tracks).primary.unwrap().position;
-    let diff_x = (vertex.x - Length::new::<meter>(0.0)).abs();
+    let clusters = cluster_spacepoints(raw_points).clusters;
+    assert_eq!(clusters.len(), 2);
+    // Both halves are the same circle, but they go in opposite directions
+    // from the origin.
+    let tracks = fit_tracks(clusters);
+    assert_eq!(tracks.len(), 2);
+}
+
+#[test]
+fn fit_tracks_keep_different_tracks() {
+    let z0 = Length::new::<centimeter>(0.0);
+    let r = Length::new::<centimeter>(3.0);
+    let h = Length::new::<centimeter>(20.0);
+    let first = helix_points(
+        Length::new::<centimeter>(0.0),
+        Length::new::<centimeter>(15.0),
+        z0,
+        r,
+        h,
+        (-0.9 * PI, 0.9 * PI),
+    );
+    let second = helix_points(
+        Length::new::<centimeter>(0.0),
+        Length::new::<centimeter>(-15.0),
+        z0,
+        r,
+        h,
+        (-0.9 * PI, 0.9 * PI),
+    );
+
+    let tracks = fit_tracks(vec![Cluster(first), Cluster(second)]);
+    assert_eq!(tracks.len(), 2);
+}
+
+#[test]
+fn tag_cosmics_back_to_back_clusters() {
+    let x0 = Length::new::<meter>(5.02);
+    let r = Length::new::<meter>(5.0);
+    let (mut top, mut bottom) = (Vec::new(), Vec::new());
+    let num_points = 100;
+    for i in 0..num_points {
+        let t = Angle::new::<radian>(-0.04 + 0.08 * i as f64 / num_points as f64);
+        let x = x0 - r * t.cos();
+        let y = r * t.sin();
+
+        let point = SpacePoint {
+            r: x.hypot(y),
+            phi: y.atan2(x),
+            z: Length::new::<meter>(2.0 * t.get::<radian>()),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        };
+        if is_within_tpc_volume(&point) {
+            if y > Length::new::<meter>(0.0) {
+                top.push(point);
+            } else {
+                bottom.push(point);
+            }
+        }
+    }
+
+    let result = tag_cosmics(vec![Cluster(top), Cluster(bottom)]);
+    assert!(result.remainder.is_empty());
+    assert_eq!(result.cosmics.len(), 1);
+    assert!(result.cosmics[0].is_cosmic());
+}
+
+#[test]
+fn tag_cosmics_keep_low_momentum_tracks() {
+    let mut raw_points = Vec::new();
+
+    let r = Length::new::<centimeter>(20.0);
+    let num_points = 400;
+    for i in 0..num_points {
+        let theta = Angle::FULL_TURN * i as f64 / num_points as f64;
+        let x = r * theta.cos() + r;
+        let y = r * theta.sin();
+
+        let point = SpacePoint {
+            r: (x * x + y * y).sqrt(),
+            phi: y.atan2(x),
+            z: Length::new::<meter>(0.0),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        };
+
+        if is_within_tpc_volume(&point) {
+            raw_points.push(point);
+        }
+    }
+
+    let clusters = cluster_spacepoints(raw_points).clusters;
+    assert_eq!(clusters.len(), 2);
+
+    let result = tag_cosmics(clusters);
+    assert!(result.cosmics.is_empty());
+    assert_eq!(result.remainder.len(), 2);
+    for track in fit_tracks(result.remainder) {
+        assert!(!track.is_cosmic());
+    }
+}
+
+fn trivial_kalman_helix_fit(x0: Length, y0: Length, z0: Length, r: Length, phi0: Angle, h: Length) {
+    let mut raw_points = Vec::new();
+    let num_points = 2000;
+    for i in 0..num_points {
+        let t = Angle::FULL_TURN * i as f64 / num_points as f64 - Angle::HALF_TURN;
+        let coord = Coordinate {
+            x: r * (t + phi0).cos() + x0,
+            y: r * (t + phi0).sin() + y0,
+            z: (h / Angle::FULL_TURN) * t + z0,
+        };
+
+        let point = SpacePoint {
+            r: coord.x.hypot(coord.y),
+            phi: coord.y.atan2(coord.x),
+            z: coord.z,
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        };
+
+        if is_within_tpc_volume(&point) {
+            raw_points.push(point);
+        }
+    }
+
+    let clustering_result = cluster_spacepoints(raw_points);
+    assert_eq!(clustering_result.clusters.len(), 2);
+
+    for cluster in clustering_result.clusters {
+        let track = fit_track_kalman(cluster.clone()).unwrap();
+
+        for &point in &cluster {
+            let t = track.helix.closest_t(point, f64::EPSILON, 20);
+            let closest = track.at(t);
+            let diff = (closest.x - point.x())
+                .hypot(closest.y - point.y())
+                .hypot(closest.z - point.z);
+            assert!(diff < Length::new::<millimeter>(0.1), "{diff:?}");
+        }
+    }
+}
+
+#[test]
+fn trivial_kalman_track_fitting() {
+    for (x0, y0, phi0) in [
+        (20.0, 20.0, -3.0 * PI / 4.0),
+        (-20.0, 20.0, -PI / 4.0),
+        (-20.0, -20.0, PI / 4.0),
+        (20.0, -20.0, 3.0 * PI / 4.0),
+    ] {
+        for h in [50.0, -50.0, 0.0] {
+            trivial_kalman_helix_fit(
+                Length::new::<centimeter>(x0),
+                Length::new::<centimeter>(y0),
+                Length::new::<centimeter>(0.0),
+                Length::new::<centimeter>(30.0),
+                Angle::new::<radian>(phi0),
+                Length::new::<centimeter>(h),
+            );
+        }
+    }
+}
+
+#[test]
+fn track_fit_algorithm_kalman() {
+    let x0 = Length::new::<centimeter>(0.0);
+    let y0 = Length::new::<centimeter>(15.0);
+    let z0 = Length::new::<centimeter>(0.0);
+    let r = Length::new::<centimeter>(3.0);
+    let h = Length::new::<centimeter>(20.0);
+    let cluster = Cluster(helix_points(x0, y0, z0, r, h, (-0.9 * PI, 0.9 * PI)));
+    let mut config = ReconstructionConfig::default();
+    config.track_fit.algorithm = TrackFitAlgorithm::Kalman;
+
+    assert_eq!(
+        fit_track_with_config(cluster.clone(), &config).unwrap(),
+        fit_track_kalman(cluster).unwrap()
+    );
+}
+
+#[test]
+fn fit_tracks_in_field_map() {
+    let x0 = Length::new::<centimeter>(0.0);
+    let y0 = Length::new::<centimeter>(15.0);
+    let z0 = Length::new::<centimeter>(0.0);
+    let r = Length::new::<centimeter>(3.0);
+    let h = Length::new::<centimeter>(20.0);
+    let mut rng = StdRng::seed_from_u64(0);
+    let points = helix_points(x0, y0, z0, r, h, (-0.9 * PI, 0.9 * PI))
+        .into_iter()
+        .map(|sp| SpacePoint {
+            r: sp.r + Length::new::<millimeter>(rng.sample::<f64, _>(StandardNormal)),
+            ..sp
+        })
+        .collect();
+    let clusters = vec![Cluster(points)];
+    let mut config = ReconstructionConfig::default();
+    config.track_fit.algorithm = TrackFitAlgorithm::Kalman;
+
+    let nominal = fit_tracks_with_config(clusters.clone(), &config);
+    assert_eq!(
+        fit_tracks_in_field(clusters.clone(), &MagneticField::default(), &config),
+        nominal
+    );
+    // Weaker field away from the center of the solenoid.
+    let map = FieldMap::try_from(
+        r#"{
+            "r": [0.0, 0.3],
+            "z": [-1.5, 0.0, 1.5],
+            "b_r": [[0.0, 0.0], [0.0, 0.0], [0.0, 0.0]],
+            "b_z": [[0.1, 0.1], [0.2, 0.2], [0.1, 0.1]]
+        }"#
+        .as_bytes(),
+    )
+    .unwrap();
+    let tracks = fit_tracks_in_field(clusters, &MagneticField::Map(map), &config);
+    assert_eq!(tracks.len(), 1);
+    assert_ne!(tracks, nominal);
+}
+
+#[test]
+fn kalman_track_fitting_bad_initial_parameters_regression() {
+    let mut points = Vec::new();
+    for i in 0..100 {
+        points.push(SpacePoint {
+            r: Length::new::<centimeter>(10.0 + 0.09 * i as f64),
+            phi: Angle::new::<radian>(0.0),
+            z: Length::new::<centimeter>(0.0),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        });
+    }
+
+    let clustering_result = cluster_spacepoints(points);
+    assert_eq!(clustering_result.clusters.len(), 1);
+
+    let cluster = clustering_result.clusters[0].clone();
+    assert!(fit_track_kalman(cluster).is_err());
+}
+
+fn test_trivial_vertex(z: Length) {
+    let mut points = Vec::new();
+
+    let r = Length::new::<centimeter>(20.0);
+    let num_points = 2000;
+    for i in 0..num_points {
+        let theta = Angle::FULL_TURN * i as f64 / num_points as f64;
+        let x = r * theta.cos() + r;
+        let y = r * theta.sin();
+
+        let point = SpacePoint {
+            r: (x * x + y * y).sqrt(),
+            phi: y.atan2(x),
+            z,
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        };
+
+        if is_within_tpc_volume(&point) {
+            points.push(point);
+        }
+    }
+
+    let clusters = cluster_spacepoints(points).clusters;
+    let tracks = clusters
+        .into_iter()
+        .map(|cluster| Track::try_from(cluster).unwrap())
+        .collect();
+    let vertex = find_vertices(tracks).primary.unwrap().position;
+    let diff_x = (vertex.x - Length::new::<meter>(0.0)).abs();
     let diff_y = (vertex.y - Length::new::<meter>(0.0)).abs();
     let diff_z = (vertex.z - z).abs();
     assert!(diff_x < Length::new::<meter>(1e-6));
@@ -355,3 +1358,1165 @@ fn trivial_vertex_fitting() {
     test_trivial_vertex(Length::new::<meter>(-0.5));
     test_trivial_vertex(Length::new::<meter>(-1.0));
 }
+
+#[test]
+fn single_track_vertex_fitting_beam_spot() {
+    let mut points = Vec::new();
+
+    let r = Length::new::<centimeter>(20.0);
+    let z = Length::new::<centimeter>(30.0);
+    let num_points = 2000;
+    // Half a circle through the origin i.e. a single track.
+    for i in 0..num_points {
+        let theta = Angle::HALF_TURN * i as f64 / num_points as f64;
+        let x = r * theta.cos() + r;
+        let y = r * theta.sin();
+
+        let point = SpacePoint {
+            r: (x * x + y * y).sqrt(),
+            phi: y.atan2(x),
+            z,
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        };
+
+        if is_within_tpc_volume(&point) {
+            points.push(point);
+        }
+    }
+
+    let clusters = cluster_spacepoints(points).clusters;
+    assert_eq!(clusters.len(), 1);
+    let tracks: Vec<Track> = clusters
+        .into_iter()
+        .map(|cluster| Track::try_from(cluster).unwrap())
+        .collect();
+
+    assert!(find_vertices(tracks.clone()).primary.is_none());
+
+    let mut config = ReconstructionConfig::default();
+    config.vertex_fit.beam_spot = Some(BeamSpotConstraint {
+        sigma_x: Length::new::<millimeter>(3.0),
+        sigma_y: Length::new::<millimeter>(3.0),
+    });
+    let vertex = find_vertices_with_config(tracks, &config).primary.unwrap();
+    assert_eq!(vertex.tracks.len(), 1);
+
+    let position = vertex.position;
+    assert!(position.x.abs() < Length::new::<millimeter>(0.1));
+    assert!(position.y.abs() < Length::new::<millimeter>(0.1));
+    assert!((position.z - z).abs() < Length::new::<millimeter>(0.1));
+}
+
+#[test]
+fn vertex_fitting_covariance() {
+    let mut points = Vec::new();
+
+    let r = Length::new::<centimeter>(20.0);
+    let num_points = 2000;
+    // Two circles through the origin with perpendicular tangents at the origin.
+    for (cx, cy) in [
+        (r, Length::new::<meter>(0.0)),
+        (Length::new::<meter>(0.0), r),
+    ] {
+        for i in 0..num_points {
+            let theta = Angle::FULL_TURN * i as f64 / num_points as f64;
+            let x = r * theta.cos() + cx;
+            let y = r * theta.sin() + cy;
+
+            let point = SpacePoint {
+                r: (x * x + y * y).sqrt(),
+                phi: y.atan2(x),
+                z: Length::new::<meter>(0.2),
+                wire_amplitude: 0.0,
+                pad_amplitude: 0.0,
+                sigma_r: Length::new::<millimeter>(1.0),
+                sigma_rphi: Length::new::<millimeter>(1.0),
+                sigma_z: Length::new::<millimeter>(1.0),
+            };
+
+            if is_within_tpc_volume(&point) {
+                points.push(point);
+            }
+        }
+    }
+
+    let clusters = cluster_spacepoints(points).clusters;
+    let tracks = clusters
+        .into_iter()
+        .map(|cluster| Track::try_from(cluster).unwrap())
+        .collect();
+    let vertex = find_vertices(tracks).primary.unwrap();
+    assert!(vertex.chi2 < 1e-6);
+
+    let covariance = vertex.covariance.unwrap();
+    for (i, row) in covariance.iter().enumerate() {
+        assert!(row[i] > Area::new::<square_meter>(0.0));
+        // Can't be worse than the resolution of a single track.
+        assert!(row[i].sqrt() < Length::new::<millimeter>(15.0));
+        for (j, value) in row.iter().enumerate() {
+            let diff = (*value - covariance[j][i]).abs();
+            assert!(diff < Area::new::<square_meter>(1e-12));
+        }
+    }
+}
+
+#[test]
+fn track_fitting_covariance() {
+    let points = helix_points(
+        Length::new::<centimeter>(20.0),
+        Length::new::<centimeter>(0.0),
+        Length::new::<centimeter>(0.0),
+        Length::new::<centimeter>(20.0),
+        Length::new::<centimeter>(50.0),
+        (-0.5 * PI, 0.5 * PI),
+    );
+
+    for track in [
+        Track::try_from(Cluster(points.clone())).unwrap(),
+        fit_track_kalman(Cluster(points)).unwrap(),
+    ] {
+        let covariance = track.covariance().unwrap();
+        for (i, row) in covariance.iter().enumerate() {
+            assert!(row[i] > 0.0);
+            for (j, value) in row.iter().enumerate() {
+                let diff = (value - covariance[j][i]).abs();
+                assert!(diff <= 1e-6 * (row[i] * covariance[j][j]).sqrt());
+            }
+        }
+        // Can't be worse than the resolution of a single SpacePoint (even
+        // after extrapolating to the perigee).
+        assert!(covariance[0][0].sqrt() < 1e-2);
+    }
+}
+
+#[test]
+fn track_momentum_from_perigee() {
+    let track = Track::from_perigee(Perigee {
+        d0: Length::new::<meter>(0.0),
+        z0: Length::new::<meter>(0.0),
+        phi0: Angle::new::<radian>(0.3),
+        curvature: ReciprocalLength::new::<reciprocal_meter>(-2.0),
+        tan_lambda: 0.75,
+    });
+    let field = MagneticField::Uniform(MagneticFluxDensity::new::<tesla>(1.0));
+    let momentum = track.momentum(&field).unwrap();
+    // A singly charged particle with a radius of 0.5 meters in a 1 tesla field
+    // has a transverse momentum of about 0.15 GeV/c.
+    let pt = 0.5 * 1.602_176_634e-19;
+
+    let unit = |value: Momentum| value.get::<kilogram_meter_per_second>();
+    assert!((unit(momentum.pt) - pt).abs() < 1e-9 * pt);
+    assert!((unit(momentum.pz) - 0.75 * pt).abs() < 1e-9 * pt);
+    assert!((unit(momentum.p) - 1.25 * pt).abs() < 1e-9 * pt);
+    assert!(momentum.sigma_pt.is_none());
+    assert!(momentum.sigma_pz.is_none());
+    assert!(momentum.sigma_p.is_none());
+
+    // The momentum scales with the field.
+    let field = MagneticField::Uniform(MagneticFluxDensity::new::<tesla>(-2.0));
+    let doubled = track.momentum(&field).unwrap();
+    assert!((unit(doubled.pt) - 2.0 * pt).abs() < 1e-9 * pt);
+}
+
+#[test]
+fn track_momentum_uncertainty() {
+    let points = helix_points(
+        Length::new::<centimeter>(20.0),
+        Length::new::<centimeter>(0.0),
+        Length::new::<centimeter>(0.0),
+        Length::new::<centimeter>(20.0),
+        Length::new::<centimeter>(50.0),
+        (-0.5 * PI, 0.5 * PI),
+    );
+    let track = Track::try_from(Cluster(points)).unwrap();
+    let momentum = track.momentum(&MagneticField::default()).unwrap();
+
+    let sigma_pt = momentum.sigma_pt.unwrap();
+    assert!(sigma_pt.value > 0.0);
+    assert!(sigma_pt < momentum.pt);
+    assert!(momentum.sigma_pz.unwrap().value > 0.0);
+    assert!(momentum.sigma_p.unwrap().value > 0.0);
+    assert!(momentum.p >= momentum.pt);
+}
+
+#[test]
+fn track_charge_sign_from_perigee() {
+    let track = Track::from_perigee(Perigee {
+        d0: Length::new::<meter>(0.0),
+        z0: Length::new::<meter>(0.0),
+        phi0: Angle::new::<radian>(1.0),
+        curvature: ReciprocalLength::new::<reciprocal_meter>(-2.0),
+        tan_lambda: 0.2,
+    });
+
+    let estimate = track.charge_sign(&MagneticField::default()).unwrap();
+    assert_eq!(estimate.sign, Some(ChargeSign::Positive));
+    assert!(estimate.significance.is_infinite());
+    assert_eq!(estimate.confidence, 1.0);
+
+    let field = MagneticField::Uniform(MagneticFluxDensity::new::<tesla>(-1.0));
+    let estimate = track.charge_sign(&field).unwrap();
+    assert_eq!(estimate.sign, Some(ChargeSign::Negative));
+
+    let field = MagneticField::Uniform(MagneticFluxDensity::new::<tesla>(0.0));
+    let estimate = track.charge_sign(&field).unwrap();
+    assert_eq!(estimate.sign, None);
+    assert_eq!(estimate.confidence, 0.5);
+}
+
+#[test]
+fn track_charge_sign_low_significance() {
+    let mut track = Track::from_perigee(Perigee {
+        d0: Length::new::<meter>(0.0),
+        z0: Length::new::<meter>(0.0),
+        phi0: Angle::new::<radian>(0.0),
+        curvature: ReciprocalLength::new::<reciprocal_meter>(0.5),
+        tan_lambda: 0.0,
+    });
+    let mut covariance = [[0.0; 5]; 5];
+    for (i, row) in covariance.iter_mut().enumerate() {
+        row[i] = 1e-6;
+    }
+    covariance[3][3] = 1.0;
+    track.covariance = Some(covariance);
+
+    let estimate = track.charge_sign(&MagneticField::default()).unwrap();
+    assert_eq!(estimate.sign, None);
+    assert!((estimate.significance - 0.5).abs() < 1e-9);
+    // Standard normal cumulative distribution at 0.5.
+    assert!((estimate.confidence - 0.691_462_461_274_013).abs() < 1e-9);
+}
+
+#[test]
+fn track_charge_sign_fitted() {
+    // Track that bends counter-clockwise while moving outwards.
+    let points = helix_points(
+        Length::new::<centimeter>(20.0),
+        Length::new::<centimeter>(0.0),
+        Length::new::<centimeter>(0.0),
+        Length::new::<centimeter>(20.0),
+        Length::new::<centimeter>(50.0),
+        (-0.8 * PI, -0.1 * PI),
+    );
+    let track = Track::try_from(Cluster(points)).unwrap();
+
+    let estimate = track.charge_sign(&MagneticField::default()).unwrap();
+    assert_eq!(estimate.sign, Some(ChargeSign::Negative));
+    assert!(estimate.significance > MIN_CHARGE_SIGN_SIGNIFICANCE);
+    assert!(estimate.confidence > 0.99);
+}
+
+#[test]
+fn track_fitting_chi2_and_ndf() {
+    let points = helix_points(
+        Length::new::<centimeter>(20.0),
+        Length::new::<centimeter>(0.0),
+        Length::new::<centimeter>(0.0),
+        Length::new::<centimeter>(20.0),
+        Length::new::<centimeter>(50.0),
+        (-0.5 * PI, 0.5 * PI),
+    );
+    let track = Track::try_from(Cluster(points.clone())).unwrap();
+
+    assert_eq!(track.ndf(), 2 * points.len() - 5);
+    assert!(track.chi2() < 1e-6);
+    let sum = points.iter().map(|&p| track.residual(p).chi2).sum::<f64>();
+    assert!((track.chi2() - sum).abs() < 1e-9);
+
+    let track = fit_track_kalman(Cluster(points.clone())).unwrap();
+    assert_eq!(track.ndf(), 2 * points.len() - 5);
+    assert!(track.chi2() >= 0.0);
+}
+
+#[test]
+fn track_fitting_chi2_probability_distribution() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let sigma = Length::new::<millimeter>(1.0);
+    let num_tracks = 30;
+
+    let mut least_squares = Vec::new();
+    let mut kalman = Vec::new();
+    for _ in 0..num_tracks {
+        // Track going outwards through the rTPC volume.
+        let points: Vec<_> = helix_points(
+            Length::new::<centimeter>(20.0),
+            Length::new::<centimeter>(0.0),
+            Length::new::<centimeter>(0.0),
+            Length::new::<centimeter>(20.0),
+            Length::new::<centimeter>(50.0),
+            (0.68 * PI, 0.82 * PI),
+        )
+        .into_iter()
+        .step_by(20)
+        .map(|mut p| {
+            let (dr, drphi, dz): (f64, f64, f64) = (
+                rng.sample(StandardNormal),
+                rng.sample(StandardNormal),
+                rng.sample(StandardNormal),
+            );
+            p.r += sigma * dr;
+            p.phi += Angle::new::<radian>((sigma / p.r).get::<ratio>() * drphi);
+            p.z += sigma * dz;
+            p
+        })
+        .collect();
+
+        let track = Track::try_from(Cluster(points.clone())).unwrap();
+        least_squares.push(chi2_probability(track.chi2(), track.ndf()));
+        let track = fit_track_kalman(Cluster(points)).unwrap();
+        kalman.push(chi2_probability(track.chi2(), track.ndf()));
+    }
+    // The probability of a correct fit is uniformly distributed.
+    for probabilities in [least_squares, kalman] {
+        let mean = probabilities.iter().sum::<f64>() / num_tracks as f64;
+        assert!((mean - 0.5).abs() < 0.1, "{mean}");
+        let fraction =
+            probabilities.iter().filter(|&&p| p < 0.1).count() as f64 / num_tracks as f64;
+        assert!((fraction - 0.1).abs() < 0.1, "{fraction}");
+    }
+}
+
+#[test]
+fn track_residual() {
+    let track = extrapolation_track();
+    let c = track.at(0.3);
+    let shift = Length::new::<millimeter>(2.0);
+    let sp = SpacePoint {
+        r: c.x.hypot(c.y),
+        phi: c.y.atan2(c.x),
+        z: c.z + shift,
+        wire_amplitude: 0.0,
+        pad_amplitude: 0.0,
+        sigma_r: Length::new::<millimeter>(1.0),
+        sigma_rphi: Length::new::<millimeter>(1.0),
+        sigma_z: Length::new::<millimeter>(1.0),
+    };
+    let residual = track.residual(sp);
+
+    // The closest point on the track can only be closer than the original.
+    let distance = residual
+        .radial
+        .hypot(residual.azimuthal)
+        .hypot(residual.axial);
+    assert!(distance <= shift);
+    assert!(residual.axial > Length::new::<meter>(0.0));
+    let expected = (distance / Length::new::<millimeter>(1.0))
+        .get::<ratio>()
+        .powi(2);
+    assert!((residual.chi2 - expected).abs() < 1e-9);
+
+    let sp = SpacePoint { z: c.z, ..sp };
+    assert!(track.residual(sp).chi2 < 1e-12);
+}
+
+#[test]
+fn track_residual_with_config() {
+    let track = extrapolation_track();
+    let c = track.at(0.3);
+    let sp = SpacePoint {
+        r: c.x.hypot(c.y),
+        phi: c.y.atan2(c.x),
+        z: c.z + Length::new::<centimeter>(5.0),
+        wire_amplitude: 0.0,
+        pad_amplitude: 0.0,
+        sigma_r: Length::new::<millimeter>(1.0),
+        sigma_rphi: Length::new::<millimeter>(1.0),
+        sigma_z: Length::new::<millimeter>(1.0),
+    };
+    let mut config = ReconstructionConfig::default();
+    let expected = track.residual(sp);
+    assert_eq!(track.residual_with_config(sp, &config), expected);
+
+    config.track_fit.max_num_closest_t_iter = 0;
+    let residual = track.residual_with_config(sp, &config);
+    assert!((residual.t - expected.t).abs() > 1e-6);
+}
+
+#[test]
+fn track_from_perigee_no_covariance() {
+    assert!(Track::from_perigee(extrapolation_track().perigee())
+        .covariance()
+        .is_none());
+}
+
+// Two tracks (half circles leaving the beamline at `z`) with perpendicular
+// tangents at the beamline. The second track is shifted along `z`.
+// Only half of each circle is used; a full circle would cross the rTPC twice
+// and give 2 tracks.
+fn two_perpendicular_tracks(z: Length, z_shift: Length) -> Vec<Track> {
+    let mut points = Vec::new();
+
+    let r = Length::new::<centimeter>(20.0);
+    let num_points = 1000;
+    for (cx, cy, z_track) in [
+        (r, Length::new::<meter>(0.0), z),
+        (Length::new::<meter>(0.0), r, z + z_shift),
+    ] {
+        let start = cy.atan2(cx);
+        for i in 0..num_points {
+            let theta = start + Angle::HALF_TURN * i as f64 / num_points as f64;
+            let x = r * theta.cos() + cx;
+            let y = r * theta.sin() + cy;
+
+            let point = SpacePoint {
+                r: (x * x + y * y).sqrt(),
+                phi: y.atan2(x),
+                z: z_track,
+                wire_amplitude: 0.0,
+                pad_amplitude: 0.0,
+                sigma_r: Length::new::<millimeter>(1.0),
+                sigma_rphi: Length::new::<millimeter>(1.0),
+                sigma_z: Length::new::<millimeter>(1.0),
+            };
+
+            if is_within_tpc_volume(&point) {
+                points.push(point);
+            }
+        }
+    }
+
+    cluster_spacepoints(points)
+        .clusters
+        .into_iter()
+        .map(|cluster| Track::try_from(cluster).unwrap())
+        .collect()
+}
+
+#[test]
+fn vertex_fitting_quality() {
+    let vertex = find_vertices(two_perpendicular_tracks(
+        Length::new::<meter>(0.2),
+        Length::new::<meter>(0.0),
+    ))
+    .primary
+    .unwrap();
+
+    assert_eq!(vertex.ndf, 1);
+    assert!(vertex.probability > 0.99);
+    assert_eq!(vertex.weights.len(), vertex.tracks.len());
+    // Even a perfectly compatible track has a weight of
+    // 1 / (1 + exp(-max_track_chi2 / 2)) i.e. ~0.989 by default.
+    assert!(vertex.weights.iter().all(|&weight| weight > 0.98));
+    assert!(!vertex.has_incompatible_tracks());
+
+    let mut config = ReconstructionConfig::default();
+    config.vertex_fit.track_resolution = Length::new::<millimeter>(1.0);
+    let vertex = find_vertices_with_config(
+        two_perpendicular_tracks(Length::new::<meter>(0.2), Length::new::<centimeter>(2.0)),
+        &config,
+    )
+    .primary
+    .unwrap();
+
+    assert!(vertex.probability < 1e-6);
+    assert!(vertex.has_incompatible_tracks());
+}
+
+#[test]
+fn vertex_resolution() {
+    let vertex = find_vertices(two_perpendicular_tracks(
+        Length::new::<meter>(0.2),
+        Length::new::<meter>(0.0),
+    ))
+    .primary
+    .unwrap();
+
+    let resolution = vertex.resolution().unwrap();
+    for sigma in [resolution.sigma_x, resolution.sigma_y, resolution.sigma_z] {
+        assert!(sigma > Length::new::<meter>(0.0));
+        // Long tracks with 1 mm SpacePoints. Better than the default
+        // `track_resolution` assumed by the vertex fit.
+        assert!(sigma < Length::new::<millimeter>(15.0));
+    }
+    for (i, row) in resolution.covariance.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
+            let diff = (*value - resolution.covariance[j][i]).abs();
+            assert!(diff < Area::new::<square_meter>(1e-12));
+        }
+    }
+    // Tracks that do not come from a fit have no covariance.
+    let vertex = VertexInfo {
+        tracks: vertex
+            .tracks
+            .iter()
+            .map(|(track, t)| (Track::from_perigee(track.perigee()), *t))
+            .collect(),
+        ..vertex
+    };
+    assert!(vertex.resolution().is_none());
+}
+
+#[test]
+fn multiple_vertices() {
+    let z_primary = Length::new::<meter>(-0.5);
+    let z_secondary = Length::new::<meter>(0.5);
+    let tracks: Vec<Track> = two_perpendicular_tracks(z_primary, Length::new::<meter>(0.0))
+        .into_iter()
+        .chain(two_perpendicular_tracks(
+            z_secondary,
+            Length::new::<meter>(0.0),
+        ))
+        .collect();
+    assert_eq!(tracks.len(), 4);
+
+    let result = find_vertices(tracks.clone());
+    assert_eq!(result.secondaries.len(), 1);
+    assert!(result.remainder.is_empty());
+    assert_eq!(result.vertices().count(), 2);
+    let mut z = result
+        .vertices()
+        .map(|vertex| {
+            assert_eq!(vertex.tracks.len(), 2);
+            vertex.position.z.get::<meter>()
+        })
+        .collect::<Vec<_>>();
+    z.sort_by(|a, b| a.total_cmp(b));
+    assert!((z[0] - z_primary.get::<meter>()).abs() < 1e-6);
+    assert!((z[1] - z_secondary.get::<meter>()).abs() < 1e-6);
+
+    let mut config = ReconstructionConfig::default();
+    config.vertex_fit.max_num_vertices = Some(1);
+    let result = find_vertices_with_config(tracks, &config);
+    assert!(result.primary.is_some());
+    assert!(result.secondaries.is_empty());
+    assert_eq!(result.remainder.len(), 2);
+}
+
+// SpacePoints along half a circle (at a constant `z`) of radius 20 cm centered
+// at a distance `20 cm + offset` from the beamline along the `angle` direction.
+fn half_circle_spacepoints(angle: f64, offset: Length, z: Length) -> Vec<SpacePoint> {
+    let r = Length::new::<centimeter>(20.0);
+    let (cx, cy) = ((r + offset) * angle.cos(), (r + offset) * angle.sin());
+    (0..2000)
+        .map(|i| {
+            let theta = Angle::new::<radian>(angle) + Angle::HALF_TURN * i as f64 / 2000.0;
+            let x = r * theta.cos() - cx;
+            let y = r * theta.sin() - cy;
+
+            SpacePoint {
+                r: x.hypot(y),
+                phi: y.atan2(x),
+                z,
+                wire_amplitude: 0.0,
+                pad_amplitude: 0.0,
+                sigma_r: Length::new::<millimeter>(1.0),
+                sigma_rphi: Length::new::<millimeter>(1.0),
+                sigma_z: Length::new::<millimeter>(1.0),
+            }
+        })
+        .filter(is_within_tpc_volume)
+        .collect()
+}
+
+fn half_circle_track(angle: f64, offset: Length, z: Length) -> Track {
+    // A few points are enough, and keep the fit fast.
+    let points = half_circle_spacepoints(angle, offset, z)
+        .into_iter()
+        .step_by(10)
+        .collect();
+
+    Track::try_from(Cluster(points)).unwrap()
+}
+
+#[test]
+fn tag_spiral_electron() {
+    let mut spiral = Vec::new();
+    // Three turns of radius 2 cm around an axis 15 cm away from the beamline.
+    let (cx, cy) = (Length::new::<centimeter>(15.0), Length::new::<meter>(0.0));
+    let radius = Length::new::<centimeter>(2.0);
+    for i in 0..150 {
+        let theta = Angle::FULL_TURN * 3.0 * i as f64 / 150.0;
+        let x = cx + radius * theta.cos();
+        let y = cy + radius * theta.sin();
+
+        spiral.push(SpacePoint {
+            r: x.hypot(y),
+            phi: y.atan2(x),
+            z: Length::new::<centimeter>(-20.0 + 0.1 * i as f64),
+            wire_amplitude: 0.0,
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        });
+    }
+    // Annihilation track far from the spiral.
+    let track = half_circle_spacepoints(
+        PI,
+        Length::new::<meter>(0.0),
+        Length::new::<centimeter>(30.0),
+    );
+    let spacepoints: Vec<_> = spiral.iter().chain(&track).copied().collect();
+
+    let result = tag_spirals(spacepoints);
+    assert_eq!(result.spirals.len(), 1);
+    assert_eq!(result.spirals[0].0.len(), spiral.len());
+    assert_eq!(result.remainder.len(), track.len());
+
+    // Less than a full turn is not a spiral.
+    let result = tag_spirals(spiral[..25].to_vec());
+    assert!(result.spirals.is_empty());
+    assert_eq!(result.remainder.len(), 25);
+}
+
+#[test]
+fn vertex_median_seed() {
+    let z = Length::new::<centimeter>(20.0);
+    // The seed is purely geometrical; no need to fit the tracks.
+    let track = |phi0: f64, d0: Length, z0: Length| {
+        Track::from_perigee(Perigee {
+            d0,
+            z0,
+            phi0: Angle::new::<radian>(phi0),
+            curvature: ReciprocalLength::new::<reciprocal_meter>(5.0),
+            tan_lambda: 0.0,
+        })
+    };
+
+    let zero = Length::new::<meter>(0.0);
+    let mut tracks: Vec<_> = [0.0, 1.5, 3.0, 4.5]
+        .into_iter()
+        .map(|phi0| track(phi0, zero, z))
+        .collect();
+    // Badly fitted track that misses the vertex.
+    tracks.push(track(
+        0.7,
+        Length::new::<centimeter>(4.0),
+        Length::new::<meter>(0.8),
+    ));
+
+    let seed = vertex_fitting::median_seed(&tracks, zero, f64::EPSILON, 20);
+    assert!(seed.x.abs() < Length::new::<millimeter>(1.0));
+    assert!(seed.y.abs() < Length::new::<millimeter>(1.0));
+    assert!((seed.z - z).abs() < Length::new::<millimeter>(1.0));
+
+    let seed = vertex_fitting::median_seed(&tracks[..1], z, f64::EPSILON, 20);
+    assert_eq!((seed.x, seed.y, seed.z), (zero, zero, z));
+}
+
+#[test]
+fn adaptive_vertex_fitting() {
+    let z = Length::new::<centimeter>(20.0);
+    let zero = Length::new::<meter>(0.0);
+    let tracks = vec![
+        half_circle_track(0.0, zero, z),
+        half_circle_track(PI / 2.0, zero, z),
+        // Outlier 2 cm away from the vertex.
+        half_circle_track(4.0, zero, z + Length::new::<centimeter>(2.0)),
+    ];
+
+    let mut config = ReconstructionConfig::default();
+    config.vertex_fit.track_resolution = Length::new::<millimeter>(1.0);
+    let vertex = find_vertices_with_config(tracks.clone(), &config)
+        .primary
+        .unwrap();
+    assert_eq!(vertex.tracks.len(), 3);
+    assert!((vertex.position.z - z).abs() > Length::new::<millimeter>(5.0));
+
+    config.vertex_fit.annealing = Some(AnnealingSchedule::default());
+    let vertex = find_vertices_with_config(tracks, &config).primary.unwrap();
+    assert_eq!(vertex.tracks.len(), 3);
+    assert!((vertex.position.z - z).abs() < Length::new::<millimeter>(0.1));
+    assert!(vertex.position.x.abs() < Length::new::<millimeter>(0.1));
+    assert!(vertex.position.y.abs() < Length::new::<millimeter>(0.1));
+    assert!(vertex.has_incompatible_tracks());
+    assert_eq!(vertex.ndf, 1);
+}
+
+#[test]
+fn vertex_chi2_probability() {
+    assert_eq!(vertex_fitting::chi2_probability(0.0, 3), 1.0);
+    assert_eq!(vertex_fitting::chi2_probability(5.0, 0), 1.0);
+    // With 2 degrees of freedom, the probability is exp(-chi2 / 2).
+    for chi2 in [0.1, 1.0, 2.5, 10.0, 50.0] {
+        let diff = vertex_fitting::chi2_probability(chi2, 2) - (-0.5 * chi2).exp();
+        assert!(diff.abs() < 1e-12);
+    }
+    // Reference values from the closed form expressions of the chi-squared
+    // survival function.
+    for (chi2, ndf, expected) in [
+        (1.0, 1, 0.317_310_507_862_914_04),
+        (3.84, 1, 0.050_043_521_248_705_09),
+        (10.0, 5, 0.075_235_246_146_512_2),
+        (20.0, 30, 0.916_541_527_065_337_2),
+    ] {
+        let diff = vertex_fitting::chi2_probability(chi2, ndf) - expected;
+        assert!(diff.abs() < 1e-9);
+    }
+}
+
+#[test]
+fn track_truncated_mean_dedx() {
+    let mut points = Vec::new();
+    let r = Length::new::<centimeter>(20.0);
+    let num_points = 2000;
+    for i in 0..num_points {
+        let theta = Angle::HALF_TURN * i as f64 / num_points as f64;
+        let x = r * theta.cos() + r;
+        let y = r * theta.sin();
+
+        let point = SpacePoint {
+            r: (x * x + y * y).sqrt(),
+            phi: y.atan2(x),
+            z: Length::new::<meter>(0.0),
+            // Every 10th point is a large (Landau tail) deposit.
+            wire_amplitude: if i % 10 == 0 { 100.0 } else { 1.0 },
+            pad_amplitude: 0.0,
+            sigma_r: Length::new::<millimeter>(1.0),
+            sigma_rphi: Length::new::<millimeter>(1.0),
+            sigma_z: Length::new::<millimeter>(1.0),
+        };
+
+        if is_within_tpc_volume(&point) {
+            points.push(point);
+        }
+    }
+    let num_points = points.len() as f64;
+
+    for track in [
+        Track::try_from(Cluster(points.clone())).unwrap(),
+        fit_track_kalman(Cluster(points)).unwrap(),
+    ] {
+        let length = track.helix.arc_length(track.t_inner(), track.t_outer());
+        let expected = num_points / length;
+
+        let diff = (track.dedx() - expected).abs();
+        assert!(diff < expected * 1e-6);
+    }
+}
+
+#[test]
+fn helix_t_at_radius() {
+    let helix = Helix {
+        x0: Length::new::<centimeter>(20.0),
+        y0: Length::new::<centimeter>(5.0),
+        z0: Length::new::<centimeter>(0.0),
+        r: Length::new::<centimeter>(-20.0),
+        phi0: Angle::new::<radian>(1.0),
+        h: Length::new::<centimeter>(30.0),
+    };
+    let radius = Length::new::<centimeter>(22.0);
+
+    let crossings = helix.t_at_radius(radius);
+    assert_eq!(crossings.len(), 2);
+    for t in crossings {
+        let c = helix.at(t);
+        let diff = c.x.hypot(c.y) - radius;
+        assert!(diff.abs() < Length::new::<meter>(1e-9));
+    }
+
+    assert!(helix
+        .t_at_radius(Length::new::<centimeter>(100.0))
+        .is_empty());
+}
+
+fn extrapolation_track() -> Track {
+    Track {
+        helix: Helix {
+            x0: Length::new::<meter>(0.3),
+            y0: Length::new::<meter>(0.0),
+            z0: Length::new::<centimeter>(10.0),
+            r: Length::new::<meter>(0.3),
+            phi0: Angle::HALF_TURN,
+            h: Length::new::<meter>(0.5),
+        },
+        t_inner: 0.6,
+        t_outer: 0.1,
+        dedx: ReciprocalLength::new::<reciprocal_meter>(0.0),
+        cosmic: false,
+        covariance: None,
+        chi2: 0.0,
+        ndf: 0,
+        num_iter: 0,
+    }
+}
+
+#[test]
+fn track_state_direction() {
+    let track = extrapolation_track();
+    for t in [-2.0, -0.5, 0.0, 0.3, 1.0] {
+        let state = track.state(t);
+        assert_eq!(state.t, t);
+        assert_eq!(state.position, track.at(t));
+
+        let norm = state.direction.iter().map(|d| d.powi(2)).sum::<f64>();
+        assert!((norm - 1.0).abs() < 1e-12);
+        // `t_outer < t_inner`, so going outwards means decreasing `t`.
+        let (before, after) = (track.at(t + 1e-6), track.at(t - 1e-6));
+        let diff = [
+            (after.x - before.x).get::<meter>(),
+            (after.y - before.y).get::<meter>(),
+            (after.z - before.z).get::<meter>(),
+        ];
+        let diff_norm = diff.iter().map(|d| d.powi(2)).sum::<f64>().sqrt();
+        for (d, u) in diff.iter().zip(state.direction) {
+            assert!((d / diff_norm - u).abs() < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn track_extrapolate_to_radius() {
+    let track = extrapolation_track();
+    let radius = Length::new::<centimeter>(40.0);
+
+    let states = track.extrapolate_to_radius(radius);
+    assert_eq!(states.len(), 2);
+    for state in states {
+        let r = state.position.x.hypot(state.position.y);
+        assert!((r - radius).abs() < Length::new::<meter>(1e-9));
+    }
+    assert!(track
+        .extrapolate_to_radius(Length::new::<meter>(1.0))
+        .is_empty());
+}
+
+#[test]
+fn track_extrapolate_to_z() {
+    let track = extrapolation_track();
+    for z in [-1.0, 0.0, 0.3, 2.0] {
+        let z = Length::new::<meter>(z);
+        let state = track.extrapolate_to_z(z).unwrap();
+        assert!((state.position.z - z).abs() < Length::new::<meter>(1e-12));
+    }
+
+    assert!(bv_matching_track(false)
+        .extrapolate_to_z(Length::new::<meter>(0.0))
+        .is_none());
+}
+
+#[test]
+fn track_closest_to_point() {
+    let track = extrapolation_track();
+    let t = 0.3;
+    let state = track.closest_to_point(track.at(t));
+
+    assert!((state.t - t).abs() < 1e-6);
+}
+
+#[test]
+fn track_closest_to_point_with_config() {
+    let track = extrapolation_track();
+    let point = Coordinate {
+        z: track.at(0.3).z + Length::new::<centimeter>(5.0),
+        ..track.at(0.3)
+    };
+    let mut config = ReconstructionConfig::default();
+    let expected = track.closest_to_point(point);
+    assert_eq!(track.closest_to_point_with_config(point, &config), expected);
+
+    config.track_fit.max_num_closest_t_iter = 0;
+    let state = track.closest_to_point_with_config(point, &config);
+    assert!((state.t - expected.t).abs() > 1e-6);
+}
+
+#[test]
+fn track_closest_to_line() {
+    let track = extrapolation_track();
+    let origin = Coordinate {
+        x: Length::new::<meter>(0.0),
+        y: Length::new::<meter>(0.0),
+        z: Length::new::<meter>(0.0),
+    };
+
+    let state = track.closest_to_line(origin, [0.0, 0.0, 2.0]);
+    assert!(state.t.abs() < 1e-6);
+    let expected = track.helix.closest_to_beamline();
+    assert!((state.position.z - expected.z).abs() < Length::new::<meter>(1e-6));
+
+    // A line parallel to the `x` axis at the `z` of the track at `t = 1`.
+    let point = Coordinate {
+        x: Length::new::<meter>(5.0),
+        y: track.at(1.0).y,
+        z: track.at(1.0).z,
+    };
+    let state = track.closest_to_line(point, [1.0, 0.0, 0.0]);
+    assert!((state.t - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn track_closest_approach() {
+    // Both tracks go through the origin with perpendicular directions, but at
+    // a different (constant) `z`.
+    let track = |phi0: f64, z0: f64| {
+        Track::from_perigee(Perigee {
+            d0: Length::new::<meter>(0.0),
+            z0: Length::new::<centimeter>(z0),
+            phi0: Angle::new::<radian>(phi0),
+            curvature: ReciprocalLength::new::<reciprocal_meter>(2.0),
+            tan_lambda: 0.0,
+        })
+    };
+    let (a, b) = (track(0.0, 0.0), track(PI / 2.0, 2.0));
+
+    for (first, second) in [(&a, &b), (&b, &a)] {
+        let approach = first.closest_approach(second);
+        assert!(
+            (approach.distance - Length::new::<centimeter>(2.0)).abs() < Length::new::<meter>(1e-9)
+        );
+        assert!(approach.midpoint.x.abs() < Length::new::<meter>(1e-9));
+        assert!(approach.midpoint.y.abs() < Length::new::<meter>(1e-9));
+        assert!(
+            (approach.midpoint.z - Length::new::<centimeter>(1.0)).abs()
+                < Length::new::<meter>(1e-9)
+        );
+        assert_eq!(approach.first.position, first.at(approach.first.t));
+        assert_eq!(approach.second.position, second.at(approach.second.t));
+    }
+}
+
+#[test]
+fn track_closest_approach_with_config() {
+    let track = |phi0: f64, z0: f64| {
+        Track::from_perigee(Perigee {
+            d0: Length::new::<meter>(0.0),
+            z0: Length::new::<centimeter>(z0),
+            phi0: Angle::new::<radian>(phi0),
+            curvature: ReciprocalLength::new::<reciprocal_meter>(2.0),
+            tan_lambda: 0.5,
+        })
+    };
+    let (a, b) = (track(0.0, 0.0), track(PI / 2.0, 2.0));
+    let mut config = ReconstructionConfig::default();
+    let expected = a.closest_approach(&b);
+    assert_eq!(a.closest_approach_with_config(&b, &config), expected);
+
+    config.track_fit.max_num_closest_t_iter = 0;
+    let approach = a.closest_approach_with_config(&b, &config);
+    assert!((approach.distance - expected.distance).abs() > Length::new::<millimeter>(0.1));
+}
+
+#[test]
+fn track_path_length() {
+    let track = extrapolation_track();
+    // The track goes through the origin, so `r = 2 * R * sin(u / 2)` where `u`
+    // is the offset from the perigee.
+    let offset = |r: f64| 2.0 * (r / 0.6).asin();
+    let length = |u: f64| u * (0.3f64.powi(2) + (0.5 / (2.0 * PI)).powi(2)).sqrt();
+
+    let (r1, r2) = (0.15, 0.25);
+    let expected = length(offset(r2) - offset(r1));
+    for (a, b) in [(r1, r2), (r2, r1)] {
+        let result = track.path_length(Length::new::<meter>(a), Length::new::<meter>(b));
+        assert!((result.get::<meter>() - expected).abs() < 1e-12);
+    }
+    // Clamped to the maximum radius of the track.
+    let result = track.path_length(Length::new::<meter>(r1), Length::new::<meter>(1.0));
+    assert!((result.get::<meter>() - length(PI - offset(r1))).abs() < 1e-12);
+
+    let result = track.path_length(Length::new::<meter>(0.7), Length::new::<meter>(1.0));
+    assert_eq!(result, Length::new::<meter>(0.0));
+}
+
+#[test]
+fn track_length_in_tpc() {
+    let mut track = extrapolation_track();
+    let expected = track.path_length(
+        Length::new::<meter>(INNER_CATHODE_RADIUS),
+        Length::new::<meter>(CATHODE_PADS_RADIUS),
+    );
+    assert!((track.length_in_tpc() - expected).abs() < Length::new::<meter>(1e-12));
+
+    track.cosmic = true;
+    assert!((track.length_in_tpc() - 2.0 * expected).abs() < Length::new::<meter>(1e-12));
+    // Leaves the detector through the end cap.
+    let track = Track::from_perigee(Perigee {
+        d0: Length::new::<meter>(0.0),
+        z0: Length::new::<meter>(1.0),
+        phi0: Angle::new::<radian>(0.5),
+        curvature: ReciprocalLength::new::<reciprocal_meter>(1.0),
+        tan_lambda: 1.0,
+    });
+    let s_inner = 2.0 * (INNER_CATHODE_RADIUS / 2.0).asin();
+    let s_end = DETECTOR_LENGTH / 2.0 - 1.0;
+    let expected = 2.0f64.sqrt() * (s_end - s_inner);
+    assert!((track.length_in_tpc().get::<meter>() - expected).abs() < 1e-9);
+}
+
+#[test]
+fn track_perigee() {
+    let perigee = extrapolation_track().perigee();
+
+    assert!(perigee.d0.abs() < Length::new::<meter>(1e-12));
+    assert!((perigee.z0 - Length::new::<centimeter>(10.0)).abs() < Length::new::<meter>(1e-12));
+    assert!((perigee.phi0.get::<radian>() - PI / 2.0).abs() < 1e-12);
+    assert!(
+        (perigee.curvature.get::<reciprocal_meter>() + 1.0 / 0.3).abs() < 1e-9,
+        "{perigee:?}"
+    );
+    assert!((perigee.tan_lambda + 0.5 / (2.0 * PI * 0.3)).abs() < 1e-12);
+}
+
+#[test]
+fn track_impact_parameters() {
+    let track = extrapolation_track();
+    assert!(track.d0().abs() < Length::new::<meter>(1e-12));
+    assert!((track.z0() - Length::new::<centimeter>(10.0)).abs() < Length::new::<meter>(1e-12));
+
+    for d0 in [-0.02, 0.0, 0.01] {
+        let perigee = Perigee {
+            d0: Length::new::<meter>(d0),
+            z0: Length::new::<centimeter>(-7.0),
+            phi0: Angle::new::<radian>(1.0),
+            curvature: ReciprocalLength::new::<reciprocal_meter>(-3.0),
+            tan_lambda: 0.5,
+        };
+        let track = Track::from_perigee(perigee);
+
+        assert!((track.d0() - perigee.d0).abs() < Length::new::<meter>(1e-9));
+        assert!((track.z0() - perigee.z0).abs() < Length::new::<meter>(1e-9));
+        assert_eq!(track.d0(), track.perigee().d0);
+        assert_eq!(track.z0(), track.perigee().z0);
+    }
+}
+
+#[test]
+fn perigee_round_trip() {
+    for (d0, phi0, curvature, tan_lambda) in [
+        (0.01, 0.3, 2.0, 0.5),
+        (-0.02, -2.0, -5.0, -1.5),
+        (0.0, 3.0, 0.5, 0.0),
+        (0.05, 1.0, -0.1, 3.0),
+    ] {
+        let perigee = Perigee {
+            d0: Length::new::<meter>(d0),
+            z0: Length::new::<centimeter>(-7.0),
+            phi0: Angle::new::<radian>(phi0),
+            curvature: ReciprocalLength::new::<reciprocal_meter>(curvature),
+            tan_lambda,
+        };
+        let track = Track::from_perigee(perigee);
+        let result = track.perigee();
+
+        assert!((result.d0 - perigee.d0).abs() < Length::new::<meter>(1e-9));
+        assert!((result.z0 - perigee.z0).abs() < Length::new::<meter>(1e-9));
+        assert!((result.phi0 - perigee.phi0).abs() < Angle::new::<radian>(1e-9));
+        assert!(
+            (result.curvature - perigee.curvature).abs()
+                < ReciprocalLength::new::<reciprocal_meter>(1e-9)
+        );
+        assert!((result.tan_lambda - perigee.tan_lambda).abs() < 1e-9);
+
+        // Cartesian state at the perigee.
+        let state = track.state(0.0);
+        let position = perigee.position();
+        assert!((state.position.x - position.x).abs() < Length::new::<meter>(1e-12));
+        assert!((state.position.y - position.y).abs() < Length::new::<meter>(1e-12));
+        for (a, b) in state.direction.iter().zip(perigee.direction()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+}
+
+// Track going through the origin, with center at `x0 = 0.3 m` and `y0 = 0`.
+fn bv_matching_track(cosmic: bool) -> Track {
+    Track {
+        helix: Helix {
+            x0: Length::new::<meter>(0.3),
+            y0: Length::new::<meter>(0.0),
+            z0: Length::new::<centimeter>(10.0),
+            r: Length::new::<meter>(0.3),
+            phi0: Angle::HALF_TURN,
+            h: Length::new::<meter>(0.0),
+        },
+        t_inner: 0.1,
+        t_outer: 0.6,
+        dedx: ReciprocalLength::new::<reciprocal_meter>(0.0),
+        cosmic,
+        covariance: None,
+        chi2: 0.0,
+        ndf: 0,
+        num_iter: 0,
+    }
+}
+
+// Return the BV hit at the crossing with positive or negative `y`.
+fn bv_matching_hit(positive_y: bool, t: Time) -> BvHit {
+    let radius = BAR_RADIUS.get::<meter>();
+    let x = radius.powi(2) / (2.0 * 0.3);
+    let y = (radius.powi(2) - x.powi(2)).sqrt();
+    let y = if positive_y { y } else { -y };
+    let pitch = 2.0 * PI / BV_BARS as f64;
+    let bar = (y.atan2(x) / pitch).round().rem_euclid(BV_BARS as f64) as usize;
+
+    BvHit {
+        bar: bar.try_into().unwrap(),
+        z: Length::new::<centimeter>(15.0),
+        t,
+        energy: 1.0,
+    }
+}
+
+#[test]
+fn match_bv_hits_annihilation_track() {
+    let track = bv_matching_track(false);
+    // The track moves towards negative `y` when leaving the rTPC.
+    let good = bv_matching_hit(false, Time::new::<nanosecond>(10.0));
+    let bad = bv_matching_hit(true, Time::new::<nanosecond>(10.0));
+
+    let matches = match_bv_hits(&[track], &[bad, good]);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].hits, vec![good]);
+    assert!(matches[0].tof.is_none());
+    assert!(matches[0].path_length.is_none());
+
+    let far = BvHit {
+        z: Length::new::<meter>(1.0),
+        ..good
+    };
+    assert!(match_bv_hits(&[track], &[far])[0].hits.is_empty());
+}
+
+#[test]
+fn match_bv_hits_cosmic_track() {
+    let track = bv_matching_track(true);
+    let first = bv_matching_hit(true, Time::new::<nanosecond>(10.0));
+    let second = bv_matching_hit(false, Time::new::<nanosecond>(12.0));
+
+    let matches = match_bv_hits(&[track], &[second, first]);
+    assert_eq!(matches[0].hits, vec![first, second]);
+    let diff = matches[0].tof.unwrap() - Time::new::<nanosecond>(2.0);
+    assert!(diff.abs() < Time::new::<nanosecond>(1e-6));
+
+    let crossings = track.helix.t_at_radius(BAR_RADIUS);
+    let expected = track.helix.arc_length(crossings[0], crossings[1]);
+    let diff = matches[0].path_length.unwrap() - expected;
+    assert!(diff.abs() < Length::new::<meter>(1e-9));
+}
+
+#[test]
+fn estimate_vertex_z_empty() {
+    assert!(estimate_vertex_z(&[]).is_none());
+    assert!(estimate_vertex_z(&[[0.0; TPC_PAD_ROWS]; 4]).is_none());
+    assert!(estimate_vertex_z(&[[-1.0; TPC_PAD_ROWS]; 4]).is_none());
+}
+
+#[test]
+fn estimate_vertex_z_symmetric_tracks() {
+    let vertex_row = 200;
+    let vertex_z = TpcPadRow::try_from(vertex_row).unwrap().z();
+    // Pairs of tracks going up and down by the same amount from the vertex.
+    let mut pad_charges = Vec::new();
+    for offset in [5, 20, 40] {
+        for row in [vertex_row + offset, vertex_row - offset] {
+            let mut column = [0.0; TPC_PAD_ROWS];
+            column[row] = 100.0;
+            column[row + 1] = 50.0;
+            column[row - 1] = 50.0;
+            pad_charges.push(column);
+        }
+    }
+    // Noise in an unrelated column.
+    let mut noise = [0.0; TPC_PAD_ROWS];
+    noise[0] = -30.0;
+    noise[TPC_PAD_ROWS - 1] = 10.0;
+    pad_charges.push(noise);
+
+    let z = estimate_vertex_z(&pad_charges).unwrap();
+    let offset_5 = (TpcPadRow::try_from(vertex_row + 5).unwrap().z() - vertex_z).abs();
+    assert!((z.get::<meter>() - vertex_z).abs() <= offset_5 + 1e-9);
+}
@@ -1,11 +1,78 @@
-use crate::reconstruction::{Cluster, ClusteringResult};
+use crate::reconstruction::{Cluster, ClusteringResult, HoughPrecision};
 use crate::SpacePoint;
 use alpha_g_detector::alpha16::aw_map::INNER_CATHODE_RADIUS;
-use indexmap::IndexMap;
+use std::f64::consts::TAU;
+use std::ops::{Add, Div, Mul};
+use uom::si::angle::radian;
 use uom::si::f64::{Angle, Length, ReciprocalLength};
-use uom::si::ratio::ratio;
+use uom::si::length::meter;
+use uom::si::reciprocal_length::reciprocal_meter;
 use uom::typenum::P2;
 
+// Floating point type used to vote in Hough space (see `HoughPrecision`).
+trait HoughFloat: Copy + Add<Output = Self> + Mul<Output = Self> + Div<Output = Self> {
+    fn from_f64(value: f64) -> Self;
+    fn sin_cos(self) -> (Self, Self);
+    fn floor(self) -> i32;
+}
+
+impl HoughFloat for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+    fn sin_cos(self) -> (Self, Self) {
+        f32::sin_cos(self)
+    }
+    fn floor(self) -> i32 {
+        f32::floor(self) as i32
+    }
+}
+
+impl HoughFloat for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+    fn sin_cos(self) -> (Self, Self) {
+        f64::sin_cos(self)
+    }
+    fn floor(self) -> i32 {
+        f64::floor(self) as i32
+    }
+}
+
+// Maximum distance between neighboring SpacePoints, with independent cuts in
+// the transverse (x-y) plane and along z. The density of SpacePoints along z
+// (pad pitch and diffusion) is very different from the x-y plane (anode wire
+// pitch and drift time sampling), so a single Euclidean cut either splits
+// tracks or merges separate tracks at nearby z.
+//
+// Two points are neighbors if `(dt / transverse)^2 + (dz / longitudinal)^2 <=
+// 1`. With equal cuts, this is the same as a Euclidean distance cut.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct NeighborDistance {
+    // Both in meters.
+    transverse: f64,
+    longitudinal: f64,
+}
+
+impl NeighborDistance {
+    // The longitudinal cut is the same as the transverse cut if `None`.
+    pub(crate) fn new(transverse: Length, longitudinal: Option<Length>) -> Self {
+        Self {
+            transverse: transverse.get::<meter>(),
+            longitudinal: longitudinal.unwrap_or(transverse).get::<meter>(),
+        }
+    }
+    // Distance relative to the cuts. Points are neighbors if this is at most
+    // `1.0`.
+    fn normalized(&self, dt: f64, dz: f64) -> f64 {
+        // A zero cut only accepts a zero distance (instead of 0/0).
+        let ratio = |d: f64, cut: f64| if d == 0.0 { 0.0 } else { d / cut };
+
+        ratio(dt.abs(), self.transverse).hypot(ratio(dz.abs(), self.longitudinal))
+    }
+}
+
 // A track, as seen from the x-y plane, will form a circle.
 //
 // In the x-y plane, the conformal transformation:
@@ -18,42 +85,41 @@ use uom::typenum::P2;
 // We can filter potential annihilation tracks (which originate close to the
 // origin) by finding straight lines in the u-v plane.
 pub(crate) fn cluster_spacepoints(
-    mut sp: Vec<SpacePoint>,
+    sp: Vec<SpacePoint>,
     min_num_points_per_cluster: usize,
+    max_num_clusters: Option<usize>,
     rho_bins: u32,
     theta_bins: u32,
-    max_distance: Length,
+    precision: HoughPrecision,
+    max_distance: NeighborDistance,
 ) -> ClusteringResult {
-    let mut accumulator = HoughSpaceAccumulator {
-        rho_bins,
-        theta_bins,
-        accumulator: IndexMap::new(),
-    };
-    for &point in sp.iter() {
-        accumulator.add(point);
-    }
+    let mut accumulator = HoughSpaceAccumulator::new(&sp, rho_bins, theta_bins, precision);
     // Given an accumulator in a particular state, identify the best cluster of
     // SpacePoints i.e. largest number of points that form a line in Hough space
-    // and are close enough to be a single track.
+    // and follow a single helix.
     // Leave the accumulator in a state where the corresponding points have been
     // removed.
     fn best_cluster(
         accumulator: &mut HoughSpaceAccumulator,
-        max_distance: Length,
-    ) -> Vec<SpacePoint> {
+        sp: &[SpacePoint],
+        max_distance: NeighborDistance,
+    ) -> Vec<usize> {
         let mut prev_best = Vec::new();
 
         loop {
-            let best = largest_cluster(accumulator.most_popular(), max_distance);
+            let best = match accumulator.most_popular() {
+                Some((line, points)) => largest_helix_segment(sp, line, points, max_distance),
+                None => Vec::new(),
+            };
             if best.len() <= prev_best.len() {
                 break;
             }
 
-            for &point in best.iter() {
-                accumulator.remove_unchecked(point);
+            for &index in best.iter() {
+                accumulator.remove_unchecked(index);
             }
-            for &point in prev_best.iter() {
-                accumulator.add(point);
+            for &index in prev_best.iter() {
+                accumulator.add_unchecked(index);
             }
 
             prev_best = best;
@@ -62,27 +128,76 @@ pub(crate) fn cluster_spacepoints(
         prev_best
     }
 
+    let mut clustered = vec![false; sp.len()];
     let mut clusters = Vec::new();
-    loop {
-        let cluster = best_cluster(&mut accumulator, max_distance);
+    while max_num_clusters.is_none_or(|max| clusters.len() < max) {
+        let cluster = best_cluster(&mut accumulator, &sp, max_distance);
         if cluster.len() < min_num_points_per_cluster {
             break;
         }
 
-        clusters.push(Cluster(cluster));
+        for &index in cluster.iter() {
+            clustered[index] = true;
+        }
+        clusters.push(Cluster(cluster.into_iter().map(|i| sp[i]).collect()));
     }
     // The remainder is the set of points that were not clustered.
-    for &point in clusters.iter().flatten() {
-        // All points clustered are guaranteed to come from the original set of
-        // SpacePoints; hence it is safe to unwrap.
-        let index = sp.iter().position(|&p| p == point).unwrap();
-        sp.swap_remove(index);
-    }
+    let remainder = sp
+        .into_iter()
+        .zip(clustered)
+        .filter_map(|(point, clustered)| (!clustered).then_some(point))
+        .collect();
 
     ClusteringResult {
         clusters,
-        remainder: sp,
+        remainder,
+    }
+}
+
+// Number of times that the binning of Hough space is halved (and doubled) with
+// respect to the nominal number of bins during the adaptive search.
+const ADAPTIVE_HOUGH_LEVELS: u32 = 2;
+
+// Choose the number of (rho, theta) bins in Hough space for a particular set of
+// SpacePoints.
+//
+// Bins that are too coarse merge different tracks (and noise) into the same
+// bin, whereas bins that are too fine split the SpacePoints of a single track
+// across neighboring bins (their spread in Hough space is dominated by the
+// resolution and multiple scattering). The best binning depends on the number
+// and spread of SpacePoints in each event.
+//
+// Coarse-to-fine search: start from the coarsest binning, and keep doubling the
+// number of bins for as long as the most popular bin still has enough points to
+// be a cluster. Sparse events stop early, so they only pay for a few coarse
+// accumulators.
+pub(crate) fn adaptive_hough_bins(
+    sp: &[SpacePoint],
+    min_num_points_per_cluster: usize,
+    rho_bins: u32,
+    theta_bins: u32,
+    precision: HoughPrecision,
+) -> (u32, u32) {
+    let scaled = |bins: u32, level: u32| -> u32 {
+        let bins = (bins >> ADAPTIVE_HOUGH_LEVELS).max(1);
+        bins.saturating_mul(1 << level)
+    };
+
+    let mut best = (scaled(rho_bins, 0), scaled(theta_bins, 0));
+    for level in 1..=2 * ADAPTIVE_HOUGH_LEVELS {
+        let candidate = (scaled(rho_bins, level), scaled(theta_bins, level));
+        let accumulator = HoughSpaceAccumulator::new(sp, candidate.0, candidate.1, precision);
+        let peak = accumulator
+            .most_popular()
+            .map_or(0, |(_, points)| points.len());
+        if peak < min_num_points_per_cluster {
+            break;
+        }
+
+        best = candidate;
     }
+
+    best
 }
 
 // The maximum possible `rho` in Hough space is the maximum distance from the
@@ -93,17 +208,44 @@ const RHO_MAX: ReciprocalLength = ReciprocalLength {
     value: 1.0 / INNER_CATHODE_RADIUS,
 };
 
+// Simply counting the number of votes for each bin is not enough for our
+// purposes. Keep track explicitly of which SpacePoints have gone through each
+// bin in Hough space. This makes it easier to remove all SpacePoints that
+// contributed to e.g. the most popular bin.
+//
+// SpacePoints are identified by their index in the original slice, and they
+// are only ever removed and re-added during clustering (never new ones). Hence,
+// the bins that each SpacePoint votes for are computed once, and the list of
+// voters of each bin never changes. Only the vote counts (and whether a
+// SpacePoint is currently in the accumulator) need to be updated.
 struct HoughSpaceAccumulator {
-    rho_bins: u32,
-    theta_bins: u32,
-    // Simply counting the number of votes for each bin is not enough for our
-    // purposes. Keep track explicitly of which SpacePoints have gone through
-    // each bin in Hough space.
-    // This makes it easier to remove all SpacePoints that contributed to e.g.
-    // the most popular bin.
-    // First index is theta, second index is rho.
-    // Using IndexMap instead of HashMap to make the algorithm deterministic.
-    accumulator: IndexMap<(u32, u32), Vec<SpacePoint>>,
+    // Number of votes for each bin from the SpacePoints currently in the
+    // accumulator.
+    counts: Vec<u32>,
+    // Indices of all the SpacePoints that vote for each bin. The voters of bin
+    // `b` are `voters[offsets[b]..offsets[b + 1]]`.
+    offsets: Vec<usize>,
+    voters: Vec<usize>,
+    // Bins that each SpacePoint votes for.
+    point_bins: Vec<Vec<usize>>,
+    // Whether each SpacePoint is currently in the accumulator.
+    active: Vec<bool>,
+    // Bins in the order in which they received their first vote. Iterate in
+    // this order to make the algorithm deterministic.
+    order: Vec<usize>,
+    // Size of the `rho` dimension of the flattened bins, and the size of each
+    // bin.
+    rho_dim: usize,
+    delta_theta: Angle,
+    delta_rho: ReciprocalLength,
+}
+
+// Line in the u-v plane given by the center of a bin in Hough space:
+// rho = u * cos(theta) + v * sin(theta)
+#[derive(Clone, Copy, Debug)]
+struct HoughLine {
+    theta: Angle,
+    rho: ReciprocalLength,
 }
 
 // Conformal transformation from x-y plane to u-v plane.
@@ -114,101 +256,449 @@ fn u_v(point: SpacePoint) -> (ReciprocalLength, ReciprocalLength) {
     (u, v)
 }
 
+// Given a SpacePoint, return all the (theta, rho) bins in Hough space that it
+// votes for.
+fn hough_bins<F: HoughFloat>(
+    point: SpacePoint,
+    rho_bins: u32,
+    theta_bins: u32,
+) -> Vec<(usize, usize)> {
+    // Conformal mapping coordinates
+    let (u, v) = u_v(point);
+    // All the voting is done with plain floats (see `HoughFloat`). These are in
+    // `RECIPROCAL_METER` and `RADIAN`.
+    let u = F::from_f64(u.get::<reciprocal_meter>());
+    let v = F::from_f64(v.get::<reciprocal_meter>());
+
+    let delta_theta = F::from_f64((Angle::FULL_TURN / f64::from(theta_bins)).get::<radian>());
+    let delta_rho = F::from_f64((RHO_MAX / f64::from(rho_bins)).get::<reciprocal_meter>());
+
+    let mut bins = Vec::new();
+    // Hough space is parametrized as:
+    // rho = u * cos(theta) + v * sin(theta)
+    // The first bin has theta = 0
+    let mut prev_rho_bin = (u / delta_rho).floor();
+    for theta_bin in 1..=theta_bins {
+        let theta = F::from_f64(f64::from(theta_bin)) * delta_theta;
+        let (sin, cos) = theta.sin_cos();
+        let rho = u * cos + v * sin;
+        let rho_bin = (rho / delta_rho).floor();
+        // If rho has only been negative between this and the previous
+        // iteration, we don't want to vote for any bins.
+        // Those bins are just duplicates of other bins with positive values
+        // of rho and different theta.
+        if !rho_bin.is_negative() || !prev_rho_bin.is_negative() {
+            let min_bin = prev_rho_bin.min(rho_bin);
+            let max_bin = prev_rho_bin.max(rho_bin);
+            for bin in min_bin.max(0)..=max_bin {
+                bins.push(((theta_bin - 1) as usize, bin.try_into().unwrap()));
+            }
+        }
+        prev_rho_bin = rho_bin;
+    }
+
+    bins
+}
+
 impl HoughSpaceAccumulator {
-    // Given a SpacePoint, return all the bins in Hough space that it votes for.
-    fn get_bins(&self, point: SpacePoint) -> Vec<(u32, u32)> {
-        // Conformal mapping coordinates
-        let (u, v) = u_v(point);
-
-        let delta_theta = Angle::FULL_TURN / f64::from(self.theta_bins);
-        let delta_rho = RHO_MAX / f64::from(self.rho_bins);
-
-        let mut bins = Vec::new();
-        // Hough space is parametrized as:
-        // rho = u * cos(theta) + v * sin(theta)
-        // The first bin has theta = 0
-        let mut prev_rho_bin = (u / delta_rho).get::<ratio>().floor() as i32;
-        for theta_bin in 1..=self.theta_bins {
-            let theta = f64::from(theta_bin) * delta_theta;
-            let (sin, cos) = theta.sin_cos();
-            let rho = u * cos + v * sin;
-            let rho_bin = (rho / delta_rho).get::<ratio>().floor() as i32;
-            // If rho has only been negative between this and the previous
-            // iteration, we don't want to vote for any bins.
-            // Those bins are just duplicates of other bins with positive values
-            // of rho and different theta.
-            if !rho_bin.is_negative() || !prev_rho_bin.is_negative() {
-                let min_bin = prev_rho_bin.min(rho_bin);
-                let max_bin = prev_rho_bin.max(rho_bin);
-                for bin in min_bin.max(0)..=max_bin {
-                    bins.push((theta_bin - 1, bin.try_into().unwrap()));
-                }
+    // Create an accumulator with all the SpacePoints in it.
+    fn new(
+        points: &[SpacePoint],
+        rho_bins: u32,
+        theta_bins: u32,
+        precision: HoughPrecision,
+    ) -> Self {
+        let hough_bins = match precision {
+            HoughPrecision::Double => hough_bins::<f64>,
+            HoughPrecision::Single => hough_bins::<f32>,
+        };
+        // Voting is independent for each SpacePoint, and it is the most
+        // expensive part of filling the accumulator.
+        #[cfg(feature = "rayon")]
+        let bins: Vec<_> = {
+            use rayon::prelude::*;
+            points
+                .par_iter()
+                .map(|&point| hough_bins(point, rho_bins, theta_bins))
+                .collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let bins: Vec<_> = points
+            .iter()
+            .map(|&point| hough_bins(point, rho_bins, theta_bins))
+            .collect();
+
+        // Bins are flattened as `theta_bin * rho_dim + rho_bin`.
+        // SpacePoints inside the inner cathode have `rho > RHO_MAX`. These are
+        // not physical, but they still vote for (extra) bins. Hence `rho_dim`
+        // can be larger than the number of `rho` bins.
+        let rho_dim = bins
+            .iter()
+            .flatten()
+            .map(|&(_, rho_bin)| rho_bin + 1)
+            .max()
+            .unwrap_or(0)
+            .max(rho_bins as usize);
+        let point_bins: Vec<Vec<usize>> = bins
+            .into_iter()
+            .map(|bins| {
+                bins.into_iter()
+                    .map(|(theta_bin, rho_bin)| theta_bin * rho_dim + rho_bin)
+                    .collect()
+            })
+            .collect();
+
+        let num_bins = theta_bins as usize * rho_dim;
+        let mut counts = vec![0; num_bins];
+        let mut order = Vec::new();
+        for &bin in point_bins.iter().flatten() {
+            if counts[bin] == 0 {
+                order.push(bin);
+            }
+            counts[bin] += 1;
+        }
+
+        let mut offsets = Vec::with_capacity(num_bins + 1);
+        offsets.push(0);
+        for &count in counts.iter() {
+            offsets.push(offsets.last().unwrap() + count as usize);
+        }
+        let mut voters = vec![0; *offsets.last().unwrap()];
+        let mut next = offsets.clone();
+        for (index, bins) in point_bins.iter().enumerate() {
+            for &bin in bins {
+                voters[next[bin]] = index;
+                next[bin] += 1;
             }
-            prev_rho_bin = rho_bin;
         }
 
-        bins
+        Self {
+            counts,
+            offsets,
+            voters,
+            point_bins,
+            active: vec![true; points.len()],
+            order,
+            rho_dim,
+            delta_theta: Angle::FULL_TURN / f64::from(theta_bins),
+            delta_rho: RHO_MAX / f64::from(rho_bins),
+        }
     }
-    // Add a SpacePoint to the accumulator.
-    fn add(&mut self, point: SpacePoint) {
-        for bin in self.get_bins(point) {
-            self.accumulator.entry(bin).or_default().push(point);
+    // Add a SpacePoint back to the accumulator.
+    // The SpacePoint must not be in the accumulator already.
+    fn add_unchecked(&mut self, index: usize) {
+        debug_assert!(!self.active[index]);
+        self.active[index] = true;
+        for &bin in self.point_bins[index].iter() {
+            self.counts[bin] += 1;
         }
     }
     // Remove a SpacePoint from the accumulator.
-    // Panic if the SpacePoint is not in the accumulator.
-    fn remove_unchecked(&mut self, point: SpacePoint) {
-        for bin in self.get_bins(point) {
-            let vec = self.accumulator.get_mut(&bin).unwrap();
-            let pos = vec.iter().position(|p| *p == point).unwrap();
-            vec.swap_remove(pos);
+    // The SpacePoint must be in the accumulator.
+    fn remove_unchecked(&mut self, index: usize) {
+        debug_assert!(self.active[index]);
+        self.active[index] = false;
+        for &bin in self.point_bins[index].iter() {
+            self.counts[bin] -= 1;
         }
     }
-    // Return the SpacePoints that voted for the most popular bin. Return an
-    // empty vector if the accumulator is empty.
-    fn most_popular(&self) -> Vec<SpacePoint> {
-        self.accumulator
-            .values()
-            .max_by_key(|v| v.len())
-            .cloned()
-            .unwrap_or_default()
+    // Return the line of the most popular bin together with the indices of
+    // the SpacePoints (currently in the accumulator) that voted for it. Return
+    // `None` if no SpacePoint ever voted.
+    fn most_popular(&self) -> Option<(HoughLine, Vec<usize>)> {
+        let &bin = self.order.iter().max_by_key(|&&bin| self.counts[bin])?;
+        let line = HoughLine {
+            theta: self.delta_theta * ((bin / self.rho_dim) as f64 + 0.5),
+            rho: self.delta_rho * ((bin % self.rho_dim) as f64 + 0.5),
+        };
+        let points = self.voters[self.offsets[bin]..self.offsets[bin + 1]]
+            .iter()
+            .copied()
+            .filter(|&index| self.active[index])
+            .collect();
+
+        Some((line, points))
+    }
+}
+
+// A line in the u-v plane corresponds to a circle through the origin in the
+// x-y plane:
+// x^2 + y^2 - (x * cos(theta) + y * sin(theta)) / rho = 0
+// Return the signed arc length along this circle from the origin to (the
+// projection of) a SpacePoint.
+//
+// The arc length wraps around at the point of the circle opposite to the
+// origin. Tracks that curl back past this point are split in two.
+fn arc_length(point: SpacePoint, line: HoughLine) -> Length {
+    let radius = (0.5 / line.rho).get::<meter>();
+    let theta = line.theta.get::<radian>();
+    let (cx, cy) = (radius * theta.cos(), radius * theta.sin());
+    let (px, py) = (point.x().get::<meter>() - cx, point.y().get::<meter>() - cy);
+    // Angle from the center-to-origin to the center-to-point direction.
+    let alpha = (cy * px - cx * py).atan2(-cx * px - cy * py);
+
+    Length::new::<meter>(radius * alpha)
+}
+
+// SpacePoints that follow a single helix, in increasing order of arc length.
+// Along a helix, `z` is a linear function of the arc length `s`. Keep the sums
+// of the least squares fit `z = a + b * s` to predict the next SpacePoints.
+#[derive(Default)]
+struct HelixSegment {
+    indices: Vec<usize>,
+    // (s, z) in meters.
+    coords: Vec<(f64, f64)>,
+    sum_s: f64,
+    sum_z: f64,
+    sum_ss: f64,
+    sum_sz: f64,
+}
+
+impl HelixSegment {
+    fn push(&mut self, index: usize, s: f64, z: f64) {
+        self.indices.push(index);
+        self.coords.push((s, z));
+        self.sum_s += s;
+        self.sum_z += z;
+        self.sum_ss += s * s;
+        self.sum_sz += s * z;
+    }
+    // Normalized distance (see `NeighborDistance`) in the s-z plane to the
+    // closest SpacePoint in the segment. Only the SpacePoints within the
+    // transverse cut along `s` are considered.
+    fn distance(&self, s: f64, z: f64, max_distance: NeighborDistance) -> Option<f64> {
+        self.coords
+            .iter()
+            .rev()
+            .take_while(|&&(other_s, _)| s - other_s <= max_distance.transverse)
+            .map(|&(other_s, other_z)| max_distance.normalized(s - other_s, z - other_z))
+            .min_by(f64::total_cmp)
+    }
+    // Predicted `z` at a given arc length. The fit is only reliable (and
+    // returned) once the segment spans at least `min_extent` along `s`.
+    fn predict(&self, s: f64, min_extent: f64) -> Option<f64> {
+        let (first, last) = (self.coords.first()?.0, self.coords.last()?.0);
+        if last - first < min_extent {
+            return None;
+        }
+        let n = self.coords.len() as f64;
+        let slope = (n * self.sum_sz - self.sum_s * self.sum_z)
+            / (n * self.sum_ss - self.sum_s * self.sum_s);
+        let intercept = (self.sum_z - slope * self.sum_s) / n;
+
+        Some(intercept + slope * s)
     }
 }
 
-// Given a collection of SpacePoints, find the largest subset of SpacePoints
-// such that they all can be reached from each other by a path of SpacePoints
-// that are within a certain distance.
+// Given the SpacePoints (indices into `sp`) that voted for a line in Hough
+// space, find the largest subset that follows a single helix.
 //
 // This is necessary after identifying lines in Hough space because of the
 // following scenarios:
 //   1. Two tracks that go in opposite directions will be picked up as one
-//   single line in Hough space. These tracks will have a gap in the middle
-//   (inner cathode of the rTPC).
-//   2. Two tracks that go in the same direction but occur at different values
-//   of z. They will be picked as the same track when seen from the x-y (u-v)
-//   plane.
-fn largest_cluster(mut points: Vec<SpacePoint>, max_distance: Length) -> Vec<SpacePoint> {
-    let mut clusters: Vec<Vec<_>> = Vec::new();
-
-    while let Some(point) = points.pop() {
-        let mut cluster = vec![point];
-        let mut i = 0;
-        while i < cluster.len() {
-            let mut j = 0;
-            while j < points.len() {
-                if cluster[i].distance(points[j]) <= max_distance {
-                    cluster.push(points.swap_remove(j));
-                } else {
-                    j += 1;
+//   single line in Hough space. These tracks are on opposite sides of the
+//   origin along the circle (opposite sign of the arc length), separated by
+//   the inner cathode of the rTPC.
+//   2. Two (or more) tracks that overlap when seen from the x-y (u-v) plane,
+//   but are different in z.
+//
+// SpacePoints are unrolled onto the s-z plane, where `s` is the arc length
+// along the circle, and grouped into segments by following them in order of
+// `s`. A SpacePoint can extend any segment with a SpacePoint within
+// `max_distance` (the arc length is the transverse distance); if it can extend
+// more than one (e.g. two tracks at a shallow angle close to where they
+// cross), it joins the segment whose straight line `z(s)` predicts it best. Unlike plain Euclidean connectivity, this never
+// merges two crossing tracks into a single cluster.
+fn largest_helix_segment(
+    sp: &[SpacePoint],
+    line: HoughLine,
+    points: Vec<usize>,
+    max_distance: NeighborDistance,
+) -> Vec<usize> {
+    let mut coords: Vec<_> = points
+        .into_iter()
+        .map(|i| {
+            let s = arc_length(sp[i], line).get::<meter>();
+            (i, s, sp[i].z.get::<meter>())
+        })
+        .collect();
+    // Stable sort to make the algorithm deterministic.
+    coords.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut segments: Vec<HelixSegment> = Vec::new();
+    for (index, s, z) in coords {
+        let best = segments
+            .iter_mut()
+            .filter_map(|segment| {
+                let distance = segment.distance(s, z, max_distance)?;
+                if distance > 1.0 {
+                    return None;
                 }
+                // Same normalization as the distance to compare segments with
+                // and without a reliable fit.
+                let score = segment
+                    .predict(s, max_distance.transverse)
+                    .map_or(distance, |predicted| {
+                        max_distance.normalized(0.0, z - predicted)
+                    });
+
+                Some((score, segment))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+
+        match best {
+            Some((_, segment)) => segment.push(index, s, z),
+            None => {
+                let mut segment = HelixSegment::default();
+                segment.push(index, s, z);
+                segments.push(segment);
             }
-            i += 1;
         }
-        clusters.push(cluster);
     }
 
-    clusters
+    segments
         .into_iter()
-        .max_by_key(|c| c.len())
+        .map(|segment| segment.indices)
+        .max_by_key(|indices| indices.len())
         .unwrap_or_default()
 }
+
+// Density-based clustering (DBSCAN) of SpacePoints.
+//
+// Unlike the Hough transform above, this does not assume anything about the
+// shape of the tracks (or where they come from). It performs better for short,
+// low-multiplicity tracks far from the origin, and it is useful to cross-check
+// the Hough clustering in systematic studies.
+//
+// A point is a "core" point if it has at least `min_num_neighbors` points
+// (including itself) within `max_distance` (transverse distance in the x-y
+// plane and axial distance along z). Clusters are the sets of points reachable
+// from a core point through a chain of core points. Points that are
+// not reachable from any core point are left as noise (remainder).
+pub(crate) fn dbscan_cluster_spacepoints(
+    sp: Vec<SpacePoint>,
+    min_num_points_per_cluster: usize,
+    max_num_clusters: Option<usize>,
+    min_num_neighbors: usize,
+    max_distance: NeighborDistance,
+) -> ClusteringResult {
+    dbscan(
+        sp,
+        min_num_points_per_cluster,
+        max_num_clusters,
+        min_num_neighbors,
+        |a, b| {
+            let dt = (a.x() - b.x()).hypot(a.y() - b.y());
+            let dz = a.z - b.z;
+            max_distance.normalized(dt.get::<meter>(), dz.get::<meter>()) <= 1.0
+        },
+    )
+}
+
+// Same as `dbscan_cluster_spacepoints`, but in the z-phi plane (i.e. ignoring
+// `r`) for SpacePoints reconstructed only from the pad signals. Their `phi` is
+// only known up to the width of a pad column, so the usual neighbor distance is
+// meaningless in the x-y plane.
+pub(crate) fn pad_only_cluster_spacepoints(
+    sp: Vec<SpacePoint>,
+    min_num_points_per_cluster: usize,
+    max_num_clusters: Option<usize>,
+    min_num_neighbors: usize,
+    max_phi: Angle,
+    max_z: Length,
+) -> ClusteringResult {
+    dbscan(
+        sp,
+        min_num_points_per_cluster,
+        max_num_clusters,
+        min_num_neighbors,
+        |a, b| {
+            let dphi = (a.phi - b.phi).get::<radian>().rem_euclid(TAU);
+            dphi.min(TAU - dphi) <= max_phi.get::<radian>() && (a.z - b.z).abs() <= max_z
+        },
+    )
+}
+
+// Generic DBSCAN given the neighborhood criterion between two SpacePoints.
+fn dbscan<F>(
+    sp: Vec<SpacePoint>,
+    min_num_points_per_cluster: usize,
+    max_num_clusters: Option<usize>,
+    min_num_neighbors: usize,
+    is_neighbor: F,
+) -> ClusteringResult
+where
+    F: Fn(&SpacePoint, &SpacePoint) -> bool,
+{
+    let neighbors = |i: usize| -> Vec<usize> {
+        (0..sp.len())
+            .filter(|&j| is_neighbor(&sp[i], &sp[j]))
+            .collect()
+    };
+
+    let mut labels: Vec<Option<usize>> = vec![None; sp.len()];
+    let mut visited = vec![false; sp.len()];
+    let mut num_clusters = 0;
+    // Iterate in the input order to make the algorithm deterministic.
+    for i in 0..sp.len() {
+        if visited[i] {
+            continue;
+        }
+        visited[i] = true;
+
+        let seeds = neighbors(i);
+        if seeds.len() < min_num_neighbors {
+            // Noise for now. It can still become a border point of a cluster
+            // found later.
+            continue;
+        }
+
+        let label = num_clusters;
+        num_clusters += 1;
+        labels[i] = Some(label);
+
+        let mut queue = seeds;
+        while let Some(j) = queue.pop() {
+            if labels[j].is_none() {
+                labels[j] = Some(label);
+            }
+            if visited[j] {
+                continue;
+            }
+            visited[j] = true;
+
+            let reachable = neighbors(j);
+            if reachable.len() >= min_num_neighbors {
+                queue.extend(reachable.into_iter().filter(|&k| !visited[k]));
+            }
+        }
+    }
+
+    let mut groups = vec![Vec::new(); num_clusters];
+    let mut remainder = Vec::new();
+    for (point, label) in sp.into_iter().zip(labels) {
+        match label {
+            Some(label) => groups[label].push(point),
+            None => remainder.push(point),
+        }
+    }
+    // Same as the Hough clustering, larger clusters come first. The sort is
+    // stable, so ties keep a deterministic order.
+    groups.sort_by_key(|g| std::cmp::Reverse(g.len()));
+
+    let mut clusters = Vec::new();
+    for group in groups {
+        if group.len() >= min_num_points_per_cluster
+            && max_num_clusters.is_none_or(|max| clusters.len() < max)
+        {
+            clusters.push(Cluster(group));
+        } else {
+            remainder.extend(group);
+        }
+    }
+
+    ClusteringResult {
+        clusters,
+        remainder,
+    }
+}
@@ -1,21 +1,28 @@
+use crate::field::MagneticField;
+use crate::reconstruction::matrix::{inverse, inverse_3x3, mat_add, mat_mul, transpose, Matrix};
 use crate::reconstruction::{
-    angle_between_vectors, Cluster, Coordinate, Helix, Track, TryTrackFromClusterError,
+    angle_between_vectors, truncated_mean_dedx, Cluster, Coordinate, Helix, Perigee, Track,
+    TryTrackFromClusterError,
 };
 use crate::SpacePoint;
 use argmin::core::{CostFunction, Error, Executor};
 use argmin::solver::neldermead::NelderMead;
 use itertools::Itertools;
 use num_complex::Complex;
-use std::f64::consts::PI;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::f64::consts::{PI, TAU};
 use uom::si::angle::radian;
-use uom::si::area::square_meter;
-use uom::si::f64::{Angle, Area, Length};
+use uom::si::f64::{Angle, Length, ReciprocalLength};
 use uom::si::length::meter;
-use uom::typenum::P2;
+use uom::si::magnetic_flux_density::tesla;
+use uom::si::ratio::ratio;
+use uom::si::reciprocal_length::reciprocal_meter;
 
 // To first order, the full track from the vertex to outside of the rTPC gas
 // volume is a helix with axis parallel to the z-axis.
 // Minimize the orthogonal distance between the track and the SpacePoints.
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn fit_cluster_to_helix(
     cluster: Cluster,
     max_num_solver_iter: u64,
@@ -30,14 +37,22 @@ pub(crate) fn fit_cluster_to_helix(
     // See Helix::closest_t for details on these 2 parameters.
     max_num_closest_t_iter: usize,
     closest_t_tolerance: f64,
+    // See `truncated_mean_dedx` for details.
+    dedx_kept_fraction: f64,
+    // Step used to numerically calculate the Hessian of the chi-squared with
+    // respect to the perigee parameters.
+    hessian_step: f64,
+    // See `three_template_points` for details on these 2 parameters.
+    max_num_resample: usize,
+    seed: u64,
 ) -> Result<Track, TryTrackFromClusterError> {
     let sp = cluster.0;
-    // This assert is here just to make sure we don't accidentally change the
-    // minimum number of points required in a cluster.
-    assert!(sp.len() >= 3);
+    if sp.len() < 3 {
+        return Err(TryTrackFromClusterError::NotEnoughPoints(sp.len()));
+    }
     // Three points are enough to get a reasonable first guess for the helix
     // parameters.
-    let (first, middle, last) = three_template_points(&sp)?;
+    let (first, middle, last) = three_template_points(&sp, max_num_resample, seed)?;
 
     let (x0, y0, r) = circle_through_three_points(
         (first.x(), first.y()),
@@ -94,6 +109,15 @@ pub(crate) fn fit_cluster_to_helix(
         initial_simplex.push(new_point);
     }
 
+    let wire_amplitudes = sp.iter().map(|p| p.wire_amplitude).collect();
+    let num_points = sp.len();
+    // Need to evaluate the cost function after minimization to get the
+    // chi-squared and covariance.
+    let evaluator = Problem {
+        points: sp.clone(),
+        tolerance: closest_t_tolerance,
+        max_num_iter: max_num_closest_t_iter,
+    };
     let problem = Problem {
         points: sp,
         tolerance: closest_t_tolerance,
@@ -117,17 +141,124 @@ pub(crate) fn fit_cluster_to_helix(
         phi0: Angle::new::<radian>(best_params[4]),
         h: Length::new::<meter>(best_params[5]),
     };
-    Ok(Track {
+    let t_inner = helix.closest_t(first, closest_t_tolerance, max_num_closest_t_iter);
+    let t_outer = helix.closest_t(last, closest_t_tolerance, max_num_closest_t_iter);
+    let mut track = Track {
         helix,
-        t_inner: helix.closest_t(first, closest_t_tolerance, max_num_closest_t_iter),
-        t_outer: helix.closest_t(last, closest_t_tolerance, max_num_closest_t_iter),
+        t_inner,
+        t_outer,
+        dedx: truncated_mean_dedx(
+            wire_amplitudes,
+            helix.arc_length(t_inner, t_outer),
+            dedx_kept_fraction,
+        ),
+        cosmic: false,
+        covariance: None,
+        // The cost function never returns an error.
+        chi2: evaluator.cost(&best_params).unwrap(),
+        // The residual is evaluated at the point of closest approach, so the
+        // position of each SpacePoint along the track is a free parameter.
+        ndf: ndf(num_points, 2),
+        num_iter: res.state.iter,
+    };
+    // The cost function is already a chi-squared (distances are in units of
+    // the SpacePoint resolution). Its Hessian with respect to the perigee
+    // parameters is 2 * (covariance)^-1.
+    //
+    // The helix of a perigee has the perigee at `t = 0`. Shift its phase to
+    // match the fitted helix; otherwise, `closest_t` can find the closest
+    // point on a different turn than the one found by the fit. The shift has
+    // to be continuous around the perigee of the track.
+    let wrap = |angle: f64| (angle + PI).rem_euclid(TAU) - PI;
+    let phase = |helix: Helix| (helix.phi0 - track.helix.phi0).get::<radian>();
+    let reference = wrap(phase(Track::from_perigee(track.perigee()).helix));
+    let perigee = perigee_to_params(track.perigee());
+    let chi2 = |params: &[f64; 5]| {
+        let mut helix = Track::from_perigee(params_to_perigee(params)).helix;
+        let shift = reference + wrap(phase(helix) - reference);
+        helix.phi0 -= Angle::new::<radian>(shift);
+        helix.z0 -= helix.h * shift / TAU;
+        let params = vec![
+            helix.x0.get::<meter>(),
+            helix.y0.get::<meter>(),
+            helix.z0.get::<meter>(),
+            helix.r.get::<meter>(),
+            helix.phi0.get::<radian>(),
+            helix.h.get::<meter>(),
+        ];
+        // The cost function never returns an error.
+        evaluator.cost(&params).unwrap()
+    };
+    track.covariance = inverse(&hessian(chi2, &perigee, hessian_step))
+        .filter(|inverse| (0..5).all(|i| inverse[i][i] > 0.0))
+        .map(|inverse| inverse.map(|row| row.map(|value| 2.0 * value)));
+
+    Ok(track)
+}
+
+// Flatten the perigee parameters into SI units (i.e. `METER`, `RADIAN`, and
+// `RECIPROCAL_METER`). The order is the same as in `Track::covariance`.
+fn perigee_to_params(perigee: Perigee) -> [f64; 5] {
+    [
+        perigee.d0.get::<meter>(),
+        perigee.z0.get::<meter>(),
+        perigee.phi0.get::<radian>(),
+        perigee.curvature.get::<reciprocal_meter>(),
+        perigee.tan_lambda,
+    ]
+}
+
+// Inverse of `perigee_to_params`.
+fn params_to_perigee(params: &[f64; 5]) -> Perigee {
+    Perigee {
+        d0: Length::new::<meter>(params[0]),
+        z0: Length::new::<meter>(params[1]),
+        phi0: Angle::new::<radian>(params[2]),
+        curvature: ReciprocalLength::new::<reciprocal_meter>(params[3]),
+        tan_lambda: params[4],
+    }
+}
+
+// Numerically calculate the Hessian of a function at a given point.
+fn hessian<F, const N: usize>(f: F, point: &[f64; N], step: f64) -> Matrix<N, N>
+where
+    F: Fn(&[f64; N]) -> f64,
+{
+    let value = |di: usize, si: f64, dj: usize, sj: f64| {
+        let mut p = *point;
+        p[di] += si * step;
+        p[dj] += sj * step;
+        f(&p)
+    };
+
+    std::array::from_fn(|i| {
+        std::array::from_fn(|j| {
+            // Make sure the Hessian is exactly symmetric.
+            let (i, j) = (i.min(j), i.max(j));
+            if i == j {
+                (value(i, 1.0, i, 0.0) - 2.0 * value(i, 0.0, i, 0.0) + value(i, -1.0, i, 0.0))
+                    / step.powi(2)
+            } else {
+                (value(i, 1.0, j, 1.0) - value(i, 1.0, j, -1.0) - value(i, -1.0, j, 1.0)
+                    + value(i, -1.0, j, -1.0))
+                    / (4.0 * step.powi(2))
+            }
+        })
     })
 }
 
 // With 3 spread out points, we can get a decent first guess on the helix
 // parameters.
+//
+// If the natural choice of points (see below) can not produce a circle, up to
+// `max_num_resample` random triplets are tried instead. The random number
+// generator is seeded with `seed` for every cluster, so the result only
+// depends on the cluster itself (not e.g. on the order in which clusters are
+// fitted, or on the number of threads).
 fn three_template_points(
     points: &[SpacePoint],
+    max_num_resample: usize,
+    seed: u64,
     // In theory, we would expect our tracks to originate from (near) the origin
     // and travel outwards.
     // Sorting by `r` feels like a natural ordering.
@@ -148,23 +279,39 @@ fn three_template_points(
         .copied()
         .unwrap();
 
-    // If the 3 points are collinear, then there is no circle containing the
-    // three points( with finite radius).
-    // Also, if any pair of points are the same, then there is no circle
-    // because we effectively have only 2 points.
-    // There are 3 possible comparisons to make between slopes. I just did this
-    // one because it exactly matches a fail mode of
-    // `circle_through_three_points`.
-    // Any other comparison (or using this as a test of collinearity to e.g.
-    // resample the points) would require some epsilon distance difference
-    // instead of exact equality (i.e. the usual way of comparing floats).
-    if (last.x() - first.x()) * (middle.y() - first.y())
-        == (middle.x() - first.x()) * (last.y() - first.y())
-    {
-        return Err(TryTrackFromClusterError::NoInitialParameters);
+    if !is_degenerate_triplet(first, middle, last) {
+        return Ok((first, middle, last));
     }
 
-    Ok((first, middle, last))
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..max_num_resample {
+        let mut triplet: Vec<_> = rand::seq::index::sample(&mut rng, points.len(), 3)
+            .into_iter()
+            .map(|i| points[i])
+            .collect();
+        triplet.sort_by(|a, b| a.r.partial_cmp(&b.r).unwrap());
+        let (first, middle, last) = (triplet[0], triplet[1], triplet[2]);
+
+        if !is_degenerate_triplet(first, middle, last) {
+            return Ok((first, middle, last));
+        }
+    }
+
+    Err(TryTrackFromClusterError::NoInitialParameters)
+}
+
+// If the 3 points are collinear, then there is no circle containing the
+// three points( with finite radius).
+// Also, if any pair of points are the same, then there is no circle
+// because we effectively have only 2 points.
+// There are 3 possible comparisons to make between slopes. I just did this
+// one because it exactly matches a fail mode of
+// `circle_through_three_points`.
+// Any other comparison would require some epsilon distance difference instead
+// of exact equality (i.e. the usual way of comparing floats).
+fn is_degenerate_triplet(first: SpacePoint, middle: SpacePoint, last: SpacePoint) -> bool {
+    (last.x() - first.x()) * (middle.y() - first.y())
+        == (middle.x() - first.x()) * (last.y() - first.y())
 }
 
 // Return the center and radius of the circle that goes through three points.
@@ -217,7 +364,8 @@ fn center_of_mass(points: &[SpacePoint]) -> Coordinate {
 }
 
 // The actual minimization problem is to find the helix that minimizes the
-// sum of squared distances to all points.
+// sum of squared distances to all points (weighted by the resolution of each
+// point).
 struct Problem {
     points: Vec<SpacePoint>,
     // Parameters required to calculate the distance between a point and a helix.
@@ -225,13 +373,35 @@ struct Problem {
     max_num_iter: usize,
 }
 
-// Calculate the squared distance between a SpacePoint and a Coordinate.
-fn norm_sqr(sp: SpacePoint, c: Coordinate) -> Area {
-    let x = c.x - sp.x();
-    let y = c.y - sp.y();
-    let z = c.z - sp.z;
+// Decompose the distance from a Coordinate to a SpacePoint along the radial,
+// azimuthal, and axial directions at the SpacePoint.
+pub(crate) fn residual_components(sp: SpacePoint, c: Coordinate) -> [Length; 3] {
+    let x = sp.x() - c.x;
+    let y = sp.y() - c.y;
+
+    let (sin, cos) = sp.phi.sin_cos();
+    let radial = x * cos + y * sin;
+    let azimuthal = y * cos - x * sin;
+
+    [radial, azimuthal, sp.z - c.z]
+}
+
+// Calculate the squared distance between a SpacePoint and a Coordinate in
+// units of the SpacePoint uncertainties.
+fn weighted_norm_sqr(sp: SpacePoint, c: Coordinate) -> f64 {
+    let [radial, azimuthal, axial] = residual_components(sp, c);
 
-    x.powi(P2::new()) + y.powi(P2::new()) + z.powi(P2::new())
+    (radial / sp.sigma_r).get::<ratio>().powi(2)
+        + (azimuthal / sp.sigma_rphi).get::<ratio>().powi(2)
+        + (axial / sp.sigma_z).get::<ratio>().powi(2)
+}
+
+// Number of degrees of freedom of a track fit given the number of SpacePoints
+// and the number of coordinates of each SpacePoint that actually constrain the
+// track. The helix has 5 independent parameters.
+fn ndf(num_points: usize, constraints_per_point: usize) -> usize {
+    // Fits require at least 3 SpacePoints.
+    constraints_per_point * num_points - 5
 }
 // Argmin parameters need to be a single type, so we just use the internal
 // f64 representation of the helix parameters.
@@ -258,7 +428,7 @@ impl CostFunction for Problem {
                 let t = helix.closest_t(p, self.tolerance, self.max_num_iter);
                 let closest_point = helix.at(t);
 
-                let val = norm_sqr(p, closest_point);
+                let val = weighted_norm_sqr(p, closest_point);
                 // Argmin needs non-NaN values to work properly.
                 // If we got NaN at this point, there is a bug somewhere that
                 // needs to be fixed.
@@ -266,7 +436,375 @@ impl CostFunction for Problem {
 
                 val
             })
-            .sum::<Area>()
-            .get::<square_meter>())
+            .sum::<f64>())
+    }
+}
+
+// Alternative to `fit_cluster_to_helix` based on an (extended) Kalman filter.
+//
+// The global least-squares fit treats all SpacePoints equally and assumes that
+// the particle follows a perfect helix. The Kalman filter instead follows the
+// particle from the outermost to the innermost SpacePoint, taking into account
+// the resolution of each SpacePoint and the multiple scattering in the rTPC
+// gas. The final state (at the innermost SpacePoint) is the best estimate of
+// the track parameters close to the vertex.
+//
+// Internally, the state vector is:
+//     (x, y, z, psi, tan_lambda, kappa)
+// Where `psi` is the azimuthal angle of the direction of motion, `tan_lambda`
+// is dz/ds (with `s` the path length in the x-y plane), and `kappa` is the
+// signed curvature in the x-y plane (positive for counter-clockwise rotation).
+// Just as with argmin, all f64s are in `METER` and `RADIAN`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn kalman_fit_cluster_to_helix(
+    cluster: Cluster,
+    // Magnetic field used to estimate the momentum of the particle (required
+    // for multiple scattering).
+    magnetic_field: &MagneticField,
+    // Radiation length of the rTPC gas.
+    radiation_length: Length,
+    // See Helix::closest_t for details on these 2 parameters.
+    max_num_closest_t_iter: usize,
+    closest_t_tolerance: f64,
+    // See `truncated_mean_dedx` for details.
+    dedx_kept_fraction: f64,
+    // See `three_template_points` for details on these 2 parameters.
+    max_num_resample: usize,
+    seed: u64,
+) -> Result<Track, TryTrackFromClusterError> {
+    let mut sp = cluster.0;
+    // Same minimum number of points as the least-squares fit.
+    if sp.len() < 3 {
+        return Err(TryTrackFromClusterError::NotEnoughPoints(sp.len()));
     }
+    let (first, middle, last) = three_template_points(&sp, max_num_resample, seed)?;
+    let (x0, y0, r) = circle_through_three_points(
+        (first.x(), first.y()),
+        (middle.x(), middle.y()),
+        (last.x(), last.y()),
+    );
+    let (x0, y0, r) = (x0.get::<meter>(), y0.get::<meter>(), r.get::<meter>());
+    // Seed the filter at the outermost SpacePoint, moving inwards (i.e.
+    // towards the middle SpacePoint).
+    let (lx, ly, lz) = (
+        last.x().get::<meter>(),
+        last.y().get::<meter>(),
+        last.z.get::<meter>(),
+    );
+    // Counter-clockwise tangent to the seed circle.
+    let (tx, ty) = (-(ly - y0) / r, (lx - x0) / r);
+    let (psi, kappa) =
+        if tx * (middle.x().get::<meter>() - lx) + ty * (middle.y().get::<meter>() - ly) >= 0.0 {
+            (ty.atan2(tx), 1.0 / r)
+        } else {
+            ((-ty).atan2(-tx), -1.0 / r)
+        };
+    let chord = (first.x() - last.x())
+        .hypot(first.y() - last.y())
+        .get::<meter>();
+    let tan_lambda = (first.z - last.z).get::<meter>() / chord;
+    // Only needed at the end to convert the state into a helix.
+    let cm = center_of_mass(&sp);
+
+    let mut state = [lx, ly, lz, psi, tan_lambda, kappa];
+    // Large initial uncertainties on the direction and curvature. The seed
+    // position is the outermost SpacePoint, which will be the first
+    // measurement anyway.
+    let mut cov = [[0.0; 6]; 6];
+    cov[0][0] = 1.0;
+    cov[1][1] = 1.0;
+    cov[2][2] = 1.0;
+    cov[3][3] = 1.0;
+    cov[4][4] = 1.0;
+    cov[5][5] = kappa.powi(2);
+
+    let radiation_length = radiation_length.get::<meter>();
+
+    let wire_amplitudes = sp.iter().map(|p| p.wire_amplitude).collect();
+    let num_points = sp.len();
+    let mut chi2 = 0.0;
+    sp.sort_unstable_by(|a, b| b.r.partial_cmp(&a.r).unwrap());
+    for p in sp {
+        let m = [
+            p.x().get::<meter>(),
+            p.y().get::<meter>(),
+            p.z.get::<meter>(),
+        ];
+
+        let s = path_to_closest_approach(&state, m);
+        let jacobian = propagation_jacobian(&state, s);
+        // Only the axial component of the field bends the track in the x-y
+        // plane.
+        let b_z = magnetic_field
+            .at(Coordinate {
+                x: Length::new::<meter>(state[0]),
+                y: Length::new::<meter>(state[1]),
+                z: Length::new::<meter>(state[2]),
+            })?
+            .z
+            .get::<tesla>();
+        let noise = scattering_noise(&state, s, b_z, radiation_length);
+        state = propagate(&state, s);
+        cov = mat_add(
+            &mat_mul(&mat_mul(&jacobian, &cov), &transpose(&jacobian)),
+            &noise,
+        );
+        // The SpacePoint resolution is given in the (r, phi) frame. Rotate it
+        // into the x-y frame.
+        let sigma_r = p.sigma_r.get::<meter>();
+        let sigma_rphi = p.sigma_rphi.get::<meter>();
+        let sigma_z = p.sigma_z.get::<meter>();
+        let (sin, cos) = p.phi.get::<radian>().sin_cos();
+        let meas_cov = [
+            [
+                (sigma_r * cos).powi(2) + (sigma_rphi * sin).powi(2),
+                (sigma_r.powi(2) - sigma_rphi.powi(2)) * sin * cos,
+                0.0,
+            ],
+            [
+                (sigma_r.powi(2) - sigma_rphi.powi(2)) * sin * cos,
+                (sigma_r * sin).powi(2) + (sigma_rphi * cos).powi(2),
+                0.0,
+            ],
+            [0.0, 0.0, sigma_z.powi(2)],
+        ];
+        let (new_state, new_cov, increment) = kalman_update(&state, &cov, m, &meas_cov);
+        state = new_state;
+        cov = new_cov;
+        chi2 += increment;
+    }
+
+    let helix = state_to_helix(&state, cm);
+    let t_inner = helix.closest_t(first, closest_t_tolerance, max_num_closest_t_iter);
+    let t_outer = helix.closest_t(last, closest_t_tolerance, max_num_closest_t_iter);
+    let mut track = Track {
+        helix,
+        t_inner,
+        t_outer,
+        dedx: truncated_mean_dedx(
+            wire_amplitudes,
+            helix.arc_length(t_inner, t_outer),
+            dedx_kept_fraction,
+        ),
+        cosmic: false,
+        covariance: None,
+        chi2,
+        // Even though each update adds a 3-component chi-squared, the state is
+        // always propagated to the point of closest approach. The residual
+        // along the track vanishes by construction and only 2 coordinates
+        // constrain the track (same as the least-squares fit).
+        ndf: ndf(num_points, 2),
+        // The Kalman filter is not iterative.
+        num_iter: 0,
+    };
+    // Propagate the covariance of the final state to the perigee parameters.
+    // The direction of the track (i.e. `t_inner` and `t_outer`) is kept fixed.
+    let perigee = |state: &State| {
+        perigee_to_params(
+            Track {
+                helix: state_to_helix(state, cm),
+                ..track
+            }
+            .perigee(),
+        )
+    };
+    let mut jacobian = [[0.0; 6]; 5];
+    for j in 0..6 {
+        let eps = 1e-7 * state[j].abs().max(1.0);
+        let mut plus = state;
+        plus[j] += eps;
+        let mut minus = state;
+        minus[j] -= eps;
+        let (plus, minus) = (perigee(&plus), perigee(&minus));
+        for i in 0..5 {
+            let diff = if i == 2 {
+                wrap_angle(plus[i] - minus[i])
+            } else {
+                plus[i] - minus[i]
+            };
+            jacobian[i][j] = diff / (2.0 * eps);
+        }
+    }
+    let covariance = mat_mul(&mat_mul(&jacobian, &cov), &transpose(&jacobian));
+    track.covariance = (0..5).all(|i| covariance[i][i] > 0.0).then_some(covariance);
+
+    Ok(track)
+}
+
+type State = [f64; 6];
+
+// Convert the state vector into a helix. Keep the same convention as the
+// least-squares fit i.e. `phi0` pointing towards the center of mass of the
+// SpacePoints.
+fn state_to_helix(state: &State, cm: Coordinate) -> Helix {
+    let [x, y, z, psi, tan_lambda, kappa] = *state;
+    // A straight line can not be represented by our helix. Just make it a
+    // helix with a very large radius.
+    let kappa = if kappa.abs() < 1e-6 {
+        1e-6_f64.copysign(kappa)
+    } else {
+        kappa
+    };
+    let xc = x - psi.sin() / kappa;
+    let yc = y + psi.cos() / kappa;
+    let h = 2.0 * PI * tan_lambda / kappa;
+    let phi0 = (cm.y.get::<meter>() - yc).atan2(cm.x.get::<meter>() - xc);
+    let t = wrap_angle((y - yc).atan2(x - xc) - phi0);
+    let z0 = z - h / (2.0 * PI) * t;
+
+    Helix {
+        x0: Length::new::<meter>(xc),
+        y0: Length::new::<meter>(yc),
+        z0: Length::new::<meter>(z0),
+        r: Length::new::<meter>(kappa.abs().recip()),
+        phi0: Angle::new::<radian>(phi0),
+        h: Length::new::<meter>(h),
+    }
+}
+
+// Wrap an angle (in RADIAN) to [-pi, pi).
+fn wrap_angle(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+// Below this curvature (times path length) the trajectory is treated as a
+// straight line to avoid dividing by zero.
+const STRAIGHT_LINE_THRESHOLD: f64 = 1e-9;
+
+// Move the state a (signed) path length `s` in the x-y plane.
+fn propagate(state: &State, s: f64) -> State {
+    let [x, y, z, psi, tan_lambda, kappa] = *state;
+    let (dx, dy) = if (kappa * s).abs() < STRAIGHT_LINE_THRESHOLD {
+        (s * psi.cos(), s * psi.sin())
+    } else {
+        (
+            ((psi + kappa * s).sin() - psi.sin()) / kappa,
+            (psi.cos() - (psi + kappa * s).cos()) / kappa,
+        )
+    };
+
+    [
+        x + dx,
+        y + dy,
+        z + tan_lambda * s,
+        psi + kappa * s,
+        tan_lambda,
+        kappa,
+    ]
+}
+
+// Return the path length in the x-y plane from the current state to the point
+// of closest approach (in the x-y plane) to a measurement.
+fn path_to_closest_approach(state: &State, m: [f64; 3]) -> f64 {
+    let [x, y, _, psi, _, kappa] = *state;
+    if kappa.abs() < STRAIGHT_LINE_THRESHOLD {
+        return (m[0] - x) * psi.cos() + (m[1] - y) * psi.sin();
+    }
+    let xc = x - psi.sin() / kappa;
+    let yc = y + psi.cos() / kappa;
+    let delta = wrap_angle((m[1] - yc).atan2(m[0] - xc) - (y - yc).atan2(x - xc));
+
+    delta / kappa
+}
+
+// Numerical Jacobian of `propagate` with respect to the state (for a fixed
+// path length).
+fn propagation_jacobian(state: &State, s: f64) -> Matrix<6, 6> {
+    let mut jacobian = [[0.0; 6]; 6];
+    for j in 0..6 {
+        let eps = 1e-7 * state[j].abs().max(1.0);
+        let mut plus = *state;
+        plus[j] += eps;
+        let mut minus = *state;
+        minus[j] -= eps;
+        let (plus, minus) = (propagate(&plus, s), propagate(&minus, s));
+        for i in 0..6 {
+            jacobian[i][j] = (plus[i] - minus[i]) / (2.0 * eps);
+        }
+    }
+
+    jacobian
+}
+
+// Process noise due to multiple scattering after traveling a path length `s`
+// in the x-y plane. The RMS scattering angle is given by the Highland formula
+// assuming `beta = 1`.
+fn scattering_noise(
+    state: &State,
+    s: f64,
+    // In TESLA.
+    magnetic_field: f64,
+    // In METER.
+    radiation_length: f64,
+) -> Matrix<6, 6> {
+    let mut noise = [[0.0; 6]; 6];
+    let [_, _, _, _, tan_lambda, kappa] = *state;
+    let sec_lambda_sqr = 1.0 + tan_lambda.powi(2);
+    let length = s.abs() * sec_lambda_sqr.sqrt();
+    if length == 0.0 || kappa == 0.0 {
+        return noise;
+    }
+    // Momentum in GeV/c.
+    let p = 0.299_792_458 * magnetic_field / kappa.abs() * sec_lambda_sqr.sqrt();
+    let x = length / radiation_length;
+    let theta0 = (0.0136 / p) * x.sqrt() * (1.0 + 0.038 * x.ln()).max(0.0);
+
+    noise[3][3] = theta0.powi(2) * sec_lambda_sqr;
+    noise[4][4] = theta0.powi(2) * sec_lambda_sqr.powi(2);
+
+    noise
+}
+
+// Standard Kalman update given a measurement of the (x, y, z) position. Also
+// return the chi-squared increment of the (predicted) residual.
+fn kalman_update(
+    state: &State,
+    cov: &Matrix<6, 6>,
+    m: [f64; 3],
+    meas_cov: &Matrix<3, 3>,
+) -> (State, Matrix<6, 6>, f64) {
+    // The measurement matrix just selects the first 3 components of the state.
+    let mut s = *meas_cov;
+    for i in 0..3 {
+        for j in 0..3 {
+            s[i][j] += cov[i][j];
+        }
+    }
+    let Some(s_inv) = inverse_3x3(&s) else {
+        // Can only happen if the measurement covariance is not positive
+        // definite. Just skip the measurement.
+        return (*state, *cov, 0.0);
+    };
+    // Kalman gain: K = P H^T S^-1
+    let mut gain = [[0.0; 3]; 6];
+    for i in 0..6 {
+        for j in 0..3 {
+            gain[i][j] = (0..3).map(|k| cov[i][k] * s_inv[k][j]).sum();
+        }
+    }
+    let residual = [m[0] - state[0], m[1] - state[1], m[2] - state[2]];
+    let chi2 = (0..3)
+        .flat_map(|i| (0..3).map(move |j| (i, j)))
+        .map(|(i, j)| residual[i] * s_inv[i][j] * residual[j])
+        .sum();
+
+    let mut new_state = *state;
+    for i in 0..6 {
+        new_state[i] += (0..3).map(|k| gain[i][k] * residual[k]).sum::<f64>();
+    }
+    let mut new_cov = *cov;
+    for i in 0..6 {
+        for j in 0..6 {
+            new_cov[i][j] -= (0..3).map(|k| gain[i][k] * cov[k][j]).sum::<f64>();
+        }
+    }
+    // Keep the covariance symmetric (numerical errors accumulate otherwise).
+    let transposed = transpose(&new_cov);
+    for (row, transposed_row) in new_cov.iter_mut().zip(transposed) {
+        for (value, transposed_value) in row.iter_mut().zip(transposed_row) {
+            *value = 0.5 * (*value + transposed_value);
+        }
+    }
+
+    (new_state, new_cov, chi2)
 }
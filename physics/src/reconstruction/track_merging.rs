@@ -0,0 +1,192 @@
+use crate::reconstruction::{Cluster, Helix, Track, TryTrackFromClusterError};
+use crate::SpacePoint;
+use alpha_g_detector::alpha16::aw_map::INNER_CATHODE_RADIUS;
+use itertools::Itertools;
+use std::f64::consts::TAU;
+use uom::si::area::square_meter;
+use uom::si::f64::{Area, Length};
+use uom::si::length::meter;
+use uom::si::ratio::ratio;
+use uom::typenum::P2;
+
+// A single track can be split into multiple clusters e.g. by a dead region of
+// the detector or by the maximum neighbor distance during clustering. Each
+// piece is then either fitted as a short (poorly constrained) track, or thrown
+// away.
+//
+// Fit all clusters individually, and then greedily merge the pair of clusters
+// whose combined fit is best. Repeat until no pair can be merged. A pair of
+// clusters is only considered for merging if their individual helices are
+// compatible (i.e. similar circle in the x-y plane and similar dz/ds). The
+// merged fit is only accepted if the RMS distance between the SpacePoints and
+// the combined helix is small, and the combined helix doesn't go through the
+// inner cathode between both clusters (two tracks going in opposite directions
+// from a vertex are always compatible).
+//
+// Clusters that can not be fitted individually are dropped, and the reason why
+// each of them failed is returned together with the tracks.
+pub(crate) fn fit_and_merge_clusters<F>(
+    clusters: Vec<Cluster>,
+    fit: F,
+    // Maximum difference between the circle centers and radii, relative to the
+    // largest of the two radii.
+    max_relative_difference: f64,
+    // Maximum difference between dz/ds (with `s` the arc length in the x-y
+    // plane).
+    max_slope_difference: f64,
+    max_rms_residual: Length,
+    // See Helix::closest_t for details on these 2 parameters.
+    max_num_closest_t_iter: usize,
+    closest_t_tolerance: f64,
+) -> (Vec<Track>, Vec<TryTrackFromClusterError>)
+where
+    F: Fn(Cluster) -> Result<Track, TryTrackFromClusterError>,
+{
+    let mut fitted: Vec<(Cluster, Track)> = Vec::new();
+    let mut failures = Vec::new();
+    for cluster in clusters {
+        match fit(cluster.clone()) {
+            Ok(track) => fitted.push((cluster, track)),
+            Err(error) => failures.push(error),
+        }
+    }
+
+    loop {
+        let mut best: Option<(usize, usize, Cluster, Track, Length)> = None;
+        for (i, j) in (0..fitted.len()).tuple_combinations() {
+            let (cluster_i, track_i) = &fitted[i];
+            let (cluster_j, track_j) = &fitted[j];
+            if !are_compatible(
+                &track_i.helix,
+                &track_j.helix,
+                max_relative_difference,
+                max_slope_difference,
+            ) {
+                continue;
+            }
+
+            let merged = Cluster(cluster_i.iter().chain(cluster_j).copied().collect());
+            let Ok(track) = fit(merged.clone()) else {
+                continue;
+            };
+            let residual = rms_residual(
+                &track.helix,
+                &merged.0,
+                max_num_closest_t_iter,
+                closest_t_tolerance,
+            );
+            if residual > max_rms_residual
+                || crosses_inner_cathode(
+                    &track.helix,
+                    &cluster_i.0,
+                    &cluster_j.0,
+                    max_num_closest_t_iter,
+                    closest_t_tolerance,
+                )
+            {
+                continue;
+            }
+
+            if best.as_ref().is_none_or(|b| residual < b.4) {
+                best = Some((i, j, merged, track, residual));
+            }
+        }
+
+        let Some((i, j, merged, track, _)) = best else {
+            break;
+        };
+        // `j` is always greater than `i`; remove it first so that `i` is not
+        // invalidated.
+        fitted.swap_remove(j);
+        fitted.swap_remove(i);
+        fitted.push((merged, track));
+    }
+
+    let tracks = fitted.into_iter().map(|(_, track)| track).collect();
+
+    (tracks, failures)
+}
+
+fn are_compatible(
+    a: &Helix,
+    b: &Helix,
+    max_relative_difference: f64,
+    max_slope_difference: f64,
+) -> bool {
+    let r_a = a.r.abs();
+    let r_b = b.r.abs();
+    let scale = r_a.max(r_b);
+
+    let center_distance = (a.x0 - b.x0).hypot(a.y0 - b.y0);
+    if center_distance > max_relative_difference * scale
+        || (r_a - r_b).abs() > max_relative_difference * scale
+    {
+        return false;
+    }
+    // The parameter `t` always goes counter-clockwise, so the sign of dz/ds is
+    // consistent between two fits of the same track.
+    let slope_a = (a.h / (TAU * r_a)).get::<ratio>();
+    let slope_b = (b.h / (TAU * r_b)).get::<ratio>();
+
+    (slope_a - slope_b).abs() <= max_slope_difference
+}
+
+pub(crate) fn rms_residual(
+    helix: &Helix,
+    points: &[SpacePoint],
+    max_num_closest_t_iter: usize,
+    closest_t_tolerance: f64,
+) -> Length {
+    let sum = points
+        .iter()
+        .map(|&p| {
+            let c = helix.at(helix.closest_t(p, closest_t_tolerance, max_num_closest_t_iter));
+
+            (c.x - p.x()).powi(P2::new())
+                + (c.y - p.y()).powi(P2::new())
+                + (c.z - p.z).powi(P2::new())
+        })
+        .sum::<Area>();
+
+    Length::new::<meter>((sum.get::<square_meter>() / points.len() as f64).sqrt())
+}
+
+// Check if the helix goes inside the inner cathode in the gap between the two
+// clusters.
+pub(crate) fn crosses_inner_cathode(
+    helix: &Helix,
+    a: &[SpacePoint],
+    b: &[SpacePoint],
+    max_num_closest_t_iter: usize,
+    closest_t_tolerance: f64,
+) -> bool {
+    let t_range = |points: &[SpacePoint]| {
+        points
+            .iter()
+            .map(|&p| helix.closest_t(p, closest_t_tolerance, max_num_closest_t_iter))
+            .minmax_by(|x, y| x.total_cmp(y))
+            .into_option()
+            .unwrap()
+    };
+    let (min_a, max_a) = t_range(a);
+    let (min_b, max_b) = t_range(b);
+
+    let (start, end) = if max_a < min_b {
+        (max_a, min_b)
+    } else if max_b < min_a {
+        (max_b, min_a)
+    } else {
+        // Overlapping ranges; there is no gap between the clusters.
+        return false;
+    };
+    // The gap is small compared to a full revolution. A few samples are
+    // enough.
+    let num_samples = 10;
+    let inner_radius = Length::new::<meter>(INNER_CATHODE_RADIUS);
+    (0..=num_samples).any(|i| {
+        let t = start + (end - start) * f64::from(i) / f64::from(num_samples);
+        let c = helix.at(t);
+
+        c.x.hypot(c.y) < inner_radius
+    })
+}
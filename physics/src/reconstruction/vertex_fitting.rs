@@ -1,11 +1,14 @@
+use crate::reconstruction::matrix::{inverse_3x3, Matrix};
 use crate::reconstruction::{Coordinate, Track, VertexInfo, VertexingResult};
 use crate::SpacePoint;
 use argmin::core::{CostFunction, Error, Executor};
 use argmin::solver::neldermead::NelderMead;
 use itertools::Itertools;
+use std::f64::consts::PI;
 use uom::si::area::square_meter;
 use uom::si::f64::{Area, Length};
 use uom::si::length::meter;
+use uom::si::ratio::ratio;
 use uom::typenum::P2;
 
 #[allow(clippy::too_many_arguments)]
@@ -27,6 +30,23 @@ pub(crate) fn find_vertices(
     // Nelder-Mead stops whenever the standard deviation between the cost at all
     // simplex vertices is below this threshold.
     nelder_mead_sd_tolerance: f64,
+    // Expected distance of closest approach between a track and the true
+    // vertex. Used to calculate the chi-squared and covariance of the vertex.
+    track_resolution: Length,
+    // Step size to numerically calculate the Hessian of the cost function.
+    hessian_step: Length,
+    // Chi-squared contribution of a single track above which it is considered
+    // incompatible with the vertex.
+    max_track_chi2: f64,
+    // Optional Gaussian constraint of the vertex to the beamline. Widths along
+    // `x` and `y` respectively.
+    beam_spot: Option<(Length, Length)>,
+    // Maximum number of vertices (primary plus secondaries) to fit. No limit if
+    // `None`.
+    max_num_vertices: Option<usize>,
+    // Initial temperature and ratio between consecutive temperatures of the
+    // adaptive vertex fit. Each track has the same weight if `None`.
+    annealing: Option<(f64, f64)>,
 ) -> VertexingResult {
     let primary_tracks = tracks
         .iter()
@@ -37,71 +57,130 @@ pub(crate) fn find_vertices(
         .copied()
         .collect();
 
-    let vertex = beamline_clusters(primary_tracks, max_beamline_clustering_distance)
-        .into_iter()
-        .filter(|(cluster, _)| cluster.len() > 1)
-        .max_set_by_key(|(cluster, _)| cluster.len())
-        .into_iter()
-        .max_by(|(c_a, _), (c_b, _)| {
-            c_a.iter()
-                .map(|track| track.helix.r)
-                .sum::<Length>()
-                .partial_cmp(&c_b.iter().map(|track| track.helix.r).sum::<Length>())
-                .unwrap()
-        })
-        .map(|(tracks, mean_z)| {
-            // Argmin needs all parameters to be same type. Work with internal
-            // f64.
-            // It has to be in `METER` because that is what the `cost_function`
-            // expects internally.
-            let initial_guess = vec![0.0, 0.0, mean_z.get::<meter>()];
-            let mut initial_simplex = vec![initial_guess.clone()];
-            for i in 0..initial_guess.len() {
-                let mut new_point = initial_guess.clone();
-                if new_point[i] == 0.0 {
-                    // Default value from scipy's implementation.
-                    // I don't think this is important enough to make it a parameter.
-                    new_point[i] = 0.00025;
-                } else {
-                    new_point[i] *= 1.0 + initial_simplex_delta;
+    // The beamline constraint is enough to fit a vertex with a single track.
+    let min_num_tracks = if beam_spot.is_some() { 1 } else { 2 };
+    // Weight of the beamline constraint relative to the distance of closest
+    // approach to a track. Everything in the cost function is a squared
+    // distance divided by the track resolution squared (up to a global factor).
+    let beam_spot_weights = beam_spot.map(|(sigma_x, sigma_y)| {
+        [
+            (track_resolution / sigma_x).get::<ratio>().powi(2),
+            (track_resolution / sigma_y).get::<ratio>().powi(2),
+        ]
+    });
+
+    // Rank the candidates by number of tracks. Ties are broken by the sum of
+    // the radii of curvature (i.e. higher momentum tracks).
+    let sum_r = |cluster: &[Track]| cluster.iter().map(|track| track.helix.r).sum::<Length>();
+    let mut vertices: Vec<VertexInfo> =
+        beamline_clusters(primary_tracks, max_beamline_clustering_distance)
+            .into_iter()
+            .filter(|(cluster, _)| cluster.len() >= min_num_tracks)
+            .sorted_by(|(c_a, _), (c_b, _)| {
+                c_b.len()
+                    .cmp(&c_a.len())
+                    .then_with(|| sum_r(c_b).partial_cmp(&sum_r(c_a)).unwrap())
+            })
+            .take(max_num_vertices.unwrap_or(usize::MAX))
+            .map(|(tracks, mean_z)| {
+                let seed =
+                    median_seed(&tracks, mean_z, closest_t_tolerance, max_num_closest_t_iter);
+                // Argmin needs all parameters to be same type. Work with internal
+                // f64.
+                // It has to be in `METER` because that is what the `cost_function`
+                // expects internally.
+                let initial_guess = vec![
+                    seed.x.get::<meter>(),
+                    seed.y.get::<meter>(),
+                    seed.z.get::<meter>(),
+                ];
+
+                let problem = |weights: Vec<f64>| Problem {
+                    tracks: tracks.clone(),
+                    weights,
+                    tolerance: closest_t_tolerance,
+                    max_num_iter: max_num_closest_t_iter,
+                    beam_spot_weights,
+                };
+                let minimize = |initial_guess: &[f64], weights: Vec<f64>| {
+                    let solver =
+                        NelderMead::new(initial_simplex(initial_guess, initial_simplex_delta))
+                            .with_sd_tolerance(nelder_mead_sd_tolerance)
+                            .unwrap();
+                    let res = Executor::new(problem(weights), solver)
+                        .configure(|state| state.max_iters(max_num_solver_iter))
+                        .run()
+                        .unwrap();
+
+                    res.state.best_param.unwrap()
+                };
+                let sigma_sqr = track_resolution.powi(P2::new()).get::<square_meter>();
+                // Contribution of each track to the chi-squared at a given
+                // position.
+                let track_chi2 = |params: &[f64]| -> Vec<f64> {
+                    let sp = as_spacepoint(Coordinate {
+                        x: Length::new::<meter>(params[0]),
+                        y: Length::new::<meter>(params[1]),
+                        z: Length::new::<meter>(params[2]),
+                    });
+                    tracks
+                        .iter()
+                        .map(|track| {
+                            let t = track.helix.closest_t(
+                                sp,
+                                closest_t_tolerance,
+                                max_num_closest_t_iter,
+                            );
+                            norm_sqr(sp, track.at(t)).get::<square_meter>() / sigma_sqr
+                        })
+                        .collect()
+                };
+
+                let mut weights = vec![1.0; tracks.len()];
+                let mut best_params = minimize(&initial_guess, weights.clone());
+                // Adaptive fit. Re-weight all tracks given their distance to the
+                // previous vertex, and fit again at a lower temperature.
+                for temperature in annealing_temperatures(annealing) {
+                    weights = track_chi2(&best_params)
+                        .into_iter()
+                        .map(|chi2| adaptive_weight(chi2, max_track_chi2, temperature))
+                        .collect();
+                    best_params = minimize(&best_params, weights.clone());
                 }
-                initial_simplex.push(new_point);
-            }
+                // Again, remember that the f64s in the `cost_function` are in
+                // `METER`
+                let position = Coordinate {
+                    x: Length::new::<meter>(best_params[0]),
+                    y: Length::new::<meter>(best_params[1]),
+                    z: Length::new::<meter>(best_params[2]),
+                };
 
-            let problem = Problem {
-                tracks: tracks.clone(),
-                tolerance: closest_t_tolerance,
-                max_num_iter: max_num_closest_t_iter,
-            };
-            let solver = NelderMead::new(initial_simplex)
-                .with_sd_tolerance(nelder_mead_sd_tolerance)
-                .unwrap();
-            let res = Executor::new(problem, solver)
-                .configure(|state| state.max_iters(max_num_solver_iter))
-                .run()
-                .unwrap();
-            let best_params = res.state.best_param.unwrap();
-            // Again, remember that the f64s in the `cost_function` are in
-            // `METER`
-            let position = Coordinate {
-                x: Length::new::<meter>(best_params[0]),
-                y: Length::new::<meter>(best_params[1]),
-                z: Length::new::<meter>(best_params[2]),
-            };
-
-            VertexInfo {
-                position,
-                tracks: tracks
+                // Need to evaluate the cost function after minimization to get the
+                // chi-squared and covariance (with the same weights as the last
+                // fit).
+                let sum_weights = weights.iter().sum::<f64>();
+                let evaluator = problem(weights);
+                // chi2 = cost / sigma^2
+                // and the covariance is 2 * (Hessian of chi2)^-1
+                let chi2 = evaluator.cost(&best_params).unwrap() / sigma_sqr;
+                let covariance = inverse_3x3(&hessian(
+                    &evaluator,
+                    &best_params,
+                    hessian_step.get::<meter>(),
+                ))
+                .filter(|inverse| (0..3).all(|i| inverse[i][i] > 0.0))
+                .map(|inverse| {
+                    inverse.map(|row| {
+                        row.map(|value| Area::new::<square_meter>(2.0 * sigma_sqr * value))
+                    })
+                });
+
+                // There is already a method in `Track` to calculate the closest_t
+                // to a SpacePoint. Just use that.
+                let sp = as_spacepoint(position);
+                let tracks: Vec<_> = tracks
                     .into_iter()
                     .map(|track| {
-                        // There is already a method in `Track` to calculate the
-                        // closest_t to a SpacePoint. Just use that.
-                        let sp = SpacePoint {
-                            r: position.x.hypot(position.y),
-                            phi: position.y.atan2(position.x),
-                            z: position.z,
-                        };
-
                         let t =
                             track
                                 .helix
@@ -109,25 +188,126 @@ pub(crate) fn find_vertices(
 
                         (track, t)
                     })
-                    .collect(),
-            }
-        });
-    // The remainder is the set of tracks that are not associated with the
-    // primary vertex.
-    for (track, _) in vertex.iter().flat_map(|v| v.tracks.iter()) {
-        // All tracks in the primary vertex are guaranteed to come from the
-        // original set of tracks.
+                    .collect();
+                // Same weights as an adaptive vertex fitter at unit temperature.
+                let weights = tracks
+                    .iter()
+                    .map(|(track, t)| {
+                        let track_chi2 =
+                            norm_sqr(sp, track.at(*t)).get::<square_meter>() / sigma_sqr;
+
+                        adaptive_weight(track_chi2, max_track_chi2, 1.0)
+                    })
+                    .collect();
+                // Each track constrains the 2 directions perpendicular to it, and
+                // the beam spot constrains both `x` and `y`. A down-weighted track
+                // (adaptive fit) only contributes a fraction of its constraints.
+                let num_constraints =
+                    (2.0 * sum_weights).round() as usize + if beam_spot.is_some() { 2 } else { 0 };
+                let ndf = num_constraints.saturating_sub(3);
+
+                VertexInfo {
+                    position,
+                    covariance,
+                    chi2,
+                    ndf,
+                    probability: chi2_probability(chi2, ndf),
+                    tracks,
+                    weights,
+                }
+            })
+            .collect();
+    // The remainder is the set of tracks that are not associated with any
+    // vertex.
+    for (track, _) in vertices.iter().flat_map(|v| v.tracks.iter()) {
+        // All tracks in a vertex are guaranteed to come from the original set
+        // of tracks (and each track is in at most one vertex).
         let index = tracks.iter().position(|t| t == track).unwrap();
         tracks.swap_remove(index);
     }
+    let primary = (!vertices.is_empty()).then(|| vertices.remove(0));
 
     VertexingResult {
-        primary: vertex,
-        secondaries: Vec::new(),
+        primary,
+        secondaries: vertices,
         remainder: tracks,
     }
 }
 
+// Probability of getting a chi-squared larger than `chi2` by chance given
+// `ndf` degrees of freedom i.e. the regularized upper incomplete gamma
+// function Q(ndf / 2, chi2 / 2).
+// See section 6.2 of Numerical Recipes (3rd edition).
+pub(crate) fn chi2_probability(chi2: f64, ndf: usize) -> f64 {
+    // Nothing to compare against; any fit is as good as it gets.
+    if ndf == 0 || chi2 <= 0.0 {
+        return 1.0;
+    }
+    const MAX_NUM_ITER: usize = 1000;
+
+    let a = 0.5 * ndf as f64;
+    let x = 0.5 * chi2;
+    let prefactor = (a * x.ln() - x - ln_gamma_half_integer(a)).exp();
+    if x < a + 1.0 {
+        // The series representation of P(a, x) converges quickly here.
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..MAX_NUM_ITER {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * f64::EPSILON {
+                break;
+            }
+        }
+
+        (1.0 - sum * prefactor).max(0.0)
+    } else {
+        // Continued fraction representation of Q(a, x) evaluated with the
+        // modified Lentz's method.
+        let tiny = f64::MIN_POSITIVE / f64::EPSILON;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..MAX_NUM_ITER {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < f64::EPSILON {
+                break;
+            }
+        }
+
+        prefactor * h
+    }
+}
+
+// Logarithm of the gamma function for positive half-integers (i.e. the only
+// values needed for a chi-squared distribution).
+fn ln_gamma_half_integer(mut a: f64) -> f64 {
+    let mut ln_gamma = 0.0;
+    while a > 1.0 {
+        a -= 1.0;
+        ln_gamma += a.ln();
+    }
+    // Gamma(1/2) = sqrt(pi) and Gamma(1) = 1.
+    if a < 1.0 {
+        ln_gamma += 0.5 * PI.ln();
+    }
+
+    ln_gamma
+}
+
 // Cluster tracks by the `z` coordinate of their closest approach to the
 // beamline.
 fn beamline_clusters(
@@ -180,13 +360,195 @@ fn beamline_clusters(
         .collect()
 }
 
+// Initial simplex around an initial guess. Following scipy's way of defining
+// the simplex vertices.
+fn initial_simplex(initial_guess: &[f64], initial_simplex_delta: f64) -> Vec<Vec<f64>> {
+    let mut initial_simplex = vec![initial_guess.to_vec()];
+    for i in 0..initial_guess.len() {
+        let mut new_point = initial_guess.to_vec();
+        // Default value from scipy's implementation.
+        // I don't think this is important enough to make it a parameter.
+        // Unlike scipy, it is also used for tiny (but not exactly zero) values
+        // e.g. `x` and `y` of a seed on the beamline.
+        if (new_point[i] * initial_simplex_delta).abs() < 0.00025 {
+            new_point[i] += 0.00025;
+        } else {
+            new_point[i] *= 1.0 + initial_simplex_delta;
+        }
+        initial_simplex.push(new_point);
+    }
+
+    initial_simplex
+}
+
+// Weight of a track in an adaptive vertex fit at a given temperature i.e.
+// exp(-chi2 / 2T) / (exp(-chi2 / 2T) + exp(-cut-off / 2T)). A track with a
+// chi-squared equal to the cut-off has a weight of 0.5 at any temperature; at
+// high temperatures all weights are close to 0.5, and they approach a hard cut
+// (0 or 1) as the temperature decreases.
+fn adaptive_weight(chi2: f64, cut_off: f64, temperature: f64) -> f64 {
+    1.0 / (1.0 + ((chi2 - cut_off) / (2.0 * temperature)).exp())
+}
+
+// Temperatures of the deterministic annealing of the adaptive vertex fit, from
+// the initial temperature down to 1 (geometric sequence with the given ratio).
+// Empty if there is no annealing, and only unit temperature if the schedule is
+// not valid (it would never reach 1).
+fn annealing_temperatures(annealing: Option<(f64, f64)>) -> Vec<f64> {
+    let Some((initial_temperature, cooling_ratio)) = annealing else {
+        return Vec::new();
+    };
+    if !(initial_temperature.is_finite() && cooling_ratio > 0.0 && cooling_ratio < 1.0) {
+        return vec![1.0];
+    }
+
+    let mut temperatures: Vec<_> =
+        std::iter::successors(Some(initial_temperature), |t| Some(t * cooling_ratio))
+            .take_while(|&t| t > 1.0)
+            .collect();
+    temperatures.push(1.0);
+
+    temperatures
+}
+
+// SpacePoint at a given coordinate. Useful to find the closest point on a
+// track to an arbitrary coordinate.
+fn as_spacepoint(c: Coordinate) -> SpacePoint {
+    SpacePoint {
+        r: c.x.hypot(c.y),
+        phi: c.y.atan2(c.x),
+        z: c.z,
+        wire_amplitude: 0.0,
+        pad_amplitude: 0.0,
+        // Irrelevant to find the closest point on a track.
+        sigma_r: Length::new::<meter>(0.0),
+        sigma_rphi: Length::new::<meter>(0.0),
+        sigma_z: Length::new::<meter>(0.0),
+    }
+}
+
+// Maximum number of iterations to find the closest approach between two
+// tracks, and the change in position below which it is considered converged.
+const MAX_NUM_CLOSEST_APPROACH_ITER: usize = 10;
+const CLOSEST_APPROACH_TOLERANCE: Area = Area {
+    dimension: uom::lib::marker::PhantomData,
+    units: uom::lib::marker::PhantomData,
+    value: 1.0e-12,
+};
+
+// Values of `t` of the closest approach between two tracks (on `a` and `b`
+// respectively). Alternate between the closest point on each track to the
+// current point on the other track, starting from the closest approach of `a`
+// to the beamline.
+pub(crate) fn closest_approach(
+    a: &Track,
+    b: &Track,
+    tolerance: f64,
+    max_num_iter: usize,
+) -> (f64, f64) {
+    let closest_t = |track: &Track, c: Coordinate| {
+        track
+            .helix
+            .closest_t(as_spacepoint(c), tolerance, max_num_iter)
+    };
+
+    let mut t_a = a.helix.closest_t_to_beamline();
+    let mut t_b = closest_t(b, a.at(t_a));
+    for _ in 0..MAX_NUM_CLOSEST_APPROACH_ITER {
+        let next = closest_t(a, b.at(t_b));
+        let converged = norm_sqr(as_spacepoint(a.at(next)), a.at(t_a)) < CLOSEST_APPROACH_TOLERANCE;
+        t_a = next;
+        t_b = closest_t(b, a.at(t_a));
+        if converged {
+            break;
+        }
+    }
+
+    (t_a, t_b)
+}
+
+// Midpoint of the closest approach between two tracks.
+fn closest_approach_midpoint(
+    a: &Track,
+    b: &Track,
+    tolerance: f64,
+    max_num_iter: usize,
+) -> Coordinate {
+    let (t_a, t_b) = closest_approach(a, b, tolerance, max_num_iter);
+    let (on_a, on_b) = (a.at(t_a), b.at(t_b));
+
+    Coordinate {
+        x: 0.5 * (on_a.x + on_b.x),
+        y: 0.5 * (on_a.y + on_b.y),
+        z: 0.5 * (on_a.z + on_b.z),
+    }
+}
+
+// Robust initial guess of the vertex position for the minimization: the
+// (component-wise) median of the midpoints of closest approach between all
+// pairs of tracks. A single badly fitted track can only affect the pairs it is
+// part of, so it can not drag the seed (and then the minimizer) away from the
+// rest of the tracks.
+//
+// The seed is `(0, 0, fallback_z)` if there are less than 2 tracks (or the
+// median is not finite).
+pub(crate) fn median_seed(
+    tracks: &[Track],
+    fallback_z: Length,
+    tolerance: f64,
+    max_num_iter: usize,
+) -> Coordinate {
+    let fallback = Coordinate {
+        x: Length::new::<meter>(0.0),
+        y: Length::new::<meter>(0.0),
+        z: fallback_z,
+    };
+    let midpoints: Vec<_> = tracks
+        .iter()
+        .tuple_combinations()
+        .map(|(a, b)| closest_approach_midpoint(a, b, tolerance, max_num_iter))
+        .collect();
+    if midpoints.is_empty() {
+        return fallback;
+    }
+
+    let median = |value: fn(&Coordinate) -> Length| {
+        let mut values: Vec<_> = midpoints.iter().map(|c| value(c).get::<meter>()).collect();
+        values.sort_unstable_by(f64::total_cmp);
+        let mid = values.len() / 2;
+        let median = if values.len() % 2 == 0 {
+            0.5 * (values[mid - 1] + values[mid])
+        } else {
+            values[mid]
+        };
+
+        Length::new::<meter>(median)
+    };
+    let seed = Coordinate {
+        x: median(|c| c.x),
+        y: median(|c| c.y),
+        z: median(|c| c.z),
+    };
+
+    if [seed.x, seed.y, seed.z].iter().all(|v| v.is_finite()) {
+        seed
+    } else {
+        fallback
+    }
+}
+
 // The actual minimization problem is to find the coordinate that minimizes the
-// sum of squared distances to all tracks.
+// (weighted) sum of squared distances to all tracks.
 struct Problem {
     tracks: Vec<Track>,
+    // Weight of the squared distance to each track (same order as `tracks`).
+    weights: Vec<f64>,
     // Parameters required to calculate the distance between a point and a helix.
     tolerance: f64,
     max_num_iter: usize,
+    // Weights of the squared `x` and `y` distance to the beamline (if the vertex
+    // is constrained to the beamline).
+    beam_spot_weights: Option<[f64; 2]>,
 }
 
 // Calculate the squared distance between a SpacePoint and a Coordinate.
@@ -211,16 +573,17 @@ impl CostFunction for Problem {
         let z = Length::new::<meter>(p[2]);
         // There is already a method in Track to calculate the closest point to
         // a SpacePoint. So just use that.
-        let sp = SpacePoint {
-            r: x.hypot(y),
-            phi: y.atan2(x),
-            z,
-        };
+        let sp = as_spacepoint(Coordinate { x, y, z });
+
+        let constraint = self
+            .beam_spot_weights
+            .map_or(0.0, |[w_x, w_y]| w_x * p[0].powi(2) + w_y * p[1].powi(2));
 
         Ok(self
             .tracks
             .iter()
-            .map(|track| {
+            .zip(&self.weights)
+            .map(|(track, weight)| {
                 let t = track.helix.closest_t(sp, self.tolerance, self.max_num_iter);
                 let closest_point = track.at(t);
 
@@ -230,9 +593,36 @@ impl CostFunction for Problem {
                 // needs to be fixed.
                 assert!(!val.is_nan(), "found NaN in vertex_fitting::cost_function");
 
-                val
+                *weight * val
             })
             .sum::<Area>()
-            .get::<square_meter>())
+            .get::<square_meter>()
+            + constraint)
     }
 }
+
+// Numerically calculate the Hessian of the cost function at a given point.
+fn hessian(problem: &Problem, point: &[f64], step: f64) -> Matrix<3, 3> {
+    let cost = |di: usize, si: f64, dj: usize, sj: f64| {
+        let mut p = point.to_vec();
+        p[di] += si * step;
+        p[dj] += sj * step;
+        // The cost function never returns an error.
+        problem.cost(&p).unwrap()
+    };
+
+    std::array::from_fn(|i| {
+        std::array::from_fn(|j| {
+            // Make sure the Hessian is exactly symmetric.
+            let (i, j) = (i.min(j), i.max(j));
+            if i == j {
+                (cost(i, 1.0, i, 0.0) - 2.0 * cost(i, 0.0, i, 0.0) + cost(i, -1.0, i, 0.0))
+                    / step.powi(2)
+            } else {
+                (cost(i, 1.0, j, 1.0) - cost(i, 1.0, j, -1.0) - cost(i, -1.0, j, 1.0)
+                    + cost(i, -1.0, j, -1.0))
+                    / (4.0 * step.powi(2))
+            }
+        })
+    })
+}
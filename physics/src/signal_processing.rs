@@ -0,0 +1,659 @@
+use crate::deconvolution::pads::{pad_deconvolution, PAD_RESPONSE};
+use crate::deconvolution::wires::WIRE_RESPONSE;
+use alpha_g_detector::alpha16::{ADC_MAX, ADC_MIN};
+use alpha_g_detector::padwing::{PWB_MAX, PWB_MIN};
+use dyn_stack::ReborrowMut;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+// Fraction of the charge induced on a pad by an avalanche in front of a
+// neighboring pad row. Element `i` corresponds to a pad `i` rows away.
+// The induced charge distribution is roughly Gaussian with a width comparable
+// to the pad pitch (anode-cathode gap of the same order of magnitude).
+pub(crate) const PAD_RESPONSE_FUNCTION: [f64; 3] = [1.0, 0.31, 0.024];
+
+/// Remove the PWB amplifier (and rTPC) response from a pad waveform.
+///
+/// The `waveform` is given in raw ADC counts (e.g. as returned by
+/// [`PwbPacket::waveform_at`](alpha_g_detector::padwing::PwbPacket::waveform_at)),
+/// and `baseline` is subtracted from all samples before the deconvolution. The
+/// returned vector has the same length as the waveform, and each element is
+/// the (non-negative) charge of the avalanches at that time bin in arbitrary
+/// units.
+///
+/// This is the same deconvolution used internally to reconstruct
+/// [`Avalanche`](crate::Avalanche)s. The only difference is that no gain
+/// calibration is applied.
+pub fn pad_amplifier_deconvolution(waveform: &[i16], baseline: i16) -> Vec<f64> {
+    let signal: Vec<_> = waveform
+        .iter()
+        .map(|&v| f64::from(v) - f64::from(baseline))
+        .collect();
+
+    pad_deconvolution(&signal)
+}
+
+/// Remove the charge sharing between neighboring pad rows.
+///
+/// An avalanche induces a charge not only on the pad in front of it, but also
+/// on the neighboring pads along `z`. Each element of `pad_inputs` is the
+/// output of [`pad_amplifier_deconvolution`] for consecutive pad rows in the
+/// same pad column (missing rows can be given as empty vectors). The returned
+/// vector has one element per row, with the (non-negative) charge actually
+/// produced in front of each pad at each time bin.
+pub fn pad_response_deconvolution(pad_inputs: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let j = pad_inputs.len();
+    let i = pad_inputs
+        .iter()
+        .map(|input| input.len())
+        .max()
+        .unwrap_or(0);
+    if i == 0 {
+        return pad_inputs.to_vec();
+    }
+    // Same idea as the cross-talk removal in the wire deconvolution. The set of
+    // pad inputs can be expressed as the matrix equation:
+    // Y = X * A
+    // where:
+    // - Y is the observed pad inputs (each column is a pad row).
+    // - X is the unknown/wanted charge in front of each pad row.
+    // - A is the (symmetric) pad response matrix.
+    let mut a = faer_core::Mat::with_dims(j, j, |r, c| {
+        let diff = r.abs_diff(c);
+        PAD_RESPONSE_FUNCTION.get(diff).copied().unwrap_or(0.0)
+    });
+    let mut y = faer_core::Mat::with_dims(i, j, |t, row| {
+        pad_inputs[row].get(t).copied().unwrap_or(0.0)
+    });
+
+    let mut mem = dyn_stack::GlobalMemBuffer::new(
+        faer_cholesky::llt::compute::cholesky_in_place_req::<f64>(
+            j,
+            faer_core::Parallelism::None,
+            Default::default(),
+        )
+        .unwrap(),
+    );
+    let mut stack = dyn_stack::DynStack::new(&mut mem);
+    // The pad response matrix is strictly diagonally dominant, hence positive
+    // definite.
+    faer_cholesky::llt::compute::cholesky_in_place(
+        a.as_mut(),
+        faer_core::Parallelism::None,
+        stack.rb_mut(),
+        Default::default(),
+    )
+    .unwrap();
+
+    let mut mem = dyn_stack::GlobalMemBuffer::new(
+        faer_cholesky::llt::solve::solve_transpose_in_place_req::<f64>(
+            j,
+            i,
+            faer_core::Parallelism::None,
+        )
+        .unwrap(),
+    );
+    let mut stack = dyn_stack::DynStack::new(&mut mem);
+    faer_cholesky::llt::solve::solve_transpose_in_place_with_conj(
+        a.as_ref(),
+        faer_core::Conj::No,
+        y.as_mut().transpose(),
+        faer_core::Parallelism::None,
+        stack.rb_mut(),
+    );
+
+    pad_inputs
+        .iter()
+        .enumerate()
+        .map(|(row, input)| {
+            // Keep the original length (i.e. empty rows stay empty). Only
+            // positive charges make sense.
+            (0..input.len()).map(|t| y.read(t, row).max(0.0)).collect()
+        })
+        .collect()
+}
+
+/// Estimate the relative timing offset of each channel.
+///
+/// This is the procedure used to produce the per-channel timing (T0)
+/// calibration. Each element of `times` is the time of the earliest pulse of a
+/// channel in many different events (e.g. from a
+/// [`ConstantFractionDiscriminator`]). Ionization right next to the anode wires
+/// has the shortest drift time, hence the leading edge of the time distribution
+/// (given by its `quantile`, e.g. 0.01) is the same for all channels up to
+/// cable and firmware delays. The offset of each channel is its leading edge
+/// minus the median leading edge of all channels.
+///
+/// Channels without any time are ignored. Times and offsets have the same
+/// units.
+pub fn time_offsets<K>(times: &HashMap<K, Vec<f64>>, quantile: f64) -> HashMap<K, f64>
+where
+    K: Clone + Eq + Hash,
+{
+    let leading_edges: HashMap<K, f64> = times
+        .iter()
+        .filter(|(_, times)| !times.is_empty())
+        .map(|(channel, times)| {
+            let mut times = times.clone();
+            times.sort_unstable_by(|a, b| a.total_cmp(b));
+            let index = ((times.len() - 1) as f64 * quantile.clamp(0.0, 1.0)).round() as usize;
+
+            (channel.clone(), times[index])
+        })
+        .collect();
+    if leading_edges.is_empty() {
+        return HashMap::new();
+    }
+
+    let mut edges: Vec<f64> = leading_edges.values().copied().collect();
+    edges.sort_unstable_by(|a, b| a.total_cmp(b));
+    let median = if edges.len().is_multiple_of(2) {
+        (edges[edges.len() / 2 - 1] + edges[edges.len() / 2]) / 2.0
+    } else {
+        edges[edges.len() / 2]
+    };
+
+    leading_edges
+        .into_iter()
+        .map(|(channel, edge)| (channel, edge - median))
+        .collect()
+}
+
+/// Polarity of a pulse with respect to the baseline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Polarity {
+    Positive,
+    Negative,
+}
+
+/// Constant-fraction discriminator.
+///
+/// The time of a pulse is the time at which its leading edge crosses a
+/// constant fraction of its amplitude. As opposed to a fixed threshold (or the
+/// time of the peak), this removes the time walk between small and large
+/// pulses with the same shape.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConstantFractionDiscriminator {
+    /// Fraction of the pulse amplitude at which the time is measured.
+    pub fraction: f64,
+    /// A pulse is only identified if its amplitude is above this threshold.
+    /// This is the same units as the signal, and it should be well above the
+    /// noise level.
+    pub threshold: f64,
+    /// Polarity of the pulses.
+    pub polarity: Polarity,
+}
+
+impl ConstantFractionDiscriminator {
+    /// Return the time of all pulses in a baseline-subtracted `signal`.
+    ///
+    /// Times are given in (fractional) number of samples since the beginning
+    /// of the signal; divide by the sampling rate of the corresponding
+    /// digitizer (e.g. [`ADC32_RATE`](alpha_g_detector::alpha16::ADC32_RATE)
+    /// or [`PWB_RATE`](alpha_g_detector::padwing::PWB_RATE)) to get a time.
+    /// Pulses whose leading edge is not fully contained in the signal are
+    /// ignored.
+    pub fn pulse_times(&self, signal: &[f64]) -> Vec<f64> {
+        let signal: Vec<_> = match self.polarity {
+            Polarity::Positive => signal.to_vec(),
+            Polarity::Negative => signal.iter().map(|v| -v).collect(),
+        };
+
+        let mut times = Vec::new();
+        let mut i = 0;
+        while i < signal.len() {
+            if signal[i] <= self.threshold {
+                i += 1;
+                continue;
+            }
+            // A pulse is everything until the signal drops below threshold.
+            let start = i;
+            while i < signal.len() && signal[i] > self.threshold {
+                i += 1;
+            }
+            let (peak_index, &peak) = signal[start..i]
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .unwrap();
+            let peak_index = start + peak_index;
+
+            let level = self.fraction * peak;
+            // Walk back from the peak along the leading edge.
+            if let Some(j) = (0..peak_index)
+                .rev()
+                .find(|&j| signal[j] < level && signal[j + 1] >= level)
+            {
+                let fraction = (level - signal[j]) / (signal[j + 1] - signal[j]);
+                times.push(j as f64 + fraction);
+            }
+        }
+
+        times
+    }
+}
+
+/// Correction of slow baseline drifts (e.g. the undershoot after a large
+/// pulse) within a single baseline-subtracted waveform.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BaselineRestoration {
+    /// Leave the waveform unchanged.
+    #[default]
+    Disabled,
+    /// Subtract a moving baseline given by the mean of the last `window`
+    /// samples outside of a pulse. A sample is part of a pulse if it deviates
+    /// from the current baseline by more than `threshold` in the direction of
+    /// the pulse `polarity`. The baseline is frozen during a pulse, but it
+    /// follows any undershoot after it.
+    MovingWindow {
+        window: usize,
+        threshold: f64,
+        polarity: Polarity,
+    },
+    /// Pole-zero correction of an exponential undershoot. The undershoot has
+    /// an area equal to a `fraction` of the area of the pulse that caused it,
+    /// and it decays with a time constant `decay` (in number of samples).
+    PoleZero { fraction: f64, decay: f64 },
+}
+
+impl BaselineRestoration {
+    /// Return the restored baseline-subtracted `signal`.
+    pub fn apply(&self, signal: &[f64]) -> Vec<f64> {
+        match *self {
+            Self::Disabled => signal.to_vec(),
+            Self::MovingWindow {
+                window,
+                threshold,
+                polarity,
+            } => {
+                let sign = match polarity {
+                    Polarity::Positive => 1.0,
+                    Polarity::Negative => -1.0,
+                };
+                let mut quiet = VecDeque::with_capacity(window + 1);
+                let mut sum = 0.0;
+                let mut baseline = 0.0;
+
+                signal
+                    .iter()
+                    .map(|&v| {
+                        if sign * (v - baseline) <= threshold && window > 0 {
+                            quiet.push_back(v);
+                            sum += v;
+                            if quiet.len() > window {
+                                sum -= quiet.pop_front().unwrap();
+                            }
+                            baseline = sum / quiet.len() as f64;
+                        }
+
+                        v - baseline
+                    })
+                    .collect()
+            }
+            Self::PoleZero { fraction, decay } => {
+                let k = (-1.0 / decay).exp();
+                // Exponentially weighted sum of all the previous restored
+                // samples. The undershoot at a given sample is proportional
+                // to it.
+                let mut tail = 0.0;
+                let mut previous = 0.0;
+
+                signal
+                    .iter()
+                    .map(|&v| {
+                        tail = k * tail + previous;
+                        let restored = v + fraction * (1.0 - k) * tail;
+                        previous = restored;
+
+                        restored
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Exponential component of the ion tail of a pulse.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TailComponent {
+    /// Area of this component relative to the area of the pulse that caused
+    /// it.
+    pub fraction: f64,
+    /// Decay time constant in number of samples.
+    pub decay: f64,
+}
+
+/// Cancellation of the ion tail that follows a pulse within a single
+/// baseline-subtracted waveform.
+///
+/// The ions drifting away from an anode wire induce a long tail (with the same
+/// polarity as the pulse) that is added to the amplitude of any later pulse in
+/// the same waveform.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum IonTailCancellation {
+    /// Leave the waveform unchanged.
+    #[default]
+    Disabled,
+    /// Subtract a tail given by the sum of two exponential `components` (e.g. a
+    /// fast and a slow one). This is a good approximation of the `1/t` shape of
+    /// an ion tail. Set the `fraction` of a component to zero to ignore it.
+    Exponential { components: [TailComponent; 2] },
+}
+
+impl IonTailCancellation {
+    /// Return the baseline-subtracted `signal` without the ion tails.
+    pub fn apply(&self, signal: &[f64]) -> Vec<f64> {
+        match *self {
+            Self::Disabled => signal.to_vec(),
+            Self::Exponential { components } => {
+                let k = components.map(|c| (-1.0 / c.decay).exp());
+                // Same as the pole-zero baseline restoration, but the tail has
+                // the same polarity as the pulse and there is one
+                // exponentially weighted sum per component.
+                let mut tails = [0.0; 2];
+                let mut previous = 0.0;
+
+                signal
+                    .iter()
+                    .map(|&v| {
+                        let mut cancelled = v;
+                        for ((tail, k), component) in tails.iter_mut().zip(k).zip(components) {
+                            *tail = k * *tail + previous;
+                            cancelled -= component.fraction * (1.0 - k) * *tail;
+                        }
+                        previous = cancelled;
+
+                        cancelled
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Correction of the coherent (common-mode) noise shared by all the pad
+/// channels read out by the same electronics.
+///
+/// The common-mode noise is estimated at each time bin as the median of the
+/// baseline-subtracted waveforms of all channels in a group (see
+/// [`common_mode_noise`]), and it is subtracted from each of them before any
+/// calibration or threshold is applied.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CommonModeCorrection {
+    /// Leave the waveforms unchanged.
+    #[default]
+    Disabled,
+    /// Group together all channels of the same AFTER chip. Groups with fewer
+    /// than `min_channels` channels are left unchanged.
+    AfterChip { min_channels: usize },
+    /// Group together all channels of the same Padwing board (i.e. all its
+    /// AFTER chips). Groups with fewer than `min_channels` channels are left
+    /// unchanged.
+    Pwb { min_channels: usize },
+}
+
+/// Return the common-mode noise of a group of baseline-subtracted `signals`.
+///
+/// Element `i` of the returned vector is the median of sample `i` over all
+/// signals that have such sample. As long as less than half of the channels
+/// have an actual pulse at a given time, the median is insensitive to them.
+/// Samples for which there are fewer than `min_channels` signals are `0.0`
+/// i.e. there is not enough information to estimate the common-mode noise.
+pub fn common_mode_noise(signals: &[Vec<f64>], min_channels: usize) -> Vec<f64> {
+    let len = signals.iter().map(Vec::len).max().unwrap_or(0);
+
+    (0..len)
+        .map(|i| {
+            let mut samples: Vec<_> = signals.iter().filter_map(|s| s.get(i)).copied().collect();
+            if samples.is_empty() || samples.len() < min_channels {
+                return 0.0;
+            }
+            samples.sort_unstable_by(f64::total_cmp);
+            let middle = samples.len() / 2;
+            if samples.len() % 2 == 0 {
+                0.5 * (samples[middle - 1] + samples[middle])
+            } else {
+                samples[middle]
+            }
+        })
+        .collect()
+}
+
+/// Pulse identified in a waveform.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pulse {
+    /// Time of the pulse in (fractional) number of samples since the beginning
+    /// of the signal.
+    pub t: f64,
+    /// Height of the signal (with respect to the baseline) at the time of the
+    /// pulse. This is always positive (independent of the polarity), and it
+    /// includes the contribution of any overlapping pulse.
+    pub amplitude: f64,
+}
+
+/// Detector of multiple, possibly overlapping, pulses in a single waveform.
+///
+/// Pile-up pulses do not necessarily produce separate maxima in the signal
+/// e.g. a small pulse on the falling edge of a large pulse is just a shoulder.
+/// However, each pulse still produces a separate local minimum in the second
+/// derivative of the signal (i.e. where the signal is the most concave). The
+/// second derivative is estimated with a 5-point Savitzky-Golay filter to
+/// reduce its sensitivity to noise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PileUpDetector {
+    /// A pulse is only identified if the signal is above this threshold at the
+    /// time of the pulse. This is the same units as the signal.
+    pub threshold: f64,
+    /// A pulse is only identified if the second derivative of the signal at
+    /// the time of the pulse is below `-min_curvature`. This is the same units
+    /// as the signal per squared sample, and it rejects small fluctuations on
+    /// top of a pulse.
+    pub min_curvature: f64,
+    /// Polarity of the pulses.
+    pub polarity: Polarity,
+}
+
+impl PileUpDetector {
+    /// Return all the pulses in a baseline-subtracted `signal` sorted by time.
+    ///
+    /// As opposed to [`ConstantFractionDiscriminator::pulse_times`], a single
+    /// region above threshold can contain multiple pulses. Times are given at
+    /// the minimum of the second derivative (interpolated between samples),
+    /// which is the peak of an isolated symmetric pulse. Pulses within the
+    /// first or last 3 samples are ignored.
+    pub fn pulses(&self, signal: &[f64]) -> Vec<Pulse> {
+        let signal: Vec<_> = match self.polarity {
+            Polarity::Positive => signal.to_vec(),
+            Polarity::Negative => signal.iter().map(|v| -v).collect(),
+        };
+        if signal.len() < 7 {
+            return Vec::new();
+        }
+
+        let second_derivative: Vec<_> = signal
+            .windows(5)
+            .map(|w| (2.0 * w[0] - w[1] - 2.0 * w[2] - w[3] + 2.0 * w[4]) / 7.0)
+            .collect();
+        // Element `i` of the second derivative corresponds to sample `i + 2`.
+        second_derivative
+            .windows(3)
+            .enumerate()
+            .filter_map(|(j, w)| {
+                let i = j + 3;
+                let is_minimum = w[1] < -self.min_curvature && w[1] <= w[0] && w[1] < w[2];
+                if !is_minimum || signal[i] <= self.threshold {
+                    return None;
+                }
+                // Vertex of the parabola through the three points.
+                let denominator = w[0] - 2.0 * w[1] + w[2];
+                let offset = if denominator > 0.0 {
+                    (0.5 * (w[0] - w[2]) / denominator).clamp(-0.5, 0.5)
+                } else {
+                    0.0
+                };
+
+                Some(Pulse {
+                    t: i as f64 + offset,
+                    amplitude: signal[i],
+                })
+            })
+            .collect()
+    }
+}
+
+// Step (in number of samples) of the scan over the time of a saturated pulse.
+const SATURATION_TIME_STEP: f64 = 0.1;
+// The part of a saturated pulse above the clipping level is not measured at
+// all; it is an extrapolation of the nominal pulse shape. This fraction of the
+// extrapolated amplitude is taken as the uncertainty due to differences
+// between the actual and nominal pulse shapes.
+const SATURATION_SHAPE_UNCERTAINTY: f64 = 0.25;
+
+/// Amplitude of a saturated pulse recovered from a fit of its pulse shape.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecoveredPulse {
+    /// Time of the peak of the pulse in (fractional) number of samples since
+    /// the beginning of the waveform.
+    pub t: f64,
+    /// Height of the peak of the pulse with respect to the baseline in ADC
+    /// counts. This is always positive (independent of the polarity), and it
+    /// is larger than the clipping level.
+    pub amplitude: f64,
+    /// Uncertainty of the amplitude. This includes the statistical
+    /// uncertainty of the fit, and a (dominant) uncertainty on the
+    /// extrapolation of the nominal pulse shape above the clipping level.
+    pub uncertainty: f64,
+}
+
+/// Recover the amplitude of a saturated pad pulse.
+///
+/// The `waveform` is given in raw ADC counts (e.g. as returned by
+/// [`PwbPacket::waveform_at`](alpha_g_detector::padwing::PwbPacket::waveform_at)),
+/// and `baseline` is subtracted from all samples. Samples equal to
+/// [`PWB_MIN`] or [`PWB_MAX`] are clipped; the nominal pad pulse shape is
+/// fitted to all other samples, with the constraint that the fitted pulse is
+/// beyond the clipping level at all the clipped samples of the pulse.
+///
+/// This gives an estimate of the true amplitude of a saturated channel
+/// instead of discarding it e.g. from the gain calibration or dE/dx. Returns
+/// [`None`] if the pulse is not saturated (or it does not look like the
+/// nominal pulse shape at all).
+pub fn recover_saturated_pad_pulse(waveform: &[i16], baseline: i16) -> Option<RecoveredPulse> {
+    recover_saturated_pulse(waveform, baseline, (PWB_MIN, PWB_MAX), &PAD_RESPONSE)
+}
+
+/// Recover the amplitude of a saturated anode wire pulse.
+///
+/// Same as [`recover_saturated_pad_pulse`], but with the nominal anode wire
+/// pulse shape and the [`ADC_MIN`] and [`ADC_MAX`] clipping levels of the
+/// Alpha16 waveforms (e.g. as returned by
+/// [`AdcPacket::waveform`](alpha_g_detector::alpha16::AdcPacket::waveform)).
+pub fn recover_saturated_wire_pulse(waveform: &[i16], baseline: i16) -> Option<RecoveredPulse> {
+    recover_saturated_pulse(waveform, baseline, (ADC_MIN, ADC_MAX), &WIRE_RESPONSE)
+}
+
+// Linear interpolation of a pulse shape at a fractional sample. The pulse
+// shape is zero outside of its range.
+fn interpolate(shape: &[f64], x: f64) -> f64 {
+    if x < 0.0 || x > (shape.len() - 1) as f64 {
+        return 0.0;
+    }
+    let i = x.floor() as usize;
+    match shape.get(i + 1) {
+        Some(next) => shape[i] + (x - i as f64) * (next - shape[i]),
+        None => shape[i],
+    }
+}
+
+fn recover_saturated_pulse(
+    waveform: &[i16],
+    baseline: i16,
+    (min, max): (i16, i16),
+    shape: &[f64],
+) -> Option<RecoveredPulse> {
+    let (peak_index, &peak) = shape
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))?;
+    // Work with a positive pulse with a unit peak. Then the fitted scale is
+    // directly the amplitude of the pulse.
+    let sign = peak.signum();
+    let shape: Vec<_> = shape.iter().map(|v| v / peak).collect();
+    let (level, limit) = if sign > 0.0 {
+        (f64::from(max) - f64::from(baseline), max)
+    } else {
+        (f64::from(baseline) - f64::from(min), min)
+    };
+
+    let signal: Vec<_> = waveform
+        .iter()
+        .map(|&v| sign * (f64::from(v) - f64::from(baseline)))
+        .collect();
+    // Samples clipped in the direction of the pulse. Any other clipped sample
+    // (e.g. a large undershoot) is just ignored.
+    let saturated: Vec<_> = waveform
+        .iter()
+        .enumerate()
+        .filter(|(_, &v)| v == limit)
+        .map(|(i, _)| i)
+        .collect();
+    let (&first, &last) = (saturated.first()?, saturated.last()?);
+    let unclipped: Vec<_> = (0..waveform.len())
+        .filter(|&i| waveform[i] != min && waveform[i] != max)
+        .collect();
+
+    // (chi2, t, amplitude, sum of the squared pulse shape)
+    let mut best: Option<(f64, f64, f64, f64)> = None;
+    // The peak of a saturated pulse has to be within the clipped samples.
+    let steps = ((last - first + 2) as f64 / SATURATION_TIME_STEP).round() as usize;
+    for step in 0..=steps {
+        let t = first as f64 - 1.0 + step as f64 * SATURATION_TIME_STEP;
+        let shift = t - peak_index as f64;
+
+        let (sum_fy, sum_ff) = unclipped.iter().fold((0.0, 0.0), |(fy, ff), &i| {
+            let f = interpolate(&shape, i as f64 - shift);
+            (fy + f * signal[i], ff + f * f)
+        });
+        if sum_ff <= 0.0 {
+            continue;
+        }
+        // The least-squares amplitude, but large enough to reach the clipping
+        // level at all saturated samples.
+        let mut amplitude = sum_fy / sum_ff;
+        let mut is_valid = true;
+        for &i in &saturated {
+            let f = interpolate(&shape, i as f64 - shift);
+            if f <= 0.0 {
+                is_valid = false;
+                break;
+            }
+            amplitude = amplitude.max(level / f);
+        }
+        if !is_valid {
+            continue;
+        }
+
+        let chi2 = unclipped
+            .iter()
+            .map(|&i| (signal[i] - amplitude * interpolate(&shape, i as f64 - shift)).powi(2))
+            .sum::<f64>();
+        match best {
+            Some((best_chi2, ..)) if best_chi2 <= chi2 => {}
+            _ => best = Some((chi2, t, amplitude, sum_ff)),
+        }
+    }
+
+    let (chi2, t, amplitude, sum_ff) = best?;
+    let variance = chi2 / unclipped.len().saturating_sub(2).max(1) as f64;
+    let statistical = (variance / sum_ff).sqrt();
+    let shape_uncertainty = SATURATION_SHAPE_UNCERTAINTY * (amplitude - level);
+
+    Some(RecoveredPulse {
+        t,
+        amplitude,
+        uncertainty: statistical.hypot(shape_uncertainty),
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,382 @@
+use super::*;
+use crate::deconvolution::pads::PAD_RESPONSE;
+use crate::deconvolution::wires::WIRE_RESPONSE;
+
+#[test]
+fn trivial_pad_amplifier_deconvolution() {
+    // Large enough that rounding to ADC counts is negligible.
+    let scale = 10000.0;
+    let baseline = -100;
+    let waveform: Vec<i16> = PAD_RESPONSE
+        .iter()
+        .map(|x| (x * scale).round() as i16 + baseline)
+        .collect();
+
+    let deconvolved = pad_amplifier_deconvolution(&waveform, baseline);
+    assert_eq!(deconvolved.len(), waveform.len());
+
+    let diff = deconvolved[0] - scale;
+    assert!(diff.abs() < 0.01 * scale);
+    let rest = deconvolved[1..].iter().sum::<f64>();
+    assert!(rest.abs() < 0.01 * scale);
+}
+
+#[test]
+fn empty_pad_response_deconvolution() {
+    assert!(pad_response_deconvolution(&[]).is_empty());
+
+    let inputs = vec![Vec::new(), Vec::new()];
+    assert_eq!(pad_response_deconvolution(&inputs), inputs);
+}
+
+#[test]
+fn trivial_pad_response_deconvolution() {
+    let num_rows = 20;
+    let center = 10;
+    let charge = 100.0;
+    // Single avalanche in front of `center` at the second time bin.
+    let inputs: Vec<Vec<f64>> = (0..num_rows)
+        .map(|row: usize| {
+            let induced = PAD_RESPONSE_FUNCTION
+                .get(row.abs_diff(center))
+                .copied()
+                .unwrap_or(0.0);
+            vec![0.0, charge * induced, 0.0]
+        })
+        .collect();
+
+    let deconvolved = pad_response_deconvolution(&inputs);
+    assert_eq!(deconvolved.len(), num_rows);
+    for (row, output) in deconvolved.iter().enumerate() {
+        assert_eq!(output.len(), 3);
+        for (t, value) in output.iter().enumerate() {
+            let expected = if row == center && t == 1 { charge } else { 0.0 };
+            assert!((value - expected).abs() < 1e-9);
+        }
+    }
+}
+
+// Gaussian pulse centered at `center` with a given `amplitude`.
+fn gaussian_pulse(len: usize, center: f64, sigma: f64, amplitude: f64) -> Vec<f64> {
+    (0..len)
+        .map(|i| amplitude * (-0.5 * ((i as f64 - center) / sigma).powi(2)).exp())
+        .collect()
+}
+
+#[test]
+fn cfd_no_time_walk() {
+    let cfd = ConstantFractionDiscriminator {
+        fraction: 0.5,
+        threshold: 10.0,
+        polarity: Polarity::Positive,
+    };
+    let small = gaussian_pulse(100, 40.0, 5.0, 50.0);
+    let large = gaussian_pulse(100, 40.0, 5.0, 5000.0);
+
+    let small_times = cfd.pulse_times(&small);
+    let large_times = cfd.pulse_times(&large);
+    assert_eq!(small_times.len(), 1);
+    assert_eq!(large_times.len(), 1);
+    // Half maximum of a Gaussian is at `sigma * sqrt(2 ln 2)` from the center.
+    let expected = 40.0 - 5.0 * (2.0 * 2.0f64.ln()).sqrt();
+    // Linear interpolation between samples.
+    assert!((small_times[0] - expected).abs() < 0.05);
+    assert!((small_times[0] - large_times[0]).abs() < 1e-9);
+}
+
+#[test]
+fn cfd_negative_polarity() {
+    let cfd = ConstantFractionDiscriminator {
+        fraction: 0.3,
+        threshold: 10.0,
+        polarity: Polarity::Negative,
+    };
+    let positive = gaussian_pulse(100, 40.0, 5.0, 500.0);
+    let negative: Vec<_> = positive.iter().map(|v| -v).collect();
+
+    assert!(cfd.pulse_times(&positive).is_empty());
+    let times = cfd.pulse_times(&negative);
+    assert_eq!(times.len(), 1);
+    assert!(times[0] > 30.0 && times[0] < 40.0);
+}
+
+#[test]
+fn cfd_multiple_pulses() {
+    let cfd = ConstantFractionDiscriminator {
+        fraction: 0.5,
+        threshold: 10.0,
+        polarity: Polarity::Positive,
+    };
+    let signal: Vec<_> = gaussian_pulse(200, 40.0, 5.0, 100.0)
+        .into_iter()
+        .zip(gaussian_pulse(200, 140.0, 5.0, 1000.0))
+        .zip(gaussian_pulse(200, 90.0, 5.0, 5.0))
+        .map(|((a, b), c)| a + b + c)
+        .collect();
+
+    let times = cfd.pulse_times(&signal);
+    // The pulse at 90 is below threshold.
+    assert_eq!(times.len(), 2);
+    assert!((times[1] - times[0] - 100.0).abs() < 0.1);
+}
+
+#[test]
+fn cfd_truncated_leading_edge() {
+    let cfd = ConstantFractionDiscriminator {
+        fraction: 0.5,
+        threshold: 10.0,
+        polarity: Polarity::Positive,
+    };
+    let signal = gaussian_pulse(100, 0.0, 5.0, 100.0);
+
+    assert!(cfd.pulse_times(&signal).is_empty());
+}
+
+#[test]
+fn trivial_time_offsets() {
+    let mut times = HashMap::new();
+    for (channel, delay) in [(0, 0.0), (1, 4.0), (2, -2.0), (3, 1.0), (4, 0.5)] {
+        let channel_times: Vec<f64> = (0..100).map(|i| delay + 10.0 + i as f64).collect();
+        times.insert(channel, channel_times);
+    }
+    times.insert(5, Vec::new());
+
+    let offsets = time_offsets(&times, 0.0);
+    assert_eq!(offsets.len(), 5);
+    assert_eq!(offsets[&0], -0.5);
+    assert_eq!(offsets[&1], 3.5);
+    assert_eq!(offsets[&2], -2.5);
+    assert_eq!(offsets[&3], 0.5);
+    assert_eq!(offsets[&4], 0.0);
+
+    assert!(time_offsets::<usize>(&HashMap::new(), 0.01).is_empty());
+}
+
+#[test]
+fn pile_up_single_pulse() {
+    let detector = PileUpDetector {
+        threshold: 10.0,
+        min_curvature: 0.5,
+        polarity: Polarity::Positive,
+    };
+    for sigma in [3.0, 5.0] {
+        let signal = gaussian_pulse(100, 40.3, sigma, 100.0);
+
+        let pulses = detector.pulses(&signal);
+        assert_eq!(pulses.len(), 1);
+        assert!((pulses[0].t - 40.3).abs() < 0.1);
+        assert!((pulses[0].amplitude - 100.0).abs() < 1.0);
+    }
+}
+
+#[test]
+fn pile_up_shoulder() {
+    let detector = PileUpDetector {
+        threshold: 10.0,
+        min_curvature: 0.5,
+        polarity: Polarity::Negative,
+    };
+    let signal: Vec<_> = gaussian_pulse(100, 40.0, 3.0, 100.0)
+        .into_iter()
+        .zip(gaussian_pulse(100, 47.0, 3.0, 50.0))
+        .map(|(a, b)| -(a + b))
+        .collect();
+    // There is a single maximum i.e. the second pulse is just a shoulder.
+    assert!(signal[40..47].windows(2).all(|w| w[0] <= w[1]));
+
+    let pulses = detector.pulses(&signal);
+    assert_eq!(pulses.len(), 2);
+    assert!((pulses[0].t - 40.0).abs() < 0.5);
+    // The tail of the large pulse biases the time of the small one.
+    assert!((pulses[1].t - 47.0).abs() < 1.0);
+    assert!(pulses[0].amplitude > pulses[1].amplitude);
+}
+
+#[test]
+fn pile_up_below_threshold() {
+    let detector = PileUpDetector {
+        threshold: 10.0,
+        min_curvature: 0.1,
+        polarity: Polarity::Positive,
+    };
+    let signal = gaussian_pulse(100, 40.0, 3.0, 5.0);
+    assert!(detector.pulses(&signal).is_empty());
+    assert!(detector.pulses(&signal[..6]).is_empty());
+}
+
+#[test]
+fn baseline_restoration_disabled() {
+    let signal = gaussian_pulse(100, 40.0, 3.0, 100.0);
+    assert_eq!(BaselineRestoration::Disabled.apply(&signal), signal);
+}
+
+#[test]
+fn baseline_restoration_moving_window() {
+    let restoration = BaselineRestoration::MovingWindow {
+        window: 10,
+        threshold: 5.0,
+        polarity: Polarity::Positive,
+    };
+    // Pulse on top of a slowly drifting baseline.
+    let signal: Vec<_> = gaussian_pulse(200, 100.0, 3.0, 100.0)
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| v - 0.01 * i as f64)
+        .collect();
+
+    let restored = restoration.apply(&signal);
+    assert_eq!(restored.len(), signal.len());
+    // Lagging by half a window.
+    for i in (20..80).chain(120..200) {
+        assert!(restored[i].abs() < 0.1);
+    }
+    assert!((restored[100] - 100.0).abs() < 1.5);
+}
+
+#[test]
+fn baseline_restoration_pole_zero() {
+    let (fraction, decay): (f64, f64) = (0.2, 30.0);
+    let pulse = gaussian_pulse(300, 50.0, 3.0, 100.0);
+    // Add the exponential undershoot that the pole-zero correction removes.
+    let k = (-1.0 / decay).exp();
+    let mut tail = 0.0;
+    let mut previous = 0.0;
+    let signal: Vec<_> = pulse
+        .iter()
+        .map(|&v| {
+            tail = k * tail + previous;
+            previous = v;
+            v - fraction * (1.0 - k) * tail
+        })
+        .collect();
+    assert!(signal[100] < -0.5);
+
+    let restored = BaselineRestoration::PoleZero { fraction, decay }.apply(&signal);
+    for (restored, expected) in restored.iter().zip(pulse) {
+        assert!((restored - expected).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn ion_tail_cancellation_disabled() {
+    let signal = gaussian_pulse(100, 40.0, 3.0, 100.0);
+    assert_eq!(IonTailCancellation::Disabled.apply(&signal), signal);
+}
+
+#[test]
+fn ion_tail_cancellation_exponential() {
+    let components = [
+        TailComponent {
+            fraction: 0.3,
+            decay: 10.0,
+        },
+        TailComponent {
+            fraction: 0.1,
+            decay: 100.0,
+        },
+    ];
+    // Two pulses; the second one sits on top of the tail of the first.
+    let pulses: Vec<_> = gaussian_pulse(400, 50.0, 3.0, 100.0)
+        .into_iter()
+        .zip(gaussian_pulse(400, 120.0, 3.0, 50.0))
+        .map(|(a, b)| a + b)
+        .collect();
+    let mut tails = [0.0; 2];
+    let mut previous = 0.0;
+    let signal: Vec<_> = pulses
+        .iter()
+        .map(|&v| {
+            let mut total = v;
+            for (tail, component) in tails.iter_mut().zip(components) {
+                let k = (-1.0 / component.decay).exp();
+                *tail = k * *tail + previous;
+                total += component.fraction * (1.0 - k) * *tail;
+            }
+            previous = v;
+            total
+        })
+        .collect();
+    assert!(signal[120] - pulses[120] > 1.0);
+
+    let cancelled = IonTailCancellation::Exponential { components }.apply(&signal);
+    for (cancelled, expected) in cancelled.iter().zip(pulses) {
+        assert!((cancelled - expected).abs() < 1e-9);
+    }
+}
+
+// Waveform with a pulse of a given amplitude (with the same polarity and shape
+// as the nominal `response`) and peak at `t`.
+fn clipped_waveform(
+    response: &[f64],
+    amplitude: f64,
+    t: usize,
+    len: usize,
+    (min, max): (i16, i16),
+) -> Vec<i16> {
+    let (peak_index, peak) = response
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+        .map(|(i, &v)| (i, v.abs()))
+        .unwrap();
+    let shift = t - peak_index;
+
+    (0..len)
+        .map(|i| {
+            let v = i
+                .checked_sub(shift)
+                .and_then(|j| response.get(j))
+                .map_or(0.0, |v| amplitude * v / peak);
+            v.round().clamp(f64::from(min), f64::from(max)) as i16
+        })
+        .collect()
+}
+
+#[test]
+fn recover_saturated_pad_pulse_amplitude() {
+    let waveform = clipped_waveform(&PAD_RESPONSE, 3000.0, 111, 511, (PWB_MIN, PWB_MAX));
+    assert!(waveform.contains(&PWB_MIN));
+
+    let pulse = recover_saturated_pad_pulse(&waveform, 0).unwrap();
+    assert!((pulse.t - 111.0).abs() < 0.2);
+    assert!((pulse.amplitude - 3000.0).abs() < 0.01 * 3000.0);
+    // Dominated by the extrapolation above the clipping level.
+    assert!(pulse.uncertainty > 0.2 * (3000.0 + f64::from(PWB_MIN)));
+}
+
+#[test]
+fn recover_saturated_wire_pulse_amplitude() {
+    let waveform = clipped_waveform(&WIRE_RESPONSE, 40000.0, 202, 700, (ADC_MIN, ADC_MAX));
+    assert!(waveform.contains(&ADC_MIN));
+
+    let pulse = recover_saturated_wire_pulse(&waveform, 0).unwrap();
+    assert!((pulse.t - 202.0).abs() < 0.2);
+    assert!((pulse.amplitude - 40000.0).abs() < 0.01 * 40000.0);
+}
+
+#[test]
+fn recover_unsaturated_pulse() {
+    let waveform = clipped_waveform(&PAD_RESPONSE, 1000.0, 111, 511, (PWB_MIN, PWB_MAX));
+    assert!(recover_saturated_pad_pulse(&waveform, 0).is_none());
+}
+
+#[test]
+fn common_mode_noise_median() {
+    let noise = [1.0, -2.0, 3.0, 0.5];
+    let mut signals = vec![noise.to_vec(); 4];
+    // A real pulse in a single channel does not bias the median.
+    signals[0][2] += 100.0;
+    // Shorter channel.
+    signals[1].truncate(2);
+
+    assert_eq!(common_mode_noise(&signals, 3), noise.to_vec());
+}
+
+#[test]
+fn common_mode_noise_min_channels() {
+    let signals = vec![vec![1.0, 2.0, 3.0], vec![1.0, 2.0]];
+
+    assert_eq!(common_mode_noise(&signals, 2), vec![1.0, 2.0, 0.0]);
+    assert_eq!(common_mode_noise(&signals, 3), vec![0.0; 3]);
+    assert!(common_mode_noise(&[], 0).is_empty());
+}
@@ -0,0 +1,454 @@
+use crate::calibration::is_simulated_run;
+use crate::calibration::pads::baseline::try_pad_baseline;
+use crate::calibration::pads::delay::try_pad_delay;
+use crate::calibration::pads::gain::try_pad_gain;
+use crate::calibration::set::CalibrationSet;
+use crate::calibration::wires::baseline::try_wire_baseline;
+use crate::calibration::wires::delay::try_wire_delay;
+use crate::calibration::wires::gain::try_wire_gain;
+use crate::deconvolution::pads::PAD_RESPONSE;
+use crate::deconvolution::wires::{NEIGHBOR_FACTORS, WIRE_RESPONSE};
+use crate::matching::{wire_index, wire_to_pad_column};
+use crate::reconstruction::Coordinate;
+use crate::{
+    empty_pad_signals, pad_signal, wire_signal, Avalanche, DriftTables, MainEvent, SignalOptions,
+    TryDriftLookupError, TryMainEventFromDataBanksError,
+};
+use alpha_g_detector::alpha16::aw_map::{TpcWirePosition, TPC_ANODE_WIRES};
+use alpha_g_detector::alpha16::{self, EmulateSuppressionError, ADC32_RATE, ADC_MAX, ADC_MIN};
+use alpha_g_detector::padwing::map::{
+    TpcPadColumn, TpcPadPosition, TpcPadRow, DETECTOR_LENGTH, PAD_PITCH_Z, TPC_PAD_COLUMNS,
+    TPC_PAD_ROWS,
+};
+use alpha_g_detector::padwing::{self, CalculateSuppressionBaselineError, PWB_MAX, PWB_MIN};
+use rand::Rng;
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use thiserror::Error;
+use uom::si::angle::radian;
+use uom::si::f64::*;
+use uom::si::length::{meter, millimeter};
+use uom::si::time::second;
+
+pub use diffusion::{
+    annihilation_tracks, drift_clusters, drift_spacepoints, DriftConfig, DriftedCluster,
+    SimulateDriftError,
+};
+
+// Fast parametric drift and diffusion of the ionization electrons. Skips the
+// waveform simulation and reconstruction altogether.
+mod diffusion;
+
+/// Parameters of the toy detector simulation.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulationConfig {
+    /// Number of samples in each anode wire waveform.
+    pub wire_samples: usize,
+    /// Number of samples in each pad waveform.
+    pub pad_samples: usize,
+    /// Standard deviation of the electronic (white) noise on the anode wires
+    /// in ADC counts.
+    pub wire_noise: f64,
+    /// Standard deviation of the electronic (white) noise on the pads in ADC
+    /// counts.
+    pub pad_noise: f64,
+    /// Data suppression threshold of the Alpha16 boards. `None` disables data
+    /// suppression, i.e. all anode wires are kept.
+    pub wire_threshold: Option<u16>,
+    /// Number of samples kept by the Alpha16 data suppression after the last
+    /// sample over threshold.
+    pub wire_keep_more: usize,
+    /// Data suppression threshold of the PWB boards. `None` disables data
+    /// suppression, i.e. all pads are kept.
+    pub pad_threshold: Option<u16>,
+    /// Standard deviation of the charge induced on the pads along `z`.
+    pub pad_sigma_z: Length,
+    /// Run number of the simulated events. This selects the simulation
+    /// calibration (baselines, gains, delays, etc.) both when generating and
+    /// when reading back the waveforms. It has to be a simulated run (see
+    /// [`declare_simulated_runs`](crate::declare_simulated_runs)).
+    pub run_number: u32,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            // Typical settings during data taking.
+            wire_samples: 511,
+            pad_samples: 511,
+            wire_noise: 10.0,
+            pad_noise: 4.0,
+            wire_threshold: Some(1500),
+            wire_keep_more: 0,
+            pad_threshold: Some(100),
+            // Roughly a pad pitch. Enough to induce a signal on at least 3
+            // contiguous pads (required to reconstruct the `z` position).
+            pad_sigma_z: Length::new::<millimeter>(4.0),
+            run_number: u32::MAX,
+        }
+    }
+}
+
+/// The error type returned when simulating an event fails.
+#[derive(Debug, Error)]
+pub enum SimulateEventError {
+    /// The anode wire waveforms are too short for the Alpha16 data suppression.
+    #[error("bad anode wire waveform length")]
+    WireSuppression(#[from] EmulateSuppressionError),
+    /// The pad waveforms are too short for the PWB data suppression.
+    #[error("bad pad waveform length")]
+    PadSuppression(#[from] CalculateSuppressionBaselineError),
+    /// Converting the simulated waveforms to a [`MainEvent`] failed.
+    #[error("conversion to main event failed")]
+    MainEvent(#[from] TryMainEventFromDataBanksError),
+    /// The run number is not a simulated run.
+    #[error("run number `{0}` is not a simulated run")]
+    NotSimulatedRun(u32),
+}
+
+/// Charged particle track used as input of the toy simulation.
+///
+/// Tracks are straight lines i.e. the curvature due to the magnetic field is
+/// ignored. This is good enough to validate the reconstruction chain with
+/// known truth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToyTrack {
+    /// Starting point of the track. The track only moves away from this point.
+    pub origin: Coordinate,
+    /// Azimuthal angle of the direction of the track.
+    pub phi: Angle,
+    /// Polar angle (with respect to the `z` axis) of the direction of the
+    /// track.
+    pub theta: Angle,
+    /// Anode wire amplitude of the avalanches along the track. Same units as
+    /// [`Avalanche::wire_amplitude`].
+    pub wire_amplitude: f64,
+    /// Pad amplitude of the avalanches along the track. Same units as
+    /// [`Avalanche::pad_amplitude`].
+    pub pad_amplitude: f64,
+}
+
+/// Output of the toy simulation.
+#[derive(Clone, Debug)]
+pub struct SimulatedEvent {
+    /// Simulated detector event.
+    pub event: MainEvent,
+    /// True avalanches that generated the event.
+    pub avalanches: Vec<Avalanche>,
+}
+
+// Return the fraction of the charge induced on each pad row by an avalanche
+// at a given `z`. Only rows within 3 standard deviations are considered.
+fn pad_row_weights(z: Length, sigma: Length) -> Vec<(usize, f64)> {
+    let z = z.get::<meter>();
+    let sigma = sigma.get::<meter>();
+    let first = ((z - 3.0 * sigma + 0.5 * DETECTOR_LENGTH) / PAD_PITCH_Z).floor();
+    let last = ((z + 3.0 * sigma + 0.5 * DETECTOR_LENGTH) / PAD_PITCH_Z).floor();
+    if last < 0.0 || first >= TPC_PAD_ROWS as f64 {
+        return Vec::new();
+    }
+
+    (first.max(0.0) as usize..=(last as usize).min(TPC_PAD_ROWS - 1))
+        .map(|row| {
+            let dz = TpcPadRow::try_from(row).unwrap().z() - z;
+            let weight =
+                (-0.5 * (dz / sigma).powi(2)).exp() * PAD_PITCH_Z / (sigma * (2.0 * PI).sqrt());
+
+            (row, weight)
+        })
+        .collect()
+}
+
+/// Return the true avalanches generated by a track.
+///
+/// The track is sampled once per anode wire sample (the same time binning as
+/// the reconstructed avalanches). The ionization position at each drift time
+/// is found by inverting the drift tables, and then the Lorentz angle is used
+/// to find where the electrons reach the anode wires.
+pub fn track_avalanches(
+    track: &ToyTrack,
+    tables: &DriftTables,
+    config: &SimulationConfig,
+) -> Vec<Avalanche> {
+    let (ox, oy, oz) = (
+        track.origin.x.get::<meter>(),
+        track.origin.y.get::<meter>(),
+        track.origin.z.get::<meter>(),
+    );
+    let (uy, ux) = track.phi.get::<radian>().sin_cos();
+    let (sin_theta, cos_theta) = track.theta.get::<radian>().sin_cos();
+    // Position of the track when it crosses a given radius (outgoing).
+    let crossing = |r: Length| {
+        let r = r.get::<meter>();
+        let b = ox * ux + oy * uy;
+        let discriminant = b.powi(2) - (ox.powi(2) + oy.powi(2) - r.powi(2));
+        if discriminant < 0.0 || sin_theta == 0.0 {
+            return None;
+        }
+        let s = -b + discriminant.sqrt();
+        (s >= 0.0).then(|| (ox + s * ux, oy + s * uy, oz + s * cos_theta / sin_theta))
+    };
+
+    let mut avalanches = Vec::new();
+    for bin in 0..config.wire_samples {
+        let t = Time::new::<second>(bin as f64 / ADC32_RATE);
+        // The drift table depends on `z`. A couple of iterations are enough
+        // to converge given that the tables change very slowly with `z`.
+        let mut z = oz;
+        let mut ionization = None;
+        for _ in 0..3 {
+            match tables.at(Length::new::<meter>(z), t) {
+                Ok((r, correction)) => {
+                    ionization = crossing(r).map(|(x, y, z)| (x, y, z, correction));
+                    match ionization {
+                        Some((_, _, new_z, _)) => z = new_z,
+                        None => break,
+                    }
+                }
+                // Longer drift times are also out of range.
+                Err(TryDriftLookupError::DriftTimeOutOfRange(_)) => return avalanches,
+                Err(TryDriftLookupError::AxialPositionOutOfRange(_)) => {
+                    ionization = None;
+                    break;
+                }
+            }
+        }
+        let Some((x, y, z, correction)) = ionization else {
+            continue;
+        };
+
+        let z = Length::new::<meter>(z);
+        avalanches.push(Avalanche {
+            t,
+            phi: Angle::new::<radian>(y.atan2(x)) + correction,
+            z,
+            wire_amplitude: track.wire_amplitude,
+            pad_amplitude: track.pad_amplitude,
+            pad_multiplicity: pad_row_weights(z, config.pad_sigma_z).len(),
+        });
+    }
+
+    avalanches
+}
+
+// Add the response to an input at `start` to a signal.
+fn add_response(signal: &mut [f64], start: usize, amplitude: f64, response: &[f64]) {
+    if let Some(signal) = signal.get_mut(start..) {
+        signal
+            .iter_mut()
+            .zip(response)
+            .for_each(|(s, r)| *s += amplitude * r);
+    }
+}
+
+// Add baseline and noise to a (calibrated) signal and digitize it.
+fn digitize<R: Rng + ?Sized>(
+    signal: &[f64],
+    baseline: i16,
+    gain: f64,
+    noise: f64,
+    (min, max): (i16, i16),
+    rng: &mut R,
+) -> Vec<i16> {
+    signal
+        .iter()
+        .map(|&s| {
+            let n: f64 = rng.sample(StandardNormal);
+            let v = f64::from(baseline) + s / gain + noise * n;
+            v.round().clamp(f64::from(min), f64::from(max)) as i16
+        })
+        .collect()
+}
+
+/// Simulate the detector response to a set of avalanches.
+///
+/// Each avalanche induces a signal on its closest anode wire (and its
+/// neighbors) and on the pads of the corresponding column around its `z`
+/// position. The raw waveforms include the baseline, electronic noise,
+/// saturation, and data suppression of the real electronics. The waveforms are
+/// then calibrated exactly as real data (with the simulation calibration).
+///
+/// The [`Avalanche::pad_multiplicity`] of the input is ignored (it is given by
+/// [`SimulationConfig::pad_sigma_z`]). Channels without any induced signal are
+/// only simulated if data suppression is disabled.
+pub fn simulate_avalanches<R: Rng + ?Sized>(
+    avalanches: &[Avalanche],
+    config: &SimulationConfig,
+    rng: &mut R,
+) -> Result<MainEvent, SimulateEventError> {
+    let run_number = config.run_number;
+    if !is_simulated_run(run_number) {
+        return Err(SimulateEventError::NotSimulatedRun(run_number));
+    }
+    let calibration =
+        CalibrationSet::for_run(run_number).map_err(TryMainEventFromDataBanksError::from)?;
+    let wire_delay = try_wire_delay(run_number).map_err(TryMainEventFromDataBanksError::from)?;
+    let pad_delay = try_pad_delay(run_number).map_err(TryMainEventFromDataBanksError::from)?;
+
+    // Noiseless signals (baseline subtracted and calibrated) in the raw sample
+    // space i.e. before removing the delay. Maps to iterate in a deterministic
+    // order (the random numbers depend on it).
+    let mut wire_inputs: BTreeMap<usize, Vec<f64>> = BTreeMap::new();
+    let mut pad_inputs: BTreeMap<(usize, usize), Vec<f64>> = BTreeMap::new();
+    for avalanche in avalanches {
+        let bin = (avalanche.t.get::<second>() * ADC32_RATE).round();
+        if bin < 0.0 {
+            continue;
+        }
+        let bin = bin as usize;
+
+        let wire = wire_index(avalanche.phi);
+        for (k, factor) in NEIGHBOR_FACTORS.iter().enumerate() {
+            let mut neighbors = vec![(wire + k) % TPC_ANODE_WIRES];
+            if k != 0 {
+                neighbors.push((wire + TPC_ANODE_WIRES - k) % TPC_ANODE_WIRES);
+            }
+            for neighbor in neighbors {
+                let signal = wire_inputs
+                    .entry(neighbor)
+                    .or_insert_with(|| vec![0.0; config.wire_samples]);
+                add_response(
+                    signal,
+                    wire_delay + bin,
+                    factor * avalanche.wire_amplitude,
+                    &WIRE_RESPONSE,
+                );
+            }
+        }
+
+        let column = wire_to_pad_column(wire);
+        for (row, weight) in pad_row_weights(avalanche.z, config.pad_sigma_z) {
+            let signal = pad_inputs
+                .entry((column, row))
+                .or_insert_with(|| vec![0.0; config.pad_samples]);
+            add_response(
+                signal,
+                pad_delay + bin,
+                weight * avalanche.pad_amplitude,
+                &PAD_RESPONSE,
+            );
+        }
+    }
+    if config.wire_threshold.is_none() {
+        for wire in 0..TPC_ANODE_WIRES {
+            wire_inputs
+                .entry(wire)
+                .or_insert_with(|| vec![0.0; config.wire_samples]);
+        }
+    }
+    if config.pad_threshold.is_none() {
+        for column in 0..TPC_PAD_COLUMNS {
+            for row in 0..TPC_PAD_ROWS {
+                pad_inputs
+                    .entry((column, row))
+                    .or_insert_with(|| vec![0.0; config.pad_samples]);
+            }
+        }
+    }
+
+    let mut wire_signals = [(); TPC_ANODE_WIRES].map(|_| None);
+    for (wire, input) in wire_inputs {
+        let position = TpcWirePosition::try_from(wire).unwrap();
+        let baseline = try_wire_baseline(run_number, position)
+            .map_err(TryMainEventFromDataBanksError::from)?;
+        let gain =
+            try_wire_gain(run_number, position).map_err(TryMainEventFromDataBanksError::from)?;
+        let waveform = digitize(
+            &input,
+            baseline,
+            gain,
+            config.wire_noise,
+            (ADC_MIN, ADC_MAX),
+            rng,
+        );
+        let waveform = match config.wire_threshold {
+            Some(threshold) => {
+                alpha16::emulate_suppression(&waveform, threshold, config.wire_keep_more)?
+                    .waveform()
+                    .to_vec()
+            }
+            None => waveform,
+        };
+        if waveform.is_empty() {
+            continue;
+        }
+
+        let signal = wire_signal(&calibration, position, &waveform, &SignalOptions::default())?;
+        if !signal.is_empty() {
+            wire_signals[wire] = Some(signal);
+        }
+    }
+
+    let mut pad_signals = empty_pad_signals();
+    for ((column, row), input) in pad_inputs {
+        let position = TpcPadPosition {
+            column: TpcPadColumn::try_from(column).unwrap(),
+            row: TpcPadRow::try_from(row).unwrap(),
+        };
+        let baseline =
+            try_pad_baseline(run_number, position).map_err(TryMainEventFromDataBanksError::from)?;
+        let gain =
+            try_pad_gain(run_number, position).map_err(TryMainEventFromDataBanksError::from)?;
+        let waveform = digitize(
+            &input,
+            baseline,
+            gain,
+            config.pad_noise,
+            (PWB_MIN, PWB_MAX),
+            rng,
+        );
+        let waveform = match config.pad_threshold {
+            Some(threshold) => {
+                match padwing::emulate_suppression(run_number, &waveform, threshold)? {
+                    Some(waveform) => waveform.to_vec(),
+                    None => continue,
+                }
+            }
+            None => waveform,
+        };
+
+        let signal = pad_signal(
+            &calibration,
+            position,
+            &waveform,
+            &[],
+            &SignalOptions::default(),
+        )?;
+        if !signal.is_empty() {
+            pad_signals[column][row] = Some(signal);
+        }
+    }
+
+    Ok(MainEvent {
+        wire_signals,
+        pad_signals,
+        thresholds: calibration.thresholds(),
+        phi_correction: calibration.phi_correction_map(),
+        pad_response_deconvolution: false,
+        wire_timing: None,
+        trigger_timestamp: 0,
+    })
+}
+
+/// Simulate the detector response to a set of tracks. See
+/// [`track_avalanches`] and [`simulate_avalanches`] for details.
+pub fn simulate_tracks<R: Rng + ?Sized>(
+    tracks: &[ToyTrack],
+    tables: &DriftTables,
+    config: &SimulationConfig,
+    rng: &mut R,
+) -> Result<SimulatedEvent, SimulateEventError> {
+    let avalanches: Vec<_> = tracks
+        .iter()
+        .flat_map(|track| track_avalanches(track, tables, config))
+        .collect();
+    let event = simulate_avalanches(&avalanches, config, rng)?;
+
+    Ok(SimulatedEvent { event, avalanches })
+}
+
+#[cfg(test)]
+mod tests;
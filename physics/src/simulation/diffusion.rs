@@ -0,0 +1,285 @@
+use super::ToyTrack;
+use crate::gas::{GasProperties, GasPropertiesByRun};
+use crate::reconstruction::Coordinate;
+use crate::{Avalanche, DriftTables, SpacePoint};
+use alpha_g_detector::alpha16::aw_map::{
+    ANODE_WIRES_RADIUS, ANODE_WIRE_PITCH_PHI, TPC_ANODE_WIRES,
+};
+use alpha_g_detector::alpha16::ADC32_RATE;
+use alpha_g_detector::padwing::map::DETECTOR_LENGTH;
+use rand::Rng;
+use rand_distr::{Exp, Gamma, StandardNormal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::f64::consts::TAU;
+use thiserror::Error;
+use uom::si::angle::radian;
+use uom::si::f64::*;
+use uom::si::length::{centimeter, meter};
+use uom::si::time::second;
+
+/// Parameters of the fast (parametric) drift simulation.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DriftConfig {
+    /// Mean distance between primary ionization clusters along a track.
+    pub cluster_spacing: Length,
+    /// Longitudinal (radial) diffusion after drifting 1 cm. The diffusion
+    /// scales with the square root of the drift distance.
+    pub longitudinal_diffusion: Length,
+    /// Transverse (azimuthal and axial) diffusion after drifting 1 cm. The
+    /// diffusion scales with the square root of the drift distance.
+    pub transverse_diffusion: Length,
+    /// Shape parameter `theta` of the Polya distribution of the gas gain. The
+    /// relative fluctuation of the gain is `1 / sqrt(1 + theta)` i.e. `0` gives
+    /// an exponential distribution and larger values give smaller fluctuations.
+    pub polya_theta: f64,
+    /// Mean gas gain relative to the reference conditions (see
+    /// [`GasProperties::relative_gain`]).
+    pub relative_gain: f64,
+}
+
+impl Default for DriftConfig {
+    /// Same as [`DriftConfig::from_gas_properties`] with the default
+    /// [`GasProperties`].
+    fn default() -> Self {
+        Self::from_gas_properties(&GasProperties::default())
+    }
+}
+
+impl DriftConfig {
+    /// Return the drift simulation parameters for the given gas properties.
+    pub fn from_gas_properties(gas: &GasProperties) -> Self {
+        Self {
+            cluster_spacing: gas.cluster_spacing,
+            longitudinal_diffusion: gas.longitudinal_diffusion,
+            transverse_diffusion: gas.transverse_diffusion,
+            polya_theta: gas.polya_theta,
+            relative_gain: gas.relative_gain,
+        }
+    }
+    /// Same as [`DriftConfig::from_gas_properties`], with the gas properties
+    /// of a given run number (e.g.
+    /// [`SimulationConfig::run_number`](crate::simulation::SimulationConfig::run_number)).
+    pub fn for_run(gas: &GasPropertiesByRun, run_number: u32) -> Self {
+        Self::from_gas_properties(gas.get(run_number))
+    }
+}
+
+/// The error type returned when the fast drift simulation fails.
+#[derive(Debug, Error)]
+pub enum SimulateDriftError {
+    /// The mean cluster spacing is not positive.
+    #[error("bad cluster spacing `{0:?}`")]
+    BadClusterSpacing(Length),
+    /// The Polya shape parameter is not larger than `-1`.
+    #[error("bad polya theta `{0}`")]
+    BadPolyaTheta(f64),
+    /// The mean relative gas gain is not positive.
+    #[error("bad relative gain `{0}`")]
+    BadRelativeGain(f64),
+}
+
+/// Output of a single drifted ionization cluster.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DriftedCluster {
+    /// True position of the primary ionization.
+    pub ionization: Coordinate,
+    /// Arrival time at the anode wires.
+    pub t: Time,
+    /// Azimuthal angle at which the electrons reach the anode wires. This
+    /// includes the Lorentz angle and diffusion.
+    pub phi: Angle,
+    /// Axial position at which the electrons reach the anode wires. This
+    /// includes diffusion.
+    pub z: Length,
+    /// Gas gain relative to the reference conditions (e.g. the average is
+    /// [`DriftConfig::relative_gain`]).
+    pub gain: f64,
+}
+
+// Return the positions of the primary ionization clusters along a track
+// within the detector. Positions are separated by exponentially distributed
+// distances.
+fn ionization_clusters<R: Rng + ?Sized>(
+    track: &ToyTrack,
+    spacing: &Exp<f64>,
+    rng: &mut R,
+) -> Vec<Coordinate> {
+    let (ox, oy, oz) = (
+        track.origin.x.get::<meter>(),
+        track.origin.y.get::<meter>(),
+        track.origin.z.get::<meter>(),
+    );
+    let (sin_phi, cos_phi) = track.phi.get::<radian>().sin_cos();
+    let (sin_theta, cos_theta) = track.theta.get::<radian>().sin_cos();
+    let (ux, uy, uz) = (sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+
+    let mut clusters = Vec::new();
+    let mut s = 0.0;
+    loop {
+        s += rng.sample(spacing);
+        let (x, y, z) = (ox + s * ux, oy + s * uy, oz + s * uz);
+        // Once a track leaves the drift region it never comes back (the
+        // detector is convex).
+        if x.hypot(y) > ANODE_WIRES_RADIUS || z.abs() > 0.5 * DETECTOR_LENGTH {
+            break;
+        }
+        clusters.push(Coordinate {
+            x: Length::new::<meter>(x),
+            y: Length::new::<meter>(y),
+            z: Length::new::<meter>(z),
+        });
+    }
+
+    clusters
+}
+
+/// Drift the primary ionization clusters of a set of tracks to the anode
+/// wires.
+///
+/// The drift time and Lorentz angle are given by the drift tables. On top of
+/// that, each cluster is smeared by longitudinal and transverse diffusion, and
+/// assigned a random gas gain. Clusters that are outside the range of the drift
+/// tables (e.g. inside the inner cathode) are ignored.
+pub fn drift_clusters<R: Rng + ?Sized>(
+    tracks: &[ToyTrack],
+    tables: &DriftTables,
+    config: &DriftConfig,
+    rng: &mut R,
+) -> Result<Vec<DriftedCluster>, SimulateDriftError> {
+    let spacing = config.cluster_spacing.get::<meter>();
+    let spacing = (spacing > 0.0)
+        .then(|| Exp::new(1.0 / spacing).ok())
+        .flatten()
+        .ok_or(SimulateDriftError::BadClusterSpacing(
+            config.cluster_spacing,
+        ))?;
+    if !(config.relative_gain > 0.0 && config.relative_gain.is_finite()) {
+        return Err(SimulateDriftError::BadRelativeGain(config.relative_gain));
+    }
+    // Gamma distribution with mean equal to the relative gain.
+    let shape = 1.0 + config.polya_theta;
+    let gain = Gamma::new(shape, config.relative_gain / shape)
+        .map_err(|_| SimulateDriftError::BadPolyaTheta(config.polya_theta))?;
+
+    let mut clusters = Vec::new();
+    for track in tracks {
+        for ionization in ionization_clusters(track, &spacing, rng) {
+            let r = ionization.x.hypot(ionization.y);
+            let drift_distance = (Length::new::<meter>(ANODE_WIRES_RADIUS) - r).get::<centimeter>();
+            let scale = drift_distance.max(0.0).sqrt();
+
+            let longitudinal: f64 = rng.sample(StandardNormal);
+            let r = r + config.longitudinal_diffusion * scale * longitudinal;
+            let Some((t, correction)) = tables.drift_time(ionization.z, r) else {
+                continue;
+            };
+
+            let transverse_rphi: f64 = rng.sample(StandardNormal);
+            let transverse_z: f64 = rng.sample(StandardNormal);
+            let phi = Angle::new::<radian>(
+                ionization
+                    .y
+                    .get::<meter>()
+                    .atan2(ionization.x.get::<meter>()),
+            ) + correction
+                + Angle::from(
+                    config.transverse_diffusion * scale * transverse_rphi
+                        / Length::new::<meter>(ANODE_WIRES_RADIUS),
+                );
+            let z = ionization.z + config.transverse_diffusion * scale * transverse_z;
+
+            clusters.push(DriftedCluster {
+                ionization,
+                t,
+                phi,
+                z,
+                gain: rng.sample(gain),
+            });
+        }
+    }
+
+    Ok(clusters)
+}
+
+/// Convert a set of tracks into [`SpacePoint`]s with a realistic smearing
+/// without simulating (and reconstructing) the detector waveforms.
+///
+/// The clusters from [`drift_clusters`] are grouped by anode wire and sampling
+/// time bin (as they would be seen by the electronics). The amplitude of each
+/// resulting avalanche is the sum of the gas gains times the track amplitude,
+/// and its `z` is the amplitude-weighted average. The [`SpacePoint`] is then
+/// obtained as usual from the [`Avalanche`] and the drift tables.
+pub fn drift_spacepoints<R: Rng + ?Sized>(
+    tracks: &[ToyTrack],
+    tables: &DriftTables,
+    config: &DriftConfig,
+    rng: &mut R,
+) -> Result<Vec<SpacePoint>, SimulateDriftError> {
+    // (wire_amplitude, pad_amplitude, pad_amplitude * z) of each wire and
+    // time bin.
+    let mut groups: BTreeMap<(usize, usize), (f64, f64, f64)> = BTreeMap::new();
+    for track in tracks {
+        for cluster in drift_clusters(std::slice::from_ref(track), tables, config, rng)? {
+            let phi = cluster.phi.get::<radian>().rem_euclid(TAU);
+            let wire = ((phi / ANODE_WIRE_PITCH_PHI) as usize).min(TPC_ANODE_WIRES - 1);
+            let bin = (cluster.t.get::<second>() * ADC32_RATE).round() as usize;
+
+            let (wire_amplitude, pad_amplitude, weighted_z) =
+                groups.entry((wire, bin)).or_default();
+            *wire_amplitude += cluster.gain * track.wire_amplitude;
+            *pad_amplitude += cluster.gain * track.pad_amplitude;
+            *weighted_z += cluster.gain * track.pad_amplitude * cluster.z.get::<meter>();
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .filter_map(
+            |((wire, bin), (wire_amplitude, pad_amplitude, weighted_z))| {
+                if pad_amplitude <= 0.0 {
+                    return None;
+                }
+                // Same as the azimuthal position of the wire from the detector
+                // map i.e. the center of the wire cell.
+                let wire_phi = ANODE_WIRE_PITCH_PHI * (wire as f64 + 0.5);
+
+                let avalanche = Avalanche {
+                    t: Time::new::<second>(bin as f64 / ADC32_RATE),
+                    phi: Angle::new::<radian>(wire_phi),
+                    z: Length::new::<meter>(weighted_z / pad_amplitude),
+                    wire_amplitude,
+                    pad_amplitude,
+                    pad_multiplicity: 0,
+                };
+                SpacePoint::try_from_avalanche(avalanche, tables).ok()
+            },
+        )
+        .collect())
+}
+
+/// Generate the charged tracks of an annihilation with a given multiplicity.
+/// Directions are isotropic and all tracks start at the given vertex.
+pub fn annihilation_tracks<R: Rng + ?Sized>(
+    vertex: Coordinate,
+    multiplicity: usize,
+    amplitude: f64,
+    rng: &mut R,
+) -> Vec<ToyTrack> {
+    (0..multiplicity)
+        .map(|_| {
+            let cos_theta: f64 = rng.gen_range(-1.0..=1.0);
+            ToyTrack {
+                origin: vertex,
+                phi: Angle::new::<radian>(rng.gen_range(0.0..TAU)),
+                theta: Angle::new::<radian>(cos_theta.acos()),
+                wire_amplitude: amplitude,
+                pad_amplitude: amplitude,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
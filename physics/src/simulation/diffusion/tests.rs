@@ -0,0 +1,139 @@
+use super::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use uom::si::angle::degree;
+use uom::si::length::millimeter;
+
+fn origin() -> Coordinate {
+    Coordinate {
+        x: Length::new::<meter>(0.0),
+        y: Length::new::<meter>(0.0),
+        z: Length::new::<meter>(0.0),
+    }
+}
+
+fn radial_track(phi: f64) -> ToyTrack {
+    ToyTrack {
+        origin: origin(),
+        phi: Angle::new::<radian>(phi),
+        theta: Angle::new::<degree>(90.0),
+        wire_amplitude: 100.0,
+        pad_amplitude: 100.0,
+    }
+}
+
+#[test]
+fn drift_config_default_round_trip() {
+    let config = DriftConfig::default();
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(serde_json::from_str::<DriftConfig>(&json).unwrap(), config);
+    assert_eq!(serde_json::from_str::<DriftConfig>("{}").unwrap(), config);
+}
+
+#[test]
+fn drift_clusters_bad_config() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let tables = DriftTables::default();
+    let tracks = [radial_track(1.0)];
+
+    let config = DriftConfig {
+        cluster_spacing: Length::new::<meter>(0.0),
+        ..Default::default()
+    };
+    assert!(matches!(
+        drift_clusters(&tracks, &tables, &config, &mut rng),
+        Err(SimulateDriftError::BadClusterSpacing(_))
+    ));
+
+    let config = DriftConfig {
+        polya_theta: -1.0,
+        ..Default::default()
+    };
+    assert!(matches!(
+        drift_clusters(&tracks, &tables, &config, &mut rng),
+        Err(SimulateDriftError::BadPolyaTheta(_))
+    ));
+
+    let config = DriftConfig {
+        relative_gain: 0.0,
+        ..Default::default()
+    };
+    assert!(matches!(
+        drift_clusters(&tracks, &tables, &config, &mut rng),
+        Err(SimulateDriftError::BadRelativeGain(_))
+    ));
+}
+
+#[test]
+fn drift_clusters_without_diffusion() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let tables = DriftTables::default();
+    let config = DriftConfig {
+        longitudinal_diffusion: Length::new::<meter>(0.0),
+        transverse_diffusion: Length::new::<meter>(0.0),
+        ..Default::default()
+    };
+    let clusters = drift_clusters(&[radial_track(1.0)], &tables, &config, &mut rng).unwrap();
+    assert!(!clusters.is_empty());
+
+    for cluster in clusters {
+        let (r, correction) = tables.at(cluster.z, cluster.t).unwrap();
+        let expected_r = cluster.ionization.x.hypot(cluster.ionization.y);
+        assert!((r - expected_r).abs() < Length::new::<millimeter>(1e-6));
+        assert!(
+            (cluster.phi - correction - Angle::new::<radian>(1.0)).abs()
+                < Angle::new::<radian>(1e-9)
+        );
+        assert_eq!(cluster.z, cluster.ionization.z);
+    }
+}
+
+#[test]
+fn drift_clusters_gain_mean() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let tracks = annihilation_tracks(origin(), 50, 100.0, &mut rng);
+    let clusters = drift_clusters(
+        &tracks,
+        &DriftTables::default(),
+        &DriftConfig::default(),
+        &mut rng,
+    )
+    .unwrap();
+
+    let mean = clusters.iter().map(|c| c.gain).sum::<f64>() / clusters.len() as f64;
+    assert!((mean - 1.0).abs() < 0.05);
+}
+
+#[test]
+fn drift_spacepoints_radial_track() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let points = drift_spacepoints(
+        &[radial_track(1.0)],
+        &DriftTables::default(),
+        &DriftConfig::default(),
+        &mut rng,
+    )
+    .unwrap();
+    assert!(!points.is_empty());
+
+    for point in points {
+        // Within a couple of wire pitches.
+        assert!((point.phi.get::<radian>() - 1.0).abs() < 0.06);
+        assert!(point.z.abs() < Length::new::<millimeter>(2.0));
+    }
+}
+
+#[test]
+fn annihilation_tracks_isotropic() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let tracks = annihilation_tracks(origin(), 10000, 1.0, &mut rng);
+    assert_eq!(tracks.len(), 10000);
+
+    let mean_cos_theta = tracks
+        .iter()
+        .map(|t| t.theta.get::<radian>().cos())
+        .sum::<f64>()
+        / 10000.0;
+    assert!(mean_cos_theta.abs() < 0.05);
+    assert!(tracks.iter().all(|t| t.origin == origin()));
+}
@@ -0,0 +1,156 @@
+use super::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use uom::si::angle::degree;
+use uom::si::time::nanosecond;
+
+fn noiseless_config() -> SimulationConfig {
+    SimulationConfig {
+        wire_noise: 0.0,
+        pad_noise: 0.0,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn simulation_config_default_round_trip() {
+    let config = SimulationConfig::default();
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(
+        serde_json::from_str::<SimulationConfig>(&json).unwrap(),
+        config
+    );
+    assert_eq!(
+        serde_json::from_str::<SimulationConfig>("{}").unwrap(),
+        config
+    );
+}
+
+#[test]
+fn wire_index_round_trip() {
+    for wire in 0..TPC_ANODE_WIRES {
+        let phi = TpcWirePosition::try_from(wire).unwrap().phi();
+        assert_eq!(wire_index(Angle::new::<radian>(phi)), wire);
+    }
+}
+
+#[test]
+fn pad_row_weights_normalized() {
+    let weights = pad_row_weights(Length::new::<meter>(0.0), Length::new::<millimeter>(4.0));
+    let sum: f64 = weights.iter().map(|(_, w)| w).sum();
+    assert!((sum - 1.0).abs() < 0.01);
+
+    assert!(pad_row_weights(Length::new::<meter>(2.0), Length::new::<millimeter>(4.0)).is_empty());
+}
+
+#[test]
+fn simulate_empty_event() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let event = simulate_avalanches(&[], &SimulationConfig::default(), &mut rng).unwrap();
+
+    assert!(event.avalanches().is_empty());
+}
+
+#[test]
+fn simulate_not_simulated_run() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let config = SimulationConfig {
+        run_number: 11506,
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        simulate_avalanches(&[], &config, &mut rng),
+        Err(SimulateEventError::NotSimulatedRun(11506))
+    ));
+}
+
+#[test]
+fn simulate_single_avalanche() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let wire_phi = TpcWirePosition::try_from(100).unwrap().phi();
+    let truth = Avalanche {
+        t: Time::new::<nanosecond>(1600.0),
+        phi: Angle::new::<radian>(wire_phi),
+        // Center of a pad row.
+        z: Length::new::<millimeter>(102.0),
+        // Large enough to be well above threshold, but without saturating the
+        // ADC.
+        wire_amplitude: 100.0,
+        pad_amplitude: 1000.0,
+        pad_multiplicity: 0,
+    };
+    let event = simulate_avalanches(&[truth], &noiseless_config(), &mut rng).unwrap();
+    let avalanches = event.avalanches();
+    let reconstructed = avalanches
+        .iter()
+        .max_by(|a, b| a.wire_amplitude.total_cmp(&b.wire_amplitude))
+        .unwrap();
+
+    assert_eq!(reconstructed.phi, truth.phi);
+    assert!((reconstructed.z - truth.z).abs() < Length::new::<millimeter>(10.0));
+    assert!((reconstructed.t - truth.t).abs() <= Time::new::<nanosecond>(16.0));
+}
+
+#[test]
+fn simulate_noise_only() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let config = SimulationConfig {
+        wire_threshold: None,
+        ..Default::default()
+    };
+    let event = simulate_avalanches(&[], &config, &mut rng).unwrap();
+
+    let samples: Vec<f64> = event
+        .wire_signals
+        .iter()
+        .flatten()
+        .flatten()
+        .copied()
+        .collect();
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    let std = (samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n).sqrt();
+    assert!(mean.abs() < 0.5);
+    assert!((std - config.wire_noise).abs() < 0.5);
+}
+
+#[test]
+fn simulate_radial_track() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let track = ToyTrack {
+        origin: Coordinate {
+            x: Length::new::<meter>(0.0),
+            y: Length::new::<meter>(0.0),
+            // Center of a pad row.
+            z: Length::new::<millimeter>(2.0),
+        },
+        phi: Angle::new::<radian>(1.0),
+        theta: Angle::new::<degree>(90.0),
+        // There is an avalanche in every time bin. The signals of consecutive
+        // avalanches pile up, and they would saturate the ADC with larger
+        // amplitudes.
+        wire_amplitude: 10.0,
+        pad_amplitude: 100.0,
+    };
+    let simulated = simulate_tracks(
+        &[track],
+        &DriftTables::default(),
+        &noiseless_config(),
+        &mut rng,
+    )
+    .unwrap();
+    assert!(!simulated.avalanches.is_empty());
+
+    let points: Vec<_> = simulated
+        .event
+        .avalanches()
+        .into_iter()
+        .filter_map(|a| crate::SpacePoint::try_from(a).ok())
+        .collect();
+    assert!(!points.is_empty());
+    for point in points {
+        assert!((point.phi.get::<radian>() - 1.0).abs() < 0.1);
+        assert!((point.z - Length::new::<millimeter>(2.0)).abs() < Length::new::<millimeter>(10.0));
+    }
+}
@@ -1,7 +1,10 @@
 use super::*;
+use crate::gas::GasProperties;
+use alpha_g_detector::padwing::map::{TpcPadColumn, TpcPadRow};
 use uom::si::angle::radian;
 use uom::si::frequency::megahertz;
 use uom::si::length::millimeter;
+use uom::si::time::nanosecond;
 
 #[test]
 fn anode_wires_radius() {
@@ -21,6 +24,11 @@ fn spacepoint_x_coordinate() {
         r: Length::new::<millimeter>(5.0),
         phi: Angle::new::<radian>(4.0f64.atan2(3.0)),
         z: Length::new::<millimeter>(0.0),
+        wire_amplitude: 0.0,
+        pad_amplitude: 0.0,
+        sigma_r: Length::new::<millimeter>(1.0),
+        sigma_rphi: Length::new::<millimeter>(1.0),
+        sigma_z: Length::new::<millimeter>(1.0),
     };
 
     let diff = (p.x() - Length::new::<millimeter>(3.0)).abs();
@@ -33,23 +41,266 @@ fn spacepoint_y_coordinate() {
         r: Length::new::<millimeter>(5.0),
         phi: Angle::new::<radian>(4.0f64.atan2(3.0)),
         z: Length::new::<millimeter>(0.0),
+        wire_amplitude: 0.0,
+        pad_amplitude: 0.0,
+        sigma_r: Length::new::<millimeter>(1.0),
+        sigma_rphi: Length::new::<millimeter>(1.0),
+        sigma_z: Length::new::<millimeter>(1.0),
     };
 
     let diff = (p.y() - Length::new::<millimeter>(4.0)).abs();
     assert!(diff < Length::new::<millimeter>(1e-6));
 }
 
+#[test]
+fn spacepoint_record() {
+    let p = SpacePoint {
+        r: Length::new::<millimeter>(5.0),
+        phi: Angle::new::<radian>(4.0f64.atan2(3.0)),
+        z: Length::new::<millimeter>(-2.0),
+        wire_amplitude: 10.0,
+        pad_amplitude: 20.0,
+        sigma_r: Length::new::<millimeter>(1.0),
+        sigma_rphi: Length::new::<millimeter>(2.0),
+        sigma_z: Length::new::<millimeter>(3.0),
+    };
+    assert_eq!(p.z(), p.z);
+
+    let record = SpacePointRecord::from(p);
+    assert!((record.x - 3e-3).abs() < 1e-9);
+    assert!((record.y - 4e-3).abs() < 1e-9);
+    assert!((record.z + 2e-3).abs() < 1e-9);
+    assert!((record.r - 5e-3).abs() < 1e-9);
+    assert_eq!(record.phi, 4.0f64.atan2(3.0));
+    assert_eq!(record.wire_amplitude, 10.0);
+    assert_eq!(record.pad_amplitude, 20.0);
+    assert!((record.sigma_r - 1e-3).abs() < 1e-9);
+    assert!((record.sigma_rphi - 2e-3).abs() < 1e-9);
+    assert!((record.sigma_z - 3e-3).abs() < 1e-9);
+
+    let json = serde_json::to_string(&record).unwrap();
+    assert_eq!(
+        serde_json::from_str::<SpacePointRecord>(&json).unwrap(),
+        record
+    );
+    let json = serde_json::to_string(&p).unwrap();
+    assert_eq!(serde_json::from_str::<SpacePoint>(&json).unwrap(), p);
+}
+
+#[test]
+fn reconstruct_missing_trg_bank() {
+    assert!(matches!(
+        reconstruct(0, []),
+        Err(TryMainEventFromDataBanksError::MissingTrgBank)
+    ));
+}
+
+#[test]
+fn reconstruct_empty_event() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let event =
+        simulation::simulate_avalanches(&[], &simulation::SimulationConfig::default(), &mut rng)
+            .unwrap();
+    let reconstructed = event.reconstruct();
+
+    assert_eq!(reconstructed.timestamp, event.timestamp());
+    assert!(reconstructed.spacepoints.is_empty());
+    assert!(reconstructed.cosmics.is_empty());
+    assert_eq!(reconstructed.vertexing.vertices().count(), 0);
+    assert!(reconstructed.vertexing.remainder.is_empty());
+    assert!(event.vertex().is_none());
+
+    let (_, diagnostics) = event.reconstruct_with_diagnostics(&ReconstructionConfig::default());
+    assert_eq!(diagnostics.num_avalanches, 0);
+    assert_eq!(diagnostics.num_spacepoints, 0);
+    assert_eq!(diagnostics.num_clusters, 0);
+    assert_eq!(diagnostics.num_tracks, 0);
+    assert_eq!(diagnostics.num_vertices, 0);
+    assert!(diagnostics.track_fit_failures.is_empty());
+}
+
+#[test]
+fn pad_response_deconvolution_avalanches() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let truth = Avalanche {
+        t: Time::new::<nanosecond>(1600.0),
+        phi: Angle::new::<radian>(TpcWirePosition::try_from(100).unwrap().phi()),
+        // Center of a pad row.
+        z: Length::new::<millimeter>(102.0),
+        wire_amplitude: 100.0,
+        pad_amplitude: 1000.0,
+        pad_multiplicity: 0,
+    };
+    let config = simulation::SimulationConfig {
+        wire_noise: 0.0,
+        pad_noise: 0.0,
+        ..Default::default()
+    };
+    let mut event = simulation::simulate_avalanches(&[truth], &config, &mut rng).unwrap();
+    let largest = |event: &MainEvent| {
+        event
+            .avalanches()
+            .into_iter()
+            .max_by(|a, b| a.wire_amplitude.total_cmp(&b.wire_amplitude))
+            .unwrap()
+    };
+
+    let original = largest(&event);
+    event.pad_response_deconvolution = true;
+    let deconvolved = largest(&event);
+
+    assert_eq!(deconvolved.t, original.t);
+    // The central pad also sees the charge induced by the avalanche in front
+    // of its neighbors.
+    assert!(deconvolved.pad_amplitude < original.pad_amplitude);
+    assert!(deconvolved.pad_amplitude > 0.5 * original.pad_amplitude);
+    for avalanche in [original, deconvolved] {
+        assert!((avalanche.z - truth.z).abs() < Length::new::<millimeter>(1.0));
+    }
+}
+
+#[test]
+fn wire_timing_avalanches() {
+    use crate::signal_processing::{ConstantFractionDiscriminator, Polarity};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use uom::si::time::second;
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let truth = Avalanche {
+        t: Time::new::<nanosecond>(1600.0),
+        phi: Angle::new::<radian>(TpcWirePosition::try_from(100).unwrap().phi()),
+        z: Length::new::<millimeter>(102.0),
+        wire_amplitude: 100.0,
+        pad_amplitude: 1000.0,
+        pad_multiplicity: 0,
+    };
+    let config = simulation::SimulationConfig {
+        wire_noise: 0.0,
+        pad_noise: 0.0,
+        ..Default::default()
+    };
+    let mut event = simulation::simulate_avalanches(&[truth], &config, &mut rng).unwrap();
+    // The simulation places avalanches at whole time bins. Delay all wire
+    // signals by a fraction of a sample.
+    for wire_signal in event.wire_signals.iter_mut() {
+        if let Some(signal) = wire_signal.take() {
+            *wire_signal = Some(shift_signal(signal, -0.4));
+        }
+    }
+    let expected = truth.t + Time::new::<second>(0.4 / alpha16::ADC32_RATE);
+    let largest = |event: &MainEvent| {
+        event
+            .avalanches()
+            .into_iter()
+            .max_by(|a, b| a.wire_amplitude.total_cmp(&b.wire_amplitude))
+            .unwrap()
+    };
+
+    let binned = largest(&event);
+    event.wire_timing = Some(ConstantFractionDiscriminator {
+        fraction: 0.5,
+        threshold: 100.0,
+        polarity: Polarity::Negative,
+    });
+    let timed = largest(&event);
+
+    assert_eq!(binned.phi, timed.phi);
+    assert_eq!(binned.wire_amplitude, timed.wire_amplitude);
+    assert!((binned.t - expected).abs() > Time::new::<nanosecond>(4.0));
+    assert!((timed.t - expected).abs() < Time::new::<nanosecond>(2.0));
+}
+
+#[test]
+fn run_context_reconstruct() {
+    use crate::reconstruction::Coordinate;
+    use crate::simulation::{simulate_tracks, SimulationConfig, ToyTrack};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn is_send_sync<T: Send + Sync>() {}
+    is_send_sync::<RunContext>();
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let track = ToyTrack {
+        origin: Coordinate {
+            x: Length::new::<meter>(0.0),
+            y: Length::new::<meter>(0.0),
+            z: Length::new::<meter>(0.0),
+        },
+        phi: Angle::new::<radian>(1.0),
+        theta: Angle::HALF_TURN / 2.0,
+        wire_amplitude: 1000.0,
+        pad_amplitude: 1000.0,
+    };
+    let config = SimulationConfig::default();
+    let event = simulate_tracks(&[track], &DriftTables::default(), &config, &mut rng)
+        .unwrap()
+        .event;
+
+    let mut context = RunContext::new(config.run_number, &DriftTablesByRun::new()).unwrap();
+    assert_eq!(context.run_number(), config.run_number);
+    // Only compare the SpacePoints; skip track finding and fitting (the slow
+    // stages) on both sides.
+    context.config.clustering.min_num_points_per_cluster = usize::MAX;
+    let reconstructed = context.reconstruct(&event);
+    assert!(!reconstructed.spacepoints.is_empty());
+    assert_eq!(
+        reconstructed.spacepoints,
+        event.reconstruct_with_config(&context.config).spacepoints
+    );
+}
+
+#[test]
+fn run_context_drift_tables() {
+    let gas = GasProperties {
+        drift_velocity: GasProperties::default().drift_velocity * 2.0,
+        ..GasProperties::default()
+    };
+    let mut drift_tables = DriftTablesByRun::new();
+    drift_tables
+        .insert(
+            u32::MAX..=u32::MAX,
+            DriftTables::from_gas_properties(&gas).unwrap(),
+        )
+        .unwrap();
+
+    let z = Length::new::<millimeter>(0.0);
+    let r = Length::new::<millimeter>(150.0);
+    let expected = drift_tables.get(u32::MAX).drift_time(z, r).unwrap();
+    let context = RunContext::new(u32::MAX, &drift_tables).unwrap();
+    assert_eq!(context.drift_tables.drift_time(z, r).unwrap(), expected);
+    let default = DriftTables::default().drift_time(z, r).unwrap();
+    assert_ne!(context.drift_tables.drift_time(z, r).unwrap(), default);
+}
+
 #[test]
 fn spacepoint_distance() {
     let p1 = SpacePoint {
         r: Length::new::<millimeter>(10.0),
         phi: Angle::new::<radian>(1.5),
         z: Length::new::<millimeter>(0.5),
+        wire_amplitude: 0.0,
+        pad_amplitude: 0.0,
+        sigma_r: Length::new::<millimeter>(1.0),
+        sigma_rphi: Length::new::<millimeter>(1.0),
+        sigma_z: Length::new::<millimeter>(1.0),
     };
     let p2 = SpacePoint {
         r: Length::new::<millimeter>(5.0),
         phi: Angle::new::<radian>(0.5),
         z: Length::new::<millimeter>(-1.0),
+        wire_amplitude: 0.0,
+        pad_amplitude: 0.0,
+        sigma_r: Length::new::<millimeter>(1.0),
+        sigma_rphi: Length::new::<millimeter>(1.0),
+        sigma_z: Length::new::<millimeter>(1.0),
     };
 
     assert_eq!(p1.distance(p2), p2.distance(p1));
@@ -61,8 +312,101 @@ fn spacepoint_distance() {
         r: Length::new::<millimeter>(15.0),
         phi: Angle::new::<radian>(1.5),
         z: Length::new::<millimeter>(0.5),
+        wire_amplitude: 0.0,
+        pad_amplitude: 0.0,
+        sigma_r: Length::new::<millimeter>(1.0),
+        sigma_rphi: Length::new::<millimeter>(1.0),
+        sigma_z: Length::new::<millimeter>(1.0),
     };
 
     let diff = p1.distance(p3) - Length::new::<millimeter>(5.0);
     assert!(diff.abs() < Length::new::<millimeter>(1e-6));
 }
+
+#[test]
+fn spacepoint_uncertainties() {
+    let avalanche = Avalanche {
+        t: Time::new::<nanosecond>(100.0),
+        phi: Angle::new::<radian>(0.5),
+        z: Length::new::<millimeter>(10.0),
+        wire_amplitude: 10.0 * REFERENCE_AMPLITUDE,
+        pad_amplitude: 10.0 * REFERENCE_AMPLITUDE,
+        pad_multiplicity: NOMINAL_PAD_MULTIPLICITY,
+    };
+    let base = SpacePoint::try_from(avalanche).unwrap();
+    assert_eq!(base.sigma_r, Length::new::<millimeter>(2.0));
+    assert_eq!(base.sigma_rphi, Length::new::<millimeter>(1.3));
+    assert_eq!(base.sigma_z, Length::new::<millimeter>(1.2));
+
+    let small = Avalanche {
+        wire_amplitude: REFERENCE_AMPLITUDE / 4.0,
+        pad_amplitude: REFERENCE_AMPLITUDE / 4.0,
+        ..avalanche
+    };
+    let sp = SpacePoint::try_from(small).unwrap();
+    let diff = sp.sigma_r - 2.0 * base.sigma_r;
+    assert!(diff.abs() < Length::new::<millimeter>(1e-9));
+    let diff = sp.sigma_rphi - 2.0 * base.sigma_rphi;
+    assert!(diff.abs() < Length::new::<millimeter>(1e-9));
+    let diff = sp.sigma_z - 2.0 * base.sigma_z;
+    assert!(diff.abs() < Length::new::<millimeter>(1e-9));
+
+    let wide = Avalanche {
+        pad_multiplicity: 2 * NOMINAL_PAD_MULTIPLICITY,
+        ..avalanche
+    };
+    let sp = SpacePoint::try_from(wide).unwrap();
+    assert_eq!(sp.sigma_r, base.sigma_r);
+    let diff = sp.sigma_z - 2.0 * base.sigma_z;
+    assert!(diff.abs() < Length::new::<millimeter>(1e-9));
+
+    let empty = Avalanche {
+        wire_amplitude: 0.0,
+        pad_amplitude: 0.0,
+        ..avalanche
+    };
+    let sp = SpacePoint::try_from(empty).unwrap();
+    let diff = sp.sigma_r - MAX_AMPLITUDE_SCALE * base.sigma_r;
+    assert!(diff.abs() < Length::new::<millimeter>(1e-9));
+}
+
+#[test]
+fn shift_signal_fractional_samples() {
+    let signal = vec![0.0, 1.0, 2.0, 3.0, 4.0];
+
+    assert_eq!(shift_signal(signal.clone(), 0.0), signal);
+    assert_eq!(
+        shift_signal(signal.clone(), 1.0),
+        vec![1.0, 2.0, 3.0, 4.0, 0.0]
+    );
+    assert_eq!(
+        shift_signal(signal.clone(), -2.0),
+        vec![0.0, 0.0, 0.0, 1.0, 2.0]
+    );
+    assert_eq!(
+        shift_signal(signal.clone(), 0.5),
+        vec![0.5, 1.5, 2.5, 3.5, 2.0]
+    );
+    assert_eq!(shift_signal(signal, -0.5), vec![0.0, 0.5, 1.5, 2.5, 3.5]);
+}
+
+#[test]
+fn pad_signal_baseline_overflow() {
+    let calibration = CalibrationSet::for_run(u32::MAX).unwrap();
+    let position = TpcPadPosition {
+        column: TpcPadColumn::try_from(0).unwrap(),
+        row: TpcPadRow::try_from(0).unwrap(),
+    };
+    let waveform = [i16::MIN; 600];
+
+    assert!(matches!(
+        pad_signal(
+            &calibration,
+            position,
+            &waveform,
+            &[],
+            &SignalOptions::default()
+        ),
+        Err(TryMainEventFromDataBanksError::PadBaselineOverflow { position: p }) if p == position
+    ));
+}
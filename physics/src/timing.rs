@@ -0,0 +1,204 @@
+use crate::{Avalanche, DriftTables, TRG_CLOCK_FREQ};
+use uom::si::f64::*;
+use uom::si::time::second;
+
+/// Continuous time of consecutive events from their TRG timestamps.
+///
+/// The TRG timestamp is a 32-bit counter (see
+/// [`MainEvent::timestamp`](crate::MainEvent::timestamp)) that wraps around
+/// roughly every 68.7 seconds. A timestamp smaller than the previous one is
+/// interpreted as a single wrap-around, i.e. consecutive events have to be
+/// given in order and less than a full period apart.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TrgTimeline {
+    // Number of ticks (including wrap-arounds) of the first and last events.
+    first: Option<u64>,
+    last: u64,
+}
+
+impl TrgTimeline {
+    /// Create a timeline that starts at the next event.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Return the time of an event since the first event in the timeline.
+    pub fn time(&mut self, timestamp: u32) -> Time {
+        let timestamp = u64::from(timestamp);
+        let first = *self.first.get_or_insert(timestamp);
+
+        let mut ticks = (self.last & !u64::from(u32::MAX)) | timestamp;
+        if ticks < self.last {
+            ticks += 1 << 32;
+        }
+        self.last = ticks;
+
+        (ticks - first) as f64 / TRG_CLOCK_FREQ
+    }
+}
+
+/// Alignment between the TRG timeline and the Chronobox.
+///
+/// The Chronobox has a continuous time since the beginning of the run, and it
+/// is the time used by all the other systems (e.g. the sequencer). The main
+/// trigger is recorded by a Chronobox channel; the Chronobox time of each
+/// event is the leading edge of this channel that matches its TRG time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChronoboxAlignment {
+    hits: Vec<Time>,
+    tolerance: Time,
+    // Chronobox time minus TRG time of the last matched event. This follows
+    // any slow drift between the two clocks.
+    offset: Option<Time>,
+}
+
+impl ChronoboxAlignment {
+    /// Create an alignment given the `hits` (leading edges in any order) of
+    /// the Chronobox channel that records the main trigger. A trigger is only
+    /// matched to a hit within `tolerance` of its expected time.
+    ///
+    /// Before any match, the first event of the [`TrgTimeline`] is assumed to
+    /// be the first hit.
+    pub fn new(mut hits: Vec<Time>, tolerance: Time) -> Self {
+        hits.sort_unstable_by(|a, b| a.value.total_cmp(&b.value));
+
+        Self {
+            hits,
+            tolerance,
+            offset: None,
+        }
+    }
+    /// Return the Chronobox time of an event given its time in the
+    /// [`TrgTimeline`]. Returns [`None`] if there is no matching hit.
+    pub fn chronobox_time(&mut self, trg_time: Time) -> Option<Time> {
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => *self.hits.first()? - trg_time,
+        };
+        let expected = trg_time + offset;
+
+        let index = self.hits.partition_point(|&hit| hit < expected);
+        let hit = [index.checked_sub(1), Some(index)]
+            .into_iter()
+            .flatten()
+            .filter_map(|i| self.hits.get(i).copied())
+            .min_by(|a, b| {
+                (*a - expected)
+                    .abs()
+                    .value
+                    .total_cmp(&(*b - expected).abs().value)
+            })?;
+        if (hit - expected).abs() > self.tolerance {
+            return None;
+        }
+        self.offset = Some(hit - trg_time);
+
+        Some(hit)
+    }
+}
+
+/// Time of the interaction with respect to the trigger.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct T0 {
+    /// Time of the interaction in the same reference as [`Avalanche::t`].
+    pub t0: Time,
+    /// Uncertainty of `t0`.
+    pub sigma: Time,
+}
+
+impl T0 {
+    /// Return the avalanche with its time measured from the interaction
+    /// instead of the trigger. This is the time that should be converted into
+    /// a radius (e.g. with
+    /// [`SpacePoint::try_from_avalanche`](crate::SpacePoint::try_from_avalanche)).
+    pub fn correct(&self, avalanche: Avalanche) -> Avalanche {
+        Avalanche {
+            t: avalanche.t - self.t0,
+            ..avalanche
+        }
+    }
+}
+
+/// Estimate the time of the interaction from the drift times of all the
+/// avalanches in an event.
+///
+/// The ionization of every avalanche happened between the anode wires (no
+/// drift) and the inner cathode (maximum drift time in the [`DriftTables`]).
+/// Then, each avalanche gives an interval in which the interaction has to be:
+/// `t - t_max(z) <= t0 <= t`. The returned `t0` is the center of the
+/// intersection of all intervals, with an uncertainty that assumes it is
+/// uniformly distributed within it. If the intervals do not overlap (e.g. due
+/// to noise), the center of the gap is returned instead.
+///
+/// Tracks that cross the full drift region constrain both ends, so
+/// avalanches that are not part of a track should be excluded. Returns
+/// [`None`] if there are no avalanches within the range of the drift tables.
+pub fn fit_t0(avalanches: &[Avalanche], tables: &DriftTables) -> Option<T0> {
+    let (earliest, latest) = avalanches
+        .iter()
+        .filter_map(|avalanche| {
+            let max_drift_time = tables.max_drift_time(avalanche.z)?;
+            Some((avalanche.t, avalanche.t - max_drift_time))
+        })
+        .reduce(|(upper, lower), (t, t_minus_max)| (upper.min(t), lower.max(t_minus_max)))?;
+
+    Some(T0 {
+        t0: 0.5 * (earliest + latest),
+        sigma: (earliest - latest).abs() / 12.0f64.sqrt(),
+    })
+}
+
+/// Absolute time of an event.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EventTime {
+    /// Chronobox time of the trigger.
+    pub trigger: Time,
+    /// Time of the interaction with respect to the trigger. This is [`None`]
+    /// if it could not be estimated from the drift times.
+    pub t0: Option<T0>,
+}
+
+impl EventTime {
+    /// Return the Chronobox time of the interaction. This is the same as the
+    /// trigger time if `t0` is not available.
+    pub fn absolute(&self) -> Time {
+        self.trigger + self.t0.map_or(Time::new::<second>(0.0), |t0| t0.t0)
+    }
+}
+
+/// Reconstruction of the [`EventTime`] of consecutive events from the TRG
+/// timestamps, the Chronobox, and the drift times.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventClock {
+    timeline: TrgTimeline,
+    alignment: ChronoboxAlignment,
+}
+
+impl EventClock {
+    /// Create a clock that starts at the next event.
+    pub fn new(alignment: ChronoboxAlignment) -> Self {
+        Self {
+            timeline: TrgTimeline::new(),
+            alignment,
+        }
+    }
+    /// Return the time of the next event given its TRG timestamp and
+    /// avalanches (see [`fit_t0`]). Events have to be given in order.
+    ///
+    /// Returns [`None`] if the trigger does not match any Chronobox hit.
+    pub fn event_time(
+        &mut self,
+        trg_timestamp: u32,
+        avalanches: &[Avalanche],
+        tables: &DriftTables,
+    ) -> Option<EventTime> {
+        let trg_time = self.timeline.time(trg_timestamp);
+
+        Some(EventTime {
+            trigger: self.alignment.chronobox_time(trg_time)?,
+            t0: fit_t0(avalanches, tables),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,130 @@
+use super::*;
+use uom::si::angle::radian;
+use uom::si::length::meter;
+use uom::si::time::{microsecond, nanosecond};
+
+fn avalanche(t: Time) -> Avalanche {
+    Avalanche {
+        t,
+        phi: Angle::new::<radian>(0.0),
+        z: Length::new::<meter>(0.0),
+        wire_amplitude: 1.0,
+        pad_amplitude: 1.0,
+        pad_multiplicity: 1,
+    }
+}
+
+#[test]
+fn trg_timeline_wrap_around() {
+    let mut timeline = TrgTimeline::new();
+    let period = 2.0f64.powi(32) / TRG_CLOCK_FREQ.get::<uom::si::frequency::hertz>();
+
+    assert_eq!(timeline.time(u32::MAX - 9).get::<second>(), 0.0);
+    let t = timeline.time(u32::MAX).get::<second>();
+    assert!((t - 9.0 / 62.5e6).abs() < 1e-12);
+    let t = timeline.time(5).get::<second>();
+    assert!((t - 15.0 / 62.5e6).abs() < 1e-12);
+    let t = timeline.time(u32::MAX - 9).get::<second>();
+    assert!((t - period).abs() < 1e-9);
+}
+
+#[test]
+fn chronobox_alignment_follows_offset() {
+    let hits = [12.0, 10.0, 11.001, 15.0].map(Time::new::<second>).to_vec();
+    let mut alignment = ChronoboxAlignment::new(hits, Time::new::<microsecond>(10.0));
+
+    // The first event is the first hit.
+    let t = alignment.chronobox_time(Time::new::<second>(0.0)).unwrap();
+    assert_eq!(t.get::<second>(), 10.0);
+    // Small drift between the clocks.
+    let t = alignment
+        .chronobox_time(Time::new::<second>(1.000_995))
+        .unwrap();
+    assert_eq!(t.get::<second>(), 11.001);
+    let t = alignment
+        .chronobox_time(Time::new::<second>(1.999_999))
+        .unwrap();
+    assert_eq!(t.get::<second>(), 12.0);
+    // No hit around 13 seconds.
+    assert!(alignment.chronobox_time(Time::new::<second>(3.0)).is_none());
+}
+
+#[test]
+fn chronobox_alignment_no_hits() {
+    let mut alignment = ChronoboxAlignment::new(Vec::new(), Time::new::<microsecond>(10.0));
+
+    assert!(alignment.chronobox_time(Time::new::<second>(0.0)).is_none());
+}
+
+#[test]
+fn fit_t0_full_drift_region() {
+    let tables = DriftTables::default();
+    let max = tables.max_drift_time(Length::new::<meter>(0.0)).unwrap();
+    let t0 = Time::new::<nanosecond>(120.0);
+    // Ionization at both the anode wires and the inner cathode.
+    let avalanches = [
+        avalanche(t0),
+        avalanche(t0 + 0.5 * max),
+        avalanche(t0 + max),
+    ];
+
+    let fit = fit_t0(&avalanches, &tables).unwrap();
+    assert!((fit.t0 - t0).abs().get::<nanosecond>() < 1e-6);
+    assert!(fit.sigma.get::<nanosecond>() < 1e-6);
+
+    let corrected = fit.correct(avalanches[0]);
+    assert!(corrected.t.get::<nanosecond>().abs() < 1e-6);
+}
+
+#[test]
+fn fit_t0_partial_drift_region() {
+    let tables = DriftTables::default();
+    let max = tables.max_drift_time(Length::new::<meter>(0.0)).unwrap();
+    // Only half of the drift region.
+    let avalanches = [avalanche(0.0 * max), avalanche(0.5 * max)];
+
+    let fit = fit_t0(&avalanches, &tables).unwrap();
+    assert!((fit.t0 + 0.25 * max).abs().get::<nanosecond>() < 1e-6);
+    assert!(
+        (fit.sigma - 0.5 * max / 12.0f64.sqrt())
+            .abs()
+            .get::<nanosecond>()
+            < 1e-6
+    );
+}
+
+#[test]
+fn fit_t0_no_avalanches() {
+    assert!(fit_t0(&[], &DriftTables::default()).is_none());
+}
+
+#[test]
+fn event_clock_absolute_time() {
+    let tables = DriftTables::default();
+    let hits = [100.0, 101.0].map(Time::new::<second>).to_vec();
+    let mut clock = EventClock::new(ChronoboxAlignment::new(
+        hits,
+        Time::new::<microsecond>(10.0),
+    ));
+
+    let time = clock.event_time(1000, &[], &tables).unwrap();
+    assert_eq!(time.trigger.get::<second>(), 100.0);
+    assert_eq!(time.t0, None);
+    assert_eq!(time.absolute(), time.trigger);
+
+    let max = tables.max_drift_time(Length::new::<meter>(0.0)).unwrap();
+    let t0 = Time::new::<nanosecond>(50.0);
+    let avalanches = [avalanche(t0), avalanche(t0 + max)];
+    let time = clock
+        .event_time(1000 + 62_500_000, &avalanches, &tables)
+        .unwrap();
+    assert_eq!(time.trigger.get::<second>(), 101.0);
+    assert!((time.t0.unwrap().t0 - t0).abs().get::<nanosecond>() < 1e-6);
+    // Double precision at ~100 s is only good to ~1e-5 ns.
+    assert!(
+        (time.absolute() - time.trigger - t0)
+            .abs()
+            .get::<nanosecond>()
+            < 1e-3
+    );
+}